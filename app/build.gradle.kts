@@ -57,6 +57,21 @@ android {
     }
 }
 
+// Native compiler (rust_core → librust_core.so, loaded by LatexCompiler). Needs cargo-ndk and the NDK;
+// enable with RUST_CORE_BUILD=true in gradle.properties or local.properties.
+val buildRustCore = tasks.register<Exec>("buildRustCore") {
+    workingDir = rootProject.file("rust_core")
+    commandLine(
+        "cargo", "ndk",
+        "-t", "arm64-v8a", "-t", "armeabi-v7a", "-t", "x86_64",
+        "-o", file("src/main/jniLibs").absolutePath,
+        "build", "--release", "--features", "tectonic",
+    )
+}
+if ((project.findProperty("RUST_CORE_BUILD") as String?) == "true") {
+    tasks.named("preBuild") { dependsOn(buildRustCore) }
+}
+
 dependencies {
     implementation(libs.androidx.core.ktx)
     implementation(libs.androidx.appcompat)
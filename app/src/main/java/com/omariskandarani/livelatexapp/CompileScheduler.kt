@@ -0,0 +1,185 @@
+package com.omariskandarani.livelatexapp
+
+import kotlinx.coroutines.channels.BufferOverflow
+import kotlinx.coroutines.flow.Flow
+import kotlinx.coroutines.flow.MutableSharedFlow
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.SharedFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asSharedFlow
+import kotlinx.coroutines.flow.asStateFlow
+import kotlinx.coroutines.flow.filter
+import kotlinx.coroutines.flow.filterIsInstance
+import kotlinx.coroutines.flow.map
+import kotlinx.coroutines.flow.update
+
+/** A compile for [CompileScheduler.submit]; [docId] names the open document (tab) it belongs to. */
+data class CompileRequest(
+    val docId: String,
+    val latexSource: String,
+    val outputPath: String,
+    val options: CompileOptions = CompileOptions(),
+)
+
+/** What happened to a [CompileRequest]; see [CompileScheduler.events]. */
+sealed interface CompileEvent {
+    val request: CompileRequest
+
+    data class Started(override val request: CompileRequest) : CompileEvent
+
+    data class Finished(override val request: CompileRequest, val result: CompileResult) : CompileEvent
+
+    /**
+     * Dropped from the queue or stopped while running. [superseded] when a newer request for the same document and
+     * output replaced it, false after [CompileScheduler.cancel].
+     */
+    data class Cancelled(override val request: CompileRequest, val superseded: Boolean) : CompileEvent
+}
+
+/**
+ * Runs native compiles for all open documents:
+ * - one job per document at a time; its other requests wait
+ * - a request replaces the queued ones of the same document and output path, and cancels the running one (its PDF
+ *   would be stale); an export to another path still runs after it
+ * - the visible document ([setVisible]) goes first, then the others in the order they were submitted
+ * - at most [maxConcurrent] jobs at once, by default one rather than the number of CPU cores: the native engine
+ *   compiles one document at a time, so more would only wait there, out of reach of the visible document's priority.
+ *   Pass `Runtime.getRuntime().availableProcessors()` to get the per-core limit anyway
+ * - a result that arrives after its request was superseded or cancelled is reported as [CompileEvent.Cancelled]
+ *
+ * Results arrive on [events], or [results] for one document, in place of calling [LatexCompiler.compile] directly.
+ * The flow keeps the last 64 events for slow collectors and drops older ones.
+ */
+class CompileScheduler(
+    private val cachePath: String,
+    private val maxConcurrent: Int = 1,
+) {
+    private class Running(val request: CompileRequest) {
+        var job: CompileJob? = null
+        var cancelled = false
+        var superseded = false
+    }
+
+    private val lock = Any()
+    private val queue = ArrayDeque<CompileRequest>()
+    private val running = HashMap<String, Running>()
+    private var visibleDocId: String? = null
+
+    private val _events = MutableSharedFlow<CompileEvent>(
+        extraBufferCapacity = 64,
+        onBufferOverflow = BufferOverflow.DROP_OLDEST,
+    )
+    val events: SharedFlow<CompileEvent> = _events.asSharedFlow()
+
+    private val _progress = MutableStateFlow<Map<String, CompileProgress>>(emptyMap())
+
+    /** Progress of the running jobs by document id. */
+    val progress: StateFlow<Map<String, CompileProgress>> = _progress.asStateFlow()
+
+    /** Results of [docId]'s compiles; superseded and cancelled ones are left out. */
+    fun results(docId: String): Flow<CompileResult> = events
+        .filterIsInstance<CompileEvent.Finished>()
+        .filter { it.request.docId == docId }
+        .map { it.result }
+
+    fun submit(request: CompileRequest) {
+        val dropped = synchronized(lock) {
+            val replaced = queue.filter { it.supersededBy(request) }
+            queue.removeAll(replaced)
+            running[request.docId]?.takeIf { it.request.supersededBy(request) }?.let { stop(it, superseded = true) }
+            queue.addLast(request)
+            replaced
+        }
+        dropped.forEach { _events.tryEmit(CompileEvent.Cancelled(it, superseded = true)) }
+        dispatch()
+    }
+
+    /** Drops [docId]'s queued requests and stops its running one, e.g. when its tab is closed. */
+    fun cancel(docId: String) {
+        val dropped = synchronized(lock) {
+            val mine = queue.filter { it.docId == docId }
+            queue.removeAll(mine)
+            running[docId]?.let { stop(it, superseded = false) }
+            mine
+        }
+        dropped.forEach { _events.tryEmit(CompileEvent.Cancelled(it, superseded = false)) }
+    }
+
+    /** Gives [docId]'s requests priority over the other documents'; null for none. */
+    fun setVisible(docId: String?) {
+        synchronized(lock) { visibleDocId = docId }
+        dispatch()
+    }
+
+    private fun CompileRequest.supersededBy(newer: CompileRequest): Boolean =
+        docId == newer.docId && outputPath == newer.outputPath
+
+    /** Holding [lock]. The job may not have started yet; [dispatch] cancels it once it has. */
+    private fun stop(run: Running, superseded: Boolean) {
+        if (run.cancelled) return
+        run.cancelled = true
+        run.superseded = superseded
+        run.job?.cancel()
+    }
+
+    /** Starts queued requests while there are free slots. */
+    private fun dispatch() {
+        while (true) {
+            val run = synchronized(lock) {
+                if (running.size >= maxConcurrent.coerceAtLeast(1)) return
+                val next = queue.firstOrNull { it.docId == visibleDocId && it.docId !in running }
+                    ?: queue.firstOrNull { it.docId !in running }
+                    ?: return
+                queue.remove(next)
+                Running(next).also { running[next.docId] = it }
+            }
+            _events.tryEmit(CompileEvent.Started(run.request))
+            val request = run.request
+            val job = LatexCompiler.startCompile(
+                request.latexSource,
+                request.outputPath,
+                cachePath,
+                Listener(run),
+                request.options,
+            )
+            if (job.id < 0) {
+                val failure = CompileDiagnostic(null, null, "Could not start the compile", true, null)
+                finish(run, CompileEvent.Finished(request, CompileResult(null, listOf(failure), emptyList(), "")))
+                return
+            }
+            synchronized(lock) {
+                run.job = job
+                if (run.cancelled) job.cancel()
+            }
+        }
+    }
+
+    private fun finish(run: Running, event: CompileEvent) {
+        synchronized(lock) {
+            if (running[run.request.docId] === run) running.remove(run.request.docId)
+        }
+        _progress.update { it - run.request.docId }
+        _events.tryEmit(event)
+        dispatch()
+    }
+
+    private inner class Listener(private val run: Running) : CompileListener {
+        override fun onProgress(progress: CompileProgress) {
+            _progress.update { it + (run.request.docId to progress) }
+        }
+
+        /** A job stopped too late still finishes; its PDF is stale, so it counts as cancelled. */
+        override fun onFinished(result: CompileResult) {
+            val (stale, superseded) = synchronized(lock) { run.cancelled to run.superseded }
+            finish(
+                run,
+                if (stale) CompileEvent.Cancelled(run.request, superseded) else CompileEvent.Finished(run.request, result),
+            )
+        }
+
+        override fun onCancelled() {
+            val superseded = synchronized(lock) { run.superseded }
+            finish(run, CompileEvent.Cancelled(run.request, superseded))
+        }
+    }
+}
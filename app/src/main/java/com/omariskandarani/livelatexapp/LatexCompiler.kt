@@ -1,10 +1,564 @@
 package com.omariskandarani.livelatexapp
 
+import android.content.Context
+import android.graphics.Bitmap
+import androidx.annotation.Keep
+import org.json.JSONArray
+import org.json.JSONObject
+import java.io.File
+import java.nio.ByteBuffer
+import kotlin.math.ceil
+
+/** One TeX error or warning; [file] is null for the main (in-editor) source. */
+data class CompileDiagnostic(
+    val file: String?,
+    val line: Int?,
+    val message: String,
+    val isError: Boolean,
+    val context: String?,
+)
+
+/** Result of [LatexCompiler.compile]; [pdfPath] is null when no PDF was written. */
+data class CompileResult(
+    val pdfPath: String?,
+    val errors: List<CompileDiagnostic>,
+    val warnings: List<CompileDiagnostic>,
+    val log: String,
+) {
+    val success: Boolean get() = pdfPath != null
+}
+
+/** What a [TexLogEvent] is about; [OTHER_ERROR] and [OTHER_WARNING] cover everything unrecognised. */
+enum class TexLogEventKind(internal val json: String) {
+    UNDEFINED_REFERENCE("undefinedReference"),
+    UNDEFINED_CITATION("undefinedCitation"),
+    OVERFULL_BOX("overfullBox"),
+    UNDERFULL_BOX("underfullBox"),
+    MISSING_FILE("missingFile"),
+    FONT_SUBSTITUTION("fontSubstitution"),
+    RERUN("rerun"),
+    PACKAGE_ERROR("packageError"),
+    OTHER_ERROR("error"),
+    OTHER_WARNING("warning"),
+}
+
+/**
+ * One entry of the errors/warnings panel. A box's paragraph spans [line]..[endLine];
+ * [subject] is the label, citation key, file, font shape or package the event names.
+ */
+data class TexLogEvent(
+    val kind: TexLogEventKind,
+    val isError: Boolean,
+    val file: String?,
+    val line: Int?,
+    val endLine: Int?,
+    val subject: String?,
+    val message: String,
+    val context: String?,
+)
+
+/** Result of [LatexCompiler.analyzeLog]; [rerun] means cross-references are stale until the next compile. */
+data class TexLogAnalysis(val events: List<TexLogEvent>, val rerun: Boolean) {
+    val errors: List<TexLogEvent> get() = events.filter { it.isError }
+    val warnings: List<TexLogEvent> get() = events.filterNot { it.isError }
+}
+
+/**
+ * Per-document compile settings.
+ * [projectDir] is the folder of the document; BibTeX reads `.bib` databases and local `.bst` styles from it,
+ * makeindex local `.ist` styles. [mainFile] (relative to [projectDir]) makes the source stand in for that file of
+ * a multi-file project: `\input`, `\include`, images and local packages are then read from the whole folder.
+ * [indexStyle] is the `.ist` used for `\makeindex` (makeindex's `-s`).
+ * [trusted] lifts the sandbox: shell escape runs and files outside the project are readable.
+ * Only set it when the user opted in for this project ([TrustedProjectsPrefs]).
+ * [limits] stop a runaway document with a "limit exceeded" error.
+ * [sourceDateEpoch] (seconds since 1970) dates the PDF and `\today` at a fixed instant and makes the output
+ * byte-identical for identical sources, e.g. for PDFs committed to a repository.
+ * [draft] makes a fast, partial compile for the live PDF view (see [DraftOptions]).
+ * [program] is the per-document engine choice; `null` follows a `% !TEX program = xelatex` comment, then the packages.
+ */
+data class CompileOptions(
+    val projectDir: String? = null,
+    val mainFile: String? = null,
+    val indexStyle: String? = null,
+    val trusted: Boolean = false,
+    val limits: CompileLimits = CompileLimits(),
+    val program: TexProgram? = null,
+    val sourceDateEpoch: Long? = null,
+    val draft: DraftOptions? = null,
+) {
+    internal fun toJson(): String = JSONObject().apply {
+        projectDir?.let { put("projectDir", it) }
+        mainFile?.let { put("mainFile", it) }
+        indexStyle?.let { put("indexStyle", it) }
+        if (trusted) put("trusted", true)
+        put("limits", limits.toJson())
+        program?.let { put("program", it.json) }
+        sourceDateEpoch?.let { put("sourceDateEpoch", it) }
+        draft?.let { put("draft", it.toJson()) }
+    }.toString()
+}
+
+/**
+ * A draft compile: images become placeholder boxes, TeX runs once and stops after [maxPages] pages or once the page
+ * holding [untilLine] of the main file is out. Compile drafts to the same output path as full compiles: they reuse
+ * that document's preamble format and cross-references without replacing them.
+ */
+data class DraftOptions(
+    val maxPages: Int? = null,
+    val untilLine: Int? = null,
+) {
+    internal fun toJson(): JSONObject = JSONObject().apply {
+        maxPages?.let { put("maxPages", it) }
+        untilLine?.let { put("untilLine", it) }
+    }
+}
+
+/** How the native engine runs a document: pdfLaTeX-compatible, or XeLaTeX with OpenType fonts (`fontspec`). */
+enum class TexProgram(internal val json: String) {
+    PDFLATEX("pdftex"),
+    XELATEX("xetex"),
+}
+
+/**
+ * Bounds of one compile; 0 disables a limit. Compiles run one at a time, and the limits count from when one starts
+ * running: [maxMemoryMb] is what it may add to the app's resident memory. Checked on every file TeX opens and between
+ * passes, and the timeout also on every page TeX ships out, so a compile stops shortly after.
+ *
+ * TeX's main memory is not limited: the engine hardcodes its size (5,000,000 words) and cannot be given another.
+ */
+data class CompileLimits(
+    val timeoutSecs: Long = 120,
+    val maxPages: Int = 2000,
+    val maxMemoryMb: Long = 768,
+) {
+    internal fun toJson(): JSONObject = JSONObject().apply {
+        put("timeoutSecs", timeoutSecs)
+        put("maxPages", maxPages)
+        put("maxMemoryMb", maxMemoryMb)
+    }
+}
+
+/** Where a background compile is; [stage] is `format`, `tex`, `bibliography`, `index` or `pdf`. */
+data class CompileProgress(val stage: String, val pass: Int, val file: String?, val pages: Int?)
+
+/** Callbacks of [LatexCompiler.startCompile]; invoked on the compile's background thread. */
+interface CompileListener {
+    fun onProgress(progress: CompileProgress) {}
+    fun onFinished(result: CompileResult)
+    fun onCancelled() {}
+}
+
+/** Handle of a running compile. */
+class CompileJob internal constructor(val id: Long) {
+    /** Stops the compile at its next file access; false if it already finished. */
+    fun cancel(): Boolean = LatexCompiler.cancelCompile(id)
+}
+
+/** Called from native code with raw values; adapts them for a [CompileListener]. */
+@Keep
+internal class NativeCompileListener(private val delegate: CompileListener) {
+    fun onProgress(stage: String, pass: Int, file: String?, pages: Int) =
+        delegate.onProgress(CompileProgress(stage, pass, file, pages.takeIf { it >= 0 }))
+
+    fun onFinished(resultJson: String) = delegate.onFinished(LatexCompiler.parseCompileResult(resultJson))
+
+    fun onCancelled() = delegate.onCancelled()
+}
+
+/** Spot in the compiled PDF, in points from the page's top-left corner; [page] is 1-based. */
+data class PdfPosition(val page: Int, val x: Double, val y: Double, val width: Double, val height: Double)
+
+/** Source location for a PDF tap; [file] is null for the main document. */
+data class SourcePosition(val file: String?, val line: Int)
+
+/** A PDF page in points with `/Rotate` applied; a render at `dpi` is [pixelWidth] × [pixelHeight]. */
+data class PdfPageSize(val width: Double, val height: Double) {
+    fun pixelWidth(dpi: Double): Int = maxOf(1, ceil(width * dpi / 72.0).toInt())
+    fun pixelHeight(dpi: Double): Int = maxOf(1, ceil(height * dpi / 72.0).toInt())
+}
+
+/** A word of a page's text layer, its box in points from the page's top-left corner. */
+data class PdfTextWord(val text: String, val x: Double, val y: Double, val width: Double, val height: Double)
+
+/** A box in points from a page's top-left corner. */
+data class PdfBox(val x: Double, val y: Double, val width: Double, val height: Double)
+
+/** A line of a page's text: its words joined by spaces, and their box. */
+data class PdfTextLine(val text: String, val box: PdfBox)
+
+/** The text of 1-based [page] as lines, in drawing order. */
+data class PdfPageText(val page: Int, val lines: List<PdfTextLine>) {
+    val text: String get() = lines.joinToString("\n") { it.text }
+}
+
+/** A match of [LatexCompiler.searchPdf]: one box per line it covers, around the whole words it touches. */
+data class PdfSearchHit(val page: Int, val boxes: List<PdfBox>)
+
+/** What [LatexCompiler.wordCount] leaves out besides page numbers. */
+data class WordCountOptions(
+    val excludeCaptions: Boolean = false,
+    val excludeReferences: Boolean = false,
+) {
+    fun toJson(): String = JSONObject()
+        .put("excludeCaptions", excludeCaptions)
+        .put("excludeReferences", excludeReferences)
+        .toString()
+}
+
+/** Words from a bookmarked heading to the next one at the same or a higher [level], subsections included. */
+data class SectionWordCount(val title: String, val level: Int, val page: Int, val words: Int)
+
+/** Typeset word count; [pages] is indexed from page 1, [unsectioned] is what precedes the first heading. */
+data class WordCount(
+    val total: Int,
+    val pages: List<Int>,
+    val unsectioned: Int,
+    val sections: List<SectionWordCount>,
+)
+
+/** Result of [LatexCompiler.renderTikz]; [svgPath] is set when the SVG exists, freshly written or cached. */
+data class TikzSvgResult(val svgPath: String?, val errors: List<CompileDiagnostic>, val log: String)
+
+/** One package of the installed TeX bundle, for the settings sheet. */
+data class BundlePackage(val name: String, val files: Int, val bytes: Long)
+
+/** Installed TeX bundle; [version] is null when none was installed via [LatexCompiler.installBundle]. */
+data class BundleInfo(
+    val version: String?,
+    val packages: List<BundlePackage>,
+    val bundleBytes: Long,
+    val formatBytes: Long,
+    /** Per-document compile caches (preamble formats, aux files). */
+    val workBytes: Long,
+) {
+    val totalBytes: Long get() = bundleBytes + formatBytes + workBytes
+}
+
+data class BundleVerifyResult(
+    val ok: Boolean,
+    val missing: List<String>,
+    val corrupt: List<String>,
+    val extra: List<String>,
+)
+
+/** Native bundle calls report failures as `{"error": …}`; surfaced as this exception. */
+class BundleException(message: String) : Exception(message)
+
 object LatexCompiler {
     init {
         // This must match the name of your Rust output library
         System.loadLibrary("rust_core")
     }
 
+    /**
+     * Typesets [latexSource] with the embedded TeX engine and writes the PDF to [outputPath].
+     * [cachePath] holds the TeX bundle (`bundle/` from [installBundle], or `bundle.zip`) and generated formats.
+     */
     external fun compilePdf(latexSource: String, outputPath: String, cachePath: String): Boolean
-}
\ No newline at end of file
+
+    /** Same as [compilePdf], returning the JSON report parsed by [compile]. */
+    external fun compilePdfDetailed(latexSource: String, outputPath: String, cachePath: String): String
+
+    /** [compilePdfDetailed] with [CompileOptions] as JSON. */
+    external fun compileWithOptions(latexSource: String, outputPath: String, cachePath: String, optionsJson: String): String
+
+    /** Compiles [mainFile] of the project at [projectRoot] as saved on disk; JSON report like [compilePdfDetailed]. */
+    external fun compileProject(
+        projectRoot: String,
+        mainFile: String,
+        outputPath: String,
+        cachePath: String,
+        optionsJson: String,
+    ): String
+
+    private external fun startCompile(
+        latexSource: String,
+        outputPath: String,
+        cachePath: String,
+        optionsJson: String,
+        listener: NativeCompileListener,
+    ): Long
+
+    /** Cancels job [jobId]; false if it already finished. Prefer [CompileJob.cancel]. */
+    external fun cancelCompile(jobId: Long): Boolean
+
+    /**
+     * Compiles on a background thread, reporting to [listener]. Cancel the returned job
+     * when the source changes; the engine then drops its state without writing output.
+     */
+    fun startCompile(
+        latexSource: String,
+        outputPath: String,
+        cachePath: String,
+        listener: CompileListener,
+        options: CompileOptions = CompileOptions(),
+    ): CompileJob = CompileJob(
+        startCompile(latexSource, outputPath, cachePath, options.toJson(), NativeCompileListener(listener)),
+    )
+
+    /** SyncTeX lookups against the last successful compile to [pdfPath]; JSON or `null`. */
+    external fun synctexForward(cachePath: String, pdfPath: String, file: String?, line: Int, column: Int): String
+    external fun synctexInverse(cachePath: String, pdfPath: String, page: Int, x: Double, y: Double): String
+
+    /** Where [line] (1-based) of [file] (null: main document) ended up in the PDF. */
+    fun forwardSearch(cachePath: String, pdfPath: String, line: Int, column: Int = -1, file: String? = null): PdfPosition? {
+        val o = nullableJson(synctexForward(cachePath, pdfPath, file, line, column)) ?: return null
+        return PdfPosition(
+            page = o.getInt("page"),
+            x = o.getDouble("x"),
+            y = o.getDouble("y"),
+            width = o.optDouble("width", 0.0),
+            height = o.optDouble("height", 0.0),
+        )
+    }
+
+    /** Source line under a tap at ([x], [y]) points on 1-based [page]. */
+    fun inverseSearch(cachePath: String, pdfPath: String, page: Int, x: Double, y: Double): SourcePosition? {
+        val o = nullableJson(synctexInverse(cachePath, pdfPath, page, x, y)) ?: return null
+        return SourcePosition(file = o.optStringOrNull("file"), line = o.getInt("line"))
+    }
+
+    /** Compiled-PDF viewer: page sizes (JSON), RGBA pixels (null on failure) and text layer (JSON); pages are 1-based. */
+    external fun pdfPageSizes(pdfPath: String): String
+    external fun renderPdfPage(pdfPath: String, page: Int, dpi: Double): ByteArray?
+    external fun pdfPageText(pdfPath: String, page: Int): String
+
+    /** Page sizes of [pdfPath]; empty when it cannot be read. */
+    fun pdfPages(pdfPath: String): List<PdfPageSize> {
+        val arr = jsonArrayOrNull(pdfPageSizes(pdfPath)) ?: return emptyList()
+        return (0 until arr.length()).map { i ->
+            val p = arr.getJSONObject(i)
+            PdfPageSize(p.getDouble("width"), p.getDouble("height"))
+        }
+    }
+
+    /** Renders 1-based [page] of [pdfPath] at [dpi] on white; null if it cannot be read. Call off the main thread. */
+    fun renderPage(pdfPath: String, page: Int, dpi: Double): Bitmap? {
+        val size = pdfPages(pdfPath).getOrNull(page - 1) ?: return null
+        val pixels = renderPdfPage(pdfPath, page, dpi) ?: return null
+        return Bitmap.createBitmap(size.pixelWidth(dpi), size.pixelHeight(dpi), Bitmap.Config.ARGB_8888).apply {
+            copyPixelsFromBuffer(ByteBuffer.wrap(pixels))
+        }
+    }
+
+    /** Words on 1-based [page] in drawing order, for selection and search over a rendered page. */
+    fun pageText(pdfPath: String, page: Int): List<PdfTextWord> {
+        val arr = jsonArrayOrNull(pdfPageText(pdfPath, page)) ?: return emptyList()
+        return (0 until arr.length()).map { i ->
+            val w = arr.getJSONObject(i)
+            PdfTextWord(w.getString("text"), w.getDouble("x"), w.getDouble("y"), w.getDouble("width"), w.getDouble("height"))
+        }
+    }
+
+    /** Text of the whole PDF, search and typeset word count (JSON); see [pdfDocumentText], [searchPdf], [wordCount]. */
+    external fun pdfText(pdfPath: String): String
+    external fun pdfSearch(pdfPath: String, query: String): String
+    external fun pdfWordCount(pdfPath: String, optionsJson: String): String
+
+    /** Every page's text as lines; empty if [pdfPath] cannot be read. Call off the main thread. */
+    fun pdfDocumentText(pdfPath: String): List<PdfPageText> {
+        val arr = jsonArrayOrNull(pdfText(pdfPath)) ?: return emptyList()
+        return (0 until arr.length()).map { i ->
+            val p = arr.getJSONObject(i)
+            val lines = p.getJSONArray("lines")
+            PdfPageText(
+                page = p.getInt("page"),
+                lines = (0 until lines.length()).map { j ->
+                    val l = lines.getJSONObject(j)
+                    PdfTextLine(l.getString("text"), parseBox(l))
+                },
+            )
+        }
+    }
+
+    /** Where [query] occurs in [pdfPath], ignoring case and line breaks; in page order. Call off the main thread. */
+    fun searchPdf(pdfPath: String, query: String): List<PdfSearchHit> {
+        val arr = jsonArrayOrNull(pdfSearch(pdfPath, query)) ?: return emptyList()
+        return (0 until arr.length()).map { i ->
+            val h = arr.getJSONObject(i)
+            val boxes = h.getJSONArray("boxes")
+            PdfSearchHit(h.getInt("page"), (0 until boxes.length()).map { j -> parseBox(boxes.getJSONObject(j)) })
+        }
+    }
+
+    /** Words as typeset in [pdfPath], per page and bookmarked section; null if it cannot be read. Call off the main thread. */
+    fun wordCount(pdfPath: String, options: WordCountOptions = WordCountOptions()): WordCount? {
+        val o = JSONObject(pdfWordCount(pdfPath, options.toJson()))
+        if (o.has("error")) return null
+        val pages = o.getJSONArray("pages")
+        val sections = o.getJSONArray("sections")
+        return WordCount(
+            total = o.getInt("total"),
+            pages = (0 until pages.length()).map { pages.getInt(it) },
+            unsectioned = o.getInt("unsectioned"),
+            sections = (0 until sections.length()).map { i ->
+                val s = sections.getJSONObject(i)
+                SectionWordCount(s.getString("title"), s.getInt("level"), s.getInt("page"), s.getInt("words"))
+            },
+        )
+    }
+
+    /**
+     * Compiles the standalone TikZ document [texDoc] and writes its SVG to
+     * `<projectDir>/.livelatex-cache/tikz/<sha1 of texDoc>.svg`; JSON report.
+     */
+    external fun renderTikzSvg(texDoc: String, projectDir: String, cachePath: String): String
+
+    /** [renderTikzSvg], parsed; a failure outside the figure comes back as an error without a line. Call off the main thread. */
+    fun renderTikz(texDoc: String, projectDir: String, cachePath: String): TikzSvgResult {
+        val o = JSONObject(renderTikzSvg(texDoc, projectDir, cachePath))
+        o.optStringOrNull("error")?.let {
+            return TikzSvgResult(null, listOf(CompileDiagnostic(null, null, it, true, null)), "")
+        }
+        return TikzSvgResult(
+            svgPath = o.optStringOrNull("svgPath"),
+            errors = parseDiagnostics(o.optJSONArray("errors")),
+            log = o.optString("log", ""),
+        )
+    }
+
+    /** Typed events of an engine log (JSON); [mainFile] names the primary input as in [CompileOptions]. */
+    external fun analyzeTexLog(log: String, mainFile: String?): String
+
+    /** Sorts [CompileResult.log] into events for the errors/warnings panel; empty if the log cannot be read. */
+    fun analyzeLog(log: String, mainFile: String? = null): TexLogAnalysis {
+        val o = JSONObject(analyzeTexLog(log, mainFile))
+        val arr = o.optJSONArray("events") ?: JSONArray()
+        return TexLogAnalysis(
+            events = (0 until arr.length()).map { i ->
+                val e = arr.getJSONObject(i)
+                val isError = e.optString("severity") == "error"
+                TexLogEvent(
+                    kind = TexLogEventKind.entries.firstOrNull { it.json == e.optString("kind") }
+                        ?: if (isError) TexLogEventKind.OTHER_ERROR else TexLogEventKind.OTHER_WARNING,
+                    isError = isError,
+                    file = e.optStringOrNull("file"),
+                    line = if (e.isNull("line")) null else e.optInt("line"),
+                    endLine = if (e.isNull("endLine")) null else e.optInt("endLine"),
+                    subject = e.optStringOrNull("subject"),
+                    message = e.optString("message", ""),
+                    context = e.optStringOrNull("context"),
+                )
+            },
+            rerun = o.optBoolean("rerun"),
+        )
+    }
+
+    /** Installs a bundle ZIP into [cachePath]; JSON `version`, `files`, `bytes`. */
+    external fun installBundle(zipPath: String, cachePath: String): String
+    external fun listBundle(cachePath: String): String
+    external fun verifyBundle(cachePath: String): String
+    /** Removes [packages] plus stale files and formats; JSON `removedFiles`, `freedBytes`. */
+    external fun pruneBundle(cachePath: String, packages: Array<String>): String
+
+    fun compile(latexSource: String, outputPath: String, cachePath: String): CompileResult =
+        parseCompileResult(compilePdfDetailed(latexSource, outputPath, cachePath))
+
+    fun compile(latexSource: String, outputPath: String, cachePath: String, options: CompileOptions): CompileResult =
+        parseCompileResult(compileWithOptions(latexSource, outputPath, cachePath, options.toJson()))
+
+    fun compileProject(
+        projectRoot: String,
+        mainFile: String,
+        outputPath: String,
+        cachePath: String,
+        options: CompileOptions = CompileOptions(),
+    ): CompileResult =
+        parseCompileResult(compileProject(projectRoot, mainFile, outputPath, cachePath, options.toJson()))
+
+    /**
+     * Copies the bundle ZIP shipped as asset [assetName] to a temp file and installs it.
+     * Returns the installed version. Call off the main thread.
+     */
+    fun installBundleFromAsset(context: Context, assetName: String, cachePath: String): String {
+        val tmp = File(context.cacheDir, "bundle-install.zip")
+        try {
+            context.assets.open(assetName).use { input ->
+                tmp.outputStream().use { input.copyTo(it) }
+            }
+            return bundleJson(installBundle(tmp.absolutePath, cachePath)).getString("version")
+        } finally {
+            tmp.delete()
+        }
+    }
+
+    fun bundleInfo(cachePath: String): BundleInfo {
+        val o = bundleJson(listBundle(cachePath))
+        val arr = o.optJSONArray("packages") ?: JSONArray()
+        return BundleInfo(
+            version = o.optStringOrNull("version"),
+            packages = (0 until arr.length()).map { i ->
+                val p = arr.getJSONObject(i)
+                BundlePackage(p.getString("name"), p.optInt("files"), p.optLong("bytes"))
+            },
+            bundleBytes = o.optLong("bundleBytes"),
+            formatBytes = o.optLong("formatBytes"),
+            workBytes = o.optLong("workBytes"),
+        )
+    }
+
+    fun verifyInstalledBundle(cachePath: String): BundleVerifyResult {
+        val o = bundleJson(verifyBundle(cachePath))
+        return BundleVerifyResult(
+            ok = o.optBoolean("ok"),
+            missing = o.optJSONArray("missing").toStringList(),
+            corrupt = o.optJSONArray("corrupt").toStringList(),
+            extra = o.optJSONArray("extra").toStringList(),
+        )
+    }
+
+    /** Returns the number of bytes freed. */
+    fun pruneInstalledBundle(cachePath: String, packages: List<String> = emptyList()): Long =
+        bundleJson(pruneBundle(cachePath, packages.toTypedArray())).optLong("freedBytes")
+
+    private fun bundleJson(json: String): JSONObject {
+        val o = JSONObject(json)
+        o.optStringOrNull("error")?.let { throw BundleException(it) }
+        return o
+    }
+
+    /** `null` JSON → null; `{"error": …}` is treated as "no answer" for lookups. */
+    private fun nullableJson(json: String): JSONObject? {
+        if (json == "null") return null
+        val o = JSONObject(json)
+        return if (o.has("error")) null else o
+    }
+
+    /** A JSON list, or null for `{"error": …}`. */
+    private fun jsonArrayOrNull(json: String): JSONArray? =
+        if (json.trimStart().startsWith("[")) JSONArray(json) else null
+
+    private fun parseBox(o: JSONObject) =
+        PdfBox(o.getDouble("x"), o.getDouble("y"), o.getDouble("width"), o.getDouble("height"))
+
+    private fun JSONArray?.toStringList(): List<String> =
+        if (this == null) emptyList() else (0 until length()).map { getString(it) }
+
+    internal fun parseCompileResult(json: String): CompileResult {
+        val o = JSONObject(json)
+        return CompileResult(
+            pdfPath = o.optStringOrNull("pdfPath"),
+            errors = parseDiagnostics(o.optJSONArray("errors")),
+            warnings = parseDiagnostics(o.optJSONArray("warnings")),
+            log = o.optString("log", ""),
+        )
+    }
+
+    private fun parseDiagnostics(arr: JSONArray?): List<CompileDiagnostic> {
+        if (arr == null) return emptyList()
+        return (0 until arr.length()).map { i ->
+            val d = arr.getJSONObject(i)
+            CompileDiagnostic(
+                file = d.optStringOrNull("file"),
+                line = if (d.isNull("line")) null else d.optInt("line"),
+                message = d.optString("message", ""),
+                isError = d.optString("severity") == "error",
+                context = d.optStringOrNull("context"),
+            )
+        }
+    }
+
+    private fun JSONObject.optStringOrNull(key: String): String? =
+        if (!has(key) || isNull(key)) null else optString(key)
+}
@@ -77,7 +77,9 @@ data class LatexDocument(
     var displayName: String = "",
     var isDirty: Boolean = false,
     /** When known, absolute path to the main .tex file on disk — enables \\input/\\include and local assets. */
-    var sourceAbsolutePath: String? = null
+    var sourceAbsolutePath: String? = null,
+    /** Names the document in [CompileScheduler] requests and its native PDF. */
+    val id: String = UUID.randomUUID().toString(),
 ) {
     fun effectiveName(): String = displayName.ifEmpty { "Untitled" }
 }
@@ -113,6 +115,10 @@ class MainActivity : AppCompatActivity() {
     private var highlightJob: Job? = null
     private var autoSaveJob: Job? = null
     private var undoPushJob: Job? = null
+    private var compileEventsJob: Job? = null
+    private lateinit var compileScheduler: CompileScheduler
+    /** Last native compile of each open document, by [LatexDocument.id]. */
+    private val compileResults = HashMap<String, CompileResult>()
     private val debounceMs = 300L
     private val undoPushDelayMs = 1500L
     private val maxUndoSize = 50
@@ -127,6 +133,8 @@ class MainActivity : AppCompatActivity() {
     private var untitledCounter: Int = 0
     private var lastPreviewErrorLine: Int? = null
     private var lastPreviewErrorMsg: String? = null
+    /** The banner shows a native compile's error rather than one of the HTML preview. */
+    private var compileErrorShown: Boolean = false
     /** Search string for header find bar after dialog is dismissed via Next/Previous. */
     private var activeFindQuery: String = ""
     private var activeReplaceQuery: String = ""
@@ -279,6 +287,7 @@ class MainActivity : AppCompatActivity() {
         btnInsertAdd.contentDescription = getString(R.string.insert_add_button)
         btnInsertAdd.setOnClickListener { v -> requirePro { showInsertPopupMenu(v) } }
         setupFindNavigationBar()
+        setupNativeCompiles()
         previewErrorBanner.setOnClickListener { navigateFromPreviewErrorToEditor() }
         val opensExternalTex =
             intent?.action == Intent.ACTION_VIEW && intent?.data != null && intent.data?.scheme != "livelatex"
@@ -462,6 +471,8 @@ class MainActivity : AppCompatActivity() {
 
     override fun onDestroy() {
         autoSaveJob?.cancel()
+        compileEventsJob?.cancel()
+        documents.forEach { compileScheduler.cancel(it.id) }
         billingHelper.endConnection()
         super.onDestroy()
     }
@@ -562,6 +573,10 @@ class MainActivity : AppCompatActivity() {
             closeDrawer()
             showOptionsSheet()
         }
+        findViewById<Button>(R.id.btnTrustProject).setOnClickListener {
+            closeDrawer()
+            toggleProjectTrust()
+        }
 
         btnClearRecent.setOnClickListener {
             RecentFilesPrefs.clearRecent(this)
@@ -572,6 +587,7 @@ class MainActivity : AppCompatActivity() {
             override fun onDrawerOpened(drawerView: View) {
                 refreshDrawerRecentList()
                 refreshProUi()
+                refreshTrustProjectButton()
             }
             override fun onDrawerClosed(drawerView: View) {}
             override fun onDrawerSlide(drawerView: View, slideOffset: Float) {}
@@ -692,6 +708,40 @@ class MainActivity : AppCompatActivity() {
             .show()
     }
 
+    /** Folder of the current document when it is saved as a file; native compiles treat it as the project. */
+    private fun currentProjectDir(): File? =
+        documents.getOrNull(currentDocIndex)?.sourceAbsolutePath?.let(::File)?.takeIf { it.isFile }?.parentFile
+
+    private fun refreshTrustProjectButton() {
+        val dir = currentProjectDir()
+        findViewById<Button>(R.id.btnTrustProject).setText(
+            if (dir != null && TrustedProjectsPrefs.isTrusted(this, dir.absolutePath)) R.string.untrust_project
+            else R.string.trust_project
+        )
+    }
+
+    /** Trusting lifts the compile sandbox for the project, so it asks first; untrusting does not. */
+    private fun toggleProjectTrust() {
+        val dir = currentProjectDir()
+        if (dir == null) {
+            Toast.makeText(this, R.string.trust_project_unsaved, Toast.LENGTH_SHORT).show()
+            return
+        }
+        if (TrustedProjectsPrefs.isTrusted(this, dir.absolutePath)) {
+            TrustedProjectsPrefs.setTrusted(this, dir.absolutePath, false)
+            Toast.makeText(this, R.string.project_untrusted, Toast.LENGTH_SHORT).show()
+            return
+        }
+        MaterialAlertDialogBuilder(this)
+            .setTitle(R.string.trust_project_title)
+            .setMessage(getString(R.string.trust_project_message, dir.absolutePath))
+            .setPositiveButton(R.string.trust_project_confirm) { _, _ ->
+                TrustedProjectsPrefs.setTrusted(this, dir.absolutePath, true)
+            }
+            .setNegativeButton(R.string.cancel, null)
+            .show()
+    }
+
     private fun refreshDrawerRecentList() {
         val recentList = findViewById<LinearLayout>(R.id.recent_files_list)
         recentList.removeAllViews()
@@ -2222,6 +2272,7 @@ class MainActivity : AppCompatActivity() {
         LatexHighlighter.applyHighlighting(editText.text)
         updateDocumentStats()
         updateCurrentTabLabel()
+        compileScheduler.setVisible(doc.id)
         updatePreviewFromLatex(doc.content)
     }
 
@@ -2573,7 +2624,10 @@ class MainActivity : AppCompatActivity() {
     private fun closeTabAt(index: Int) {
         if (index < 0 || index >= documents.size) return
         syncEditorToCurrentDoc()
-        documents.removeAt(index)
+        documents.removeAt(index).let { closed ->
+            compileScheduler.cancel(closed.id)
+            compileResults.remove(closed.id)
+        }
         if (currentDocIndex >= documents.size) currentDocIndex = (documents.size - 1).coerceAtLeast(0)
         if (currentDocIndex > index) currentDocIndex--
         ensureAtLeastOneDocument()
@@ -2684,6 +2738,9 @@ class MainActivity : AppCompatActivity() {
         webView.isFocusable = true
         webView.isLongClickable = true
         webView.addJavascriptInterface(TikzPreviewJsBridge(), "TikzAndroid")
+        TikzRenderer.svgRenderer = { texDoc, projectDir ->
+            LatexCompiler.renderTikz(texDoc, projectDir.absolutePath, texCachePath()).svgPath?.let(::File)
+        }
         // Let WebView handle long-press for text selection (don't consume the event)
         webView.setOnLongClickListener { false }
         webView.webViewClient = object : WebViewClient() {
@@ -2731,6 +2788,7 @@ class MainActivity : AppCompatActivity() {
     }
 
     private fun updatePreviewFromLatex(latexCode: String) {
+        val compiling = requestNativeCompile(latexCode)
         CoroutineScope(Dispatchers.Default).launch {
             withContext(Dispatchers.Main) {
                 previewLoadingIndicator.visibility = if (showPreview) View.VISIBLE else View.GONE
@@ -2769,30 +2827,23 @@ class MainActivity : AppCompatActivity() {
                     } else latexCode
                 }
                 val tikzBtn = getString(R.string.render_tikz)
-                val tikzAndroidNote = getString(R.string.tikz_preview_no_compiler_note)
                 html = if (mainPath != null) {
-                    LatexHtml.wrapWithInputs(wrapped, mainPath, tikzBtn, tikzAndroidNote)
+                    LatexHtml.wrapWithInputs(wrapped, mainPath, tikzBtn)
                 } else {
-                    LatexHtml.wrap(wrapped, tikzBtn, tikzAndroidNote)
-                }
-                withContext(Dispatchers.Main) {
-                    lastPreviewErrorLine = null
-                    lastPreviewErrorMsg = null
-                    previewErrorBanner.visibility = View.GONE
+                    LatexHtml.wrap(wrapped, tikzBtn)
                 }
+                // A compile error stays up until the compile just requested reports.
+                withContext(Dispatchers.Main) { if (!compiling || !compileErrorShown) hidePreviewErrorBanner() }
             } catch (e: Exception) {
                 val lineNum = previewErrorLineNumber(e, wrapped)
                 val lineInfo = if (lineNum != null) " (line $lineNum)" else ""
                 val fullMsg = e.message ?: e.toString()
                 val msg = fullMsg.replace("<", "&lt;").replace(">", "&gt;")
                 html = """<!DOCTYPE html><html><head><meta charset="UTF-8"><meta name="viewport" content="width=device-width, initial-scale=1"></head><body style="user-select: text; -webkit-user-select: text; -webkit-touch-callout: default;"><div style="user-select: text; -webkit-user-select: text; -webkit-touch-callout: default; padding: 1em;"><p style="color:#b91c1c; white-space: pre-wrap; user-select: text; -webkit-user-select: text; -webkit-touch-callout: default;">Preview error$lineInfo: $msg</p></div></body></html>"""
-                val displayMsg = if (fullMsg.length > 120) fullMsg.take(117) + "…" else fullMsg
                 withContext(Dispatchers.Main) {
-                    lastPreviewErrorLine = lineNum
-                    lastPreviewErrorMsg = fullMsg
-                    previewErrorBannerText.text = if (lineNum != null) getString(R.string.preview_error_banner, lineNum, displayMsg) else getString(R.string.preview_error_banner_no_line, displayMsg)
-                    previewErrorBanner.contentDescription = (if (lineNum != null) getString(R.string.preview_error_banner, lineNum, fullMsg) else getString(R.string.preview_error_banner_no_line, fullMsg)) + ". Tap to fix: opens editor, goes to line if known, saves automatically when there are unsaved changes."
-                    previewErrorBanner.visibility = View.VISIBLE
+                    showPreviewErrorBanner(lineNum, fullMsg) {
+                        if (lineNum != null) getString(R.string.preview_error_banner, lineNum, it) else getString(R.string.preview_error_banner_no_line, it)
+                    }
                 }
             }
             withContext(Dispatchers.Main) {
@@ -2809,6 +2860,91 @@ class MainActivity : AppCompatActivity() {
         }
     }
 
+    /**
+     * Shows [message] in the preview error banner, worded by [text]; tapping it goes to [line] of the current
+     * document when known (see [navigateFromPreviewErrorToEditor]).
+     */
+    private fun showPreviewErrorBanner(line: Int?, message: String, text: (String) -> String) {
+        lastPreviewErrorLine = line
+        lastPreviewErrorMsg = message
+        compileErrorShown = false
+        val displayMsg = if (message.length > 120) message.take(117) + "…" else message
+        previewErrorBannerText.text = text(displayMsg)
+        previewErrorBanner.contentDescription = text(message) + ". Tap to fix: opens editor, goes to line if known, saves automatically when there are unsaved changes."
+        previewErrorBanner.visibility = View.VISIBLE
+    }
+
+    private fun hidePreviewErrorBanner() {
+        lastPreviewErrorLine = null
+        lastPreviewErrorMsg = null
+        compileErrorShown = false
+        previewErrorBanner.visibility = View.GONE
+    }
+
+    /**
+     * Puts the first error of [doc]'s native compile in the preview error banner, at the file and line TeX reported.
+     * Only a line of [doc] itself is jumped to; one in an `\input` file is named in the banner. A compile without
+     * errors clears the banner if it showed an earlier compile's error.
+     */
+    private fun showCompileErrors(doc: LatexDocument, result: CompileResult) {
+        val error = result.errors.firstOrNull()
+        if (error == null) {
+            if (compileErrorShown) hidePreviewErrorBanner()
+            return
+        }
+        val file = error.file?.removePrefix("./")
+        val inDocument = file == null || file == doc.sourceAbsolutePath?.let { File(it).name }
+        val line = error.line
+        showPreviewErrorBanner(line?.takeIf { inDocument }, error.message) {
+            when {
+                line == null -> getString(R.string.compile_error_banner_no_line, it)
+                inDocument -> getString(R.string.compile_error_banner, line, it)
+                else -> getString(R.string.compile_error_banner_in_file, file, line, it)
+            }
+        }
+        compileErrorShown = true
+    }
+
+    /** TeX bundle and format cache passed to rust_core as `cachePath`. */
+    private fun texCachePath(): String = File(filesDir, "tex").absolutePath
+
+    /** True once a TeX bundle is installed or side-loaded; without one native compiles only report that. */
+    private fun nativeEngineReady(): Boolean =
+        File(texCachePath(), "bundle").isDirectory || File(texCachePath(), "bundle.zip").isFile
+
+    /**
+     * Runs the open documents' native compiles and keeps their results; see [requestNativeCompile]. The current
+     * document's errors go to the preview error banner ([showCompileErrors]).
+     */
+    private fun setupNativeCompiles() {
+        compileScheduler = CompileScheduler(texCachePath())
+        compileEventsJob = CoroutineScope(Dispatchers.Main).launch {
+            compileScheduler.events.collect { event ->
+                if (event is CompileEvent.Finished && documents.any { it.id == event.request.docId }) {
+                    compileResults[event.request.docId] = event.result
+                    val current = documents.getOrNull(currentDocIndex)
+                    if (current?.id == event.request.docId) showCompileErrors(current, event.result)
+                }
+            }
+        }
+    }
+
+    /**
+     * Compiles the current document with the native engine in the background, replacing its previous request. Only a
+     * whole document (with `\begin{document}`) is compiled; a saved one as its project's main file, so `\input` and
+     * `\bibliography` resolve. False when nothing was submitted.
+     */
+    private fun requestNativeCompile(latexCode: String): Boolean {
+        val doc = documents.getOrNull(currentDocIndex) ?: return false
+        if (!latexCode.contains("\\begin{document}") || !nativeEngineReady()) return false
+        val main = doc.sourceAbsolutePath?.let(::File)?.takeIf { it.isFile }
+        val options = main?.parentFile?.let { dir -> TrustedProjectsPrefs.compileOptions(this, dir.absolutePath, main.name) }
+            ?: CompileOptions()
+        val output = File(File(filesDir, "pdf"), "${doc.id}.pdf").absolutePath
+        compileScheduler.submit(CompileRequest(doc.id, latexCode, output, options))
+        return true
+    }
+
     /** Directory as `file://` URL with trailing slash for [WebView.loadDataWithBaseURL] so local figures load. */
     private inner class TikzPreviewJsBridge {
         @JavascriptInterface
@@ -0,0 +1,32 @@
+package com.omariskandarani.livelatexapp
+
+import android.content.Context
+
+/**
+ * Project folders the user marked as trusted. Native compiles of these run with
+ * [CompileOptions.trusted] (shell escape, reads outside the project); everything else is sandboxed.
+ */
+object TrustedProjectsPrefs {
+    private const val PREF_NAME = "LiveLatexApp"
+    private const val KEY_TRUSTED = "trusted_projects"
+
+    fun isTrusted(context: Context, projectDir: String): Boolean =
+        projectDir in trusted(context)
+
+    fun setTrusted(context: Context, projectDir: String, trusted: Boolean) {
+        val current = trusted(context).toMutableSet()
+        if (trusted) current += projectDir else current -= projectDir
+        context.getSharedPreferences(PREF_NAME, Context.MODE_PRIVATE)
+            .edit()
+            .putStringSet(KEY_TRUSTED, current)
+            .apply()
+    }
+
+    /** [CompileOptions] for [projectDir], trusted only if the user opted in. */
+    fun compileOptions(context: Context, projectDir: String, mainFile: String? = null): CompileOptions =
+        CompileOptions(projectDir = projectDir, mainFile = mainFile, trusted = isTrusted(context, projectDir))
+
+    private fun trusted(context: Context): Set<String> =
+        context.getSharedPreferences(PREF_NAME, Context.MODE_PRIVATE)
+            .getStringSet(KEY_TRUSTED, null) ?: emptySet()
+}
@@ -0,0 +1,201 @@
+package com.omariskandarani.livelatexapp.latex
+
+import java.io.File
+import java.util.Collections
+
+/**
+ * BibTeX databases for the preview's citations.
+ *
+ * [parseBibTex] reads `@type{key, field = value, …}` entries as BibTeX does: values in braces or quotes,
+ * numbers, `@string` abbreviations and the month names, joined with `#`. `@comment` and `@preamble` are
+ * skipped, and a broken entry is dropped up to the next `@`. Values keep their LaTeX (accents, inner braces).
+ */
+
+/** One entry; [type] and the field names are lowercase. */
+internal data class BibEntry(val type: String, val key: String, val fields: Map<String, String>) {
+    operator fun get(field: String): String? = fields[field]?.takeIf { it.isNotBlank() }
+}
+
+/** A name split as BibTeX does: [last] includes a "von" part (`van Beethoven`). */
+internal data class BibName(val first: String, val last: String)
+
+private val bibCache = Collections.synchronizedMap(HashMap<String, Pair<Long, List<BibEntry>>>())
+
+/** The entries of [file], or null if it cannot be read; parsed again only when the file changes. */
+internal fun readBibFile(file: File): List<BibEntry>? {
+    if (!file.isFile) return null
+    val path = file.absolutePath
+    val modified = file.lastModified()
+    bibCache[path]?.let { (time, entries) -> if (time == modified) return entries }
+    val entries = try {
+        parseBibTex(file.readText())
+    } catch (_: Exception) {
+        return null
+    }
+    bibCache[path] = modified to entries
+    return entries
+}
+
+internal fun parseBibTex(text: String): List<BibEntry> = BibParser(text).entries()
+
+/** The names of an `author` or `editor` field, split at ` and ` outside braces. */
+internal fun parseBibNames(field: String): List<BibName> =
+    splitOutsideBraces(field, Regex("""\s+and\s+""")).map { it.trim() }.filter { it.isNotEmpty() }.map { name ->
+        val parts = splitOutsideBraces(name, Regex(""","""))
+        if (parts.size > 1) {
+            BibName(parts.drop(1).joinToString(", ") { it.trim() }, parts[0].trim())
+        } else {
+            val words = splitOutsideBraces(name, Regex("""\s+"""))
+            // The last name starts at the first lowercase word ("von") or is the last word.
+            val von = words.dropLast(1).indexOfFirst { it.firstOrNull()?.isLowerCase() == true }
+            val at = if (von >= 0) von else words.lastIndex
+            BibName(words.take(at).joinToString(" "), words.drop(at).joinToString(" "))
+        }
+    }
+
+private fun splitOutsideBraces(s: String, separator: Regex): List<String> {
+    val parts = ArrayList<String>()
+    var depth = 0
+    var from = 0
+    var i = 0
+    while (i < s.length) {
+        when (s[i]) {
+            '{' -> depth++
+            '}' -> depth--
+            '\\' -> i++
+            else -> if (depth == 0) {
+                val m = separator.matchAt(s, i)
+                if (m != null && m.value.isNotEmpty()) {
+                    parts.add(s.substring(from, i))
+                    i = m.range.last + 1
+                    from = i
+                    continue
+                }
+            }
+        }
+        i++
+    }
+    parts.add(s.substring(from))
+    return parts
+}
+
+private class BibParser(private val text: String) {
+    private var i = 0
+    private val strings = HashMap(MONTHS)
+
+    fun entries(): List<BibEntry> {
+        val out = ArrayList<BibEntry>()
+        while (true) {
+            val at = text.indexOf('@', i)
+            if (at < 0) break
+            i = at + 1
+            val type = identifier().lowercase()
+            skipBlanks()
+            val open = text.getOrNull(i)
+            if (type.isEmpty() || (open != '{' && open != '(')) continue
+            val close = if (open == '{') '}' else ')'
+            i++
+            when (type) {
+                "comment", "preamble" -> skipEntry(open, close)
+                "string" -> fields(close).forEach { (name, value) -> strings[name] = value }
+                else -> {
+                    skipBlanks()
+                    val keyStart = i
+                    while (i < text.length && text[i] != ',' && text[i] != close && !text[i].isWhitespace()) i++
+                    val key = text.substring(keyStart, i)
+                    if (key.isNotEmpty()) out.add(BibEntry(type, key, fields(close)))
+                }
+            }
+        }
+        return out
+    }
+
+    private fun skipBlanks() {
+        while (i < text.length && text[i].isWhitespace()) i++
+    }
+
+    private fun identifier(): String {
+        skipBlanks()
+        val start = i
+        while (i < text.length && (text[i].isLetterOrDigit() || text[i] in "_-:.+/")) i++
+        return text.substring(start, i)
+    }
+
+    private fun skipEntry(open: Char, close: Char) {
+        var depth = 1
+        while (i < text.length && depth > 0) {
+            if (text[i] == open) depth++ else if (text[i] == close) depth--
+            i++
+        }
+    }
+
+    /** `name = value` pairs up to [close]; stops early at something that is not one. */
+    private fun fields(close: Char): Map<String, String> {
+        val fields = LinkedHashMap<String, String>()
+        while (i < text.length) {
+            skipBlanks()
+            if (text.getOrNull(i) == ',') {
+                i++
+                continue
+            }
+            if (text.getOrNull(i) == close) {
+                i++
+                break
+            }
+            val name = identifier().lowercase()
+            skipBlanks()
+            if (name.isEmpty() || text.getOrNull(i) != '=') break
+            i++
+            fields[name] = value()
+        }
+        return fields
+    }
+
+    /** Parts joined with `#`: `{…}`, `"…"`, a number or an `@string` name. */
+    private fun value(): String = buildString {
+        while (true) {
+            skipBlanks()
+            when (text.getOrNull(i)) {
+                '{' -> {
+                    val end = findBalancedBrace(text, i)
+                    if (end < 0) {
+                        append(text.substring(i + 1))
+                        i = text.length
+                    } else {
+                        append(text, i + 1, end)
+                        i = end + 1
+                    }
+                }
+                '"' -> {
+                    val start = ++i
+                    var depth = 0
+                    while (i < text.length && !(text[i] == '"' && depth == 0)) {
+                        when (text[i]) {
+                            '{' -> depth++
+                            '}' -> depth--
+                            '\\' -> i++
+                        }
+                        i++
+                    }
+                    append(text, start, i.coerceAtMost(text.length))
+                    i++
+                }
+                null -> return@buildString
+                else -> {
+                    val word = identifier()
+                    if (word.isEmpty()) return@buildString
+                    append(if (word.all { it.isDigit() }) word else strings[word.lowercase()].orEmpty())
+                }
+            }
+            skipBlanks()
+            if (text.getOrNull(i) != '#') return@buildString
+            i++
+        }
+    }
+}
+
+private val MONTHS = mapOf(
+    "jan" to "January", "feb" to "February", "mar" to "March", "apr" to "April", "may" to "May", "jun" to "June",
+    "jul" to "July", "aug" to "August", "sep" to "September", "oct" to "October", "nov" to "November",
+    "dec" to "December",
+)
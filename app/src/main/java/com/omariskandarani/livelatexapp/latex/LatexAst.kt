@@ -0,0 +1,524 @@
+package com.omariskandarani.livelatexapp.latex
+
+/**
+ * LaTeX syntax tree for the preview, built by [LatexParser] from [LatexTokenizer] tokens.
+ *
+ * Every node keeps the source range it was parsed from. Arguments are attached to a command or
+ * environment according to its entry in [COMMAND_ARGS] / [ENVIRONMENT_ARGS]; an environment defined by
+ * `\newtheorem` earlier in the source takes an optional title. Commands without an entry get no arguments,
+ * so braces after them are ordinary [GroupNode]s.
+ *
+ * The parser never fails. An unclosed group or environment ends where an enclosing one does (or at the
+ * end of the input), a stray `}` or `\end` is dropped, and math stops at a blank line.
+ */
+internal sealed class LatexNode {
+    abstract val start: Int
+    abstract val end: Int
+}
+
+/** Letters and other characters, as written. */
+internal data class TextNode(val text: String, override val start: Int, override val end: Int) : LatexNode()
+
+/** Blanks with at most one line break. */
+internal data class SpaceNode(val text: String, override val start: Int, override val end: Int) : LatexNode()
+
+/** A blank line or `\par`. */
+internal data class ParNode(val text: String, override val start: Int, override val end: Int) : LatexNode()
+
+/** A comment, or blanks TeX skips: prints nothing, but its line breaks are still lines of the source. */
+internal data class IgnoredNode(val text: String, override val start: Int, override val end: Int) : LatexNode()
+
+internal data class GroupNode(
+    val children: List<LatexNode>,
+    override val start: Int,
+    override val end: Int,
+) : LatexNode()
+
+/** `\name` or `\x`; [args] follow the signature in [COMMAND_ARGS], null where an optional one is absent. */
+internal data class CommandNode(
+    val name: String,
+    val star: Boolean,
+    val args: List<LatexArg?>,
+    override val start: Int,
+    override val end: Int,
+) : LatexNode()
+
+/** `\begin{name}…\end{name}`; [bodyStart] and [bodyEnd] bound the source between the arguments and `\end`. */
+internal data class EnvironmentNode(
+    val name: String,
+    val args: List<LatexArg?>,
+    val children: List<LatexNode>,
+    override val start: Int,
+    override val end: Int,
+    val bodyStart: Int,
+    val bodyEnd: Int,
+) : LatexNode()
+
+/**
+ * Math kept as source for MathJax: `$…$` and `\(…\)` inline, `$$…$$`, `\[…\]` and the [MATH_ENVS] as
+ * display; [env] names the environment. [closed] is false when a blank line or the end of the input came first.
+ */
+internal data class MathNode(
+    val display: Boolean,
+    val closed: Boolean,
+    val env: String?,
+    override val start: Int,
+    override val end: Int,
+) : LatexNode()
+
+/** `\verb` or a braced URL ([env] null), or the body of one of the [VERBATIM_ENVS]. */
+internal data class VerbatimNode(
+    val text: String,
+    val env: String?,
+    override val start: Int,
+    override val end: Int,
+) : LatexNode()
+
+/** `&`, `~`, `#`, `^` or `_` outside math. */
+internal data class SpecialNode(val char: Char, override val start: Int, override val end: Int) : LatexNode()
+
+/** One argument; [start] and [end] bound its content, inside the braces or brackets. */
+internal data class LatexArg(val nodes: List<LatexNode>, val optional: Boolean, val start: Int, val end: Int) {
+    fun source(src: String): String = src.substring(start, end.coerceAtLeast(start))
+}
+
+/** Environments whose body is math, passed to MathJax whole. */
+internal val MATH_ENVS = setOf(
+    "equation", "equation*", "align", "align*", "aligned", "gather", "gather*",
+    "multline", "multline*", "flalign", "flalign*", "alignat", "alignat*",
+    "bmatrix", "pmatrix", "vmatrix", "Bmatrix", "Vmatrix", "smallmatrix",
+    "matrix", "cases", "split", "displaymath", "eqnarray", "eqnarray*",
+)
+
+/**
+ * Argument signatures: `*` an optional star, `o` an optional `[…]`, `m` a mandatory argument (a group or a
+ * single token), `g` an optional `{…}`.
+ */
+internal val COMMAND_ARGS: Map<String, String> = hashMapOf(
+    // Sectioning and front matter
+    "part" to "*om", "chapter" to "*om", "section" to "*om", "subsection" to "*om",
+    "subsubsection" to "*om", "paragraph" to "*om", "subparagraph" to "*om",
+    "title" to "om", "author" to "om", "date" to "m", "thanks" to "m", "llmark" to "om",
+    "texorpdfstring" to "mm", "addcontentsline" to "mmm",
+    // Text
+    "textbf" to "m", "textit" to "m", "textsl" to "m", "emph" to "m", "texttt" to "m", "textsf" to "m",
+    "textrm" to "m", "textsc" to "m", "textup" to "m", "textmd" to "m", "textnormal" to "m",
+    "underline" to "m", "uline" to "m", "sout" to "m", "textsuperscript" to "m", "textsubscript" to "m",
+    "mbox" to "m", "fbox" to "m", "textcolor" to "omm", "colorbox" to "omm", "color" to "om",
+    "ensuremath" to "m", "\\" to "*o", "hspace" to "*m", "vspace" to "*m",
+    "'" to "m", "`" to "m", "^" to "m", "\"" to "m", "~" to "m", "=" to "m", "." to "m",
+    "c" to "m", "u" to "m", "v" to "m", "H" to "m", "r" to "m", "k" to "m", "d" to "m", "b" to "m",
+    // Floats, lists, tables, links
+    "includegraphics" to "*om", "caption" to "om", "label" to "m", "item" to "o", "bibitem" to "om",
+    "multicolumn" to "mmm", "cline" to "m", "cmidrule" to "om", "href" to "mm", "url" to "m",
+    "num" to "om", "si" to "om", "SI" to "omm",
+    // Notes
+    "footnote" to "om", "footnotemark" to "o", "footnotetext" to "om", "marginpar" to "om",
+    "todo" to "om", "missingfigure" to "om", "listoftodos" to "o",
+    // Cross-references
+    "ref" to "m", "eqref" to "m", "autoref" to "*m", "pageref" to "*m",
+    // Citations
+    "cite" to "*oom", "citep" to "*oom", "citet" to "*oom", "citealp" to "*oom", "citealt" to "*oom",
+    "citeauthor" to "*oom", "citeyear" to "*oom", "parencite" to "*oom", "textcite" to "*oom", "autocite" to "*oom",
+    "addbibresource" to "om", "printbibliography" to "o",
+    // Definitions and settings, read but not printed
+    "newcommand" to "*moom", "renewcommand" to "*moom", "providecommand" to "*moom",
+    "DeclareMathOperator" to "*mm", "newenvironment" to "*moomm", "renewenvironment" to "*moomm",
+    "newtheorem" to "*momo", "theoremstyle" to "m", "newtheoremstyle" to "mmmmmmmmm",
+    "newcounter" to "mo", "newlength" to "m",
+    "usepackage" to "om", "RequirePackage" to "om", "documentclass" to "om",
+    "setlength" to "mm", "addtolength" to "mm", "setcounter" to "mm", "addtocounter" to "mm",
+    "stepcounter" to "m", "refstepcounter" to "m", "pagestyle" to "m", "thispagestyle" to "m",
+    "numberwithin" to "omm", "counterwithin" to "*mm", "pagenumbering" to "m", "hypersetup" to "m",
+    "graphicspath" to "m", "geometry" to "m",
+    "captionsetup" to "om", "usetikzlibrary" to "m", "tikzset" to "m", "pgfplotsset" to "m",
+    "fontsize" to "mm", "linespread" to "m", "setstretch" to "m", "index" to "m",
+    "input" to "m", "include" to "m", "nocite" to "m", "bibliographystyle" to "m", "bibliography" to "m",
+)
+
+internal val ENVIRONMENT_ARGS: Map<String, String> = hashMapOf(
+    "figure" to "o", "figure*" to "o", "table" to "o", "table*" to "o", "wrapfigure" to "omom",
+    "tabular" to "om", "longtable" to "om", "tcolorbox" to "o", "multicols" to "mo",
+    "minipage" to "ooom", "itemize" to "o", "enumerate" to "o", "description" to "o",
+    "subfigure" to "om", "thebibliography" to "m", "tikzpicture" to "o", "spacing" to "m",
+    "theorem" to "o", "lemma" to "o", "proposition" to "o", "corollary" to "o",
+    "definition" to "o", "remark" to "o", "identity" to "o", "proof" to "o",
+)
+
+/** Names the TikZ preview compiles itself (`\SSTknot[…]{…}…`): an optional, then up to three groups. */
+private fun isSstMacro(name: String) = name.length > 3 && name.startsWith("SST")
+
+/** Calls [action] for every command in [nodes], arguments and environment bodies included, in source order. */
+internal fun forEachCommand(nodes: List<LatexNode>, action: (CommandNode) -> Unit) {
+    for (n in nodes) when (n) {
+        is CommandNode -> {
+            action(n)
+            for (a in n.args) if (a != null) forEachCommand(a.nodes, action)
+        }
+        is GroupNode -> forEachCommand(n.children, action)
+        is EnvironmentNode -> {
+            for (a in n.args) if (a != null) forEachCommand(a.nodes, action)
+            forEachCommand(n.children, action)
+        }
+        else -> Unit
+    }
+}
+
+internal class LatexParser(private val src: String) {
+    private val tokens = LatexTokenizer(src).tokenize()
+    private var i = 0
+
+    /** Index of the text token [takeFirstChar] last shortened, and where the character it took ended. */
+    private var partial = -1
+    private var partialEnd = 0
+
+    /** What ends the node list being parsed; [open] holds the enclosing ones, innermost last. */
+    private sealed class Until {
+        object Eof : Until()
+        object Group : Until()
+        object Bracket : Until()
+        data class End(val name: String) : Until()
+    }
+
+    private val open = ArrayList<Until>()
+
+    /** Environments `\newtheorem` has defined so far. */
+    private val theorems = HashSet<String>()
+
+    /** Nodes up to the closer, with where the content stops and where the closer ends. */
+    private class Parsed(val nodes: List<LatexNode>, val contentEnd: Int, val end: Int)
+
+    fun parse(): List<LatexNode> = nested(Until.Eof).nodes
+
+    private fun nested(until: Until): Parsed {
+        open += until
+        try {
+            return nodes(until)
+        } finally {
+            open.removeAt(open.lastIndex)
+        }
+    }
+
+    /** Where the input stops: after the last token consumed. */
+    private fun consumedEnd(): Int = when {
+        i == partial -> partialEnd
+        i > 0 -> tokens[i - 1].end
+        else -> 0
+    }
+
+    private fun nodes(until: Until): Parsed {
+        val out = ArrayList<LatexNode>()
+        while (i < tokens.size) {
+            val t = tokens[i]
+            when (t.kind) {
+                TokenKind.EndGroup -> {
+                    if (until == Until.Group) {
+                        i++
+                        return Parsed(out, t.start, t.end)
+                    }
+                    // An enclosing group ends here and takes this list with it; a stray `}` is dropped.
+                    if (Until.Group in open) return Parsed(out, t.start, t.start)
+                    i++
+                }
+                TokenKind.Text -> {
+                    if (t.text == "]" && until == Until.Bracket) {
+                        i++
+                        return Parsed(out, t.start, t.end)
+                    }
+                    i++
+                    out += TextNode(t.text, t.start, t.end)
+                }
+                TokenKind.ControlWord -> when (t.text) {
+                    "end" -> {
+                        val name = envNameAt(i + 1)
+                        when {
+                            name != null && until == Until.End(name.first) -> {
+                                i = name.second
+                                return Parsed(out, t.start, consumedEnd())
+                            }
+                            name != null && Until.End(name.first) in open -> return Parsed(out, t.start, t.start)
+                            else -> i = name?.second ?: (i + 1)
+                        }
+                    }
+                    "begin" -> out += environment()
+                    "def", "gdef", "edef", "xdef" -> out += definition()
+                    "let" -> out += letAssignment()
+                    "par" -> {
+                        i++
+                        out += ParNode(t.text, t.start, t.end)
+                    }
+                    else -> out += command()
+                }
+                TokenKind.ControlSymbol -> when (t.text) {
+                    "(" -> out += math(false, ")")
+                    "[" -> out += math(true, "]")
+                    else -> out += command()
+                }
+                TokenKind.BeginGroup -> out += group()
+                TokenKind.MathShift -> out += dollarMath()
+                else -> {
+                    i++
+                    out += simple(t)
+                }
+            }
+        }
+        return Parsed(out, src.length, src.length)
+    }
+
+    /** Nodes for the tokens that need no look-ahead. */
+    private fun simple(t: Token): LatexNode = when (t.kind) {
+        TokenKind.Space -> SpaceNode(t.text, t.start, t.end)
+        TokenKind.ParBreak -> ParNode(t.text, t.start, t.end)
+        TokenKind.Ignored -> IgnoredNode(t.text, t.start, t.end)
+        TokenKind.Verbatim -> VerbatimNode(t.text, null, t.start, t.end)
+        TokenKind.AlignTab -> SpecialNode('&', t.start, t.end)
+        TokenKind.Param -> SpecialNode('#', t.start, t.end)
+        TokenKind.Superscript -> SpecialNode('^', t.start, t.end)
+        TokenKind.Subscript -> SpecialNode('_', t.start, t.end)
+        TokenKind.Active -> SpecialNode('~', t.start, t.end)
+        else -> TextNode(t.text, t.start, t.end)
+    }
+
+    private fun group(): GroupNode {
+        val t = tokens[i++]
+        val p = nested(Until.Group)
+        return GroupNode(p.nodes, t.start, p.end)
+    }
+
+    private fun command(): CommandNode {
+        val t = tokens[i++]
+        val signature = COMMAND_ARGS[t.text] ?: if (isSstMacro(t.text)) "oggg" else ""
+        var star = false
+        val args = ArrayList<LatexArg?>(signature.length)
+        for (c in signature) when (c) {
+            '*' -> star = star()
+            'o' -> args += optionalArg()
+            'm' -> args += mandatoryArg()
+            'g' -> args += optionalGroup()
+        }
+        if (t.text == "newtheorem") args.getOrNull(0)?.let { theorems += it.source(src).trim() }
+        return CommandNode(t.text, star, args, t.start, consumedEnd())
+    }
+
+    /** `\begin{name}` with its arguments and body up to the matching `\end`. */
+    private fun environment(): LatexNode {
+        val begin = tokens[i]
+        val (name, after) = envNameAt(i + 1) ?: return command()
+        i = after
+        if (name in MATH_ENVS) return mathEnvironment(begin, name)
+        if (name in VERBATIM_ENVS) {
+            val body = tokens.getOrNull(i)?.takeIf { it.kind == TokenKind.Verbatim }
+            if (body != null) i++
+            envNameAt(i + 1)?.takeIf { tokens[i].text == "end" && it.first == name }?.let { i = it.second }
+            return VerbatimNode(body?.text ?: "", name, begin.start, consumedEnd())
+        }
+        val args = ArrayList<LatexArg?>()
+        for (c in ENVIRONMENT_ARGS[name] ?: if (name in theorems) "o" else "") when (c) {
+            'o' -> args += optionalArg()
+            'm' -> args += mandatoryArg()
+        }
+        val bodyStart = consumedEnd()
+        val p = nested(Until.End(name))
+        return EnvironmentNode(name, args, p.nodes, begin.start, p.end, bodyStart, p.contentEnd)
+    }
+
+    /** The `{name}` of `\begin` or `\end` starting at token [at]: the name and the index after `}`. */
+    private fun envNameAt(at: Int): Pair<String, Int>? {
+        var j = at
+        while (j < tokens.size && tokens[j].kind == TokenKind.Space) j++
+        if (tokens.getOrNull(j)?.kind != TokenKind.BeginGroup) return null
+        val name = StringBuilder()
+        j++
+        while (j < tokens.size) {
+            val t = tokens[j]
+            when (t.kind) {
+                TokenKind.EndGroup -> return name.toString().trim() to j + 1
+                TokenKind.Text, TokenKind.Space -> name.append(t.text)
+                else -> return null
+            }
+            j++
+        }
+        return null
+    }
+
+    /** `$…$` or `$$…$$`; an inline formula closed by `$$` takes only its first `$`. */
+    private fun dollarMath(): MathNode {
+        val open = tokens[i++]
+        val display = open.text.length == 2
+        var j = i
+        while (j < tokens.size) {
+            val t = tokens[j]
+            if (t.kind == TokenKind.ParBreak) break
+            if (t.kind == TokenKind.MathShift) {
+                if (display && t.text.length == 2 || !display && t.text.length == 1) {
+                    i = j + 1
+                    return MathNode(display, true, null, open.start, t.end)
+                }
+                if (!display) {
+                    tokens[j] = Token(TokenKind.MathShift, "$", t.start + 1, t.end)
+                    i = j
+                    return MathNode(false, true, null, open.start, t.start + 1)
+                }
+            }
+            j++
+        }
+        i = j
+        return MathNode(display, false, null, open.start, consumedEnd())
+    }
+
+    /** `\(…\)` or `\[…\]`, closed by the control symbol [close]. */
+    private fun math(display: Boolean, close: String): MathNode {
+        val open = tokens[i++]
+        var j = i
+        while (j < tokens.size && tokens[j].kind != TokenKind.ParBreak) {
+            val t = tokens[j]
+            if (t.kind == TokenKind.ControlSymbol && t.text == close) {
+                i = j + 1
+                return MathNode(display, true, null, open.start, t.end)
+            }
+            j++
+        }
+        i = j
+        return MathNode(display, false, null, open.start, consumedEnd())
+    }
+
+    /** A [MATH_ENVS] environment from its `\begin` token up to the matching `\end`. */
+    private fun mathEnvironment(begin: Token, name: String): MathNode {
+        var depth = 1
+        var j = i
+        while (j < tokens.size && tokens[j].kind != TokenKind.ParBreak) {
+            val t = tokens[j]
+            if (t.kind == TokenKind.ControlWord && (t.text == "begin" || t.text == "end")) {
+                val env = envNameAt(j + 1)
+                if (env != null && env.first == name) {
+                    depth += if (t.text == "begin") 1 else -1
+                    if (depth == 0) {
+                        i = env.second
+                        return MathNode(true, true, name, begin.start, consumedEnd())
+                    }
+                }
+            }
+            j++
+        }
+        i = j
+        return MathNode(true, false, name, begin.start, consumedEnd())
+    }
+
+    /** `\def\name#1#2{body}`: the name, the parameter text and the body as three arguments. */
+    private fun definition(): CommandNode {
+        val t = tokens[i++]
+        skipBlanks()
+        val nameTok = tokens.getOrNull(i)
+        if (nameTok == null || nameTok.kind != TokenKind.ControlWord && nameTok.kind != TokenKind.ControlSymbol) {
+            return CommandNode(t.text, false, emptyList(), t.start, t.end)
+        }
+        i++
+        val name = LatexArg(listOf(CommandNode(nameTok.text, false, emptyList(), nameTok.start, nameTok.end)), false, nameTok.start, nameTok.end)
+        val paramStart = consumedEnd()
+        while (i < tokens.size && tokens[i].kind != TokenKind.BeginGroup && tokens[i].kind != TokenKind.ParBreak) i++
+        val params = LatexArg(emptyList(), false, paramStart, tokens.getOrNull(i)?.start ?: src.length)
+        val body = mandatoryArg()
+        return CommandNode(t.text, false, listOf(name, params, body), t.start, consumedEnd())
+    }
+
+    /** `\let\a\b` or `\let\a=\b`. */
+    private fun letAssignment(): CommandNode {
+        val t = tokens[i++]
+        val target = mandatoryArg()
+        val save = i
+        skipBlanks()
+        if (tokens.getOrNull(i)?.text == "=") i++ else i = save
+        val value = mandatoryArg()
+        return CommandNode(t.text, false, listOf(target, value), t.start, consumedEnd())
+    }
+
+    private fun skipBlanks() {
+        while (i < tokens.size && (tokens[i].kind == TokenKind.Space || tokens[i].kind == TokenKind.Ignored)) i++
+    }
+
+    /** Takes the first character of the text token at [i] alone; the rest of the token stays at [i]. */
+    private fun takeFirstChar(): Token {
+        val t = tokens[i]
+        if (t.text.length <= 1) return tokens[i++]
+        tokens[i] = Token(TokenKind.Text, t.text.substring(1), t.start + 1, t.end)
+        partial = i
+        partialEnd = t.start + 1
+        return Token(TokenKind.Text, t.text.substring(0, 1), t.start, t.start + 1)
+    }
+
+    private fun star(): Boolean {
+        val t = tokens.getOrNull(i) ?: return false
+        if (t.kind != TokenKind.Text || !t.text.startsWith("*")) return false
+        takeFirstChar()
+        return true
+    }
+
+    /** `[…]` after optional blanks, if a `]` closes it before the paragraph ends; otherwise nothing is consumed. */
+    private fun optionalArg(): LatexArg? {
+        val save = i
+        skipBlanks()
+        val t = tokens.getOrNull(i)
+        if (t == null || t.kind != TokenKind.Text || t.text != "[" || !closedBracketAhead()) {
+            i = save
+            return null
+        }
+        i++
+        val p = nested(Until.Bracket)
+        return LatexArg(p.nodes, true, t.end, p.contentEnd)
+    }
+
+    private fun closedBracketAhead(): Boolean {
+        var depth = 0
+        for (j in i + 1 until tokens.size) {
+            val t = tokens[j]
+            when (t.kind) {
+                TokenKind.BeginGroup -> depth++
+                TokenKind.EndGroup -> if (--depth < 0) return false
+                TokenKind.ParBreak -> return false
+                TokenKind.Text -> if (t.text == "]" && depth == 0) return true
+                else -> Unit
+            }
+        }
+        return false
+    }
+
+    private fun optionalGroup(): LatexArg? {
+        if (tokens.getOrNull(i)?.kind != TokenKind.BeginGroup) return null
+        return mandatoryArg()
+    }
+
+    /** A braced group, or else the next single token (character or command); null if there is none. */
+    private fun mandatoryArg(): LatexArg? {
+        val save = i
+        skipBlanks()
+        val t = tokens.getOrNull(i)
+        when (t?.kind) {
+            null, TokenKind.EndGroup, TokenKind.ParBreak -> {
+                i = save
+                return null
+            }
+            TokenKind.BeginGroup -> {
+                i++
+                val p = nested(Until.Group)
+                return LatexArg(p.nodes, false, t.end, p.contentEnd)
+            }
+            TokenKind.Verbatim -> {
+                i++
+                // A braced URL: its content lies inside the braces the token spans.
+                return LatexArg(listOf(VerbatimNode(t.text, null, t.start, t.end)), false, t.start + 1, t.end - 1)
+            }
+            TokenKind.Text -> {
+                val c = takeFirstChar()
+                return LatexArg(listOf(TextNode(c.text, c.start, c.end)), false, c.start, c.end)
+            }
+            TokenKind.ControlWord, TokenKind.ControlSymbol -> {
+                i++
+                return LatexArg(listOf(CommandNode(t.text, false, emptyList(), t.start, t.end)), false, t.start, t.end)
+            }
+            else -> {
+                i++
+                return LatexArg(listOf(simple(t)), false, t.start, t.end)
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+package com.omariskandarani.livelatexapp.latex
+
+import java.io.File
+
+/**
+ * The citations of the preview, read before [LatexHtmlEmitter] runs: what each `\cite` key refers to, how it
+ * prints and what the reference list contains, as BibTeX with natbib or biblatex would have it.
+ *
+ * Works come from `thebibliography`'s `\bibitem`s, or from the .bib files of `\bibliography` and
+ * `\addbibresource`, read next to the main file ([currentBaseDir]). A .bib file gives the list its cited works
+ * and those of `\nocite` (all of them for `\nocite{*}`): in citation order for the unsorted styles (`unsrt`,
+ * `ieeetr`, biblatex's `sorting=none`), by author, year and title otherwise.
+ *
+ * Citations are numeric, except with natbib without `numbers` (unless a numeric `\bibliographystyle` is set)
+ * and with biblatex in an authoryear, authortitle, apa or chicago style. Author-year labels that would be
+ * the same get letters after the year (`1984a`, `1984b`).
+ */
+
+/**
+ * A work that can be cited. [label] is what a numeric citation prints (`3`, or the `\bibitem`'s own label),
+ * [authors] (LaTeX) and [year] what an author-year one prints; [anchor] is the HTML id of its list entry.
+ */
+internal class CitedWork(
+    val key: String,
+    val entry: BibEntry?,
+    val label: String,
+    val authors: String,
+    val year: String,
+    val anchor: String,
+)
+
+/** Commands that cite, all with the signature `*oom`: one optional argument is a note after, two are before and after. */
+internal val CITE_COMMANDS = setOf(
+    "cite", "citep", "citet", "citealp", "citealt", "citeauthor", "citeyear",
+    "parencite", "textcite", "autocite",
+)
+
+/** The keys of a citation's key list. */
+internal fun citeKeys(list: String): List<String> = list.split(',').map { it.trim() }.filter { it.isNotEmpty() }
+
+internal class LatexCitations(private val src: String, ast: List<LatexNode>) {
+    /** Whether citations print author and year rather than a number. */
+    var authorYear = false
+        private set
+
+    /** Whether biblatex is loaded (rather than natbib or nothing). */
+    var biblatex = false
+        private set
+
+    /** The list printed at `\bibliography` and `\printbibliography`, in order. */
+    val works: List<CitedWork>
+
+    /** Cited keys with no work: the key and the offset of its first citation. */
+    val undefined: List<Pair<String, Int>>
+
+    /** .bib files named in the document that could not be read. */
+    val missingFiles: List<String>
+
+    private val byKey = HashMap<String, CitedWork>()
+    private val cited = LinkedHashMap<String, Int>()
+    private var citeAll = false
+    private var unsorted = false
+    private var anchorCount = 0
+
+    init {
+        val bibFiles = ArrayList<String>()
+        var natbib: String? = null
+        var biblatexOptions: String? = null
+        var bibliographyStyle = ""
+        val bibItems = ArrayList<CommandNode>()
+        forEachCommand(ast) { c ->
+            when (c.name) {
+                in CITE_COMMANDS -> citeKeys(arg(c, 2)).forEach { cited.putIfAbsent(it, c.start) }
+                "nocite" -> citeKeys(arg(c, 0)).forEach { if (it == "*") citeAll = true else cited.putIfAbsent(it, c.start) }
+                "bibliography" -> citeKeys(arg(c, 0)).forEach { bibFiles += if (it.endsWith(".bib")) it else "$it.bib" }
+                "addbibresource" -> bibFiles.add(arg(c, 1).trim())
+                "bibliographystyle" -> { bibliographyStyle = arg(c, 0).trim() }
+                "bibitem" -> bibItems.add(c)
+                "usepackage", "RequirePackage" -> {
+                    val names = citeKeys(arg(c, 1))
+                    if ("natbib" in names) natbib = arg(c, 0)
+                    if ("biblatex" in names) biblatexOptions = arg(c, 0)
+                }
+            }
+        }
+
+        val options = biblatexOptions
+        if (options != null) {
+            biblatex = true
+            val kv = packageOptions(options)
+            val style = kv["citestyle"] ?: kv["style"] ?: "numeric"
+            authorYear = AUTHOR_YEAR_STYLES.any { style.startsWith(it) }
+            unsorted = kv["sorting"] == "none"
+        } else {
+            natbib?.let { authorYear = "numbers" !in packageOptions(it) && bibliographyStyle !in NUMERIC_STYLES }
+            unsorted = bibliographyStyle.startsWith("unsrt") || bibliographyStyle in UNSORTED_STYLES
+        }
+
+        val missing = ArrayList<String>()
+        val entries = LinkedHashMap<String, BibEntry>()
+        for (name in bibFiles.distinct()) {
+            val file = File(name).takeIf { it.isAbsolute } ?: File(currentBaseDir ?: ".", name)
+            val read = readBibFile(file)
+            if (read == null) missing += name else read.forEach { entries.putIfAbsent(it.key, it) }
+        }
+        missingFiles = missing
+
+        val listed = if (citeAll) entries.values.toList() else cited.keys.mapNotNull { entries[it] }
+        val ordered = if (unsorted) listed else listed.sortedWith(
+            compareBy<BibEntry>({ sortNames(it) }, { it["year"].orEmpty() }, { plainLetters(it["title"].orEmpty()) }),
+        )
+        works = ordered.map { e -> CitedWork(e.key, e, "", shortAuthors(e), e["year"] ?: e["date"]?.take(4) ?: "n.d.", nextAnchor()) }
+            .let { withYearLetters(it) }
+            .mapIndexed { k, w -> CitedWork(w.key, w.entry, "${k + 1}", w.authors, w.year, w.anchor) }
+        works.forEach { byKey.putIfAbsent(it.key, it) }
+
+        bibItems.forEachIndexed { k, item ->
+            val key = arg(item, 1).trim()
+            val label = item.args.getOrNull(0)?.source(src)?.trim()
+            val natbibLabel = label?.let { NATBIB_LABEL.matchEntire(it) }
+            val work = if (natbibLabel != null) {
+                val (authors, year) = natbibLabel.destructured
+                CitedWork(key, null, "${k + 1}", authors.trim(), year.trim(), nextAnchor())
+            } else {
+                CitedWork(key, null, label ?: "${k + 1}", label ?: key, "", nextAnchor())
+            }
+            byKey.putIfAbsent(key, work)
+        }
+
+        undefined = cited.filterKeys { it !in byKey }.toList()
+    }
+
+    /** The work cited as [key]. */
+    fun work(key: String): CitedWork? = byKey[key]
+
+    /** The reference list entry of [work] as LaTeX, laid out as plain(nat) or biblatex's standard styles do. */
+    fun reference(work: CitedWork): String {
+        val e = work.entry ?: return ""
+        val editors = e["editor"]?.let(::parseBibNames).orEmpty()
+        val editedBy = if (editors.isEmpty()) null else fullNames(editors) + if (editors.size > 1) ", editors" else ", editor"
+        val authors = e["author"]?.let { fullNames(parseBibNames(it)) } ?: editedBy
+        val year = if (authorYear) null else work.year
+        val title = e["title"]
+        val sentences = ArrayList<String>()
+        if (authorYear) sentences += listOfNotNull(authors, "(${work.year})").joinToString(" ")
+        else authors?.let(sentences::add)
+        when (e.type) {
+            "article" -> {
+                title?.let(sentences::add)
+                val volume = listOfNotNull(e["volume"], e["number"]?.let { "($it)" }).joinToString("")
+                val where = e["pages"]?.let { if (volume.isEmpty()) pageRange(it) else "$volume:$it" } ?: volume
+                sentences += listOfNotNull(e["journal"]?.let { "\\emph{$it}" }, where.ifEmpty { null }, year).joinToString(", ")
+            }
+            "book", "booklet", "manual", "proceedings" -> {
+                title?.let { sentences += "\\emph{$it}" }
+                sentences += listOfNotNull(e["publisher"] ?: e["organization"], e["address"], year).joinToString(", ")
+            }
+            "inproceedings", "incollection", "inbook", "conference" -> {
+                title?.let(sentences::add)
+                val inWhat = listOfNotNull(
+                    editedBy.takeIf { e["author"] != null }, e["booktitle"]?.let { "\\emph{$it}" },
+                    e["pages"]?.let { pageRange(it) }, e["address"], year,
+                )
+                if (inWhat.isNotEmpty()) sentences += "In " + inWhat.joinToString(", ")
+                e["publisher"]?.let(sentences::add)
+            }
+            "phdthesis", "mastersthesis", "thesis" -> {
+                title?.let { sentences += "\\emph{$it}" }
+                val kind = e["type"] ?: if (e.type == "mastersthesis") "Master's thesis" else "PhD thesis"
+                sentences += listOfNotNull(kind, e["school"] ?: e["institution"], e["address"], year).joinToString(", ")
+            }
+            "techreport", "report" -> {
+                title?.let(sentences::add)
+                val report = listOfNotNull(e["type"] ?: "Technical Report", e["number"]).joinToString(" ")
+                sentences += listOfNotNull(report, e["institution"], e["address"], year).joinToString(", ")
+            }
+            else -> {
+                title?.let(sentences::add)
+                listOfNotNull(e["howpublished"], year).takeIf { it.isNotEmpty() }?.let { sentences += it.joinToString(", ") }
+            }
+        }
+        e["note"]?.let(sentences::add)
+        val text = sentences.filter { it.isNotBlank() }.joinToString(" ") { s ->
+            val t = s.trim()
+            if (t.last() in ".?!") t else "$t."
+        }
+        val links = listOfNotNull(
+            e["doi"]?.let { "\\href{https://doi.org/$it}{doi:$it}" },
+            e["url"]?.let { "\\url{$it}" },
+        )
+        return (listOf(text) + links).filter { it.isNotEmpty() }.joinToString(" ")
+    }
+
+    private fun arg(c: CommandNode, index: Int): String = c.args.getOrNull(index)?.source(src).orEmpty()
+
+    private fun nextAnchor(): String = "ll-cite-${++anchorCount}"
+
+    /** "Knuth", "Knuth and Plass", "Knuth et al." — for an author-year citation. */
+    private fun shortAuthors(e: BibEntry): String {
+        val names = (e["author"] ?: e["editor"])?.let(::parseBibNames).orEmpty()
+        val others = names.any { it.last == "others" }
+        val lasts = names.filter { it.last != "others" }.map { it.last }
+        return when {
+            lasts.isEmpty() -> e["organization"] ?: e["institution"] ?: e.key
+            lasts.size == 1 && !others -> lasts[0]
+            lasts.size == 2 && !others -> "${lasts[0]} and ${lasts[1]}"
+            else -> "${lasts[0]} et~al."
+        }
+    }
+
+    /** "Donald E. Knuth and Michael F. Plass" for numeric lists; author-year ones start with "Knuth, Donald E.". */
+    private fun fullNames(names: List<BibName>): String {
+        val others = names.any { it.last == "others" }
+        val full = names.filter { it.last != "others" }.mapIndexed { k, n ->
+            when {
+                n.first.isEmpty() -> n.last
+                k == 0 && authorYear -> "${n.last}, ${n.first}"
+                else -> "${n.first} ${n.last}"
+            }
+        }
+        return when {
+            others -> full.joinToString(", ") + " et~al."
+            full.size <= 2 -> full.joinToString(" and ")
+            else -> full.dropLast(1).joinToString(", ") + ", and " + full.last()
+        }
+    }
+
+    private fun pageRange(range: String): String = if (range.any { it == '-' || it == ',' }) "pages $range" else "page $range"
+
+    /** Letters after the year of works that would otherwise print the same author-year label. */
+    private fun withYearLetters(list: List<CitedWork>): List<CitedWork> {
+        if (!authorYear) return list
+        val groups = list.groupBy { it.authors to it.year }
+        val seen = HashMap<Pair<String, String>, Int>()
+        return list.map { w ->
+            val group = w.authors to w.year
+            if (groups.getValue(group).size < 2) return@map w
+            val k = (seen[group] ?: 0) + 1
+            seen[group] = k
+            CitedWork(w.key, w.entry, w.label, w.authors, w.year + ('a' + k - 1), w.anchor)
+        }
+    }
+
+    /** The names to sort by, as letters only: `{\"O}zt{\"u}rk` sorts as `ozturk`. */
+    private fun sortNames(e: BibEntry): String =
+        plainLetters((e["author"] ?: e["editor"])?.let(::parseBibNames).orEmpty().joinToString(" ") { "${it.last} ${it.first}" })
+
+    private fun plainLetters(s: String): String =
+        s.replace(Regex("""\\[a-zA-Z]+"""), "").filter { it.isLetterOrDigit() || it == ' ' }.lowercase()
+
+    /** `a, b=c` → {a=, b=c}. */
+    private fun packageOptions(s: String): Map<String, String> =
+        citeKeys(s).associate { o -> o.substringBefore('=').trim() to o.substringAfter('=', "").trim() }
+}
+
+/** `Knuth et~al.(1984)Knuth, Plass` — natbib's `\bibitem` label: short authors, then the year in parentheses. */
+private val NATBIB_LABEL = Regex("""(.*?)\(([^()]*)\)(.*)""", RegexOption.DOT_MATCHES_ALL)
+
+private val AUTHOR_YEAR_STYLES = listOf("authoryear", "authortitle", "apa", "chicago")
+
+private val NUMERIC_STYLES = setOf(
+    "plain", "unsrt", "alpha", "abbrv", "ieeetr", "IEEEtran", "acm", "siam", "amsplain",
+)
+
+/** Besides the `unsrt…` styles. */
+private val UNSORTED_STYLES = setOf("ieeetr", "IEEEtran", "naturemag", "apsrev4-2")
@@ -5,12 +5,11 @@ import java.io.IOException
 import java.nio.file.Paths
 import java.util.LinkedHashMap
 import kotlin.text.Regex
-import kotlin.text.RegexOption
 
 /**
  * Minimal LaTeX → HTML previewer for prose + MathJax math.
- * - Parses user \newcommand / \def into MathJax macros
- * - Converts common prose constructs (sections, lists, tables, theorems, etc.)
+ * - Tokenizes and parses the source once ([LatexTokenizer], [LatexParser]) and walks the tree ([LatexHtmlEmitter])
+ * - Turns user \newcommand / \def into MathJax macros
  * - Leaves math regions intact ($...$, \[...\], \(...\), equation/align/...)
  * - Inserts invisible line anchors to sync scroll with editor
  */
@@ -27,72 +26,32 @@ object LatexHtml {
     private var lineMapMergedToOrigJson: String? = null
 
     // ─────────────────────────── PUBLIC ENTRY ───────────────────────────
-    // BEGIN_DOCUMENT, END_DOCUMENT, slugify → LatexHtmlParsing.kt
 
     fun wrap(
         texSource: String,
         tikzRenderButtonLabel: String = "Render TikZ",
-        tikzNoCompilerNote: String? = null,
     ): String {
         lazyTikzJobs.clear()
         val srcNoComments = stripLineComments(texSource)
-        val userMacros    = extractNewcommands(srcNoComments)
-        val macrosJs      = buildMathJaxMacros(userMacros)
-        val titleMeta     = extractTitleMeta(srcNoComments)
+        val ast           = LatexParser(texSource).parse()
+        val macrosJs      = buildMathJaxMacros(extractNewcommands(ast, texSource))
         val tikzPreamble  = TikzRenderer.collectTikzPreamble(srcNoComments)
 
-        // Find body & absolute line offset of the first body line
-        val beginIdx  = texSource.indexOf(BEGIN_DOCUMENT)
-        val absOffset = if (beginIdx >= 0)
-            texSource.substring(0, beginIdx).count { it == '\n' } + 1
-        else
-            1
-
-        val body0 = stripPreamble(texSource)
-        val body1 = stripLineComments(body0)
-        // Prepend title/author/date so they always appear before the abstract in the preview
-        val body1b = if (titleMeta.title != null || titleMeta.authors != null || titleMeta.dateRaw != null)
-            buildMakTitleHtml(titleMeta) + "\n\n" + body1
-        else body1
-        val body2 = sanitizeForMathJaxProse(body1b)
-        val body2b = convertIncludeGraphics(body2)
-
-        val body2c = TikzRenderer.convertTikzPictures(
-            body2b, srcNoComments, tikzPreamble, tikzRenderButtonLabel, tikzNoCompilerNote
-        )
-        val body2d = TikzRenderer.convertSstTikzMacros(body2c, srcNoComments)
-
-        val body3 = applyProseConversions(body2d, titleMeta, absOffset, srcNoComments, tikzPreamble)
-        val body3b = convertParagraphsOutsideTags(body3)
-        val body4 = applyInlineFormattingOutsideTags(body3b)
-        val body4c = fixInlineBoundarySpaces(body4)
-        // Insert anchors (no blanket escaping here; we preserve math)
-        val withAnchors = injectLineAnchors(body4c, absOffset, everyN = 1)
-
-        return buildHtml(withAnchors, macrosJs, lineMapOrigToMergedJson, lineMapMergedToOrigJson)
-    }
-
-    private fun applyProseConversions(s: String, meta: TitleMeta, absOffset: Int,
-                                      fullSourceNoComments: String, tikzPreamble: String): String {
-        var t = s
-        t = convertLlmark(t, absOffset)
-        t = convertMakeTitle(t, meta)
-        t = convertSiunitx(t)
-        t = convertHref(t)
-        t = convertSections(t, absOffset)
-        t = convertFigureEnvs(t)
-        t = convertIncludeGraphics(t)
-        t = convertMulticols(t)
-        t = convertLongtablesToTables(t)
-        t = convertTcolorboxes(t)
-        t = convertTableEnvs(t)
-        t = convertItemize(t)
-        t = convertEnumerate(t)
-        t = convertDescription(t)
-        t = convertTabulars(t)
-        t = convertTheBibliography(t)
-        t = stripAuxDirectives(t)
-        return t
+        val body = LatexHtmlEmitter(
+            texSource,
+            LatexNumbering(texSource, ast),
+            LatexCitations(texSource, ast),
+            tikzPicture = { options, picture ->
+                TikzRenderer.convertTikzPicture(
+                    options, picture, srcNoComments, tikzPreamble, tikzRenderButtonLabel
+                )
+            },
+            sstMacro = {
+                TikzRenderer.convertSstTikzMacro(it, srcNoComments, tikzRenderButtonLabel)
+            },
+        ).document(ast)
+
+        return buildHtml(body, macrosJs, lineMapOrigToMergedJson, lineMapMergedToOrigJson)
     }
 
     // buildHtml → LatexHtmlTemplate.kt
@@ -100,52 +59,29 @@ object LatexHtml {
     // ───────────────────────────── MACROS ─────────────────────────────
     private data class Macro(val def: String, val nargs: Int)
 
-    private fun extractNewcommands(s: String): Map<String, Macro> {
+    private fun extractNewcommands(ast: List<LatexNode>, src: String): Map<String, Macro> {
         val out = LinkedHashMap<String, Macro>()
-        fun parseCommand(cmd: String) {
-            val rx = Regex("""\\$cmd\s*\{\\([A-Za-z@]+)\}(?:\s*\[(\d+)])?(?:\s*\[(.*?)\])?\s*\{""", RegexOption.DOT_MATCHES_ALL)
-            var pos = 0
-            while (true) {
-                val m = rx.find(s, pos) ?: break
-                val name = m.groupValues[1]
-                val nargs = m.groupValues[2].ifEmpty { "0" }.toInt()
-                val bodyOpen = m.range.last
-                val bodyClose = findBalancedBrace(s, bodyOpen)
-                if (bodyClose < 0) { pos = bodyOpen + 1; continue }
-                val body = s.substring(bodyOpen + 1, bodyClose).trim()
-                out[name] = Macro(body, nargs)
-                pos = bodyClose + 1
-            }
-        }
-        parseCommand("newcommand")
-        parseCommand("renewcommand")
-        parseCommand("providecommand")
-        run {
-            val rx = Regex("""\\def\\([A-Za-z@]+)\s*\{""")
-            var pos = 0
-            while (true) {
-                val m = rx.find(s, pos) ?: break
-                val name = m.groupValues[1]
-                val open = m.range.last
-                val close = findBalancedBrace(s, open)
-                if (close < 0) { pos = open + 1; continue }
-                val body = s.substring(open + 1, close).trim()
-                out.putIfAbsent(name, Macro(body, 0))
-                pos = close + 1
-            }
-        }
-        run {
-            val rx = Regex("""\\DeclareMathOperator\*?\s*\{\\([A-Za-z@]+)\}\s*\{""")
-            var pos = 0
-            while (true) {
-                val m = rx.find(s, pos) ?: break
-                val name = m.groupValues[1]
-                val open = m.range.last
-                val close = findBalancedBrace(s, open)
-                if (close < 0) { pos = open + 1; continue }
-                val opText = s.substring(open + 1, close).trim()
-                out.putIfAbsent(name, Macro("\\operatorname{$opText}", 0))
-                pos = close + 1
+        fun nameOf(arg: LatexArg?): String? = (arg?.nodes?.firstOrNull { it is CommandNode } as CommandNode?)?.name
+        forEachCommand(ast) { c ->
+            when (c.name) {
+                "newcommand", "renewcommand", "providecommand" -> {
+                    val name = nameOf(c.args.getOrNull(0)) ?: return@forEachCommand
+                    val body = c.args.getOrNull(3) ?: return@forEachCommand
+                    val nargs = c.args.getOrNull(1)?.source(src)?.trim()?.toIntOrNull() ?: 0
+                    out[name] = Macro(body.source(src).trim(), nargs)
+                }
+                "def", "gdef", "edef", "xdef" -> {
+                    val name = nameOf(c.args.getOrNull(0)) ?: return@forEachCommand
+                    val body = c.args.getOrNull(2) ?: return@forEachCommand
+                    val nargs = c.args.getOrNull(1)?.source(src)?.count { it == '#' } ?: 0
+                    out.putIfAbsent(name, Macro(body.source(src).trim(), nargs))
+                }
+                "DeclareMathOperator" -> {
+                    val name = nameOf(c.args.getOrNull(0)) ?: return@forEachCommand
+                    val opText = c.args.getOrNull(1)?.source(src)?.trim() ?: return@forEachCommand
+                    val op = if (c.star) "\\operatorname*" else "\\operatorname"
+                    out.putIfAbsent(name, Macro("$op{$opText}", 0))
+                }
             }
         }
         return out
@@ -164,6 +100,7 @@ object LatexHtml {
             "qty" to Macro("\\left(#1\\right)", 1),
             "qtyb" to Macro("\\left[#1\\right]", 1),
             "qed" to Macro("\\square", 0),
+            "qedhere" to Macro("\\quad\\square", 0),
             "si" to Macro("\\mathrm{#1}", 1),
             "num" to Macro("{#1}", 1),
             "textrm" to Macro("\\mathrm{#1}", 1),
@@ -260,8 +197,6 @@ object LatexHtml {
         false to (e.message ?: e.toString())
     }
 
-    // convertLongtablesToTables → LatexHtmlBlocks.kt
-
     // --- path where we cache compiled SVGs
     private fun tikzCacheDir(): File {
         val base = currentBaseDir?.let(::File) ?: File(".")
@@ -281,14 +216,18 @@ object LatexHtml {
     @JvmStatic
     fun renderLazyTikzKeyToSvg(key: String): File? {
         return try {
-            if (isAndroidRuntime) return null
-
             val texDoc = synchronized(lazyTikzJobs) { lazyTikzJobs[key] } ?: return null
 
             val cache = tikzCacheDir()
             val svg = File(cache, "${sha1(texDoc)}.svg")
             if (svg.exists()) return svg
 
+            // On device the embedded engine (rust_core) writes the SVG itself.
+            if (isAndroidRuntime) {
+                val render = TikzRenderer.svgRenderer ?: return null
+                return render(texDoc, currentBaseDir?.let(::File) ?: File("."))
+            }
+
             val work = File(cache, "job-$key").apply { mkdirs() }
             val tex = File(work, "fig.tex")
             val pdf = File(work, "fig.pdf")
@@ -370,7 +309,6 @@ object LatexHtml {
         texSource: String,
         mainFilePath: String,
         tikzRenderButtonLabel: String = "Render TikZ",
-        tikzNoCompilerNote: String? = null,
     ): String {
         val baseDir = directoryForMainTex(mainFilePath)
         currentBaseDir = baseDir  // package-level in LatexHtmlState.kt
@@ -421,7 +359,7 @@ object LatexHtml {
         lineMapOrigToMergedJson = o2m.joinToString(prefix = "[", postfix = "]") { it.toString() }
         lineMapMergedToOrigJson = m2o.joinToString(prefix = "[", postfix = "]") { it.toString() }
 
-        val html = wrap(fullSource, tikzRenderButtonLabel, tikzNoCompilerNote)
+        val html = wrap(fullSource, tikzRenderButtonLabel)
         // keep baseDir for subsequent renders; do not clear to allow incremental refreshes
         return html
     }
@@ -0,0 +1,1085 @@
+package com.omariskandarani.livelatexapp.latex
+
+import java.text.Normalizer
+import java.time.LocalDate
+import java.time.format.DateTimeFormatter
+
+/**
+ * [LatexParser] nodes → HTML, the last stage of the preview, in one pass in source order:
+ * - prose is HTML-escaped; math goes through as source for MathJax
+ * - each line break outside math leaves a `.syncline` anchor and each heading an `.llmark`, both carrying the
+ *   1-based source line, for scroll sync with the editor
+ * - paragraphs open at the first printed content and close at a blank line or before a block
+ * - declarations (`\bfseries`, `\small`, `\color{…}`) last to the end of their group or environment
+ * - unknown environments are transparent; an unknown command prints as its source, with the braced groups
+ *   right after it
+ * - footnotes print a linked number; their texts follow at the end of the section, before the next `\section`,
+ *   `\chapter` or `\part`. `\marginpar` and todonotes' `\todo` float to the right of the text
+ *
+ * [numbering] gives headings, captions, equations, items, theorems and footnotes their numbers and `\ref` its targets;
+ * [citations] gives `\cite` its works and the reference list its entries.
+ * [tikzPicture] gets a `tikzpicture`'s options (`[…]` or empty) and body, [sstMacro] the source of an `\SST…`
+ * macro; both return finished HTML blocks. [anchors] is false for fragments that are not part of the source.
+ */
+internal class LatexHtmlEmitter(
+    private val src: String,
+    private val numbering: LatexNumbering,
+    private val citations: LatexCitations,
+    private val tikzPicture: (options: String, body: String) -> String,
+    private val sstMacro: (source: String) -> String,
+    private val anchors: Boolean = true,
+) {
+    private enum class Flow { Paragraphs, Inline }
+
+    /** Where output goes: [Flow.Paragraphs] wraps prose in `<p>`, [Flow.Inline] prints a blank line as a space. */
+    private class Frame(val flow: Flow, val baseDecls: Int, var prefix: String?) {
+        var paraOpen = false
+    }
+
+    private var out = StringBuilder()
+    private val frames = ArrayList<Frame>()
+    /** Opening tags of the declarations in scope, outermost first; the first [emittedDecls] are open in [out]. */
+    private val decls = ArrayList<String>()
+    private var emittedDecls = 0
+    private val ids = HashSet<String>()
+    private var showAnchors = anchors
+    /** LaTeX → inline HTML of [inlineHtml], by source. */
+    private val inlined = HashMap<String, String>()
+    /** Footnote texts (HTML) still to print at the end of the section. */
+    private val footnotes = ArrayList<String>()
+    /** Whether todonotes was loaded with `disable`, which hides its notes. */
+    private var todosDisabled = false
+    /** While the title block is built: the `\thanks` notes, marked with `*`, `†`, … in order. */
+    private var titleNotes: MutableList<String>? = null
+
+    private val lineStarts: IntArray = run {
+        val starts = arrayListOf(0)
+        src.forEachIndexed { i, c -> if (c == '\n') starts += i + 1 }
+        starts.toIntArray()
+    }
+
+    /**
+     * The document body, preceded by the `\maketitle` block when there is a title, author or date, and by
+     * LaTeX's warnings about labels defined twice, undefined references and citations, and missing .bib files.
+     */
+    fun document(ast: List<LatexNode>): String {
+        val body = ast.firstOrNull { it is EnvironmentNode && it.name == "document" } as EnvironmentNode?
+        forEachCommand(ast) {
+            val packages = if (it.name == "usepackage") citeKeys(it.args.getOrNull(1)?.source(src).orEmpty()) else emptyList()
+            if ("todonotes" in packages) todosDisabled = "disable" in citeKeys(it.args.getOrNull(0)?.source(src).orEmpty())
+        }
+        out.append(warnings())
+        out.append(titleBlock(ast))
+        inFrame(Flow.Paragraphs) {
+            nodes(body?.children ?: ast)
+            flushFootnotes()
+        }
+        return out.toString()
+    }
+
+    /** [src] as inline HTML, e.g. an option value such as a tcolorbox title. */
+    fun fragment(): String {
+        val ast = LatexParser(src).parse()
+        inFrame(Flow.Inline) { nodes(ast) }
+        return out.toString()
+    }
+
+    // ─────────────────────────── OUTPUT STATE ───────────────────────────
+
+    private val frame: Frame get() = frames[frames.lastIndex]
+
+    private fun lineOf(offset: Int): Int {
+        val i = lineStarts.binarySearch(offset)
+        return if (i >= 0) i + 1 else -i - 1
+    }
+
+    private fun inFrame(flow: Flow, prefix: String? = null, body: () -> Unit) {
+        val f = Frame(flow, emittedDecls, prefix)
+        frames += f
+        val depth = decls.size
+        if (flow == Flow.Inline) openDecls()
+        body()
+        popDecls(depth)
+        if (flow == Flow.Paragraphs) closeParagraph() else closeDecls(f.baseDecls)
+        frames.removeAt(frames.lastIndex)
+    }
+
+    private fun scoped(body: () -> Unit) {
+        val depth = decls.size
+        body()
+        popDecls(depth)
+    }
+
+    private fun capture(body: () -> Unit): String {
+        val saved = out
+        out = StringBuilder()
+        body()
+        return out.toString().also { out = saved }
+    }
+
+    private fun openDecls() {
+        while (emittedDecls < decls.size) out.append(decls[emittedDecls++])
+    }
+
+    private fun closeDecls(to: Int) {
+        while (emittedDecls > to) {
+            out.append("</span>")
+            emittedDecls--
+        }
+    }
+
+    private fun pushDecl(style: String) {
+        decls += "<span style=\"$style\">"
+        if (frame.flow == Flow.Inline || frame.paraOpen) openDecls()
+    }
+
+    private fun popDecls(depth: Int) {
+        while (decls.size > depth) {
+            if (emittedDecls == decls.size) {
+                out.append("</span>")
+                emittedDecls--
+            }
+            decls.removeAt(decls.lastIndex)
+        }
+    }
+
+    /** Before printed content: opens a paragraph if none is. */
+    private fun startInline() {
+        val f = frame
+        if (f.flow != Flow.Paragraphs || f.paraOpen) return
+        out.append("<p>")
+        f.paraOpen = true
+        openDecls()
+        f.prefix?.let {
+            out.append(it)
+            f.prefix = null
+        }
+    }
+
+    /** Before a block element: closes the paragraph. */
+    private fun startBlock() {
+        if (frame.flow == Flow.Paragraphs) closeParagraph()
+    }
+
+    private fun closeParagraph() {
+        val f = frame
+        if (!f.paraOpen) return
+        closeDecls(f.baseDecls)
+        out.append("</p>")
+        f.paraOpen = false
+    }
+
+    private fun text(html: String) {
+        startInline()
+        out.append(html)
+    }
+
+    private fun synclines(text: String, start: Int) {
+        if (!showAnchors) return
+        for (k in text.indices) {
+            if (text[k] == '\n') out.append("<span class=\"syncline\" data-abs=\"").append(lineOf(start + k + 1)).append("\"></span>")
+        }
+    }
+
+    /** Only the line anchors of [list], for whitespace between list items and the like. */
+    private fun anchorsOnly(list: List<LatexNode>) {
+        for (n in list) when (n) {
+            is SpaceNode -> synclines(n.text, n.start)
+            is ParNode -> synclines(n.text, n.start)
+            is IgnoredNode -> synclines(n.text, n.start)
+            else -> Unit
+        }
+    }
+
+    /** The numbered element starting at [offset]; fragments have none. */
+    private fun numbered(offset: Int): Numbered? = if (anchors) numbering.at(offset) else null
+
+    /** ` id="…"` when a label refers to [anchor]. */
+    private fun idAttr(anchor: String?): String =
+        if (anchor != null && numbering.isTarget(anchor)) " id=\"$anchor\"" else ""
+
+    /** An empty span to scroll to, when a label refers to [anchor]. */
+    private fun anchorSpan(anchor: String?): String = idAttr(anchor).let { if (it.isEmpty()) "" else "<span$it></span>" }
+
+    private fun uniqueId(base: String): String {
+        var id = base
+        var k = 2
+        while (!ids.add(id)) id = "$base-${k++}"
+        return id
+    }
+
+    // ───────────────────────────── NODES ─────────────────────────────
+
+    private fun nodes(list: List<LatexNode>) {
+        var i = 0
+        while (i < list.size) i += node(list, i)
+    }
+
+    private fun inlineNodes(list: List<LatexNode>) = inFrame(Flow.Inline) { nodes(list) }
+
+    private fun inlineArg(arg: LatexArg?) = inlineNodes(arg?.nodes.orEmpty())
+
+    private fun wrapped(open: String, arg: LatexArg?, close: String) {
+        startInline()
+        out.append(open)
+        inlineArg(arg)
+        out.append(close)
+    }
+
+    private fun block(open: String, close: String, flow: Flow, children: List<LatexNode>, prefix: String? = null) {
+        startBlock()
+        out.append(open)
+        inFrame(flow, prefix) { nodes(children) }
+        out.append(close)
+    }
+
+    /** Prints `list[i]`; returns how many nodes it took. */
+    private fun node(list: List<LatexNode>, i: Int): Int {
+        when (val n = list[i]) {
+            is TextNode -> text(prose(n.text))
+            is SpaceNode -> {
+                if (frame.flow == Flow.Inline || frame.paraOpen) out.append(if ('\n' in n.text) '\n' else ' ')
+                synclines(n.text, n.start)
+            }
+            is ParNode -> {
+                if (frame.flow == Flow.Paragraphs) closeParagraph() else out.append(' ')
+                synclines(n.text, n.start)
+            }
+            is IgnoredNode -> synclines(n.text, n.start)
+            is GroupNode -> scoped { nodes(n.children) }
+            is CommandNode -> return command(list, i)
+            is EnvironmentNode -> scoped { environment(n) }
+            is MathNode -> text(math(n))
+            is VerbatimNode -> verbatim(n)
+            is SpecialNode -> text(
+                when (n.char) {
+                    '~' -> "&nbsp;"
+                    '&' -> "&amp;"
+                    else -> n.char.toString()
+                }
+            )
+        }
+        return 1
+    }
+
+    private fun math(n: MathNode): String {
+        if (!n.closed) return escapeHtmlKeepBackslashes(src.substring(n.start, n.end)).replace("$", "\\$")
+        val tagged = if (anchors) numbering.math(n.start) else null
+        val tex = numbering.resolve(tagged?.tex ?: src.substring(n.start, n.end))
+        return anchorSpan(tagged?.anchor) + escapeHtmlKeepBackslashes(convertSiunitx(tex))
+    }
+
+    private fun verbatim(n: VerbatimNode) {
+        when (n.env) {
+            null -> text("<code>${htmlEscapeAll(n.text)}</code>")
+            "comment" -> Unit
+            else -> {
+                startBlock()
+                out.append(PRE_OPEN).append(htmlEscapeAll(n.text)).append("</code></pre>")
+            }
+        }
+    }
+
+    // ──────────────────────────── COMMANDS ────────────────────────────
+
+    private fun command(list: List<LatexNode>, i: Int): Int {
+        val n = list[i] as CommandNode
+        val name = n.name
+        val args = n.args
+        when (name) {
+            in NOOP_COMMANDS -> Unit
+            in DECLARATIONS -> pushDecl(DECLARATIONS.getValue(name))
+            in TEXT_SYMBOLS -> text(TEXT_SYMBOLS.getValue(name))
+            in ACCENTS -> text(accent(name, args.getOrNull(0)))
+            in WRAPPERS -> WRAPPERS.getValue(name).let { (open, close) -> wrapped(open, args.getOrNull(0), close) }
+            in HEADINGS -> heading(n)
+            in VERTICAL_SPACES -> {
+                startBlock()
+                out.append("<div style=\"height:${VERTICAL_SPACES.getValue(name)}\"></div>")
+            }
+            "\\", "newline", "linebreak" -> if (frame.flow == Flow.Inline || frame.paraOpen) out.append("<br/>")
+            "today" -> text(today())
+            "textcolor" -> wrapped("<span style=\"color:${color(args)};\">", args.getOrNull(2), "</span>")
+            "colorbox" -> wrapped("<span style=\"background:${color(args)};padding:0 .2em;\">", args.getOrNull(2), "</span>")
+            "color" -> pushDecl("color:${color(args)};")
+            "href" -> link(argText(args.getOrNull(0)), args.getOrNull(1))
+            "url" -> link(argText(args.getOrNull(0)), null)
+            "includegraphics" -> image(args)
+            "caption" -> caption(n)
+            "ref", "eqref", "autoref", "pageref" -> reference(n)
+            in CITE_COMMANDS -> citation(n)
+            "multicolumn" -> inlineArg(args.getOrNull(2))
+            "ensuremath" -> text("\\(${escapeHtmlKeepBackslashes(args.getOrNull(0)?.source(src).orEmpty())}\\)")
+            "num", "si" -> siunitx("\\$name{${args.getOrNull(1)?.source(src).orEmpty()}}")
+            "SI" -> siunitx("\\SI{${args.getOrNull(1)?.source(src).orEmpty()}}{${args.getOrNull(2)?.source(src).orEmpty()}}")
+            "thanks" -> titleNotes?.let { notes ->
+                notes.add(capture { inlineArg(args.getOrNull(0)) }.trim())
+                val k = notes.size
+                text("<sup><a class=\"ll-ref\" href=\"#ll-thanks-$k\">${footnoteSymbol(k)}</a></sup>")
+            }
+            "footnote", "footnotemark", "footnotetext" -> footnote(n)
+            "qed", "qedhere" -> text(QED_BOX)
+            "marginpar" -> wrapped(SIDE_NOTE_OPEN + "border-left:2px solid var(--border);\">", args.getOrNull(1), "</span>")
+            "todo" -> todo(n)
+            "missingfigure" -> if (!todosDisabled) {
+                startBlock()
+                out.append("<div class=\"ll-todo\" style=\"background:#e5e7eb;border:1px dashed var(--muted);")
+                    .append("padding:24px 12px;margin:12px 0;text-align:center;\"><strong>Missing figure</strong><br/>")
+                inlineArg(args.getOrNull(1))
+                out.append("</div>")
+            }
+            "llmark" -> llmark(n)
+            "appendix" -> {
+                startBlock()
+                out.append("<hr style=\"border:none;border-top:1px solid var(--border);margin:16px 0;\"/>")
+            }
+            "bibliography", "printbibliography" -> referenceList(n)
+            else -> {
+                if (name.length > 3 && name.startsWith("SST")) {
+                    startBlock()
+                    out.append(sstMacro(src.substring(n.start, n.end)))
+                } else {
+                    return unknown(list, i)
+                }
+            }
+        }
+        return 1
+    }
+
+    /** A command the preview does not know: its source, with the groups right after it. */
+    private fun unknown(list: List<LatexNode>, i: Int): Int {
+        val n = list[i]
+        var end = n.end
+        var j = i + 1
+        while (j < list.size && list[j] is GroupNode && list[j].start == end) {
+            end = list[j].end
+            j++
+        }
+        text(escapeHtmlKeepBackslashes(src.substring(n.start, end)))
+        // TeX skipped the blanks after the name; the source should keep them.
+        val next = list.getOrNull(j)
+        if (j == i + 1 && next is IgnoredNode && !next.text.startsWith("%")) out.append(' ')
+        return j - i
+    }
+
+    private fun heading(n: CommandNode) {
+        val title = n.args.getOrNull(1) ?: return
+        if (n.name == "part" || n.name == "chapter" || n.name == "section") flushFootnotes()
+        val tag = HEADINGS.getValue(n.name)
+        val id = uniqueId("${n.name}-${slugify(title.source(src))}")
+        val style = if (tag == "h5" || tag == "h6") " style=\"margin:1em 0 .3em 0;\"" else ""
+        startBlock()
+        out.append(llmarkSpan(id, lineOf(n.start))).append("<$tag id=\"$id\"$style>")
+        numbered(n.start)?.let { num ->
+            out.append(anchorSpan(num.anchor)).append(if (n.name == "part") "Part ${num.the}" else num.the).append("&emsp;")
+        }
+        inlineArg(title)
+        out.append("</$tag>")
+    }
+
+    /** `\llmark[caption]{key}`: a scroll-sync mark of its own. */
+    private fun llmark(n: CommandNode) {
+        val key = n.args.getOrNull(1)?.source(src)?.trim().orEmpty().ifBlank { "mark" }
+        out.append(llmarkSpan(uniqueId("mark-${slugify(key)}"), lineOf(n.start)))
+        val caption = n.args.getOrNull(0) ?: return
+        startBlock()
+        out.append("<div style=\"opacity:.7;margin:.2em 0;\">")
+        inlineArg(caption)
+        out.append("</div>")
+    }
+
+    private fun llmarkSpan(id: String, line: Int) = "<span class=\"llmark\" data-id=\"$id\" data-abs=\"$line\"></span>"
+
+    private fun accent(name: String, arg: LatexArg?): String {
+        val base = arg?.nodes.orEmpty().joinToString("") { plain(it) }
+        if (base.isEmpty()) return if (name == "^" || name == "~") name else ""
+        return escapeHtmlKeepBackslashes(Normalizer.normalize(base + ACCENTS.getValue(name), Normalizer.Form.NFC))
+    }
+
+    /** Letters of a short argument, e.g. the base of an accent. */
+    private fun plain(n: LatexNode): String = when (n) {
+        is TextNode -> n.text
+        is CommandNode -> if (n.name == "i" || n.name == "j") TEXT_SYMBOLS.getValue(n.name) else ""
+        is GroupNode -> n.children.joinToString("") { plain(it) }
+        else -> ""
+    }
+
+    private fun argText(arg: LatexArg?): String =
+        (arg?.nodes?.singleOrNull() as? VerbatimNode)?.text ?: arg?.source(src).orEmpty()
+
+    private fun color(args: List<LatexArg?>): String {
+        val spec = args.getOrNull(1)?.source(src)?.trim().orEmpty()
+        return when (args.getOrNull(0)?.source(src)?.trim()) {
+            "HTML" -> htmlEscapeAll("#$spec")
+            else -> xcolorToCss(spec)
+        }
+    }
+
+    private fun link(url: String, label: LatexArg?) {
+        startInline()
+        out.append("<a href=\"").append(htmlEscapeAll(url.trim())).append("\" target=\"_blank\" rel=\"noopener\">")
+        if (label != null) inlineArg(label) else out.append(escapeHtmlKeepBackslashes(url.trim()))
+        out.append("</a>")
+    }
+
+    private fun image(args: List<LatexArg?>) {
+        val path = args.getOrNull(1)?.source(src)?.trim() ?: return
+        val style = includeGraphicsStyle(args.getOrNull(0)?.source(src).orEmpty())
+        text("<img src=\"${htmlEscapeAll(resolveImagePath(path))}\" alt=\"\" style=\"$style\">")
+    }
+
+    /** `Figure 2: …`, or `(a) …` for a subfigure. */
+    private fun caption(n: CommandNode) {
+        startBlock()
+        out.append("<figcaption style=\"opacity:.8;margin:6px 0 10px;\">")
+        numbered(n.start)?.let { num ->
+            out.append(if (num.counter == "subfigure") "(${num.the})&ensp;" else "${num.name}&nbsp;${num.the}:&ensp;")
+        }
+        inlineArg(n.args.getOrNull(1))
+        out.append("</figcaption>")
+    }
+
+    /**
+     * `\ref`, `\eqref` and `\autoref` print the target's number, `\pageref` an arrow towards it (the preview has
+     * no pages); each links to the target unless starred. An undefined label prints `??`.
+     */
+    private fun reference(n: CommandNode) {
+        val key = n.args.getOrNull(0)?.source(src)?.trim().orEmpty()
+        val target = numbering.labels[key]
+        if (target == null) {
+            text("<strong class=\"ll-ref-undefined\" title=\"Undefined reference: ${htmlEscapeAll(key)}\">??</strong>")
+            return
+        }
+        val number = escapeHtmlKeepBackslashes(target.ref)
+        val label = when (n.name) {
+            "eqref" -> "($number)"
+            "autoref" -> if (target.name.isEmpty()) number else "${inlineHtml(target.name)}&nbsp;$number"
+            "pageref" -> if (target.offset < n.start) "↑" else "↓"
+            else -> number
+        }
+        if (n.star || target.anchor.isEmpty()) text(label)
+        else text("<a class=\"ll-ref\" href=\"#${target.anchor}\">$label</a>")
+    }
+
+    /**
+     * `\cite` and its natbib and biblatex forms, each work linked to its list entry: `[1, 2, p. 5]` in numeric
+     * styles, `(Knuth, 1984)` or `Knuth (1984)` in author-year ones. An undefined key prints `?`.
+     */
+    private fun citation(n: CommandNode) {
+        val name = n.name
+        val twoNotes = n.args.getOrNull(1) != null
+        val pre = (if (twoNotes) n.args.getOrNull(0) else null)?.let { capture { inlineArg(it) }.trim() }.orEmpty()
+        val post = (if (twoNotes) n.args.getOrNull(1) else n.args.getOrNull(0))?.let { capture { inlineArg(it) }.trim() }.orEmpty()
+        val works = citeKeys(n.args.getOrNull(2)?.source(src).orEmpty()).map { it to citations.work(it) }
+
+        fun linked(key: String, work: CitedWork?, label: (CitedWork) -> String): String =
+            if (work == null) "<strong class=\"ll-ref-undefined\" title=\"Undefined citation: ${htmlEscapeAll(key)}\">?</strong>"
+            else "<a class=\"ll-ref\" href=\"#${work.anchor}\">${label(work)}</a>"
+
+        fun around(open: String, items: String, close: String): String =
+            open + (if (pre.isEmpty()) "" else "$pre ") + items + (if (post.isEmpty()) "" else ", $post") + close
+
+        fun year(w: CitedWork) = escapeHtmlKeepBackslashes(w.year.ifEmpty { w.label })
+
+        val textual = name == "citet" || name == "textcite" || name == "citealt" ||
+            (name == "cite" && citations.authorYear && !citations.biblatex)
+        val html = when {
+            name == "citeauthor" -> works.joinToString(", ") { (k, w) -> linked(k, w) { inlineHtml(it.authors) } }
+            name == "citeyear" -> works.joinToString(", ") { (k, w) -> linked(k, w, ::year) }
+            !citations.authorYear && textual -> works.mapIndexed { i, (k, w) ->
+                val note = if (i == works.lastIndex && post.isNotEmpty()) ", $post" else ""
+                linked(k, w) { "${inlineHtml(it.authors)}&nbsp;[${inlineHtml(it.label)}$note]" }
+            }.joinToString(", ")
+            !citations.authorYear -> around(
+                if (name == "citealp") "" else "[",
+                works.joinToString(", ") { (k, w) -> linked(k, w) { inlineHtml(it.label) } },
+                if (name == "citealp") "" else "]",
+            )
+            textual -> works.mapIndexed { i, (k, w) ->
+                val before = if (i == 0 && pre.isNotEmpty()) "$pre " else ""
+                val after = if (i == works.lastIndex && post.isNotEmpty()) ", $post" else ""
+                linked(k, w) {
+                    if (name == "citealt") "${inlineHtml(it.authors)} $before${year(it)}$after"
+                    else "${inlineHtml(it.authors)} ($before${year(it)}$after)"
+                }
+            }.joinToString(", ")
+            else -> {
+                val bare = name == "citealp" || name == "cite"
+                val separator = if (citations.biblatex) " " else ", "
+                around(
+                    if (bare) "" else "(",
+                    works.joinToString("; ") { (k, w) -> linked(k, w) { "${inlineHtml(it.authors)}$separator${year(it)}" } },
+                    if (bare) "" else ")",
+                )
+            }
+        }
+        text(html)
+    }
+
+    /** The reference list of the .bib works at `\bibliography` or `\printbibliography` (`title=` sets its heading). */
+    private fun referenceList(n: CommandNode) {
+        val title = if (n.name == "printbibliography") {
+            parseTcolorOptions(n.args.getOrNull(0)?.source(src).orEmpty())["title"]
+        } else {
+            null
+        }
+        bibliographyHeading(title)
+        out.append(BIBLIOGRAPHY_OPEN)
+        for (work in citations.works) bibEntry(work) { out.append(inlineHtml(citations.reference(work))) }
+        out.append("</ol>")
+    }
+
+    /** `\refname` or, in classes with chapters, `\bibname`, as the unnumbered heading LaTeX gives it. */
+    private fun bibliographyHeading(title: String?) {
+        val tag = if (numbering.chapters) "h1" else "h2"
+        startBlock()
+        out.append("<$tag>").append(inlineHtml(title ?: if (numbering.chapters) "Bibliography" else "References")).append("</$tag>")
+    }
+
+    /** A reference list entry: `[label]` first in numeric styles; the first line hangs out. */
+    private fun bibEntry(work: CitedWork?, body: () -> Unit) {
+        out.append("<li")
+        if (work != null && anchors && ids.add(work.anchor)) out.append(" id=\"").append(work.anchor).append('"')
+        out.append(" style=\"margin:4px 0;padding-left:2.5em;text-indent:-2.5em;\">")
+        if (work != null && !citations.authorYear) out.append('[').append(inlineHtml(work.label)).append("]&ensp;")
+        body()
+        out.append("</li>")
+    }
+
+    /** [latex] as inline HTML, outside the source: an option value, a citation label, a reference list entry. */
+    private fun inlineHtml(latex: String): String = inlined.getOrPut(latex) {
+        LatexHtmlEmitter(latex, numbering, citations, tikzPicture, sstMacro, anchors = false).fragment()
+    }
+
+    /**
+     * `\footnote` and `\footnotemark` print the number, linked to the text; the text of `\footnote` and
+     * `\footnotetext` waits in [footnotes], linked back to its mark.
+     */
+    private fun footnote(n: CommandNode) {
+        val num = numbered(n.start) ?: return
+        val mark = "${num.anchor}-mark"
+        if (n.name != "footnotetext") {
+            val id = if (ids.add(mark)) " id=\"$mark\"" else ""
+            text("<sup class=\"ll-footnote-mark\"><a class=\"ll-ref\"$id href=\"#${num.anchor}\">${num.the}</a></sup>")
+        }
+        if (n.name == "footnotemark") return
+        showAnchors = false
+        val note = capture { inlineArg(n.args.getOrNull(1)) }.trim()
+        showAnchors = anchors
+        val number = if (mark in ids) "<a class=\"ll-ref\" href=\"#$mark\">${num.the}</a>" else num.the
+        val id = if (ids.add(num.anchor)) " id=\"${num.anchor}\"" else ""
+        footnotes += "<div class=\"ll-footnote\"$id style=\"margin:2px 0;\"><sup>$number</sup>&ensp;$note</div>"
+    }
+
+    /** The footnotes since the last section, under a short rule as at the foot of a page. */
+    private fun flushFootnotes() {
+        if (footnotes.isEmpty()) return
+        startBlock()
+        out.append("<div class=\"ll-footnotes\" style=\"margin:12px 0;font-size:.85em;\">")
+            .append("<hr style=\"width:30%;margin:0 0 6px 0;border:none;border-top:1px solid var(--border);\"/>")
+        footnotes.forEach { out.append(it) }
+        footnotes.clear()
+        out.append("</div>")
+    }
+
+    /** todonotes' `\todo[options]{text}`: a side note, or a box of its own with `inline`; `color` sets the fill. */
+    private fun todo(n: CommandNode) {
+        if (todosDisabled) return
+        val options = citeKeys(n.args.getOrNull(0)?.source(src).orEmpty())
+            .associate { it.substringBefore('=').trim() to it.substringAfter('=', "").trim() }
+        if (options["disable"] != null) return
+        val fill = (options["backgroundcolor"] ?: options["color"])?.let(::xcolorToCss) ?: TODO_FILL
+        val border = options["bordercolor"]?.let(::xcolorToCss) ?: "var(--border)"
+        val author = options["author"]?.let { "<strong>${inlineHtml(it)}:</strong> " }.orEmpty()
+        if (options["inline"] != null) {
+            startBlock()
+            out.append("<div class=\"ll-todo\" style=\"background:$fill;border:1px solid $border;border-radius:4px;")
+                .append("padding:6px 10px;margin:8px 0;\">").append(author)
+            inlineArg(n.args.getOrNull(1))
+            out.append("</div>")
+        } else {
+            wrapped(
+                SIDE_NOTE_OPEN + "background:$fill;border:1px solid $border;border-radius:4px;\">$author",
+                n.args.getOrNull(1),
+                "</span>",
+            )
+        }
+    }
+
+    private fun siunitx(tex: String) = text("\\(${escapeHtmlKeepBackslashes(convertSiunitx(tex))}\\)")
+
+    private fun today(): String = LocalDate.now().format(DateTimeFormatter.ofPattern("MMMM d, yyyy"))
+
+    // ────────────────────────── ENVIRONMENTS ──────────────────────────
+
+    private fun environment(n: EnvironmentNode) {
+        val args = n.args
+        when (n.name) {
+            "itemize" -> list("<ul style=\"margin:12px 0 12px 24px;\">", "</ul>", n.children)
+            "enumerate" -> list("<ol style=\"margin:12px 0 12px 24px;\">", "</ol>", n.children)
+            "description" -> description(n.children)
+            "thebibliography" -> bibliography(n.children)
+            "tabular" -> table(args.getOrNull(1)?.source(src).orEmpty(), rows(n.children).map { it.first })
+            "longtable" -> longtable(n)
+            "figure", "figure*", "wrapfigure" -> block(
+                "<figure${floatId(n)} style=\"margin:14px 0;text-align:center;\">", "</figure>", Flow.Inline, n.children,
+            )
+            "table", "table*" -> block("<figure${floatId(n)} style=\"margin:14px 0;\">", "</figure>", Flow.Inline, n.children)
+            "center" -> block("<div style=\"text-align:center;\">", "</div>", Flow.Paragraphs, n.children)
+            "flushleft" -> block("<div style=\"text-align:left;\">", "</div>", Flow.Paragraphs, n.children)
+            "flushright" -> block("<div style=\"text-align:right;\">", "</div>", Flow.Paragraphs, n.children)
+            "quote", "quotation" ->
+                block("<blockquote style=\"margin:12px 0 12px 24px;\">", "</blockquote>", Flow.Paragraphs, n.children)
+            "abstract" -> block(
+                "<div class=\"abstract-block\" style=\"padding:12px;border-left:3px solid var(--border); background:#6b728022; margin:12px 0;\">",
+                "</div>", Flow.Paragraphs, n.children, prefix = "<strong>Abstract.</strong>&nbsp;",
+            )
+            "multicols" -> {
+                val cols = (args.getOrNull(0)?.source(src)?.trim()?.toIntOrNull() ?: 2).coerceIn(1, 8)
+                block(
+                    "<div class=\"multicol\" style=\"-webkit-column-count:$cols;column-count:$cols;-webkit-column-gap:1.2em;column-gap:1.2em;\">",
+                    "</div>", Flow.Paragraphs, n.children,
+                )
+            }
+            "minipage", "subfigure" -> {
+                val width = args.getOrNull(if (n.name == "minipage") 3 else 1)?.source(src)?.let(::linewidthToPercent)
+                val widthCss = width?.let { "width:$it%;" }.orEmpty()
+                block(
+                    "<div${floatId(n)} style=\"display:inline-block;vertical-align:top;$widthCss\">",
+                    "</div>", Flow.Paragraphs, n.children,
+                )
+            }
+            "tcolorbox" -> tcolorbox(n)
+            "tikzpicture" -> {
+                val options = args.getOrNull(0)?.let { "[${it.source(src)}]" }.orEmpty()
+                startBlock()
+                out.append(tikzPicture(options, stripLineComments(src.substring(n.bodyStart, n.bodyEnd)).trim()))
+            }
+            "proof" -> proof(n)
+            in numbering.theorems -> theorem(n, numbering.theorems.getValue(n.name))
+            else -> nodes(n.children)
+        }
+    }
+
+    private fun floatId(n: EnvironmentNode): String = if (anchors) idAttr(numbering.floatAnchor(n.start)) else ""
+
+    /** [children] split at each `\name`: the command (null before the first) and what follows it. */
+    private fun items(children: List<LatexNode>, name: String): List<Pair<CommandNode?, List<LatexNode>>> {
+        val items = ArrayList<Pair<CommandNode?, MutableList<LatexNode>>>()
+        items.add(null to ArrayList<LatexNode>())
+        for (c in children) {
+            if (c is CommandNode && c.name == name) items.add(c to ArrayList<LatexNode>()) else items[items.lastIndex].second += c
+        }
+        return items
+    }
+
+    private fun list(open: String, close: String, children: List<LatexNode>) {
+        startBlock()
+        out.append(open)
+        for ((item, content) in items(children, "item")) {
+            if (item == null) {
+                anchorsOnly(content)
+                continue
+            }
+            val label = item.args.getOrNull(0)
+            if (label == null) {
+                out.append("<li").append(idAttr(numbered(item.start)?.anchor)).append(">")
+            } else {
+                out.append("<li style=\"list-style:none;\">")
+                inlineArg(label)
+                out.append(' ')
+            }
+            inlineNodes(content)
+            out.append("</li>")
+        }
+        out.append(close)
+    }
+
+    private fun description(children: List<LatexNode>) {
+        startBlock()
+        out.append("<dl style=\"margin:12px 0 12px 24px;\">")
+        for ((item, content) in items(children, "item")) {
+            if (item == null) {
+                anchorsOnly(content)
+                continue
+            }
+            out.append("<dt><strong>")
+            inlineArg(item.args.getOrNull(0))
+            out.append("</strong></dt><dd>")
+            inlineNodes(content)
+            out.append("</dd>")
+        }
+        out.append("</dl>")
+    }
+
+    private fun bibliography(children: List<LatexNode>) {
+        bibliographyHeading(null)
+        out.append(BIBLIOGRAPHY_OPEN)
+        for ((item, content) in items(children, "bibitem")) {
+            if (item == null) {
+                anchorsOnly(content)
+                continue
+            }
+            bibEntry(citations.work(item.args.getOrNull(1)?.source(src)?.trim().orEmpty())) { inlineNodes(content) }
+        }
+        out.append("</ol>")
+    }
+
+    /** Table rows: the nodes of each and the command that ended it (`\\`, `\endhead`, …; empty for the last). */
+    private fun rows(children: List<LatexNode>): List<Pair<List<LatexNode>, String>> {
+        val rows = ArrayList<Pair<List<LatexNode>, String>>()
+        var row = ArrayList<LatexNode>()
+        for (c in children) {
+            if (c is CommandNode && c.name in ROW_ENDS) {
+                rows += row to c.name
+                row = ArrayList()
+            } else {
+                row += c
+            }
+        }
+        rows += row to ""
+        return rows
+    }
+
+    private fun isBlank(n: LatexNode) =
+        n is SpaceNode || n is ParNode || n is IgnoredNode || n is CommandNode && n.name in NOOP_COMMANDS
+
+    private fun table(spec: String, rows: List<List<LatexNode>>) {
+        val cols = parseColSpecBalanced(spec)
+        startBlock()
+        out.append("<table style=\"border:1px solid var(--border);margin:12px 0;width:100%;\">")
+        for (row in rows) {
+            if (row.all(::isBlank)) continue
+            out.append("<tr>")
+            var col = 0
+            for (cell in splitAt(row) { it is SpecialNode && it.char == '&' }) {
+                var span = 1
+                var colSpec = cols.getOrNull(col)
+                var content = cell
+                val multi = cell.singleOrNull { !isBlank(it) } as? CommandNode
+                if (multi != null && multi.name == "multicolumn") {
+                    span = multi.args.getOrNull(0)?.source(src)?.trim()?.toIntOrNull()?.coerceAtLeast(1) ?: 1
+                    colSpec = parseColSpecBalanced(multi.args.getOrNull(1)?.source(src).orEmpty()).firstOrNull()
+                    content = multi.args.getOrNull(2)?.nodes.orEmpty()
+                }
+                out.append("<td")
+                if (span > 1) out.append(" colspan=\"$span\"")
+                out.append(" style=\"")
+                colSpec?.align?.let { out.append("text-align:$it;") }
+                colSpec?.widthPct?.let { out.append("width:$it%;") }
+                out.append("padding:4px 8px;border:1px solid var(--border);vertical-align:top;\">")
+                inlineNodes(content)
+                out.append("</td>")
+                col += span
+            }
+            out.append("</tr>")
+        }
+        out.append("</table>")
+    }
+
+    /** A longtable as one table: the first head, the body and the last foot, with the caption above. */
+    private fun longtable(n: EnvironmentNode) {
+        val parts = HashMap<String, List<List<LatexNode>>>()
+        var pending = ArrayList<List<LatexNode>>()
+        var captionCommand: CommandNode? = null
+        for ((row, end) in rows(n.children)) {
+            val cap = row.firstOrNull { it is CommandNode && it.name == "caption" } as CommandNode?
+            if (cap != null) captionCommand = cap else pending.add(row)
+            if (end in LONGTABLE_PARTS) {
+                parts[end] = pending
+                pending = ArrayList()
+            }
+        }
+        val head = parts["endfirsthead"] ?: parts["endhead"].orEmpty()
+        val foot = parts["endlastfoot"] ?: parts["endfoot"].orEmpty()
+        startBlock()
+        out.append("<figure${floatId(n)} style=\"margin:14px 0;\">")
+        captionCommand?.let { caption(it) }
+        table(n.args.getOrNull(1)?.source(src).orEmpty(), head + pending + foot)
+        out.append("</figure>")
+    }
+
+    private fun tcolorbox(n: EnvironmentNode) {
+        val kv = parseTcolorOptions(n.args.getOrNull(0)?.source(src).orEmpty())
+        val title = kv["title"]?.let { inlineHtml(it) }.orEmpty()
+        val back = kv["colback"]?.let(::xcolorToCss) ?: "#f8fafc"
+        val border = kv["colframe"]?.let(::xcolorToCss) ?: "#1e3a8a"
+        startBlock()
+        out.append("<div class=\"tcb\" style=\"background:$back;border:1px solid $border;")
+            .append("border-left-width:4px;border-radius:8px;padding:10px 12px;margin:12px 0;\">")
+        if (title.isNotBlank()) {
+            out.append("<div class=\"tcb-title\" style=\"font-weight:600;margin-bottom:6px;\">").append(title).append("</div>")
+        }
+        out.append("<div class=\"tcb-body\">")
+        inFrame(Flow.Paragraphs) { nodes(n.children) }
+        out.append("</div></div>")
+    }
+
+    /**
+     * A theorem-like environment as amsthm sets it: `Theorem 2.1 (Note).` run into the first paragraph, in bold
+     * with an italic body (plain), in bold (definition) or in italics (remark).
+     */
+    private fun theorem(n: EnvironmentNode, kind: Theorem) {
+        val num = numbered(n.start)
+        val note = n.args.getOrNull(0)?.let { arg -> capture { inlineArg(arg) }.trim() }
+        val name = inlineHtml(kind.title) + num?.let { " ${it.the}" }.orEmpty()
+        val head = if (kind.style == "remark") "<em>$name</em>" else "<strong>$name</strong>"
+        val body = if (kind.style == "definition" || kind.style == "remark") "" else "font-style:italic;"
+        block(
+            "<div class=\"theorem\"${idAttr(num?.anchor)} style=\"margin:12px 0;$body\">", "</div>",
+            Flow.Paragraphs, n.children,
+            prefix = "<span style=\"font-style:normal;\">$head${note?.let { " ($it)" }.orEmpty()}.</span>&ensp;",
+        )
+    }
+
+    /**
+     * amsthm's `proof`: `Proof.` (or the optional title) in italics, and a box at the end unless `\qed` or
+     * `\qedhere` put it earlier.
+     */
+    private fun proof(n: EnvironmentNode) {
+        val title = n.args.getOrNull(0)?.let { arg -> capture { inlineArg(arg) }.trim() } ?: "Proof"
+        val qedInside = QED.containsMatchIn(src.substring(n.bodyStart, n.bodyEnd.coerceAtLeast(n.bodyStart)))
+        startBlock()
+        out.append("<div class=\"proof\" style=\"margin:12px 0;\">")
+        inFrame(Flow.Paragraphs, "<em>$title.</em>&ensp;") {
+            nodes(n.children)
+            if (!qedInside) text(QED_BOX)
+        }
+        out.append("</div>")
+    }
+
+    // ─────────────────────────── WARNINGS ───────────────────────────
+
+    /**
+     * LaTeX's "multiply defined" and "undefined" warnings for labels and citations, with their lines, and BibTeX's
+     * for .bib files it cannot open; empty if there are none.
+     */
+    private fun warnings(): String {
+        if (numbering.duplicates.isEmpty() && numbering.undefined.isEmpty() && citations.undefined.isEmpty() &&
+            citations.missingFiles.isEmpty()
+        ) {
+            return ""
+        }
+        return buildString {
+            append("<div class=\"ll-warnings\" style=\"border-left:3px solid #d97706;background:#d9770622;padding:6px 12px;margin:8px 0;font-size:.9em;\">")
+            for ((key, offsets) in numbering.duplicates) {
+                append("<div>Label ‘").append(htmlEscapeAll(key)).append("’ multiply defined (lines ")
+                append(offsets.joinToString(", ") { lineOf(it).toString() }).append(").</div>")
+            }
+            for ((key, offset) in numbering.undefined) {
+                append("<div>Reference ‘").append(htmlEscapeAll(key)).append("’ undefined (line ${lineOf(offset)}).</div>")
+            }
+            for ((key, offset) in citations.undefined) {
+                append("<div>Citation ‘").append(htmlEscapeAll(key)).append("’ undefined (line ${lineOf(offset)}).</div>")
+            }
+            for (name in citations.missingFiles) {
+                append("<div>Bibliography file ‘").append(htmlEscapeAll(name)).append("’ not found.</div>")
+            }
+            append("</div>")
+        }
+    }
+
+    // ──────────────────────────── TITLE ────────────────────────────
+
+    /** The `\maketitle` block from the last `\title`, `\author` and `\date`; empty if there are none. */
+    private fun titleBlock(ast: List<LatexNode>): String {
+        var title: CommandNode? = null
+        var author: CommandNode? = null
+        var date: CommandNode? = null
+        forEachCommand(ast) {
+            when (it.name) {
+                "title" -> title = it
+                "author" -> author = it
+                "date" -> date = it
+            }
+        }
+        val t = title
+        val a = author
+        val d = date
+        if (t == null && a == null && d == null) return ""
+        val notes = ArrayList<String>()
+        titleNotes = notes
+        showAnchors = false
+        val titleHtml = t?.args?.getOrNull(1)?.let { arg -> capture { inlineArg(arg) } }.orEmpty().trim()
+        val authors = a?.args?.getOrNull(1)?.nodes.orEmpty()
+            .let { nodes -> splitAt(nodes) { it is CommandNode && it.name == "and" } }
+            .map { part -> capture { inlineNodes(part) }.trim() }
+            .filter { it.isNotEmpty() }
+        val dateHtml = d?.args?.getOrNull(0)?.let { arg -> capture { inlineArg(arg) } }.orEmpty().trim()
+        titleNotes = null
+        showAnchors = anchors
+        return buildString {
+            append("<div class=\"maketitle\" style=\"margin:8px 0 16px;border-bottom:1px solid var(--border);padding-bottom:8px;\">")
+            if (titleHtml.isNotEmpty()) append("<h1 style=\"margin:0 0 .25em 0;\">").append(titleHtml).append("</h1>")
+            if (authors.isNotEmpty()) {
+                append("<div class=\"authors\" style=\"margin:.2em 0;\">")
+                append(authors.joinToString(AUTHOR_SEPARATOR) { "<span class=\"author\">$it</span>" })
+                append("</div>")
+            }
+            if (dateHtml.isNotEmpty()) append("<div class=\"date\" style=\"opacity:.8;margin-top:.15em;\">").append(dateHtml).append("</div>")
+            if (notes.isNotEmpty()) {
+                append("<div class=\"title-notes\" style=\"margin:.6em 0 0;font-size:.95em;\">")
+                notes.forEachIndexed { i, note ->
+                    append("<div id=\"ll-thanks-${i + 1}\"><sup>${footnoteSymbol(i + 1)}</sup>&ensp;").append(note).append("</div>")
+                }
+                append("</div>")
+            }
+            append("</div>")
+        }
+    }
+}
+
+/** A note floated to the right of the text; the caller adds its colours and closes the style. */
+private const val SIDE_NOTE_OPEN = "<span class=\"ll-sidenote\" style=\"float:right;clear:right;width:32%;" +
+    "margin:0 0 .5em 1em;padding:4px 8px;font-size:.85em;"
+
+/** todonotes' default fill, orange!40. */
+private const val TODO_FILL = "#fdd9b5"
+
+/** LaTeX's `\fnsymbol`: `*`, `†`, `‡`, `§`, `¶`, `‖`, then doubled. */
+private fun footnoteSymbol(n: Int): String {
+    val symbols = listOf("*", "†", "‡", "§", "¶", "‖")
+    val s = symbols[(n - 1).mod(symbols.size)]
+    return if (n > symbols.size) s + s else s
+}
+
+/** amsthm's `\qedsymbol`, set at the right end of the line. */
+private const val QED_BOX = "<span class=\"qed\" style=\"float:right;\">□</span>"
+
+private val QED = Regex("""\\qed(here)?(?![A-Za-z])""")
+
+private const val PRE_OPEN = "<pre style=\"background:#0001;border:1px solid var(--border);padding:8px;overflow:auto;\"><code>"
+private const val AUTHOR_SEPARATOR = "<span class=\"author-sep\" style=\"padding:0 .6em;opacity:.5;\">·</span>"
+
+/** Splits [list] at the nodes matching [separator], which are dropped. */
+private fun splitAt(list: List<LatexNode>, separator: (LatexNode) -> Boolean): List<List<LatexNode>> {
+    val parts = ArrayList<MutableList<LatexNode>>()
+    parts.add(ArrayList())
+    for (n in list) if (separator(n)) parts.add(ArrayList()) else parts[parts.lastIndex] += n
+    return parts
+}
+
+/** Escapes prose and applies TeX's dash and quote ligatures. */
+private fun prose(text: String): String =
+    escapeHtmlKeepBackslashes(text)
+        .replace("---", "—").replace("--", "–")
+        .replace("``", "\u201C").replace("''", "\u201D")
+        .replace("`", "\u2018").replace("'", "\u2019")
+
+/** siunitx's `\num`, `\si` and `\SI` in math → plain TeX that MathJax knows. */
+private fun convertSiunitx(s: String): String {
+    var t = s
+    t = t.replace(Regex("""\\num\{(.*?)\}""", RegexOption.DOT_MATCHES_ALL)) { m ->
+        val raw = m.groupValues[1].trim()
+        val sci = Regex("""^\s*([+-]?\d+(?:\.\d+)?)[eE]([+-]?\d+)\s*$""").matchEntire(raw)
+        if (sci != null) {
+            val a = sci.groupValues[1]
+            val b = sci.groupValues[2]
+            "$a\\times 10^{${b}}"
+        } else raw
+    }
+    t = t.replace(Regex("""\\si\{(.*?)\}""", RegexOption.DOT_MATCHES_ALL)) { m ->
+        val u = m.groupValues[1].replace(".", "\\,").replace("~", "\\,")
+        "\\mathrm{$u}"
+    }
+    t = t.replace(Regex("""\\SI\{(.*?)\}\{(.*?)\}""", RegexOption.DOT_MATCHES_ALL)) { m ->
+        val num  = m.groupValues[1]
+        val unit = m.groupValues[2]
+        "\\num{$num}\\,\\si{$unit}"
+    }
+    return t
+}
+
+private val HEADINGS = mapOf(
+    "part" to "h1", "chapter" to "h1", "section" to "h2", "subsection" to "h3",
+    "subsubsection" to "h4", "paragraph" to "h5", "subparagraph" to "h6",
+)
+
+private const val BIBLIOGRAPHY_OPEN = "<ol class=\"ll-bibliography\" style=\"list-style:none;margin:12px 0;padding:0;\">"
+
+private val ROW_ENDS = setOf("\\", "tabularnewline", "endfirsthead", "endhead", "endfoot", "endlastfoot")
+private val LONGTABLE_PARTS = setOf("endfirsthead", "endhead", "endfoot", "endlastfoot")
+
+private val VERTICAL_SPACES = mapOf(
+    "smallbreak" to ".5em", "smallskip" to ".5em", "medbreak" to "1em", "medskip" to "1em",
+    "bigbreak" to "1.5em", "bigskip" to "1.5em",
+)
+
+/** Commands that print nothing in the preview: settings, definitions, page layout, table rules. */
+private val NOOP_COMMANDS = setOf(
+    "label", "centering", "raggedright", "raggedleft", "noindent", "indent", "maketitle", "title", "author",
+    "date", "and", "item", "bibitem", "vspace", "vfill", "newpage", "clearpage", "cleardoublepage", "pagebreak",
+    "nopagebreak", "tableofcontents", "listoffigures", "listoftables", "normalsize", "selectfont", "protect",
+    "relax", "frenchspacing", "sloppy", "fussy", "onecolumn", "twocolumn", "titlepageOpen", "titlepageClose",
+    "newcommand", "renewcommand", "providecommand", "DeclareMathOperator", "newenvironment", "renewenvironment",
+    "newtheorem", "theoremstyle", "newtheoremstyle", "newcounter", "newlength", "numberwithin", "counterwithin",
+    "def", "gdef", "edef", "xdef", "let", "catcode",
+    "makeatletter", "makeatother", "usepackage", "RequirePackage", "documentclass", "setlength", "addtolength",
+    "setcounter", "addtocounter", "stepcounter", "refstepcounter", "pagestyle", "thispagestyle", "pagenumbering",
+    "hypersetup", "graphicspath", "geometry", "captionsetup", "usetikzlibrary", "tikzset", "pgfplotsset",
+    "fontsize", "linespread", "setstretch", "index", "listoftodos", "nocite", "bibliographystyle", "addbibresource",
+    "addcontentsline", "toprule", "midrule", "bottomrule", "hline", "cline", "cmidrule", "addlinespace",
+    "endfirsthead", "endhead", "endfoot", "endlastfoot",
+)
+
+/** Font and size switches → CSS for the rest of the group. */
+private val DECLARATIONS = mapOf(
+    "bfseries" to "font-weight:bold;", "bf" to "font-weight:bold;", "mdseries" to "font-weight:normal;",
+    "itshape" to "font-style:italic;", "it" to "font-style:italic;", "em" to "font-style:italic;",
+    "slshape" to "font-style:oblique;", "sl" to "font-style:oblique;", "upshape" to "font-style:normal;",
+    "ttfamily" to "font-family:monospace;", "tt" to "font-family:monospace;",
+    "sffamily" to "font-family:sans-serif;", "sf" to "font-family:sans-serif;",
+    "rmfamily" to "font-family:serif;", "rm" to "font-family:serif;",
+    "scshape" to "font-variant:small-caps;", "sc" to "font-variant:small-caps;",
+    "normalfont" to "font-weight:normal;font-style:normal;font-variant:normal;font-family:inherit;",
+    "tiny" to "font-size:.6em;", "scriptsize" to "font-size:.7em;", "footnotesize" to "font-size:.8em;",
+    "small" to "font-size:.9em;", "large" to "font-size:1.2em;", "Large" to "font-size:1.44em;",
+    "LARGE" to "font-size:1.73em;", "huge" to "font-size:2.07em;", "Huge" to "font-size:2.49em;",
+)
+
+/** Commands taking one argument → the HTML around it. */
+private val WRAPPERS = mapOf(
+    "textbf" to ("<strong>" to "</strong>"),
+    "textit" to ("<em>" to "</em>"), "emph" to ("<em>" to "</em>"), "textsl" to ("<em>" to "</em>"),
+    "texttt" to ("<span style=\"font-family:monospace;\">" to "</span>"),
+    "textsf" to ("<span style=\"font-family:sans-serif;\">" to "</span>"),
+    "textsc" to ("<span style=\"font-variant:small-caps;\">" to "</span>"),
+    "textrm" to ("" to ""), "textup" to ("" to ""), "textmd" to ("" to ""), "textnormal" to ("" to ""),
+    "texorpdfstring" to ("" to ""),
+    "underline" to ("<u>" to "</u>"), "uline" to ("<u>" to "</u>"), "sout" to ("<s>" to "</s>"),
+    "textsuperscript" to ("<sup>" to "</sup>"), "textsubscript" to ("<sub>" to "</sub>"),
+    "mbox" to ("<span style=\"white-space:nowrap;\">" to "</span>"),
+    "fbox" to ("<span style=\"display:inline-block;border:1px solid var(--fg);padding:0 .25em;\">" to "</span>"),
+)
+
+/** Accent commands → the combining character. */
+private val ACCENTS = mapOf(
+    "'" to '\u0301', "`" to '\u0300', "^" to '\u0302', "\"" to '\u0308', "~" to '\u0303', "=" to '\u0304',
+    "." to '\u0307', "c" to '\u0327', "u" to '\u0306', "v" to '\u030C', "H" to '\u030B', "r" to '\u030A',
+    "k" to '\u0328', "d" to '\u0323', "b" to '\u0331',
+)
+
+/** Commands and control symbols that print a fixed string (HTML). */
+private val TEXT_SYMBOLS = mapOf(
+    "%" to "%", "&" to "&amp;", "#" to "#", "_" to "_", "{" to "{", "}" to "}", "$" to "\\$",
+    " " to " ", "\n" to " ", "\t" to " ", "," to "&thinsp;", ";" to " ", ":" to " ", "!" to "", "/" to "",
+    "-" to "&shy;", "@" to "",
+    "ldots" to "…", "dots" to "…", "textellipsis" to "…",
+    "textquotedblleft" to "\u201C", "textquotedblright" to "\u201D",
+    "textquoteleft" to "\u2018", "textquoteright" to "\u2019",
+    "textemdash" to "—", "textendash" to "–", "textfractionsolidus" to "⁄", "textdiv" to "÷",
+    "texttimes" to "×", "textminus" to "−", "textpm" to "±", "textsurd" to "√", "textlnot" to "¬",
+    "textasteriskcentered" to "∗", "textbullet" to "•", "textperiodcentered" to "·",
+    "textdaggerdbl" to "‡", "textdagger" to "†", "textsection" to "§", "textparagraph" to "¶",
+    "textbardbl" to "‖", "textbackslash" to "&#92;", "textasciitilde" to "~", "textasciicircum" to "^",
+    "textunderscore" to "_", "textbar" to "|", "textless" to "&lt;", "textgreater" to "&gt;",
+    "textbraceleft" to "{", "textbraceright" to "}", "textdollar" to "\\$",
+    "S" to "§", "P" to "¶", "dag" to "†", "ddag" to "‡", "copyright" to "©", "textcopyright" to "©",
+    "textregistered" to "®", "texttrademark" to "™", "pounds" to "£", "textsterling" to "£",
+    "euro" to "€", "texteuro" to "€", "textdegree" to "°",
+    "i" to "ı", "j" to "ȷ", "ss" to "ß", "ae" to "æ", "AE" to "Æ", "oe" to "œ", "OE" to "Œ",
+    "o" to "ø", "O" to "Ø", "aa" to "å", "AA" to "Å", "l" to "ł", "L" to "Ł",
+    "TeX" to "TeX", "LaTeX" to "LaTeX", "LaTeXe" to "LaTeX2ε",
+    "quad" to "&emsp;", "qquad" to "&emsp;&emsp;", "enspace" to "&ensp;", "thinspace" to "&thinsp;",
+    "space" to " ", "nobreakspace" to "&nbsp;", "hspace" to " ", "hfill" to " ", "hfil" to " ",
+)
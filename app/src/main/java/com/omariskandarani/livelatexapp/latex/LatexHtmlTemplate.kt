@@ -90,7 +90,8 @@ internal fun buildHtml(fullTextHtml: String, macrosJs: String, lineMapOrigToMerg
     .multicol-wrap { display: flex; gap: 1em; margin: 0.5em 0; }
     .multicol-col { flex: 1 1 0; padding: 0 0.5em; }
     strong, em, u, small { display: inline; }
-    .ll-label { display: none; }
+    a.ll-ref { text-decoration: none; border-bottom: 1px dotted var(--muted); }
+    .ll-ref-undefined { color: #b91c1c; }
     /* Preview caret marker */
     .caret-mark { display:inline-block; border-left: 1.5px solid #4F46E5; height: 1em; margin-left:-0.75px; animation: llblink 1s step-end infinite; }
     @keyframes llblink { 50% { border-color: transparent; } }
@@ -713,6 +714,21 @@ internal fun buildHtml(fullTextHtml: String, macrosJs: String, lineMapOrigToMerg
     window.addEventListener('DOMContentLoaded', () => setTimeout(refreshNav, 450));
   })();
   </script>
+<script>
+  // \ref links: scroll the target below the top bar and outline it for a moment
+  document.addEventListener('click', (e) => {
+    const a = e.target.closest && e.target.closest('a.ll-ref');
+    if (!a) return;
+    e.preventDefault();
+    const target = document.getElementById((a.getAttribute('href') || '').slice(1));
+    if (!target) return;
+    const bar = document.querySelector('.ll-topbar');
+    const top = window.scrollY + target.getBoundingClientRect().top - (bar ? bar.offsetHeight : 0) - 8;
+    window.scrollTo({ top: Math.max(0, top), behavior: 'smooth' });
+    target.classList.add('sync-target');
+    setTimeout(() => target.classList.remove('sync-target'), 1200);
+  }, true);
+</script>
 <script>
 (function(){
   function setStatus(el, msg) {
@@ -0,0 +1,506 @@
+package com.omariskandarani.livelatexapp.latex
+
+/**
+ * The numbering pass of the preview, between [LatexParser] and [LatexHtmlEmitter]: steps LaTeX's counters over
+ * the tree in source order, as the standard classes do, so that the emitter can print numbers and resolve
+ * references to labels further down.
+ *
+ * - headings down to `secnumdepth` (3 in article, 2 in report and book), in letters after `\appendix`
+ * - equations for each numbered row of equation, align, gather, multline, flalign, alignat and eqnarray, with
+ *   amsmath's `\nonumber`, `\notag` and `\tag`
+ * - figures and tables at their `\caption`, subfigures within their figure
+ * - the environments of `\newtheorem`, on their own counter, one they share, or within another; and the
+ *   [DEFAULT_THEOREMS] a class may define without one
+ * - the items of `enumerate`
+ * - footnotes, with `\footnotemark` and `\footnotetext` sharing one
+ *
+ * Equations, figures, tables and footnotes count within chapters in report and book; `\numberwithin`, `\counterwithin`,
+ * `\setcounter` and `\addtocounter` are followed. A `\label` refers to the last numbered element before it,
+ * restored at the end of a group or environment as in LaTeX. A label defined twice keeps its last definition.
+ */
+
+/**
+ * A numbered element. [the] is what it prints itself (`a` for a subfigure), [ref] what `\ref` prints (`1a`)
+ * and [name] what `\autoref` puts before that; [anchor] is its HTML id, [offset] where it starts in the source.
+ */
+internal data class Numbered(
+    val counter: String,
+    val the: String,
+    val ref: String,
+    val name: String,
+    val anchor: String,
+    val offset: Int,
+)
+
+/** A numbered display: its TeX with a `\tag` or `\notag` on every row, so MathJax does not count, and its HTML id. */
+internal data class NumberedMath(val tex: String, val anchor: String)
+
+/**
+ * A theorem-like environment. [title] (LaTeX) heads it, [counter] numbers it (null for `\newtheorem*`), and
+ * [style] is the amsthm `\theoremstyle` where it was defined: plain, definition or remark.
+ */
+internal data class Theorem(val title: String, val counter: String?, val style: String)
+
+internal class LatexNumbering(private val src: String, ast: List<LatexNode>) {
+    /** A LaTeX counter: reset when [within] steps, and printed after it when [nested]. */
+    private class Counter(var within: String?, var nested: Boolean, var style: (Int) -> String) {
+        var value = 0
+    }
+
+    /** Label → what it refers to. */
+    val labels = HashMap<String, Numbered>()
+
+    /** Theorem-like environments by name: those of `\newtheorem` and the [DEFAULT_THEOREMS] it leaves. */
+    val theorems = HashMap<String, Theorem>()
+
+    /** Labels defined more than once → the offsets of their `\label`s. */
+    val duplicates: Map<String, List<Int>>
+
+    /** Undefined references: the key and the offset of its first use. */
+    val undefined: List<Pair<String, Int>>
+
+    private val numbered = HashMap<Int, Numbered>()
+    private val equations = HashMap<Int, NumberedMath>()
+    private val floats = HashMap<Int, String>()
+    private val definitions = LinkedHashMap<String, MutableList<Int>>()
+    private val references = LinkedHashMap<String, Int>()
+    private val targets: Set<String> by lazy { labels.values.mapTo(HashSet()) { it.anchor } }
+
+    /** Whether the class has chapters (book, report and the like). */
+    var chapters = false
+        private set
+
+    private val counters = HashMap<String, Counter>()
+    private var secnumdepth = 3
+    private var inAppendix = false
+    private var anchorCount = 0
+    /** What a `\label` here refers to (LaTeX's `\@currentlabel`). */
+    private var current: Numbered? = null
+    /** Inside a float: the counter its `\caption` steps, and where the float starts. */
+    private var float: String? = null
+    private var floatStart = -1
+    /** Whether the figure being read has had its own caption; subfigures before it use the number it will get. */
+    private var figureCaptioned = false
+    /** The list environments around this point, innermost last, and the `enumerate` item counters. */
+    private val lists = ArrayList<String>()
+    private val items = IntArray(4)
+    /** The number and anchor of the last `\footnotemark`, for the `\footnotetext` that goes with it. */
+    private var footnoteMark: Pair<Int, String>? = null
+
+    init {
+        setUp(ast)
+        walk(ast)
+        duplicates = definitions.filterValues { it.size > 1 }
+        undefined = references.filterKeys { it !in labels }.toList()
+    }
+
+    /** The heading, caption, item or theorem starting at [offset], if it has a number. */
+    fun at(offset: Int): Numbered? = numbered[offset]
+
+    /** The numbered display starting at [offset]. */
+    fun math(offset: Int): NumberedMath? = equations[offset]
+
+    /** The HTML id of the float starting at [offset], if it has a numbered caption. */
+    fun floatAnchor(offset: Int): String? = floats[offset]
+
+    /** Whether a label refers to [anchor]; other elements need no id. */
+    fun isTarget(anchor: String): Boolean = anchor in targets
+
+    /** Math source for MathJax: without `\label`s, and with `\ref` and `\eqref` replaced by their numbers. */
+    fun resolve(tex: String): String =
+        MATH_REF.replace(MATH_LABEL.replace(tex, "")) { m ->
+            val number = labels[m.groupValues[2].trim()]?.ref ?: "??"
+            if (m.groupValues[1] == "eq") "\\text{($number)}" else "\\text{$number}"
+        }
+
+    // ─────────────────────────── COUNTERS ───────────────────────────
+
+    private fun setUp(ast: List<LatexNode>) {
+        var documentClass = "article"
+        forEachCommand(ast) {
+            if (it.name == "documentclass") it.args.getOrNull(1)?.let { arg -> documentClass = arg.source(src).trim() }
+        }
+        chapters = documentClass in CHAPTER_CLASSES
+        secnumdepth = if (chapters) 2 else 3
+        val top = if (chapters) "chapter" else null
+        counters["part"] = Counter(null, false) { roman(it).uppercase() }
+        counters["chapter"] = Counter(null, false, ::arabic)
+        counters["section"] = Counter(top, top != null, ::arabic)
+        counters["subsection"] = Counter("section", true, ::arabic)
+        counters["subsubsection"] = Counter("subsection", true, ::arabic)
+        counters["paragraph"] = Counter("subsubsection", true, ::arabic)
+        counters["subparagraph"] = Counter("paragraph", true, ::arabic)
+        for (name in listOf("equation", "figure", "table")) counters[name] = Counter(top, top != null, ::arabic)
+        counters["subfigure"] = Counter("figure", false, ::alph)
+        counters["footnote"] = Counter(top, false, ::arabic)
+        for ((name, style) in DEFAULT_THEOREMS) {
+            theorems[name] = Theorem(name.replaceFirstChar { it.uppercaseChar() }, name, style)
+            counters[name] = Counter(null, false, ::arabic)
+        }
+        var style = "plain"
+        forEachCommand(ast) {
+            when (it.name) {
+                "theoremstyle" -> style = it.args.getOrNull(0)?.source(src)?.trim().orEmpty()
+                "newtheorem" -> newTheorem(it, style)
+            }
+        }
+    }
+
+    /**
+     * `\newtheorem{name}{Title}`, numbered on a counter of its own; `\newtheorem{name}[other]{Title}` shares
+     * other's, `\newtheorem{name}{Title}[within]` is reset by within and printed after it, and
+     * `\newtheorem*` is unnumbered.
+     */
+    private fun newTheorem(c: CommandNode, style: String) {
+        val name = c.args.getOrNull(0)?.source(src)?.trim().orEmpty()
+        if (name.isEmpty()) return
+        val title = c.args.getOrNull(2)?.source(src)?.trim().orEmpty()
+        val shared = c.args.getOrNull(1)?.source(src)?.trim()
+        val within = c.args.getOrNull(3)?.source(src)?.trim()?.takeIf { it in counters }
+        val counter = when {
+            c.star -> null
+            shared != null && shared in counters -> shared
+            else -> name.also { counters[it] = Counter(within, within != null, ::arabic) }
+        }
+        theorems[name] = Theorem(title, counter, style)
+    }
+
+    /** `\the<name>`. */
+    private fun formatted(name: String): String {
+        val c = counters[name] ?: return ""
+        val own = c.style(c.value)
+        val parent = c.within?.takeIf { c.nested }?.let(::formatted)
+        return if (parent.isNullOrEmpty()) own else "$parent.$own"
+    }
+
+    private fun step(name: String) {
+        val c = counters[name] ?: return
+        c.value++
+        reset(name)
+    }
+
+    private fun reset(name: String) {
+        for ((child, c) in counters) {
+            if (c.within == name) {
+                c.value = 0
+                reset(child)
+            }
+        }
+    }
+
+    private fun newAnchor() = "ll-ref-${++anchorCount}"
+
+    /** Records a numbered element and makes it what the next `\label` refers to. */
+    private fun number(counter: String, the: String, ref: String, name: String, offset: Int, anchor: String = newAnchor()) =
+        Numbered(counter, the, ref, name, anchor, offset).also { current = it }
+
+    // ───────────────────────────── WALK ─────────────────────────────
+
+    private fun walk(nodes: List<LatexNode>) {
+        for (n in nodes) when (n) {
+            is CommandNode -> command(n)
+            is GroupNode -> scoped { walk(n.children) }
+            is EnvironmentNode -> scoped { environment(n) }
+            is MathNode -> math(n)
+            else -> Unit
+        }
+    }
+
+    private fun scoped(body: () -> Unit) {
+        val saved = current
+        body()
+        current = saved
+    }
+
+    private fun command(n: CommandNode) {
+        when (n.name) {
+            in DEFINITIONS -> return
+            in SECTION_LEVELS -> heading(n)
+            "appendix" -> appendix()
+            "caption" -> caption(n)
+            "item" -> item(n)
+            "footnote", "footnotemark", "footnotetext" -> return footnote(n)
+            "label" -> label(n.args.getOrNull(0)?.source(src), n.start)
+            "ref", "eqref", "autoref", "pageref" -> reference(n.args.getOrNull(0)?.source(src), n.start)
+            "setcounter", "addtocounter" -> setCounter(n)
+            "numberwithin", "counterwithin" -> numberWithin(n)
+        }
+        for (a in n.args) if (a != null) walk(a.nodes)
+    }
+
+    private fun environment(n: EnvironmentNode) {
+        when (n.name) {
+            in FLOATS -> float(n, FLOATS.getValue(n.name))
+            in theorems -> {
+                val theorem = theorems.getValue(n.name)
+                theorem.counter?.let { counter ->
+                    step(counter)
+                    val the = formatted(counter)
+                    numbered[n.start] = number(n.name, the, the, theorem.title, n.start)
+                }
+                body(n)
+            }
+            "enumerate", "itemize", "description" -> {
+                lists.add(n.name)
+                val depth = lists.count { it == "enumerate" }
+                if (n.name == "enumerate" && depth <= items.size) items[depth - 1] = 0
+                body(n)
+                lists.removeAt(lists.lastIndex)
+            }
+            else -> body(n)
+        }
+    }
+
+    private fun body(n: EnvironmentNode) {
+        for (a in n.args) if (a != null) walk(a.nodes)
+        walk(n.children)
+    }
+
+    private fun heading(n: CommandNode) {
+        val level = if (n.name == "part" && chapters) -1 else SECTION_LEVELS.getValue(n.name)
+        if (n.star || level > secnumdepth) return
+        step(n.name)
+        val the = formatted(n.name)
+        val name = when {
+            inAppendix && n.name == (if (chapters) "chapter" else "section") -> "Appendix"
+            n.name == "part" -> "Part"
+            else -> n.name
+        }
+        numbered[n.start] = number(n.name, the, the, name, n.start)
+    }
+
+    private fun appendix() {
+        val top = if (chapters) "chapter" else "section"
+        counters.getValue(top).apply {
+            value = 0
+            style = { alph(it).uppercase() }
+        }
+        reset(top)
+        inAppendix = true
+    }
+
+    private fun float(n: EnvironmentNode, counter: String) {
+        val saved = float
+        val savedStart = floatStart
+        val savedCaptioned = figureCaptioned
+        float = counter
+        floatStart = n.start
+        if (counter == "figure") figureCaptioned = false
+        body(n)
+        float = saved
+        floatStart = savedStart
+        figureCaptioned = savedCaptioned
+    }
+
+    private fun caption(n: CommandNode) {
+        val counter = float ?: return
+        step(counter)
+        val the = formatted(counter)
+        val ref = if (counter != "subfigure") the else figureNumber() + the
+        if (counter == "figure") figureCaptioned = true
+        val anchor = floats.getOrPut(floatStart) { newAnchor() }
+        numbered[n.start] = number(counter, the, ref, if (counter == "table") "Table" else "Figure", n.start, anchor)
+    }
+
+    /** The number of the figure around a subfigure, including one whose caption is still to come. */
+    private fun figureNumber(): String {
+        if (figureCaptioned) return formatted("figure")
+        val figure = counters.getValue("figure")
+        figure.value++
+        return formatted("figure").also { figure.value-- }
+    }
+
+    private fun item(n: CommandNode) {
+        if (lists.lastOrNull() != "enumerate" || n.args.getOrNull(0) != null) return
+        val depth = lists.count { it == "enumerate" }.coerceAtMost(items.size)
+        items[depth - 1]++
+        for (d in depth until items.size) items[d] = 0
+        val t = (0 until depth).map { ENUMERATE_STYLES[it](items[it]) }
+        val ref = when (depth) {
+            1 -> t[0]
+            2 -> t[0] + t[1]
+            3 -> "${t[0]}(${t[1]})${t[2]}"
+            else -> "${t[0]}(${t[1]})${t[2]}${t[3]}"
+        }
+        numbered[n.start] = number("item", t[depth - 1], ref, "item", n.start)
+    }
+
+    /**
+     * `\footnote` and `\footnotemark` step the counter unless given a number; `\footnotetext` takes the current
+     * one, and the anchor of the mark with that number. A `\label` in the note refers to it, one after it does not.
+     */
+    private fun footnote(n: CommandNode) {
+        val saved = current
+        val counter = counters.getValue("footnote")
+        val value = n.args.getOrNull(0)?.source(src)?.trim()?.toIntOrNull() ?: run {
+            if (n.name != "footnotetext") step("footnote")
+            counter.value
+        }
+        val mark = footnoteMark
+        val anchor = if (n.name == "footnotetext" && mark != null && mark.first == value) mark.second else newAnchor()
+        if (n.name == "footnotemark") footnoteMark = value to anchor
+        val the = counter.style(value)
+        numbered[n.start] = number("footnote", the, the, "footnote", n.start, anchor)
+        for (a in n.args) if (a != null) walk(a.nodes)
+        current = saved
+    }
+
+    private fun label(key: String?, offset: Int, target: Numbered? = current) {
+        val k = key?.trim().orEmpty()
+        if (k.isEmpty()) return
+        definitions.getOrPut(k) { ArrayList() }.add(offset)
+        labels[k] = target ?: Numbered("", "", "", "", "", offset)
+    }
+
+    private fun reference(key: String?, offset: Int) {
+        val k = key?.trim().orEmpty()
+        if (k.isNotEmpty()) references.putIfAbsent(k, offset)
+    }
+
+    private fun setCounter(n: CommandNode) {
+        val name = n.args.getOrNull(0)?.source(src)?.trim() ?: return
+        val value = n.args.getOrNull(1)?.source(src)?.trim()?.toIntOrNull() ?: return
+        val add = n.name == "addtocounter"
+        if (name == "secnumdepth") {
+            secnumdepth = if (add) secnumdepth + value else value
+            return
+        }
+        val c = counters[name] ?: return
+        c.value = if (add) c.value + value else value
+    }
+
+    /** `\numberwithin[format]{counter}{within}` and `\counterwithin{counter}{within}`; the star only resets. */
+    private fun numberWithin(n: CommandNode) {
+        val first = if (n.name == "numberwithin") 1 else 0
+        val name = n.args.getOrNull(first)?.source(src)?.trim() ?: return
+        val within = n.args.getOrNull(first + 1)?.source(src)?.trim() ?: return
+        val c = counters[name] ?: return
+        if (within == name || within !in counters) return
+        c.within = within
+        c.nested = !n.star
+    }
+
+    // ───────────────────────────── MATH ─────────────────────────────
+
+    private fun math(n: MathNode) {
+        val tex = src.substring(n.start, n.end)
+        for (m in MATH_REF.findAll(tex)) reference(m.groupValues[2], n.start + m.range.first)
+        val env = n.env
+        if (!n.closed || env == null || env !in NUMBERED_MATH) {
+            for (m in MATH_LABEL.findAll(tex)) label(m.groupValues[1], n.start + m.range.first)
+            return
+        }
+        val bodyStart = "\\begin{$env}".length
+        val bodyEnd = tex.lastIndexOf("\\end{$env}").coerceAtLeast(bodyStart)
+        val ends = if (env == "equation" || env == "multline") listOf(bodyEnd) else rowBreaks(tex, bodyStart, bodyEnd) + bodyEnd
+        val anchor = newAnchor()
+        val out = StringBuilder()
+        var rowStart = bodyStart
+        for (end in ends) {
+            val row = tex.substring(rowStart, end)
+            val tag = TAG.find(row)?.groupValues?.get(1)
+            val notag = NOTAG.containsMatchIn(row)
+            val target = when {
+                tag != null -> Numbered("equation", tag, tag, "Equation", anchor, n.start)
+                notag || MATH_LABEL.replace(row, "").isBlank() -> null
+                else -> {
+                    step("equation")
+                    val the = formatted("equation")
+                    Numbered("equation", the, the, "Equation", anchor, n.start)
+                }
+            }
+            out.append(row)
+            if (tag == null && !notag) out.append(if (target != null) " \\tag{${target.the}}" else " \\notag")
+            if (end < bodyEnd) out.append("\\\\")
+            for (m in MATH_LABEL.findAll(row)) label(m.groupValues[1], n.start + rowStart + m.range.first, target ?: current)
+            rowStart = end + 2
+        }
+        equations[n.start] = NumberedMath(tex.substring(0, bodyStart) + out + tex.substring(bodyEnd), anchor)
+    }
+}
+
+/** Offsets of the `\\` that end rows of [tex] between [from] and [to], outside braces and nested environments. */
+private fun rowBreaks(tex: String, from: Int, to: Int): List<Int> {
+    val breaks = ArrayList<Int>()
+    var depth = 0
+    var i = from
+    while (i < to) {
+        when (tex[i]) {
+            '{' -> depth++
+            '}' -> depth--
+            '%' -> {
+                val eol = tex.indexOf('\n', i)
+                i = if (eol < 0 || eol > to) to else eol
+                continue
+            }
+            '\\' -> {
+                when {
+                    tex.startsWith("\\\\", i) -> if (depth == 0) breaks.add(i)
+                    tex.startsWith("\\begin", i) -> depth++
+                    tex.startsWith("\\end", i) -> depth--
+                }
+                i += 2
+                continue
+            }
+        }
+        i++
+    }
+    return breaks
+}
+
+private fun arabic(n: Int): String = n.toString()
+
+private fun alph(n: Int): String = if (n in 1..26) ('a' + n - 1).toString() else n.toString()
+
+private fun roman(n: Int): String {
+    if (n <= 0) return n.toString()
+    val numerals = listOf(
+        1000 to "m", 900 to "cm", 500 to "d", 400 to "cd", 100 to "c", 90 to "xc",
+        50 to "l", 40 to "xl", 10 to "x", 9 to "ix", 5 to "v", 4 to "iv", 1 to "i",
+    )
+    var rest = n
+    return buildString {
+        for ((value, numeral) in numerals) {
+            while (rest >= value) {
+                append(numeral)
+                rest -= value
+            }
+        }
+    }
+}
+
+private val CHAPTER_CLASSES = setOf("book", "report", "scrbook", "scrreprt", "memoir")
+
+/** Sectioning commands → their level; `\part` is -1 in classes with chapters. */
+private val SECTION_LEVELS = mapOf(
+    "part" to 0, "chapter" to 0, "section" to 1, "subsection" to 2,
+    "subsubsection" to 3, "paragraph" to 4, "subparagraph" to 5,
+)
+
+/** Float environments → the counter their `\caption` steps. */
+private val FLOATS = mapOf(
+    "figure" to "figure", "figure*" to "figure", "wrapfigure" to "figure", "subfigure" to "subfigure",
+    "table" to "table", "table*" to "table", "wraptable" to "table", "longtable" to "table",
+)
+
+/** `enumerate`'s labels by depth: 1, a, i, A. */
+private val ENUMERATE_STYLES: List<(Int) -> String> = listOf(::arabic, ::alph, ::roman, { alph(it).uppercase() })
+
+/** Commands whose arguments are definitions, not text: a `\section` or `\label` in them is not one here. */
+private val DEFINITIONS = setOf(
+    "newcommand", "renewcommand", "providecommand", "newenvironment", "renewenvironment",
+    "def", "gdef", "edef", "xdef", "DeclareMathOperator",
+)
+
+/** Environments of classes such as llncs and svjour → their amsthm style. */
+private val DEFAULT_THEOREMS = mapOf(
+    "theorem" to "plain", "lemma" to "plain", "proposition" to "plain", "corollary" to "plain",
+    "identity" to "plain", "definition" to "definition", "remark" to "remark",
+)
+
+private val NUMBERED_MATH = setOf("equation", "align", "gather", "multline", "flalign", "alignat", "eqnarray")
+
+private val MATH_LABEL = Regex("""\\label\s*\{([^\{\}]*)\}""")
+private val MATH_REF = Regex("""\\(eq)?ref\s*\{([^\{\}]*)\}""")
+private val TAG = Regex("""\\tag\*?\s*\{([^\{\}]*)\}""")
+private val NOTAG = Regex("""\\no(?:number|tag)(?![A-Za-z])""")
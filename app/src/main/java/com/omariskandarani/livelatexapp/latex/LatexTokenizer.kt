@@ -0,0 +1,283 @@
+package com.omariskandarani.livelatexapp.latex
+
+/**
+ * LaTeX source → tokens, the first stage of the preview
+ * ([LatexTokenizer] → [LatexParser] → [LatexNumbering] and [LatexCitations] → [LatexHtmlEmitter]).
+ *
+ * - Characters are classified by a TeX category-code table. `\makeatletter`, `\makeatother` and literal
+ *   ``\catcode`\c=n`` assignments change it from that point on (TeX would undo them at the end of a group).
+ * - Control-word names are ASCII letters, plus `@` while it is a letter. All other plain characters form
+ *   [TokenKind.Text] runs. `[` and `]` are always tokens of their own.
+ * - As in TeX, blanks after a control word and a `%` comment (with its line break and the next line's
+ *   indentation) print nothing. They become [TokenKind.Ignored] so their line breaks can still be located.
+ * - Verbatim content comes out untouched as one [TokenKind.Verbatim] token. This covers `\verb|…|`, the body
+ *   of the [VERBATIM_ENVS], and the braced URL argument of `\url` and `\href`.
+ */
+
+/** TeX's category codes; [ordinal] is the catcode number. */
+internal enum class Catcode {
+    Escape, BeginGroup, EndGroup, MathShift, AlignTab, EndLine, Param, Superscript, Subscript,
+    Ignored, Space, Letter, Other, Active, Comment, Invalid,
+}
+
+internal enum class TokenKind {
+    /** `\name`; [Token.text] is the name. */
+    ControlWord,
+    /** `\x` for a single non-letter; [Token.text] is that character. */
+    ControlSymbol,
+    BeginGroup, EndGroup,
+    /** `$`, or `$$` when two are adjacent. */
+    MathShift,
+    AlignTab, Param, Superscript, Subscript,
+    /** `~`. */
+    Active,
+    /** Blanks with at most one line break. */
+    Space,
+    /** A blank line. */
+    ParBreak,
+    /** Comments and the blanks TeX skips. */
+    Ignored,
+    Text,
+    /** Raw content; [Token.text] is the content without its delimiters. */
+    Verbatim,
+}
+
+/** One token; [start] and [end] are offsets into the source. */
+internal data class Token(val kind: TokenKind, val text: String, val start: Int, val end: Int)
+
+/** Environments whose body is not LaTeX. */
+internal val VERBATIM_ENVS = setOf("verbatim", "verbatim*", "Verbatim", "lstlisting", "minted", "comment")
+
+/** Commands whose first braced argument is taken verbatim (`%`, `#` and `_` are common in URLs). */
+private val VERBATIM_ARG_COMMANDS = setOf("url", "href")
+
+internal class LatexTokenizer(private val src: String) {
+    private val catcodes = Array(128) { defaultCatcode(it.toChar()) }
+    private val out = ArrayList<Token>()
+    private var pos = 0
+    /** After a comment: the next line break ends an empty line. */
+    private var lineStart = false
+
+    fun tokenize(): MutableList<Token> {
+        while (pos < src.length) next()
+        return out
+    }
+
+    private fun cat(c: Char): Catcode = if (c.code < 128) catcodes[c.code] else Catcode.Other
+
+    private fun emit(kind: TokenKind, text: String, start: Int, end: Int) {
+        out += Token(kind, text, start, end)
+        lineStart = false
+    }
+
+    private fun single(kind: TokenKind) {
+        pos++
+        emit(kind, src.substring(pos - 1, pos), pos - 1, pos)
+    }
+
+    private fun next() {
+        val start = pos
+        val c = src[pos]
+        when (cat(c)) {
+            Catcode.Escape -> controlSequence()
+            Catcode.BeginGroup -> single(TokenKind.BeginGroup)
+            Catcode.EndGroup -> single(TokenKind.EndGroup)
+            Catcode.MathShift -> {
+                pos += if (pos + 1 < src.length && src[pos + 1] == c) 2 else 1
+                emit(TokenKind.MathShift, src.substring(start, pos), start, pos)
+            }
+            Catcode.AlignTab -> single(TokenKind.AlignTab)
+            Catcode.Param -> single(TokenKind.Param)
+            Catcode.Superscript -> single(TokenKind.Superscript)
+            Catcode.Subscript -> single(TokenKind.Subscript)
+            Catcode.Active -> single(TokenKind.Active)
+            Catcode.Space, Catcode.EndLine -> blanks()
+            Catcode.Comment -> comment()
+            Catcode.Ignored, Catcode.Invalid -> pos++
+            Catcode.Letter, Catcode.Other -> text()
+        }
+    }
+
+    private fun isBlank(c: Char) = cat(c) == Catcode.Space || cat(c) == Catcode.EndLine
+
+    private fun blanks() {
+        val start = pos
+        var breaks = 0
+        while (pos < src.length && isBlank(src[pos])) {
+            if (src[pos] == '\n') breaks++
+            pos++
+        }
+        val par = breaks >= 2 || (breaks == 1 && lineStart)
+        emit(if (par) TokenKind.ParBreak else TokenKind.Space, src.substring(start, pos), start, pos)
+    }
+
+    /** `%` to the end of the line, the line break, and the next line's indentation. */
+    private fun comment() {
+        val start = pos
+        val eol = src.indexOf('\n', pos)
+        pos = if (eol < 0) src.length else eol + 1
+        skipIndent()
+        emit(TokenKind.Ignored, src.substring(start, pos), start, pos)
+        lineStart = eol >= 0
+    }
+
+    private fun skipIndent() {
+        while (pos < src.length && cat(src[pos]) == Catcode.Space) pos++
+    }
+
+    private fun text() {
+        val start = pos
+        val c = src[pos]
+        if (c == '[' || c == ']') return single(TokenKind.Text)
+        while (pos < src.length) {
+            val d = src[pos]
+            val k = cat(d)
+            if ((k != Catcode.Letter && k != Catcode.Other) || d == '[' || d == ']') break
+            pos++
+        }
+        emit(TokenKind.Text, src.substring(start, pos), start, pos)
+    }
+
+    private fun controlSequence() {
+        val start = pos
+        pos++
+        if (pos >= src.length) return emit(TokenKind.Text, "\\", start, pos)
+        if (cat(src[pos]) != Catcode.Letter) {
+            pos++
+            return emit(TokenKind.ControlSymbol, src.substring(pos - 1, pos), start, pos)
+        }
+        while (pos < src.length && cat(src[pos]) == Catcode.Letter) pos++
+        val name = src.substring(start + 1, pos)
+        when {
+            name == "verb" -> verb(start)
+            name == "catcode" -> catcodeAssignment(start)
+            else -> {
+                emit(TokenKind.ControlWord, name, start, pos)
+                when (name) {
+                    "makeatletter" -> catcodes['@'.code] = Catcode.Letter
+                    "makeatother" -> catcodes['@'.code] = Catcode.Other
+                }
+                if (name == "begin") verbatimEnvironment()
+                else if (name in VERBATIM_ARG_COMMANDS) verbatimArgument()
+                else skipBlanksAfterControlWord()
+            }
+        }
+    }
+
+    /** TeX skips blanks after a control word, and one line break; a blank line still ends the paragraph. */
+    private fun skipBlanksAfterControlWord() {
+        val start = pos
+        skipIndent()
+        var broke = false
+        if (pos < src.length && src[pos] == '\n') {
+            pos++
+            skipIndent()
+            broke = true
+        }
+        if (pos > start) emit(TokenKind.Ignored, src.substring(start, pos), start, pos)
+        lineStart = broke
+    }
+
+    /** `\verb|…|` and `\verb*|…|`; the delimiter may be any character but a letter, `*` or a blank. */
+    private fun verb(start: Int) {
+        if (pos < src.length && src[pos] == '*') pos++
+        val delim = src.getOrNull(pos)
+        if (delim == null || delim.isWhitespace()) return emit(TokenKind.ControlWord, "verb", start, pos)
+        val close = src.indexOf(delim, pos + 1).takeIf { it >= 0 && src.indexOf('\n', pos + 1) !in 0 until it }
+        val contentEnd = close ?: src.indexOf('\n', pos + 1).let { if (it < 0) src.length else it }
+        val content = src.substring(pos + 1, contentEnd)
+        pos = if (close != null) close + 1 else contentEnd
+        emit(TokenKind.Verbatim, content, start, pos)
+    }
+
+    /** ``\catcode`\c=n`` with a literal character and number; anything else is left to the parser. */
+    private fun catcodeAssignment(start: Int) {
+        var i = pos
+        fun skip() { while (i < src.length && src[i] == ' ') i++ }
+        skip()
+        if (src.getOrNull(i) == '`') {
+            i++
+            if (src.getOrNull(i) == '\\') i++
+            val c = src.getOrNull(i)
+            i++
+            skip()
+            if (src.getOrNull(i) == '=') i++
+            skip()
+            val digits = i
+            while (i < src.length && src[i].isDigit()) i++
+            val code = src.substring(digits, i).toIntOrNull()
+            if (c != null && c.code < 128 && code != null && code < 16) {
+                catcodes[c.code] = Catcode.entries[code]
+                pos = i
+            }
+        }
+        emit(TokenKind.ControlWord, "catcode", start, pos)
+    }
+
+    /** `{name}` at [from] after blanks: the name's range and the offset after `}`. */
+    private fun braced(from: Int): Pair<IntRange, Int>? {
+        var i = from
+        while (i < src.length && src[i] == ' ') i++
+        if (src.getOrNull(i) != '{') return null
+        var depth = 0
+        var j = i
+        while (j < src.length) {
+            when (src[j]) {
+                '\\' -> j++
+                '{' -> depth++
+                '}' -> if (--depth == 0) return (i + 1 until j) to j + 1
+            }
+            j++
+        }
+        return null
+    }
+
+    /** After `\begin` of one of the [VERBATIM_ENVS]: the name's tokens, then the body as a single token. */
+    private fun verbatimEnvironment() {
+        val (nameRange, afterName) = braced(pos) ?: return skipBlanksAfterControlWord()
+        val name = src.substring(nameRange.first, nameRange.last + 1)
+        if (name !in VERBATIM_ENVS) return skipBlanksAfterControlWord()
+        emit(TokenKind.BeginGroup, "{", nameRange.first - 1, nameRange.first)
+        emit(TokenKind.Text, name, nameRange.first, nameRange.last + 1)
+        emit(TokenKind.EndGroup, "}", afterName - 1, afterName)
+        pos = afterName
+        // Options such as lstlisting's [language=…] and minted's {python} are not shown.
+        if (src.getOrNull(pos) == '[') {
+            val close = src.indexOf(']', pos)
+            val eol = src.indexOf('\n', pos)
+            if (close >= 0 && (eol < 0 || close < eol)) pos = close + 1
+        }
+        if (name == "minted") braced(pos)?.let { pos = it.second }
+        val start = pos
+        val endTag = "\\end{$name}"
+        val end = src.indexOf(endTag, pos).let { if (it < 0) src.length else it }
+        pos = end
+        emit(TokenKind.Verbatim, src.substring(start, end).removePrefix("\r").removePrefix("\n"), start, end)
+    }
+
+    /** The `{…}` after `\url` or `\href`, braces balanced and nothing else special. */
+    private fun verbatimArgument() {
+        val (range, after) = braced(pos) ?: return skipBlanksAfterControlWord()
+        emit(TokenKind.Verbatim, src.substring(range.first, range.last + 1), range.first - 1, after)
+        pos = after
+    }
+}
+
+private fun defaultCatcode(c: Char): Catcode = when (c) {
+    '\\' -> Catcode.Escape
+    '{' -> Catcode.BeginGroup
+    '}' -> Catcode.EndGroup
+    '$' -> Catcode.MathShift
+    '&' -> Catcode.AlignTab
+    '\n' -> Catcode.EndLine
+    '#' -> Catcode.Param
+    '^' -> Catcode.Superscript
+    '_' -> Catcode.Subscript
+    '\u0000' -> Catcode.Ignored
+    ' ', '\t', '\r' -> Catcode.Space
+    '~' -> Catcode.Active
+    '%' -> Catcode.Comment
+    '\u007f' -> Catcode.Invalid
+    in 'a'..'z', in 'A'..'Z' -> Catcode.Letter
+    else -> Catcode.Other
+}
@@ -6,17 +6,25 @@ import kotlin.text.Regex
 import kotlin.text.RegexOption
 
 /**
- * TikZ renderer for Android: preamble collection and placeholder for diagrams.
- * No pdflatex/dvisvgm on device — TikZ blocks are shown as placeholders in preview;
- * full PDF is produced by Export via Rust/Tectonic.
+ * TikZ renderer for Android: preamble collection and SVG previews of diagrams.
+ * No pdflatex/dvisvgm on device — figures are compiled by [svgRenderer] (the app sets it to
+ * rust_core's `renderTikzSvg`); without one, TikZ blocks are shown as placeholders in preview.
  */
 object TikzRenderer {
 
     var currentBaseDir: String? = null
 
+    /**
+     * Compiles a standalone TikZ document into `<projectDir>/.livelatex-cache/tikz/<sha1>.svg`
+     * and returns that file, or null on failure. Blocking; null when no native compiler is available.
+     */
+    @Volatile
+    var svgRenderer: ((texDoc: String, projectDir: File) -> File?)? = null
+
+    private fun projectDir(): File = currentBaseDir?.let(::File) ?: File(".")
+
     private fun tikzCacheDir(): File {
-        val base = currentBaseDir?.let(::File) ?: File(".")
-        val dir  = File(base, ".livelatex-cache/tikz")
+        val dir  = File(projectDir(), ".livelatex-cache/tikz")
         if (!dir.exists()) dir.mkdirs()
         return dir
     }
@@ -31,26 +39,6 @@ object TikzRenderer {
         val bytes = md.digest(s.toByteArray(Charsets.UTF_8))
         return bytes.joinToString("") { "%02x".format(it) }
     }
-    private fun fileUrl(f: File) = f.toURI().toString()
-
-    /** Optional `[...]` right after `\\begin{tikzpicture}`; avoids `\\[` / `\\]` in Regex (Android ICU). */
-    private fun splitTikzPictureOptsAndBody(inner: String): Pair<String, String> {
-        val s = inner.trimStart()
-        if (!s.startsWith('[')) return "" to inner
-        var depth = 0
-        var i = 0
-        while (i < s.length) {
-            when (s[i]) {
-                '[' -> depth++
-                ']' -> {
-                    depth--
-                    if (depth == 0) return s.substring(0, i + 1).trim() to s.substring(i + 1)
-                }
-            }
-            i++
-        }
-        return "" to inner
-    }
 
     private fun collectBalanced(cmd: String, s: String): List<String> {
         val out = mutableListOf<String>()
@@ -91,18 +79,12 @@ object TikzRenderer {
         key: String,
         renderButtonLabel: String,
         svgMarkup: String?,
-        /** When set (e.g. on Android), show this instead of the Render button when no cached SVG exists. */
-        noCompilerNote: String? = null,
     ): String {
         val svgPart = if (!svgMarkup.isNullOrBlank()) {
             """<span class="tikz-wrap" style="display:block;margin:0 0 10px 0;">$svgMarkup</span>"""
         } else ""
         val footer = when {
             !svgMarkup.isNullOrBlank() -> ""
-            !noCompilerNote.isNullOrBlank() -> {
-                val esc = htmlEscapeAll(noCompilerNote)
-                """<p class="ll-tikz-no-compiler-note" style="opacity:.9;font-size:0.92em;margin:10px 0 0 0;line-height:1.4;">$esc</p>"""
-            }
             renderButtonLabel.isNotBlank() -> {
                 val labelEsc = htmlEscapeAll(renderButtonLabel)
                 """<button type="button" class="ll-render-tikz" style="font:inherit;padding:8px 14px;border-radius:6px;border:1px solid var(--border);background:var(--bg);color:var(--fg);cursor:pointer;" onclick="if(window.TikzAndroid){TikzAndroid.render('$key');}">$labelEsc</button>"""
@@ -117,35 +99,28 @@ object TikzRenderer {
             """.trimIndent()
     }
 
-    fun convertTikzPictures(
-        htmlLike: String,
+    /**
+     * One `tikzpicture` ([options] is its `[…]` or empty): registers a job for the “Render TikZ” button and returns
+     * the preview block, with the SVG when it is already cached.
+     */
+    fun convertTikzPicture(
+        options: String,
+        body: String,
         fullSourceNoComments: String,
         tikzPreamble: String,
         renderButtonLabel: String = "Render TikZ",
-        noCompilerNote: String? = null,
     ): String {
-        val rx = rxBetween(
-            "\\begin{tikzpicture}",
-            "\\end{tikzpicture}",
-            "(.*?)"
-        )
-        val userMacros = extractNewcommands(fullSourceNoComments)
-        val texMacroDefs = buildTexNewcommands(userMacros)
-        val srcLibs = collectUsetikzlibsFromSource(fullSourceNoComments)
-
-        return rx.replace(htmlLike) { m ->
-            val (opts, bodyRaw) = splitTikzPictureOptsAndBody(m.groupValues[1])
-            val body = bodyRaw.trim()
-            val hay = opts + "\n" + body
-            val autoLibs = buildSet {
-                if (Regex("""-\{?Latex""").containsMatchIn(hay) || Regex(""">=\s*Latex""").containsMatchIn(hay)) add("arrows.meta")
-                if (Regex("""\b(left|right|above|below)\s*=\s*|[^=]\bof\b""").containsMatchIn(hay)) add("positioning")
-                if (Regex("""use\s+Hobby\s+shortcut|invert\s+soft\s+blanks|\[blank=""").containsMatchIn(hay)) addAll(listOf("hobby", "topaths"))
-                if (hay.contains("\\begin{knot}") || hay.contains("flip crossing/")) addAll(listOf("knots", "hobby", "intersections", "decorations.pathreplacing", "shapes.geometric", "spath3", "topaths"))
-            }
-            val allLibs = (srcLibs + autoLibs).toSortedSet()
-            val libsLine = if (allLibs.isNotEmpty()) "\\usetikzlibrary{${allLibs.joinToString(",")}}\n" else ""
-            val texDoc = """
+        val (texMacroDefs, srcLibs) = sourceDefs(fullSourceNoComments)
+        val hay = options + "\n" + body
+        val autoLibs = buildSet {
+            if (Regex("""-\{?Latex""").containsMatchIn(hay) || Regex(""">=\s*Latex""").containsMatchIn(hay)) add("arrows.meta")
+            if (Regex("""\b(left|right|above|below)\s*=\s*|[^=]\bof\b""").containsMatchIn(hay)) add("positioning")
+            if (Regex("""use\s+Hobby\s+shortcut|invert\s+soft\s+blanks|\[blank=""").containsMatchIn(hay)) addAll(listOf("hobby", "topaths"))
+            if (hay.contains("\\begin{knot}") || hay.contains("flip crossing/")) addAll(listOf("knots", "hobby", "intersections", "decorations.pathreplacing", "shapes.geometric", "spath3", "topaths"))
+        }
+        val allLibs = (srcLibs + autoLibs).toSortedSet()
+        val libsLine = if (allLibs.isNotEmpty()) "\\usetikzlibrary{${allLibs.joinToString(",")}}\n" else ""
+        val texDoc = """
 \documentclass[tikz,border=1pt]{standalone}
 \usepackage{amsmath,amssymb,bm}
 \usepackage{tikz}
@@ -154,42 +129,58 @@ $texMacroDefs
 $tikzPreamble
 \usetikzlibrary{ spath3, intersections, arrows, knots, calc, hobby, decorations.pathreplacing, shapes.geometric, }
 \begin{document}
-\begin{tikzpicture}$opts
+\begin{tikzpicture}$options
 $body
 \end{tikzpicture}
 \end{document}
             """.trimIndent()
-            val key = sha1(texDoc)
-            LatexHtml.registerTikzRenderJob(key, texDoc)
-            val cache = tikzCacheDir()
-            val svg = File(cache, "$key.svg")
-            val svgText = if (svg.exists()) svg.readText() else null
-            return@replace tikzPreviewBlock(key, renderButtonLabel, svgText, noCompilerNote)
-        }
+        val key = sha1(texDoc)
+        LatexHtml.registerTikzRenderJob(key, texDoc)
+        val cache = tikzCacheDir()
+        val svg = File(cache, "$key.svg")
+        val svgText = if (svg.exists()) svg.readText() else null
+        return tikzPreviewBlock(key, renderButtonLabel, svgText)
     }
 
-    fun convertSstTikzMacros(s: String, srcNoComments: String): String {
-        val preSeen = collectTikzPreamble(srcNoComments)
-        val needsSst = Regex("""\\SST[A-Za-z]""").containsMatchIn(s) &&
-                !Regex("""\\usetikzlibrary\{(.*?)\}""", RegexOption.DOT_MATCHES_ALL).findAll(preSeen).any { m ->
-                    Regex("""\bsstknots\b""").containsMatchIn(m.groupValues[1])
-                }
-        val preamble = if (needsSst) preSeen + "\n\\usetikzlibrary{sstknots}\n" else preSeen
-        val rx = Regex(
-            """\\SST[A-Za-z]+(?:\[(.*?)\])?(?:\{(.*?)\}(?:\{(.*?)\}(?:\{(.*?)\})?)?)?""",
-            RegexOption.DOT_MATCHES_ALL
-        )
-        return rx.replace(s) { m ->
-            val svg = renderTikzToSvg(preamble, m.value)
-            if (svg != null)
-                """<img src="${fileUrl(svg)}" alt="tikz" style="max-width:100%;height:auto;display:block;margin:10px auto;"/>"""
-            else
-                """<pre style="background:#0001;border:1px solid var(--border);padding:8px;overflow:auto;">[TikZ not rendered on device]\n${escapeHtmlKeepBackslashes(m.value)}</pre>"""
-        }
+    /** Last source seen by [sourceDefs], with its `\newcommand`s for the figure and its TikZ libraries. */
+    private var sourceDefsCache: Pair<String, Pair<String, Set<String>>>? = null
+
+    /** Same for every picture of one preview, so computed once per source. */
+    private fun sourceDefs(fullSourceNoComments: String): Pair<String, Set<String>> {
+        sourceDefsCache?.takeIf { it.first === fullSourceNoComments }?.let { return it.second }
+        val defs = buildTexNewcommands(extractNewcommands(fullSourceNoComments)) to
+            collectUsetikzlibsFromSource(fullSourceNoComments)
+        sourceDefsCache = fullSourceNoComments to defs
+        return defs
     }
 
-    private fun renderTikzToSvg(preamble: String, tikzEnvBlock: String): File? {
-        return null // Android: no pdflatex/dvisvgm
+    /**
+     * One `\SST…` macro ([macroSource] is the whole call): like [convertTikzPicture], registers a job for the
+     * “Render TikZ” button and returns the preview block, with the SVG when it is already cached.
+     */
+    fun convertSstTikzMacro(
+        macroSource: String,
+        srcNoComments: String,
+        renderButtonLabel: String = "Render TikZ",
+    ): String {
+        val preSeen = collectTikzPreamble(srcNoComments)
+        val needsSst = !Regex("""\\usetikzlibrary\{(.*?)\}""", RegexOption.DOT_MATCHES_ALL).findAll(preSeen).any { m ->
+            Regex("""\bsstknots\b""").containsMatchIn(m.groupValues[1])
+        }
+        val preamble = if (needsSst) preSeen + "\n\\usetikzlibrary{sstknots}\n" else preSeen
+        val texDoc = """
+\documentclass[tikz,border=1pt]{standalone}
+\usepackage{amsmath,amssymb,bm}
+$preamble
+\begin{document}
+$macroSource
+\end{document}
+        """.trimIndent()
+        val key = sha1(texDoc)
+        LatexHtml.registerTikzRenderJob(key, texDoc)
+        val svg = File(tikzCacheDir(), "$key.svg")
+        val svgText = if (svg.exists()) svg.readText() else null
+        return tikzPreviewBlock(key, renderButtonLabel, svgText)
     }
 
     private fun findBalancedBrace(s: String, open: Int): Int {
@@ -232,7 +223,7 @@ $body
 
     private fun extractNewcommands(s: String): Map<String, Macro> {
         val out = linkedMapOf<String, Macro>()
-        val rxNewStart = Regex("""\\newcommand\{\\([A-Za-z@]+)\}(?:\[(\d+)])?(?:\[(.*?)\])?\{""", RegexOption.DOT_MATCHES_ALL)
+        val rxNewStart = Regex("""\\newcommand\{\\([A-Za-z@]+)\}(?:\[(\d+)\])?(?:\[(.*?)\])?\{""", RegexOption.DOT_MATCHES_ALL)
         var pos = 0
         while (true) {
             val m = rxNewStart.find(s, pos) ?: break
@@ -0,0 +1,92 @@
+package com.omariskandarani.livelatexapp.latex
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class LatexHtmlEmitterTest {
+    private fun html(src: String): String {
+        val ast = LatexParser(src).parse()
+        return LatexHtmlEmitter(
+            src, LatexNumbering(src, ast), LatexCitations(src, ast),
+            tikzPicture = { _, _ -> "" },
+            sstMacro = { "" },
+        ).document(ast)
+    }
+
+    private fun syncline(line: Int) = "<span class=\"syncline\" data-abs=\"$line\"></span>"
+
+    private fun td(align: String, content: String) =
+        "<td style=\"text-align:$align;padding:4px 8px;border:1px solid var(--border);vertical-align:top;\">$content</td>"
+
+    @Test
+    fun blankLinesSeparateParagraphs() {
+        assertEquals(
+            "<p>One\n${syncline(2)}two</p>${syncline(3)}${syncline(4)}<p>Three</p>",
+            html("One\ntwo\n\nThree"),
+        )
+    }
+
+    @Test
+    fun commentLinesKeepTheirSynclines() {
+        assertEquals("<p>a\n${syncline(2)}${syncline(3)}b</p>", html("a\n% c\nb"))
+    }
+
+    @Test
+    fun onlyTheDocumentBodyIsPrinted() {
+        assertEquals("<p>Hi</p>", html("\\documentclass{article}\n\\begin{document}Hi\\end{document}"))
+    }
+
+    @Test
+    fun tabularCellsFollowTheColumnSpec() {
+        val out = html("\\begin{tabular}{|l|c|}a & b\\\\ c & d\\\\ \\multicolumn{2}{r}{Total}\\end{tabular}")
+        assertTrue(out.startsWith("<table "))
+        assertTrue(out.contains("<tr>${td("left", "a ")}${td("center", " b")}</tr>"))
+        assertTrue(out.contains("<tr>${td("left", " c ")}${td("center", " d")}</tr>"))
+        assertTrue(out.contains("<td colspan=\"2\" style=\"text-align:right;"))
+        assertTrue(out.contains(">Total</td>"))
+    }
+
+    @Test
+    fun longtableKeepsTheFirstHeadAndTheCaption() {
+        val out = html(
+            """
+            \begin{longtable}{ll}
+            \caption{Data}\\
+            Name & Value\\
+            \endfirsthead
+            Name (cont.) & Value\\
+            \endhead
+            a & 1\\
+            \end{longtable}
+            """.trimIndent()
+        )
+        assertTrue(out.startsWith("<figure"))
+        assertTrue(out.contains("Data</figcaption>"))
+        assertTrue(out.contains("Name"))
+        assertFalse(out.contains("(cont.)"))
+        assertEquals(2, Regex("<tr>").findAll(out).count())
+    }
+
+    @Test
+    fun tcolorboxShowsTitleColorsAndBody() {
+        val out = html("\\begin{tcolorbox}[colback=red, title={Note \\textbf{B}}]\nBody text\n\\end{tcolorbox}")
+        assertTrue(out.startsWith("<div class=\"tcb\""))
+        assertTrue(out.contains("background:#dc2626;"))
+        assertTrue(out.contains("border:1px solid #1e3a8a"))
+        assertTrue(out.contains("<div class=\"tcb-title\" style=\"font-weight:600;margin-bottom:6px;\">Note "))
+        assertTrue(out.contains("<div class=\"tcb-body\">${syncline(2)}<p>Body text\n${syncline(3)}</p></div></div>"))
+    }
+
+    @Test
+    fun newcommandsBecomeMathJaxMacros() {
+        val page = LatexHtml.wrap(
+            "\\newcommand{\\R}{\\mathbb{R}}\n\\newcommand{\\pair}[2][x]{(#1,#2)}\n\\renewcommand\\vb[1]{\\vec{#1}}\n\$\\R\$"
+        )
+        assertTrue(page.contains("\"R\": \"\\\\mathbb{R}\""))
+        assertTrue(page.contains("\"pair\": [\"(#1,#2)\", 2]"))
+        assertTrue(page.contains("\"vb\": [\"\\\\vec{#1}\", 1]"))
+        assertFalse(page.contains("\"vb\": [\"\\\\mathbf{#1}\", 1]"))
+    }
+}
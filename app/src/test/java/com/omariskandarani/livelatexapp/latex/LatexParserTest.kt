@@ -0,0 +1,110 @@
+package com.omariskandarani.livelatexapp.latex
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertNull
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class LatexParserTest {
+    private fun parse(src: String) = LatexParser(src).parse()
+
+    @Test
+    fun starOptionalAndNestedBraces() {
+        val src = "\\section*[Short]{A {nested} title}"
+        val c = parse(src).single() as CommandNode
+        assertEquals("section", c.name)
+        assertTrue(c.star)
+        assertTrue(c.args[0]!!.optional)
+        assertEquals("Short", c.args[0]!!.source(src))
+        assertEquals("A {nested} title", c.args[1]!!.source(src))
+        val group = c.args[1]!!.nodes.filterIsInstance<GroupNode>().single()
+        assertEquals(listOf(TextNode("nested", 20, 26)), group.children)
+    }
+
+    @Test
+    fun unbracedArgumentsTakeOneCharacterEach() {
+        val nodes = parse("\\textcolor rgb")
+        val c = nodes[0] as CommandNode
+        assertEquals(listOf(TextNode("r", 11, 12)), c.args[1]!!.nodes)
+        assertEquals(listOf(TextNode("g", 12, 13)), c.args[2]!!.nodes)
+        assertEquals(13, c.end)
+        assertEquals(TextNode("b", 13, 14), nodes[1])
+
+        val section = parse("\\section*x").single() as CommandNode
+        assertTrue(section.star)
+        assertEquals(listOf(TextNode("x", 9, 10)), section.args[1]!!.nodes)
+    }
+
+    @Test
+    fun missingOptionalArgumentIsNull() {
+        val src = "\\section{T}"
+        val c = parse(src).single() as CommandNode
+        assertFalse(c.star)
+        assertNull(c.args[0])
+        assertEquals("T", c.args[1]!!.source(src))
+    }
+
+    @Test
+    fun optionalArgumentMustCloseBeforeTheParagraphEnds() {
+        val nodes = parse("\\item [a\n\nb]")
+        assertNull((nodes.first() as CommandNode).args[0])
+        assertTrue(nodes.any { it is ParNode })
+    }
+
+    @Test
+    fun bracketInsideBracesDoesNotCloseAnOptionalArgument() {
+        val src = "\\item[{a]b}] x"
+        val c = parse(src).first() as CommandNode
+        assertEquals("{a]b}", c.args[0]!!.source(src))
+    }
+
+    @Test
+    fun newcommandBodyIsTheFourthArgument() {
+        val src = "\\newcommand{\\pair}[2][x]{(#1,#2)}"
+        val c = parse(src).single() as CommandNode
+        assertEquals(4, c.args.size)
+        assertEquals("pair", (c.args[0]!!.nodes.single() as CommandNode).name)
+        assertEquals("2", c.args[1]!!.source(src))
+        assertEquals("x", c.args[2]!!.source(src))
+        assertEquals("(#1,#2)", c.args[3]!!.source(src))
+
+        val bare = "\\newcommand\\R{\\mathbb R}"
+        val r = parse(bare).single() as CommandNode
+        assertEquals("R", (r.args[0]!!.nodes.single() as CommandNode).name)
+        assertNull(r.args[1])
+        assertNull(r.args[2])
+        assertEquals("\\mathbb R", r.args[3]!!.source(bare))
+    }
+
+    @Test
+    fun newtheoremEnvironmentTakesATitle() {
+        val src = "\\newtheorem{claim}{Claim}\n\\begin{claim}[Note]\nBody\n\\end{claim}"
+        val env = parse(src).filterIsInstance<EnvironmentNode>().single()
+        assertEquals("claim", env.name)
+        assertEquals("Note", env.args[0]!!.source(src))
+        assertEquals("\nBody\n", src.substring(env.bodyStart, env.bodyEnd))
+        assertEquals(src.length, env.end)
+    }
+
+    @Test
+    fun mathIsKeptWhole() {
+        val nodes = parse("\$x\$ and \\[y\\] \\begin{align}a&b\\end{align}").filterIsInstance<MathNode>()
+        assertEquals(listOf(false, true, true), nodes.map { it.display })
+        assertEquals(listOf(null, null, "align"), nodes.map { it.env })
+        assertTrue(nodes.all { it.closed })
+    }
+
+    @Test
+    fun unclosedGroupEndsAtTheEndOfInput() {
+        val src = "{a \\textbf{b"
+        val group = parse(src).single() as GroupNode
+        assertEquals(src.length, group.end)
+        assertEquals("textbf", group.children.filterIsInstance<CommandNode>().single().name)
+    }
+
+    @Test
+    fun strayClosingBraceIsDropped() {
+        assertEquals(listOf(TextNode("a", 0, 1), TextNode("b", 2, 3)), parse("a}b"))
+    }
+}
@@ -0,0 +1,84 @@
+package com.omariskandarani.livelatexapp.latex
+
+import org.junit.Assert.assertEquals
+import org.junit.Test
+
+class LatexTokenizerTest {
+    private fun tokens(src: String) = LatexTokenizer(src).tokenize().map { it.kind to it.text }
+
+    @Test
+    fun controlWordsGroupsAndText() {
+        assertEquals(
+            listOf(
+                TokenKind.ControlWord to "textbf", TokenKind.BeginGroup to "{", TokenKind.Text to "a",
+                TokenKind.EndGroup to "}", TokenKind.Space to " ", TokenKind.Text to "b",
+            ),
+            tokens("\\textbf{a} b"),
+        )
+        val first = LatexTokenizer("\\textbf{a}").tokenize().first()
+        assertEquals(0 to 7, first.start to first.end)
+    }
+
+    @Test
+    fun bracketsAreTokensOfTheirOwn() {
+        assertEquals(listOf("a", "[", "b", "]", "c"), tokens("a[b]c").map { it.second })
+    }
+
+    @Test
+    fun blanksAfterControlWordsAndCommentsAreIgnored() {
+        assertEquals(
+            listOf(TokenKind.ControlWord to "foo", TokenKind.Ignored to "  ", TokenKind.Text to "bar"),
+            tokens("\\foo  bar"),
+        )
+        assertEquals(
+            listOf(
+                TokenKind.Text to "a", TokenKind.Space to " ", TokenKind.Ignored to "% note\n   ",
+                TokenKind.Text to "b",
+            ),
+            tokens("a % note\n   b"),
+        )
+    }
+
+    @Test
+    fun blankLineIsAParagraphBreak() {
+        assertEquals(
+            listOf(TokenKind.Text to "a", TokenKind.ParBreak to "\n\n", TokenKind.Text to "b"),
+            tokens("a\n\nb"),
+        )
+        assertEquals(TokenKind.Space, tokens("a\nb")[1].first)
+    }
+
+    @Test
+    fun verbAndUrlArgumentsAreVerbatim() {
+        assertEquals(
+            listOf(
+                TokenKind.Verbatim to "x_%1", TokenKind.Space to " ", TokenKind.ControlWord to "url",
+                TokenKind.Verbatim to "a%b_c",
+            ),
+            tokens("\\verb|x_%1| \\url{a%b_c}"),
+        )
+    }
+
+    @Test
+    fun verbatimEnvironmentBodyIsOneToken() {
+        assertEquals(
+            listOf(
+                TokenKind.ControlWord to "begin", TokenKind.BeginGroup to "{", TokenKind.Text to "verbatim",
+                TokenKind.EndGroup to "}", TokenKind.Verbatim to "\\x %y\n", TokenKind.ControlWord to "end",
+                TokenKind.BeginGroup to "{", TokenKind.Text to "verbatim", TokenKind.EndGroup to "}",
+            ),
+            tokens("\\begin{verbatim}\n\\x %y\n\\end{verbatim}"),
+        )
+    }
+
+    @Test
+    fun makeatletterMakesAtALetter() {
+        assertEquals(
+            listOf(
+                TokenKind.ControlWord to "makeatletter", TokenKind.ControlWord to "p@x",
+                TokenKind.ControlWord to "makeatother", TokenKind.ControlWord to "p", TokenKind.Text to "@x",
+            ),
+            tokens("\\makeatletter\\p@x\\makeatother\\p@x"),
+        )
+    }
+}
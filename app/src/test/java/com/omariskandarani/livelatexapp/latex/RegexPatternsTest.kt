@@ -0,0 +1,58 @@
+package com.omariskandarani.livelatexapp.latex
+
+import org.junit.Assert.assertTrue
+import org.junit.Test
+import java.io.File
+
+/**
+ * Android's regex engine (ICU) rejects braces that are not a quantifier unless they are escaped, where the JVM's
+ * accepts them; so every `Regex("""…""")` literal of the preview is checked here, not only compiled. A closing `]`
+ * outside a class must be escaped as well.
+ */
+class RegexPatternsTest {
+    private val sources = File("src/main/java/com/omariskandarani/livelatexapp/latex")
+    private val literal = Regex("Regex\\(\\s*\"\"\"(.*?)\"\"\"", RegexOption.DOT_MATCHES_ALL)
+    private val template = Regex("\\$[{A-Za-z_]")
+    private val quantifier = Regex("\\{\\d+(?:,\\d*)?\\}")
+
+    /** Offsets of `{`, `}` and `]` in [pattern] that are neither escaped, quantifiers nor part of a class. */
+    private fun strayBrackets(pattern: String): List<Int> {
+        val out = mutableListOf<Int>()
+        var inClass = false
+        var i = 0
+        while (i < pattern.length) {
+            val c = pattern[i]
+            when {
+                c == '\\' -> i++
+                inClass -> if (c == ']') inClass = false
+                c == '[' -> inClass = true
+                c == '{' -> {
+                    val q = quantifier.find(pattern, i)?.takeIf { it.range.first == i }
+                    if (q != null) i = q.range.last else out += i
+                }
+                c == '}' || c == ']' -> out += i
+            }
+            i++
+        }
+        return out
+    }
+
+    @Test
+    fun patternsCompileAndEscapeTheirBraces() {
+        val files = sources.listFiles { f -> f.extension == "kt" }.orEmpty()
+        assertTrue("no sources under ${sources.absolutePath}", files.isNotEmpty())
+        var checked = 0
+        for (file in files) {
+            val text = file.readText()
+            for (m in literal.findAll(text)) {
+                val pattern = m.groupValues[1]
+                if (template.containsMatchIn(pattern)) continue
+                val where = "${file.name}:${text.substring(0, m.range.first).count { it == '\n' } + 1}"
+                Regex(pattern)
+                assertTrue("$where: unescaped bracket in $pattern", strayBrackets(pattern).isEmpty())
+                checked++
+            }
+        }
+        assertTrue(checked > 0)
+    }
+}
@@ -0,0 +1,159 @@
+//! Bibliographies with the built-in BibTeX.
+//!
+//! `\bibliography{}` documents need nothing special: the engine driver runs
+//! BibTeX whenever the `.aux` names a `\bibdata`. biblatex defaults to biber,
+//! which has no embeddable implementation; its `backend=bibtex` mode produces
+//! the same `.bbl` through BibTeX (with `biblatex.bst`), so the source is
+//! switched to it before compiling.
+
+use std::borrow::Cow;
+
+use crate::diagnostics::{Diagnostic, Severity};
+
+/// Rewrites `\usepackage[…]{biblatex}` to use `backend=bibtex`. Returns the
+/// source unchanged (and no note) when biblatex is absent or already on BibTeX.
+/// The edit stays on the same line, so TeX line numbers are unaffected.
+pub fn use_bibtex_backend(source: &str) -> (Cow<'_, str>, Option<Diagnostic>) {
+    let Some(found) = find_biblatex(source) else {
+        return (Cow::Borrowed(source), None);
+    };
+    let options = found.options.map(|(start, end)| &source[start..end]);
+    let current = options.and_then(|o| {
+        o.split(',')
+            .filter_map(|kv| kv.split_once('='))
+            .find(|(k, _)| k.trim() == "backend")
+            .map(|(_, v)| v.trim())
+    });
+    if current.is_some_and(|b| b.starts_with("bibtex")) {
+        return (Cow::Borrowed(source), None);
+    }
+
+    let mut out = String::with_capacity(source.len() + 16);
+    match found.options {
+        Some((start, end)) => {
+            let rewritten: Vec<String> = source[start..end]
+                .split(',')
+                .filter(|kv| {
+                    kv.split_once('=')
+                        .is_none_or(|(k, _)| k.trim() != "backend")
+                })
+                .map(str::to_string)
+                .chain(std::iter::once("backend=bibtex".to_string()))
+                .filter(|kv| !kv.trim().is_empty())
+                .collect();
+            out.push_str(&source[..start]);
+            out.push_str(&rewritten.join(","));
+            out.push_str(&source[end..]);
+        }
+        None => {
+            out.push_str(&source[..found.name_start]);
+            out.push_str("[backend=bibtex]");
+            out.push_str(&source[found.name_start..]);
+        }
+    }
+    let note = Diagnostic {
+        file: None,
+        line: Some(source[..found.command_start].matches('\n').count() as u32 + 1),
+        message: "biblatex: biber is not available, using backend=bibtex \
+                  (no Unicode-aware sorting; biber-only options are ignored)"
+            .into(),
+        severity: Severity::Warning,
+        context: None,
+    };
+    (Cow::Owned(out), Some(note))
+}
+
+struct Biblatex {
+    command_start: usize,
+    /// Byte range inside `[...]`, if there is an option list.
+    options: Option<(usize, usize)>,
+    /// Offset of the `{biblatex}` argument.
+    name_start: usize,
+}
+
+fn find_biblatex(source: &str) -> Option<Biblatex> {
+    const CMD: &str = "\\usepackage";
+    let mut from = 0;
+    while let Some(pos) = source[from..].find(CMD) {
+        let command_start = from + pos;
+        from = command_start + CMD.len();
+        let line_start = source[..command_start].rfind('\n').map_or(0, |i| i + 1);
+        if is_commented(&source[line_start..command_start]) {
+            continue;
+        }
+        let mut at = skip_space(source, from);
+        let mut options = None;
+        if source[at..].starts_with('[') {
+            let end = at + source[at..].find(']')?;
+            options = Some((at + 1, end));
+            at = skip_space(source, end + 1);
+        }
+        if source[at..].starts_with("{biblatex}") {
+            return Some(Biblatex {
+                command_start,
+                options,
+                name_start: at,
+            });
+        }
+    }
+    None
+}
+
+fn skip_space(source: &str, at: usize) -> usize {
+    at + (source[at..].len() - source[at..].trim_start().len())
+}
+
+fn is_commented(prefix: &str) -> bool {
+    let mut escaped = false;
+    for c in prefix.chars() {
+        if c == '%' && !escaped {
+            return true;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biber_is_switched_to_bibtex_on_the_same_line() {
+        let src = "\\documentclass{article}\n\\usepackage[style=apa, backend=biber]{biblatex}\n";
+        let (out, note) = use_bibtex_backend(src);
+        assert_eq!(
+            out,
+            "\\documentclass{article}\n\\usepackage[style=apa,backend=bibtex]{biblatex}\n"
+        );
+        let note = note.unwrap();
+        assert_eq!(note.line, Some(2));
+        assert_eq!(note.severity, Severity::Warning);
+    }
+
+    #[test]
+    fn an_option_list_is_added_when_there_is_none() {
+        let (out, note) = use_bibtex_backend("\\usepackage {biblatex}");
+        assert_eq!(out, "\\usepackage [backend=bibtex]{biblatex}");
+        assert!(note.is_some());
+    }
+
+    #[test]
+    fn bibtex_backends_and_other_packages_are_left_alone() {
+        for src in [
+            "\\usepackage[backend=bibtex8]{biblatex}",
+            "\\usepackage{natbib}\n\\bibliography{refs}",
+            "% \\usepackage{biblatex}\n",
+        ] {
+            let (out, note) = use_bibtex_backend(src);
+            assert!(matches!(out, Cow::Borrowed(_)), "{src}");
+            assert!(note.is_none(), "{src}");
+        }
+    }
+
+    #[test]
+    fn an_escaped_percent_is_not_a_comment() {
+        let (out, _) = use_bibtex_backend("50\\% \\usepackage{biblatex}");
+        assert_eq!(out, "50\\% \\usepackage[backend=bibtex]{biblatex}");
+    }
+}
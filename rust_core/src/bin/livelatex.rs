@@ -0,0 +1,437 @@
+//! `livelatex`: the JNI pipeline on the command line, for scripting and
+//! regression tests on a workstation.
+//!
+//! Every subcommand prints the JSON the matching `LatexCompiler` export returns,
+//! so a run here and one on a device can be diffed. Failures outside the
+//! document print `{"error": "…"}`. Exit status: 0 on success, 1 when the
+//! compile produced no PDF or the command failed, 2 for bad arguments.
+//!
+//! The cache (bundle, formats, work dirs) is `--cache`, else `$LIVELATEX_CACHE`,
+//! else `$XDG_CACHE_HOME/livelatex` or `~/.cache/livelatex`. Put a TeX tree in
+//! its `bundle/` or a Tectonic ZIP at `bundle.zip`; the engine needs the
+//! `tectonic` feature (`cargo run --features tectonic --bin livelatex`).
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+use rust_core::synctex::SyncTex;
+use rust_core::wordcount::{self, WordCountOptions};
+use rust_core::{compile_project, compile_with, pdf, texlog, tikz, CompileOptions, CompileReport};
+
+const USAGE: &str = "\
+usage: livelatex <command> [args]
+
+  compile <file.tex|-> [-o out.pdf] [--options JSON] [--cache DIR]
+  compile-project <root> <main.tex> [-o out.pdf] [--options JSON] [--cache DIR]
+  render-tikz <figure.tex> [--project DIR] [--cache DIR]
+  parse-log <file.log|-> [--main-file NAME]
+  synctex-query <out.pdf> --line N [--file NAME] [--column N] [--cache DIR]
+  synctex-query <out.pdf> --page N --x PT --y PT [--cache DIR]
+  pdf-text <out.pdf> [--search TEXT]
+  word-count <out.pdf> [--options JSON]
+
+--options takes CompileOptions JSON (WordCountOptions for word-count), or @path
+to read it from a file.
+";
+
+/// Bad arguments; printed with [`USAGE`].
+struct Usage(String);
+
+type Outcome = std::result::Result<ExitCode, Usage>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprint!("{USAGE}");
+        return ExitCode::from(2);
+    };
+    let mut args = Args::parse(rest);
+    let outcome = match command.as_str() {
+        "compile" => compile(&mut args),
+        "compile-project" => compile_project_cmd(&mut args),
+        "render-tikz" => render_tikz(&mut args),
+        "parse-log" => parse_log(&mut args),
+        "synctex-query" => synctex_query(&mut args),
+        "pdf-text" => pdf_text(&mut args),
+        "word-count" => word_count(&mut args),
+        "-h" | "--help" | "help" => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        other => Err(Usage(format!("unknown command `{other}'"))),
+    };
+    match outcome {
+        Ok(code) => code,
+        Err(Usage(message)) => {
+            eprintln!("livelatex: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn compile(args: &mut Args) -> Outcome {
+    let input = args.positional("file.tex")?;
+    let output = args.value("-o").map(PathBuf::from);
+    let options = args.options()?;
+    let cache = args.cache()?;
+    args.finish()?;
+    let (source, output) = match input.as_str() {
+        "-" => (
+            read_stdin(),
+            output.ok_or_else(|| Usage("-o is required when reading stdin".into()))?,
+        ),
+        path => (
+            fs::read_to_string(path),
+            output.unwrap_or_else(|| Path::new(path).with_extension("pdf")),
+        ),
+    };
+    let report = source
+        .map_err(rust_core::Error::from)
+        .and_then(|source| compile_with(&source, &output, &cache, &options));
+    Ok(print_report(report))
+}
+
+fn compile_project_cmd(args: &mut Args) -> Outcome {
+    let root = PathBuf::from(args.positional("root")?);
+    let main = PathBuf::from(args.positional("main.tex")?);
+    let output = args
+        .value("-o")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root.join(&main).with_extension("pdf"));
+    let options = args.options()?;
+    let cache = args.cache()?;
+    args.finish()?;
+    Ok(print_report(compile_project(
+        &root, &main, &output, &cache, &options,
+    )))
+}
+
+fn render_tikz(args: &mut Args) -> Outcome {
+    let input = PathBuf::from(args.positional("figure.tex")?);
+    let project = args
+        .value("--project")
+        .map(PathBuf::from)
+        .or_else(|| input.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let cache = args.cache()?;
+    args.finish()?;
+    let report = fs::read_to_string(&input)
+        .map_err(rust_core::Error::from)
+        .and_then(|doc| tikz::render_svg(&doc, &project, &cache));
+    Ok(match report {
+        Ok(report) => {
+            print_json(&report);
+            exit_code(report.svg_path.is_some())
+        }
+        Err(e) => print_error(&e),
+    })
+}
+
+fn parse_log(args: &mut Args) -> Outcome {
+    let input = args.positional("file.log")?;
+    let main_file = args.value("--main-file");
+    args.finish()?;
+    // Logs are Latin-1 as often as UTF-8; keep what decodes.
+    let log = match input.as_str() {
+        "-" => {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+        }
+        path => fs::read(path),
+    };
+    Ok(match log {
+        Ok(bytes) => {
+            let log = String::from_utf8_lossy(&bytes);
+            print_json(&texlog::analyze(&log, main_file.as_deref()));
+            ExitCode::SUCCESS
+        }
+        Err(e) => print_error(&e.into()),
+    })
+}
+
+fn synctex_query(args: &mut Args) -> Outcome {
+    let pdf = PathBuf::from(args.positional("out.pdf")?);
+    let line = args.number::<u32>("--line")?;
+    let file = args.value("--file");
+    let column = args.number::<u32>("--column")?;
+    let page = args.number::<u32>("--page")?;
+    let x = args.number::<f64>("--x")?;
+    let y = args.number::<f64>("--y")?;
+    let cache = args.cache()?;
+    args.finish()?;
+    let synctex = match SyncTex::for_document(&cache, &pdf) {
+        Ok(Some(synctex)) => synctex,
+        Ok(None) => {
+            print_json(&None::<()>);
+            return Ok(ExitCode::FAILURE);
+        }
+        Err(e) => return Ok(print_error(&e)),
+    };
+    match (line, page, x, y) {
+        (Some(line), None, None, None) => {
+            let spot = synctex.forward(file.as_deref(), line.max(1), column);
+            print_json(&spot);
+            Ok(exit_code(spot.is_some()))
+        }
+        (None, Some(page), Some(x), Some(y)) => {
+            let spot = synctex.inverse(page, x, y);
+            print_json(&spot);
+            Ok(exit_code(spot.is_some()))
+        }
+        _ => Err(Usage(
+            "synctex-query takes either --line or --page, --x and --y".into(),
+        )),
+    }
+}
+
+fn pdf_text(args: &mut Args) -> Outcome {
+    let pdf = PathBuf::from(args.positional("out.pdf")?);
+    let query = args.value("--search");
+    args.finish()?;
+    let printed = match query {
+        Some(query) => pdf::search(&pdf, &query).map(|hits| print_json(&hits)),
+        None => pdf::document_text(&pdf).map(|text| print_json(&text)),
+    };
+    Ok(match printed {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => print_error(&e),
+    })
+}
+
+fn word_count(args: &mut Args) -> Outcome {
+    let pdf = PathBuf::from(args.positional("out.pdf")?);
+    let json = args.json("--options")?;
+    args.finish()?;
+    let options =
+        WordCountOptions::from_json(&json).map_err(|e| Usage(format!("--options: {e}")))?;
+    Ok(match wordcount::count_pdf(&pdf, &options) {
+        Ok(count) => {
+            print_json(&count);
+            ExitCode::SUCCESS
+        }
+        Err(e) => print_error(&e),
+    })
+}
+
+/// Prints the report; a failure outside the document becomes a report with
+/// that one error, as the JNI exports do.
+fn print_report(report: rust_core::Result<CompileReport>) -> ExitCode {
+    let report = report.unwrap_or_else(|e| CompileReport::from_error(&e));
+    print_json(&report);
+    exit_code(report.success())
+}
+
+fn print_error(err: &rust_core::Error) -> ExitCode {
+    print_json(&serde_json::json!({ "error": err.to_string() }));
+    ExitCode::FAILURE
+}
+
+fn print_json(value: &impl Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| "{}".into())
+    );
+}
+
+fn exit_code(success: bool) -> ExitCode {
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn read_stdin() -> io::Result<String> {
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)?;
+    Ok(source)
+}
+
+/// Positional arguments and `--flag value` pairs, consumed by the subcommand;
+/// [`Args::finish`] then rejects anything it did not use, before any work starts.
+struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Self {
+        let mut out = Args {
+            positional: Vec::new(),
+            flags: Vec::new(),
+            error: None,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg.starts_with('-') && arg != "-" {
+                match iter.next() {
+                    Some(value) => out.flags.push((arg.clone(), value.clone())),
+                    None => out.error = Some(format!("{arg} needs a value")),
+                }
+            } else {
+                out.positional.push(arg.clone());
+            }
+        }
+        out.positional.reverse();
+        out
+    }
+
+    fn positional(&mut self, name: &str) -> std::result::Result<String, Usage> {
+        self.positional
+            .pop()
+            .ok_or_else(|| Usage(format!("missing <{name}>")))
+    }
+
+    fn value(&mut self, flag: &str) -> Option<String> {
+        let i = self.flags.iter().position(|(f, _)| f == flag)?;
+        Some(self.flags.remove(i).1)
+    }
+
+    fn number<T: std::str::FromStr>(
+        &mut self,
+        flag: &str,
+    ) -> std::result::Result<Option<T>, Usage> {
+        self.value(flag)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| Usage(format!("{flag} takes a number, not `{v}'")))
+            })
+            .transpose()
+    }
+
+    fn options(&mut self) -> std::result::Result<CompileOptions, Usage> {
+        let json = self.json("--options")?;
+        CompileOptions::from_json(&json).map_err(|e| Usage(format!("--options: {e}")))
+    }
+
+    /// The JSON given with `flag`, read from a file for `@path`; empty if absent.
+    fn json(&mut self, flag: &str) -> std::result::Result<String, Usage> {
+        Ok(match self.value(flag) {
+            Some(v) => match v.strip_prefix('@') {
+                Some(path) => fs::read_to_string(path)
+                    .map_err(|e| Usage(format!("cannot read {path}: {e}")))?,
+                None => v,
+            },
+            None => String::new(),
+        })
+    }
+
+    fn cache(&mut self) -> std::result::Result<PathBuf, Usage> {
+        if let Some(dir) = self.value("--cache") {
+            return Ok(PathBuf::from(dir));
+        }
+        if let Some(dir) = env::var_os("LIVELATEX_CACHE") {
+            return Ok(PathBuf::from(dir));
+        }
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .map(|dir| dir.join("livelatex"))
+            .ok_or_else(|| Usage("no --cache given and no home directory".into()))
+    }
+
+    /// Complains about arguments the subcommand did not use.
+    fn finish(&self) -> std::result::Result<(), Usage> {
+        if let Some(error) = &self.error {
+            return Err(Usage(error.clone()));
+        }
+        if let Some((flag, _)) = self.flags.first() {
+            return Err(Usage(format!("unexpected {flag}")));
+        }
+        match self.positional.last() {
+            Some(arg) => Err(Usage(format!("unexpected argument `{arg}'"))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Args {
+        let list: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        Args::parse(&list)
+    }
+
+    fn usage<T>(result: std::result::Result<T, Usage>) -> String {
+        match result {
+            Ok(_) => panic!("expected a usage error"),
+            Err(Usage(message)) => message,
+        }
+    }
+
+    #[test]
+    fn positionals_and_flags_are_taken_in_any_order() {
+        let mut a = args(&["-o", "out.pdf", "main.tex", "--cache", "/c", "-"]);
+        assert_eq!(a.positional("file.tex").ok().as_deref(), Some("main.tex"));
+        assert_eq!(a.positional("second").ok().as_deref(), Some("-"));
+        assert_eq!(a.value("-o").as_deref(), Some("out.pdf"));
+        assert_eq!(a.cache().ok(), Some(PathBuf::from("/c")));
+        assert!(a.finish().is_ok());
+        assert_eq!(usage(a.positional("root")), "missing <root>");
+    }
+
+    #[test]
+    fn unused_and_incomplete_arguments_are_rejected() {
+        assert_eq!(
+            usage(args(&["a.tex", "b.tex"]).finish()),
+            "unexpected argument `a.tex'"
+        );
+        assert_eq!(usage(args(&["--x", "1"]).finish()), "unexpected --x");
+        assert_eq!(usage(args(&["a.tex", "-o"]).finish()), "-o needs a value");
+    }
+
+    #[test]
+    fn numbers_and_options_are_checked() {
+        let mut a = args(&["--line", "12", "--page", "two"]);
+        assert_eq!(a.number::<u32>("--line").ok(), Some(Some(12)));
+        assert_eq!(a.number::<u32>("--column").ok(), Some(None));
+        assert_eq!(
+            usage(a.number::<u32>("--page")),
+            "--page takes a number, not `two'"
+        );
+
+        let options = args(&["--options", r#"{"draft": {"maxPages": 2}}"#])
+            .options()
+            .ok()
+            .unwrap();
+        assert_eq!(options.draft.and_then(|d| d.max_pages), Some(2));
+        assert!(usage(args(&["--options", "{"]).options()).starts_with("--options: "));
+    }
+
+    #[test]
+    fn json_is_read_from_a_file_after_an_at() {
+        let path = env::temp_dir().join(format!("livelatex-cli-{}.json", std::process::id()));
+        fs::write(&path, r#"{"excludeCaptions": true}"#).unwrap();
+        let flag = format!("@{}", path.display());
+        let json = args(&["--options", &flag]).json("--options").ok();
+        assert_eq!(json.as_deref(), Some(r#"{"excludeCaptions": true}"#));
+        fs::remove_file(&path).unwrap();
+        assert!(usage(args(&["--options", &flag]).json("--options")).starts_with("cannot read "));
+        assert_eq!(args(&[]).json("--options").ok().as_deref(), Some(""));
+    }
+
+    #[test]
+    fn commands_fail_on_a_missing_pdf() {
+        let missing = "/nonexistent/livelatex-cli.pdf";
+        assert_eq!(
+            word_count(&mut args(&[missing])).ok(),
+            Some(ExitCode::FAILURE)
+        );
+        assert_eq!(
+            pdf_text(&mut args(&[missing])).ok(),
+            Some(ExitCode::FAILURE)
+        );
+        assert_eq!(
+            usage(word_count(&mut args(&[missing, "--search", "x"]))),
+            "unexpected --search"
+        );
+    }
+}
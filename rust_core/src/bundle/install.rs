@@ -0,0 +1,89 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek};
+use std::path::Path;
+
+use serde::Serialize;
+use zip::ZipArchive;
+
+use super::manifest::{hash_copy, Manifest, MANIFEST_NAME};
+use super::{bundle_dir_path, installed_manifest_path, remove_dir_if_exists};
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallReport {
+    pub version: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Installs the bundle ZIP at `zip_path` into `cache_path`, replacing any
+/// previous install.
+///
+/// Files are unpacked into `bundle.installing/` and hash-checked against the
+/// manifest; only a fully verified tree is swapped in as `bundle/`, so a bad
+/// archive leaves the current install untouched.
+pub fn install(zip_path: &Path, cache_path: &Path) -> Result<InstallReport> {
+    let mut archive = ZipArchive::new(File::open(zip_path)?)?;
+    let manifest = read_manifest(&mut archive, zip_path)?;
+
+    fs::create_dir_all(cache_path)?;
+    let staging = cache_path.join("bundle.installing");
+    remove_dir_if_exists(&staging)?;
+    fs::create_dir_all(&staging)?;
+    let bytes = match extract(&mut archive, &manifest, &staging) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+
+    let dir = bundle_dir_path(cache_path);
+    let old = cache_path.join("bundle.old");
+    remove_dir_if_exists(&old)?;
+    if dir.exists() {
+        fs::rename(&dir, &old)?;
+    }
+    fs::rename(&staging, &dir)?;
+    manifest.save(&installed_manifest_path(cache_path))?;
+    remove_dir_if_exists(&old)?;
+
+    Ok(InstallReport {
+        version: manifest.version,
+        files: manifest.files.len(),
+        bytes,
+    })
+}
+
+fn read_manifest<R: Read + Seek>(archive: &mut ZipArchive<R>, zip_path: &Path) -> Result<Manifest> {
+    let mut entry = archive.by_name(MANIFEST_NAME).map_err(|_| {
+        Error::InvalidBundle(format!("{} has no {MANIFEST_NAME}", zip_path.display()))
+    })?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes)?;
+    Manifest::parse(&bytes)
+}
+
+/// Unpacks every manifest file into `dest`; returns the total size.
+fn extract<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    manifest: &Manifest,
+    dest: &Path,
+) -> Result<u64> {
+    let mut total = 0;
+    for (name, entry) in &manifest.files {
+        let src = archive.by_name(name).map_err(|_| {
+            Error::InvalidBundle(format!("{name} is in the manifest but not in the archive"))
+        })?;
+        let out = BufWriter::new(File::create(dest.join(name))?);
+        let (sha256, size) = hash_copy(src, out)?;
+        if !entry.matches(&sha256, size) {
+            return Err(Error::InvalidBundle(format!(
+                "{name} does not match the manifest"
+            )));
+        }
+        total += size;
+    }
+    Ok(total)
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+/// Manifest entry inside an installable bundle ZIP.
+pub const MANIFEST_NAME: &str = "livelatex-manifest.json";
+
+/// Versioned list of every file in a bundle.
+///
+/// ```json
+/// { "version": "2025.1",
+///   "files": { "amsmath.sty": { "package": "amsmath", "size": 88191, "sha256": "…" } } }
+/// ```
+///
+/// File names are flat (the TeX tree is a single directory). Tectonic's own
+/// `SHA256SUM` must be listed too: the format cache is keyed by it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub package: String,
+    pub size: u64,
+    /// Lowercase hex.
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let manifest: Manifest = serde_json::from_slice(bytes)
+            .map_err(|e| Error::InvalidBundle(format!("bad manifest: {e}")))?;
+        if let Some(name) = manifest.files.keys().find(|n| !is_flat_name(n)) {
+            return Err(Error::InvalidBundle(format!(
+                "manifest lists a file outside the bundle root: {name}"
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// `Ok(None)` when nothing has been installed yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(bytes) => Self::parse(&bytes).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Written next to `path` first, so a crash never leaves half a manifest.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| Error::InvalidBundle(format!("cannot encode manifest: {e}")))?;
+        let tmp = path.with_extension("json.part");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Package name → (file count, bytes), sorted by name.
+    pub fn packages(&self) -> BTreeMap<&str, (usize, u64)> {
+        let mut out: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+        for entry in self.files.values() {
+            let slot = out.entry(entry.package.as_str()).or_default();
+            slot.0 += 1;
+            slot.1 += entry.size;
+        }
+        out
+    }
+}
+
+impl FileEntry {
+    pub fn matches(&self, sha256: &str, size: u64) -> bool {
+        self.size == size && self.sha256.eq_ignore_ascii_case(sha256)
+    }
+}
+
+/// No separators, no `..`, and not the manifest itself.
+fn is_flat_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name != MANIFEST_NAME
+        && !name.contains(['/', '\\'])
+}
+
+/// Copies `reader` into `writer`, returning the SHA-256 (hex) and byte count.
+pub(crate) fn hash_copy(
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> io::Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        size += n as u64;
+    }
+    writer.flush()?;
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+pub(crate) fn hash_file(path: &Path) -> io::Result<(String, u64)> {
+    hash_copy(fs::File::open(path)?, io::sink())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(name: &str) -> String {
+        format!(
+            r#"{{"version": "2025.1", "files": {{"{name}": {{"package": "amsmath", "size": 3, "sha256": "AB"}}}}}}"#
+        )
+    }
+
+    #[test]
+    fn parse_reads_flat_names() {
+        let m = Manifest::parse(manifest("amsmath.sty").as_bytes()).unwrap();
+        assert_eq!(m.version, "2025.1");
+        assert_eq!(m.packages().get("amsmath"), Some(&(1, 3)));
+        assert!(m.files["amsmath.sty"].matches("ab", 3));
+        assert!(!m.files["amsmath.sty"].matches("ab", 4));
+    }
+
+    #[test]
+    fn parse_refuses_names_outside_the_bundle_root() {
+        for name in [
+            "../evil.sty",
+            "sub/amsmath.sty",
+            r"sub\\amsmath.sty",
+            "..",
+            ".",
+            MANIFEST_NAME,
+        ] {
+            assert!(
+                Manifest::parse(manifest(name).as_bytes()).is_err(),
+                "accepted {name}"
+            );
+        }
+        assert!(Manifest::parse(b"{\"version\": 1}").is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Offline TeX bundle: install, list, verify and prune the local TeX tree.
+//!
+//! An installable bundle is a ZIP with a flat TeX tree plus [`MANIFEST_NAME`].
+//! [`install`] unpacks it into `cache/bundle/` and keeps the manifest as
+//! `cache/bundle.json`. Pruning rewrites that copy, so it always describes
+//! what is on disk and [`verify`] can flag anything missing or damaged.
+
+mod install;
+mod manifest;
+mod packages;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+pub use install::{install, InstallReport};
+pub use manifest::{FileEntry, Manifest, MANIFEST_NAME};
+pub use packages::{missing_packages, resolve_package, used_packages, PackageUse};
+
+use crate::engine::formats_dir;
+use crate::{Error, Result};
+
+/// `cache/bundle.zip` (Tectonic ZIP bundle, side-loaded by hand).
+pub fn bundle_zip_path(cache_path: &Path) -> PathBuf {
+    cache_path.join("bundle.zip")
+}
+
+/// `cache/bundle/` (flat directory bundle, written by [`install`]).
+pub fn bundle_dir_path(cache_path: &Path) -> PathBuf {
+    cache_path.join("bundle")
+}
+
+/// Tectonic's digest file; kept even when a manifest forgets to list it.
+const DIGEST_FILE: &str = "SHA256SUM";
+
+/// Manifest of the installed bundle; absent for side-loaded bundles.
+pub fn installed_manifest_path(cache_path: &Path) -> PathBuf {
+    cache_path.join("bundle.json")
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageInfo {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// What the settings sheet shows: installed packages and disk usage.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleInfo {
+    /// `None` when no manifest-backed bundle is installed.
+    pub version: Option<String>,
+    pub packages: Vec<PackageInfo>,
+    /// On-disk size of the TeX tree (`bundle/` or `bundle.zip`).
+    pub bundle_bytes: u64,
+    /// Generated `.fmt` files.
+    pub format_bytes: u64,
+    /// Per-document preamble formats and aux files under `work/`.
+    pub work_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    pub version: String,
+    pub ok: bool,
+    pub checked: usize,
+    pub missing: Vec<String>,
+    /// Wrong size or hash.
+    pub corrupt: Vec<String>,
+    /// Files in `bundle/` the manifest does not know about.
+    pub extra: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub removed_files: usize,
+    pub freed_bytes: u64,
+}
+
+pub fn list(cache_path: &Path) -> Result<BundleInfo> {
+    let manifest = Manifest::load(&installed_manifest_path(cache_path))?;
+    let dir = bundle_dir_path(cache_path);
+    let bundle_bytes = if dir.is_dir() {
+        dir_size(&dir)?
+    } else {
+        file_size(&bundle_zip_path(cache_path))
+    };
+    let packages = manifest
+        .as_ref()
+        .map(|m| {
+            m.packages()
+                .into_iter()
+                .map(|(name, (files, bytes))| PackageInfo {
+                    name: name.to_string(),
+                    files,
+                    bytes,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(BundleInfo {
+        version: manifest.map(|m| m.version),
+        packages,
+        bundle_bytes,
+        format_bytes: dir_size(&formats_dir(cache_path))?,
+        work_bytes: dir_size(&cache_path.join("work"))?,
+    })
+}
+
+/// Re-hashes every installed file against the manifest.
+pub fn verify(cache_path: &Path) -> Result<VerifyReport> {
+    let manifest = installed_manifest(cache_path)?;
+    let dir = bundle_dir_path(cache_path);
+    let mut missing = Vec::new();
+    let mut corrupt = Vec::new();
+    for (name, entry) in &manifest.files {
+        match manifest::hash_file(&dir.join(name)) {
+            Ok((sha256, size)) if entry.matches(&sha256, size) => {}
+            Ok(_) => corrupt.push(name.clone()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => missing.push(name.clone()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let extra: Vec<String> = file_names(&dir)?
+        .into_iter()
+        .filter(|n| n != DIGEST_FILE && !manifest.files.contains_key(n))
+        .collect();
+    Ok(VerifyReport {
+        ok: missing.is_empty() && corrupt.is_empty(),
+        checked: manifest.files.len(),
+        version: manifest.version,
+        missing,
+        corrupt,
+        extra,
+    })
+}
+
+/// Removes `packages` from the installed bundle, plus anything left behind:
+/// files the manifest does not list, an interrupted install, and formats
+/// dumped from other bundle versions.
+pub fn prune(cache_path: &Path, packages: &[String]) -> Result<PruneReport> {
+    let manifest_path = installed_manifest_path(cache_path);
+    let mut manifest = installed_manifest(cache_path)?;
+    let known: BTreeSet<&str> = manifest
+        .files
+        .values()
+        .map(|e| e.package.as_str())
+        .collect();
+    if let Some(unknown) = packages.iter().find(|p| !known.contains(p.as_str())) {
+        return Err(Error::InvalidBundle(format!(
+            "no installed package named {unknown}"
+        )));
+    }
+
+    let dir = bundle_dir_path(cache_path);
+    let mut report = PruneReport {
+        removed_files: 0,
+        freed_bytes: 0,
+    };
+
+    let doomed: Vec<String> = manifest
+        .files
+        .iter()
+        .filter(|(_, e)| packages.contains(&e.package))
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in doomed {
+        remove_file(&dir.join(&name), &mut report)?;
+        manifest.files.remove(&name);
+    }
+    manifest.save(&manifest_path)?;
+
+    for name in file_names(&dir)? {
+        if name != DIGEST_FILE && !manifest.files.contains_key(&name) {
+            remove_file(&dir.join(&name), &mut report)?;
+        }
+    }
+
+    for leftover in ["bundle.installing", "bundle.old"] {
+        let path = cache_path.join(leftover);
+        if path.is_dir() {
+            report.freed_bytes += dir_size(&path)?;
+            fs::remove_dir_all(&path)?;
+        }
+    }
+
+    // Format files are named `<bundle digest>-<format>-<serial>.fmt`.
+    if let Some(digest) = bundle_digest(&dir) {
+        let formats = formats_dir(cache_path);
+        for name in file_names(&formats)? {
+            if !name.starts_with(&digest) {
+                remove_file(&formats.join(&name), &mut report)?;
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn installed_manifest(cache_path: &Path) -> Result<Manifest> {
+    Manifest::load(&installed_manifest_path(cache_path))?
+        .ok_or_else(|| Error::BundleMissing(cache_path.to_path_buf()))
+}
+
+/// Tectonic's bundle digest: the hex text of `SHA256SUM`.
+fn bundle_digest(dir: &Path) -> Option<String> {
+    let text = fs::read_to_string(dir.join(DIGEST_FILE)).ok()?;
+    let digest = text.trim();
+    (digest.len() == 64).then(|| digest.to_ascii_lowercase())
+}
+
+fn remove_file(path: &Path, report: &mut PruneReport) -> Result<()> {
+    let size = file_size(path);
+    match fs::remove_file(path) {
+        Ok(()) => {
+            report.removed_files += 1;
+            report.freed_bytes += size;
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) fn remove_dir_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Plain files directly under `dir`; empty if it does not exist.
+fn file_names(dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut total = 0;
+    for entry in entries {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        if ty.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else if ty.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    /// A cache dir with an installed bundle of `(name, package, contents)`.
+    fn installed(test: &str, files: &[(&str, &str, &str)]) -> PathBuf {
+        let cache = std::env::temp_dir().join(format!("livelatex-{test}-{}", std::process::id()));
+        remove_dir_if_exists(&cache).unwrap();
+        let dir = bundle_dir_path(&cache);
+        fs::create_dir_all(&dir).unwrap();
+        let mut entries = BTreeMap::new();
+        for (name, package, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+            entries.insert(
+                name.to_string(),
+                FileEntry {
+                    package: package.to_string(),
+                    size: contents.len() as u64,
+                    sha256: format!("{:x}", Sha256::digest(contents)),
+                },
+            );
+        }
+        Manifest {
+            version: "test".to_string(),
+            files: entries,
+        }
+        .save(&installed_manifest_path(&cache))
+        .unwrap();
+        cache
+    }
+
+    #[test]
+    fn verify_reports_missing_corrupt_and_extra_files() {
+        let cache = installed(
+            "verify",
+            &[
+                ("a.sty", "a", "aaa"),
+                ("b.sty", "b", "bbb"),
+                ("c.sty", "c", "ccc"),
+            ],
+        );
+        let dir = bundle_dir_path(&cache);
+        assert!(verify(&cache).unwrap().ok);
+
+        fs::remove_file(dir.join("a.sty")).unwrap();
+        fs::write(dir.join("b.sty"), "bbx").unwrap();
+        fs::write(dir.join("stray.tex"), "").unwrap();
+        fs::write(dir.join(DIGEST_FILE), "").unwrap();
+        let report = verify(&cache).unwrap();
+        assert!(!report.ok);
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.missing, ["a.sty"]);
+        assert_eq!(report.corrupt, ["b.sty"]);
+        assert_eq!(report.extra, ["stray.tex"]);
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn prune_removes_packages_strays_and_stale_formats() {
+        let cache = installed("prune", &[("a.sty", "a", "aaa"), ("b.sty", "b", "bbbb")]);
+        let dir = bundle_dir_path(&cache);
+        let digest = "d".repeat(64);
+        fs::write(dir.join(DIGEST_FILE), &digest).unwrap();
+        fs::write(dir.join("stray.tex"), "xy").unwrap();
+        let formats = formats_dir(&cache);
+        fs::create_dir_all(&formats).unwrap();
+        fs::write(formats.join(format!("{digest}-latex-1.fmt")), "fmt").unwrap();
+        fs::write(
+            formats.join(format!("{}-latex-1.fmt", "e".repeat(64))),
+            "old",
+        )
+        .unwrap();
+
+        let report = prune(&cache, &["b".to_string()]).unwrap();
+        assert_eq!(report.removed_files, 3);
+        assert_eq!(report.freed_bytes, 4 + 2 + 3);
+        assert!(dir.join("a.sty").is_file());
+        assert!(dir.join(DIGEST_FILE).is_file());
+        assert!(!dir.join("b.sty").exists());
+        assert!(!dir.join("stray.tex").exists());
+        assert_eq!(file_names(&formats).unwrap().len(), 1);
+
+        let report = verify(&cache).unwrap();
+        assert!(report.ok);
+        assert_eq!(report.checked, 1);
+        assert!(prune(&cache, &["b".to_string()]).is_err());
+        fs::remove_dir_all(&cache).unwrap();
+    }
+}
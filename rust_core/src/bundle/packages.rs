@@ -0,0 +1,110 @@
+//! `\usepackage` / `\RequirePackage` lookups against the installed bundle.
+
+use std::path::{Path, PathBuf};
+
+use super::{bundle_dir_path, installed_manifest_path, Manifest};
+use crate::Result;
+
+/// One package named in the source, with the 1-based line of its `\usepackage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageUse {
+    pub name: String,
+    pub line: u32,
+}
+
+/// Every package the source loads, in order. Commented-out lines are ignored;
+/// the argument may span lines (`\usepackage{amsmath,\n amssymb}`).
+pub fn used_packages(source: &str) -> Vec<PackageUse> {
+    let text = strip_comments(source);
+    let mut out = Vec::new();
+    for cmd in ["\\usepackage", "\\RequirePackage"] {
+        let mut from = 0;
+        while let Some(pos) = text[from..].find(cmd) {
+            let start = from + pos;
+            from = start + cmd.len();
+            // `\usepackagefoo` is a different macro.
+            if text[from..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+                continue;
+            }
+            let mut rest = text[from..].trim_start();
+            if rest.starts_with('[') {
+                match rest.find(']') {
+                    Some(end) => rest = rest[end + 1..].trim_start(),
+                    None => continue,
+                }
+            }
+            let Some(body) = rest.strip_prefix('{') else {
+                continue;
+            };
+            let Some(end) = body.find('}') else {
+                continue;
+            };
+            let arg_start = text.len() - body.len();
+            for name in body[..end].split(',') {
+                let name = name.trim();
+                if name.is_empty() {
+                    continue;
+                }
+                let offset = arg_start + body[..end].find(name).unwrap_or(0);
+                out.push(PackageUse {
+                    name: name.to_string(),
+                    line: line_of(&text, offset),
+                });
+            }
+        }
+    }
+    out.sort_by_key(|u| u.line);
+    out
+}
+
+/// Path of `<name>.sty` in the installed bundle, if it is there.
+pub fn resolve_package(cache_path: &Path, name: &str) -> Result<Option<PathBuf>> {
+    let Some(manifest) = Manifest::load(&installed_manifest_path(cache_path))? else {
+        return Ok(None);
+    };
+    let file = format!("{name}.sty");
+    let path = bundle_dir_path(cache_path).join(&file);
+    Ok((manifest.files.contains_key(&file) && path.is_file()).then_some(path))
+}
+
+/// Packages the source loads that the installed bundle lacks. Empty when no
+/// bundle has been installed through [`super::install`] (nothing to check against).
+pub fn missing_packages(source: &str, cache_path: &Path) -> Result<Vec<PackageUse>> {
+    let Some(manifest) = Manifest::load(&installed_manifest_path(cache_path))? else {
+        return Ok(Vec::new());
+    };
+    let dir = bundle_dir_path(cache_path);
+    Ok(used_packages(source)
+        .into_iter()
+        .filter(|u| {
+            let file = format!("{}.sty", u.name);
+            !(manifest.files.contains_key(&file) && dir.join(&file).is_file())
+        })
+        .collect())
+}
+
+/// Blanks out `%` comments while keeping line structure (and byte offsets) intact.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    for (i, line) in source.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let mut escaped = false;
+        let mut cut = line.len();
+        for (j, c) in line.char_indices() {
+            if c == '%' && !escaped {
+                cut = j;
+                break;
+            }
+            escaped = c == '\\' && !escaped;
+        }
+        out.push_str(&line[..cut]);
+        out.extend(std::iter::repeat_n(' ', line.len() - cut));
+    }
+    out
+}
+
+fn line_of(text: &str, offset: usize) -> u32 {
+    text[..offset].matches('\n').count() as u32 + 1
+}
@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bibliography;
+use crate::bundle;
+use crate::diagnostics::{self, Diagnostic, Severity};
+use crate::draft::Draft;
+use crate::engine::program::{self, Program};
+use crate::engine::{self, incremental, incremental::WorkDir};
+use crate::job::Monitor;
+use crate::limits::Limits;
+use crate::sandbox::{self, Denial};
+use crate::{Error, Result};
+
+/// What `compilePdfDetailed` hands back to Kotlin (as JSON).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileReport {
+    /// Set only when a PDF was written.
+    pub pdf_path: Option<String>,
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    /// Raw engine log of the last pass.
+    pub log: String,
+}
+
+impl CompileReport {
+    pub fn success(&self) -> bool {
+        self.pdf_path.is_some()
+    }
+
+    /// Failure without any position, e.g. a missing bundle.
+    pub fn from_error(err: &Error) -> Self {
+        CompileReport {
+            pdf_path: None,
+            errors: vec![Diagnostic {
+                file: None,
+                line: None,
+                message: err.to_string(),
+                severity: Severity::Error,
+                context: None,
+            }],
+            warnings: Vec::new(),
+            log: String::new(),
+        }
+    }
+}
+
+/// Per-document settings from the app, passed over JNI as JSON.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompileOptions {
+    /// Folder of the document on disk. BibTeX looks up the `\bibliography`
+    /// databases and local `.bst` styles here, makeindex local `.ist` styles.
+    /// Other files only with [`CompileOptions::main_file`].
+    pub project_dir: Option<PathBuf>,
+    /// Main file of a multi-file project, relative to `project_dir`. The source
+    /// being compiled stands in for it, and TeX may read any file under
+    /// `project_dir`: `\input`, `\include`, `\includegraphics`, local `.sty`/`.cls`.
+    /// Diagnostics in the main file then name it instead of `None`.
+    pub main_file: Option<String>,
+    /// `.ist` style for `\makeindex` (makeindex's `-s`), looked up in the project
+    /// dir, then the bundle. Glossaries and nomencl bring their own.
+    pub index_style: Option<String>,
+    /// The user trusts this project: shell escape is enabled and TeX may read
+    /// absolute paths. Off by default; see [`crate::sandbox`].
+    pub trusted: bool,
+    /// Time, page and memory limits; generous defaults, see [`crate::limits`].
+    pub limits: Limits,
+    /// `pdftex` or `xetex` for this document. Unset, the `% !TEX program`
+    /// magic comment decides, then the packages; see [`crate::engine::program`].
+    pub program: Option<Program>,
+    /// Seconds since 1970 to date the PDF and `\today` at; makes identical
+    /// sources give byte-identical PDFs. See [`crate::reproducible`].
+    pub source_date_epoch: Option<u64>,
+    /// A fast, partial compile for the live view; see [`crate::draft`].
+    pub draft: Option<Draft>,
+}
+
+impl CompileOptions {
+    /// Empty or blank JSON means defaults.
+    pub fn from_json(json: &str) -> Result<Self> {
+        if json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Compiles `main_file` (inside `project_root`, or relative to it) as it is on
+/// disk; see [`CompileOptions::main_file`].
+pub fn compile_project(
+    project_root: &Path,
+    main_file: &Path,
+    output_path: &Path,
+    cache_path: &Path,
+    options: &CompileOptions,
+) -> Result<CompileReport> {
+    let relative = if main_file.is_absolute() {
+        main_file
+            .strip_prefix(project_root)
+            .map_err(|_| Error::OutsideProject(main_file.to_path_buf()))?
+    } else {
+        main_file
+    };
+    let name = relative.to_string_lossy().replace('\\', "/");
+    if !incremental::is_confined(&name) {
+        return Err(Error::OutsideProject(main_file.to_path_buf()));
+    }
+    let source = fs::read_to_string(project_root.join(relative))?;
+    let options = CompileOptions {
+        project_dir: Some(project_root.to_path_buf()),
+        main_file: Some(name),
+        ..options.clone()
+    };
+    compile_with(&source, output_path, cache_path, &options)
+}
+
+/// Typeset `latex_source` and write the PDF to `output_path`.
+///
+/// `cache_path` holds the TeX bundle and the generated format files; it is
+/// created if missing. The PDF is written to a sibling temp file first so a
+/// failed run never leaves a truncated document behind.
+pub fn compile_pdf(latex_source: &str, output_path: &Path, cache_path: &Path) -> Result<()> {
+    let report = compile_detailed(latex_source, output_path, cache_path)?;
+    if report.success() {
+        return Ok(());
+    }
+    let message = report
+        .errors
+        .first()
+        .map(|d| d.message.clone())
+        .unwrap_or_else(|| "no PDF was produced".into());
+    Err(Error::Engine {
+        engine: "XeTeX",
+        message,
+    })
+}
+
+/// Like [`compile_pdf`], but TeX failures come back as diagnostics instead of an `Err`.
+/// `Err` is reserved for problems outside the document (I/O, missing bundle).
+pub fn compile_detailed(
+    latex_source: &str,
+    output_path: &Path,
+    cache_path: &Path,
+) -> Result<CompileReport> {
+    compile_with(
+        latex_source,
+        output_path,
+        cache_path,
+        &CompileOptions::default(),
+    )
+}
+
+/// [`compile_detailed`] with [`CompileOptions`].
+pub fn compile_with(
+    latex_source: &str,
+    output_path: &Path,
+    cache_path: &Path,
+    options: &CompileOptions,
+) -> Result<CompileReport> {
+    compile_monitored(
+        latex_source,
+        output_path,
+        cache_path,
+        options,
+        &Monitor::silent().with_limits(options.limits),
+    )
+}
+
+/// [`compile_detailed`] under a job's [`Monitor`]; `Err(Error::Cancelled)` if it
+/// was cancelled before the PDF was written.
+pub(crate) fn compile_monitored(
+    latex_source: &str,
+    output_path: &Path,
+    cache_path: &Path,
+    options: &CompileOptions,
+    monitor: &Monitor,
+) -> Result<CompileReport> {
+    fs::create_dir_all(cache_path)?;
+
+    // Cheaper and clearer than letting XeTeX stop at the first missing `.sty`.
+    let mut missing = bundle::missing_packages(latex_source, cache_path)?;
+    if let (Some(dir), Some(main)) = (&options.project_dir, &options.main_file) {
+        missing.retain(|u| !is_local_package(dir, main, &u.name));
+    }
+    if !missing.is_empty() {
+        return Ok(CompileReport {
+            pdf_path: None,
+            errors: missing
+                .into_iter()
+                .map(|u| Diagnostic {
+                    file: options.main_file.clone(),
+                    line: Some(u.line),
+                    message: format!("Package `{}' is not installed in the TeX bundle", u.name),
+                    severity: Severity::Error,
+                    context: None,
+                })
+                .collect(),
+            warnings: Vec::new(),
+            log: String::new(),
+        });
+    }
+
+    let (program, program_note) = program::select(latex_source, options.program);
+    let (source, biblatex_note) = bibliography::use_bibtex_backend(latex_source);
+    let work = WorkDir::for_document(cache_path, output_path)?;
+    let typeset = engine::typeset(&source, cache_path, options, program, &work, monitor)?;
+    if monitor.cancelled() {
+        return Err(Error::Cancelled);
+    }
+
+    let mut found = diagnostics::parse_log(&typeset.log);
+    if let Some(blg) = &typeset.bibtex_log {
+        found.extend(diagnostics::parse_bibtex_log(blg));
+    }
+    for ilg in &typeset.index_logs {
+        found.extend(diagnostics::parse_makeindex_log(ilg));
+    }
+    found.extend(program_note);
+    found.extend(biblatex_note);
+    found.extend(
+        typeset
+            .denied
+            .iter()
+            .chain(&sandbox::shell_denials(&typeset.log))
+            .map(Denial::diagnostic),
+    );
+    if let Some(main) = &options.main_file {
+        for d in found
+            .iter_mut()
+            .filter(|d| d.file.is_none() && d.line.is_some())
+        {
+            d.file = Some(main.clone());
+        }
+    }
+    let (mut errors, warnings): (Vec<_>, Vec<_>) = found
+        .into_iter()
+        .partition(|d| d.severity == Severity::Error);
+
+    let pdf_path = match &typeset.pdf {
+        Some(pdf) => {
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let tmp = output_path.with_extension("pdf.part");
+            fs::write(&tmp, pdf)?;
+            fs::rename(&tmp, output_path)?;
+            Some(output_path.to_string_lossy().into_owned())
+        }
+        None => None,
+    };
+
+    // Whatever TeX said last, the limit is why the compile stopped.
+    if let Some(limit) = monitor.exceeded() {
+        let diagnostic = Diagnostic {
+            file: None,
+            line: None,
+            message: Error::LimitExceeded(limit).to_string(),
+            severity: Severity::Error,
+            context: None,
+        };
+        errors.insert(0, diagnostic);
+    }
+
+    // An engine abort with nothing recognisable in the log still needs one entry.
+    if pdf_path.is_none() && errors.is_empty() {
+        errors.push(Diagnostic {
+            file: None,
+            line: None,
+            message: typeset
+                .failure
+                .clone()
+                .unwrap_or_else(|| "no PDF was produced".into()),
+            severity: Severity::Error,
+            context: None,
+        });
+    }
+
+    Ok(CompileReport {
+        pdf_path,
+        errors,
+        warnings,
+        log: typeset.log,
+    })
+}
+
+/// Whether the project ships `name.sty`, next to the main file or in its root.
+fn is_local_package(project_dir: &Path, main_file: &str, name: &str) -> bool {
+    let file = format!("{name}.sty");
+    let main = project_dir.join(main_file);
+    let main_dir = main.parent().unwrap_or(project_dir);
+    main_dir.join(&file).is_file() || project_dir.join(&file).is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_packages_are_found_next_to_the_main_file_or_in_the_root() {
+        let root = std::env::temp_dir().join(format!("livelatex-compile-{}", std::process::id()));
+        fs::create_dir_all(root.join("thesis")).unwrap();
+        fs::write(root.join("thesis/mystyle.sty"), b"").unwrap();
+        fs::write(root.join("shared.sty"), b"").unwrap();
+
+        assert!(is_local_package(&root, "thesis/main.tex", "mystyle"));
+        assert!(is_local_package(&root, "thesis/main.tex", "shared"));
+        assert!(is_local_package(&root, "main.tex", "shared"));
+        assert!(!is_local_package(&root, "main.tex", "mystyle"));
+        assert!(!is_local_package(&root, "thesis/main.tex", "absent"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,543 @@
+//! Errors and warnings pulled out of a TeX `.log` for the app's error banner.
+//!
+//! - `! message` … `l.<n> context` → error at line n
+//! - `LaTeX/Package/Class … Warning: …` (with `(pkg)` continuation lines) → warning
+//! - `Overfull`/`Underfull` box reports → warning with the reported line
+//!
+//! Each is attributed to the file TeX was reading, tracked from the `(name`
+//! … `)` TeX prints around every input. The primary input is `None`.
+//!
+//! BibTeX's `.blg` is read by [`parse_bibtex_log`]:
+//! - `Warning--message` → warning
+//! - `message---line <n> of file <f>` / `message---while reading file <f>` → error
+//!
+//! makeindex transcripts (`.ilg`, `.glg`, …) by [`parse_makeindex_log`]:
+//! - `!! Input index error` / `** Input style error (file = f, line = n):` → error
+//! - `## Warning (…):` → warning; the message is on the following `   -- ` line
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Source file as TeX named it; `None` for the primary (in-memory) input.
+    pub file: Option<String>,
+    /// 1-based line in [`Diagnostic::file`], when TeX reported one.
+    pub line: Option<u32>,
+    pub message: String,
+    pub severity: Severity,
+    /// The `l.<n>` snippet TeX prints under an error, or the offending box text.
+    pub context: Option<String>,
+}
+
+/// Scans a TeX log and returns every error and warning in log order.
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut out = Vec::new();
+    let mut files = FileStack::default();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(msg) = line.strip_prefix("! ") {
+            i = parse_error(&lines, i, msg, files.current(), &mut out);
+            continue;
+        }
+        if let Some((message, consumed)) = warning_text(&lines, i) {
+            out.push(Diagnostic {
+                file: files.current(),
+                line: input_line(&message),
+                message,
+                severity: Severity::Warning,
+                context: None,
+            });
+            i += consumed;
+            continue;
+        }
+        if line.starts_with("Overfull \\") || line.starts_with("Underfull \\") {
+            let context = lines
+                .get(i + 1)
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(str::to_string);
+            // The box contents that follow are typeset text, not file parentheses.
+            i += if context.is_some() { 2 } else { 1 };
+            out.push(Diagnostic {
+                file: files.current(),
+                line: box_line(line),
+                message: line.trim().to_string(),
+                severity: Severity::Warning,
+                context,
+            });
+            continue;
+        }
+        files.scan(line);
+        i += 1;
+    }
+    out
+}
+
+/// Scans a BibTeX `.blg`. Errors found in a `.bib` carry its file and line;
+/// the ones reported against the `.aux` (bad `\bibdata`, missing style) come from
+/// the document's `\bibliography` and have no useful position.
+pub fn parse_bibtex_log(blg: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = blg.lines().collect();
+    let mut out = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(message) = line.strip_prefix("Warning--") {
+            out.push(Diagnostic {
+                file: None,
+                line: None,
+                message: format!("BibTeX: {}", message.trim()),
+                severity: Severity::Warning,
+                context: None,
+            });
+            continue;
+        }
+        let Some(pos) = line
+            .find("---line ")
+            .or_else(|| line.find("---while reading file "))
+        else {
+            continue;
+        };
+        // `I couldn't open database file x.bib` puts its position on the next line.
+        let mut message = line[..pos].trim();
+        if message.is_empty() && i > 0 {
+            message = lines[i - 1].trim();
+        }
+        let (file, line_no) = bibtex_position(&line[pos + 3..]);
+        let in_aux = file.as_deref().is_some_and(|f| f.ends_with(".aux"));
+        out.push(Diagnostic {
+            file: if in_aux { None } else { file },
+            line: if in_aux { None } else { line_no },
+            message: format!("BibTeX: {message}"),
+            severity: Severity::Error,
+            // BibTeX quotes the offending input as ` : text` lines.
+            context: lines
+                .get(i + 1)
+                .and_then(|l| l.strip_prefix(" : "))
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty()),
+        });
+    }
+    out
+}
+
+/// Scans a makeindex transcript. Only style errors keep their position: the
+/// raw entry files are written by TeX, so their line numbers mean nothing in
+/// the editor.
+pub fn parse_makeindex_log(ilg: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = ilg.lines().collect();
+    let mut out = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let (severity, header) = if let Some(h) = line.strip_prefix("!! ") {
+            (Severity::Error, h)
+        } else if let Some(h) = line.strip_prefix("** ") {
+            (Severity::Error, h)
+        } else if let Some(h) = line.strip_prefix("## ") {
+            (Severity::Warning, h)
+        } else {
+            continue;
+        };
+        let message = lines
+            .get(i + 1)
+            .and_then(|l| l.trim_start().strip_prefix("-- "))
+            .unwrap_or(header)
+            .trim();
+        let (file, line_no) = if header.starts_with("Input style error") {
+            makeindex_position(header)
+        } else {
+            (None, None)
+        };
+        out.push(Diagnostic {
+            file,
+            line: line_no.filter(|&n| n > 0),
+            message: format!("makeindex: {message}"),
+            severity,
+            context: None,
+        });
+    }
+    out
+}
+
+/// `… (file = x.ist, line = 3):` → (`x.ist`, 3).
+fn makeindex_position(header: &str) -> (Option<String>, Option<u32>) {
+    let Some(rest) = header.split_once("(file = ").map(|(_, r)| r) else {
+        return (None, None);
+    };
+    let Some((file, rest)) = rest.split_once(", line = ") else {
+        return (None, None);
+    };
+    let line = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse()
+        .ok();
+    (Some(file.to_string()), line)
+}
+
+/// `line 12 of file refs.bib` / `while reading file texput.aux`.
+fn bibtex_position(text: &str) -> (Option<String>, Option<u32>) {
+    if let Some(rest) = text.strip_prefix("line ") {
+        if let Some((n, file)) = rest.split_once(" of file ") {
+            return (Some(file.trim().to_string()), n.trim().parse().ok());
+        }
+    }
+    let file = text
+        .strip_prefix("while reading file ")
+        .map(|f| f.trim().to_string());
+    (file, None)
+}
+
+/// Consumes an `! …` block up to its `l.<n>` line; returns the index after it.
+fn parse_error(
+    lines: &[&str],
+    start: usize,
+    msg: &str,
+    file: Option<String>,
+    out: &mut Vec<Diagnostic>,
+) -> usize {
+    // "Emergency stop" and friends only restate the error that caused them.
+    if msg.starts_with("Emergency stop") || msg.starts_with("==> Fatal error") {
+        return start + 1;
+    }
+    let mut message = msg.trim().to_string();
+    let mut i = start + 1;
+    let mut line_no = None;
+    let mut context = None;
+    while i < lines.len() && i < start + 12 {
+        let l = lines[i];
+        if l.starts_with("! ") {
+            break;
+        }
+        if let Some((n, rest)) = parse_l_line(l) {
+            line_no = Some(n);
+            // TeX splits the context at the error point; the next line holds the unread part.
+            let tail = lines.get(i + 1).map(|s| s.trim()).unwrap_or("");
+            let snippet = format!("{} {}", rest.trim(), tail).trim().to_string();
+            context = Some(snippet).filter(|s| !s.is_empty());
+            i += 2;
+            break;
+        }
+        // LaTeX errors wrap their text over several lines before the help marker;
+        // stop once the sentence ended or TeX began its context dump.
+        let continues = !l.is_empty()
+            && !l.starts_with(' ')
+            && !l.starts_with('<')
+            && !l.starts_with("See the ")
+            && !l.starts_with("Type ")
+            && !message.ends_with('.');
+        if continues {
+            message.push(' ');
+            message.push_str(l.trim());
+        }
+        i += 1;
+    }
+    out.push(Diagnostic {
+        file,
+        line: line_no,
+        message,
+        severity: Severity::Error,
+        context,
+    });
+    i
+}
+
+/// Files TeX has open, innermost last; `None` marks a parenthesis that did not
+/// open a file, so its `)` still pops the right entry.
+#[derive(Default)]
+struct FileStack(Vec<Option<String>>);
+
+impl FileStack {
+    fn scan(&mut self, line: &str) {
+        let mut rest = line;
+        while let Some(i) = rest.find(['(', ')']) {
+            if rest[i..].starts_with(')') {
+                self.0.pop();
+                rest = &rest[i + 1..];
+                continue;
+            }
+            let after = &rest[i + 1..];
+            let end = after
+                .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                .unwrap_or(after.len());
+            let token = &after[..end];
+            self.0
+                .push(looks_like_file(token).then(|| token.to_string()));
+            rest = &after[end..];
+        }
+    }
+
+    /// Innermost open file, `None` when that is the primary input.
+    fn current(&self) -> Option<String> {
+        let name = self.0.iter().rev().flatten().next()?;
+        let name = name.strip_prefix("./").unwrap_or(name);
+        (name != "texput.tex").then(|| name.to_string())
+    }
+}
+
+/// `./chapter.tex`, `/abs/x.sty`, `article.cls`; not `(see` or `(1.5pt`.
+fn looks_like_file(token: &str) -> bool {
+    if token.starts_with("./") || token.starts_with('/') {
+        return true;
+    }
+    token.rsplit_once('.').is_some_and(|(stem, ext)| {
+        !stem.is_empty()
+            && (1..=4).contains(&ext.len())
+            && ext.bytes().all(|b| b.is_ascii_alphabetic())
+    })
+}
+
+/// `l.42 \foo` → (42, "\foo").
+fn parse_l_line(l: &str) -> Option<(u32, &str)> {
+    let rest = l.strip_prefix("l.")?;
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let n = rest[..digits].parse().ok()?;
+    Some((n, &rest[digits..]))
+}
+
+/// Recognizes `LaTeX Warning:`, `LaTeX Font Warning:`, `Package x Warning:` and
+/// `Class x Warning:`; joins wrapped and `(x)`-prefixed continuation lines.
+fn warning_text(lines: &[&str], start: usize) -> Option<(String, usize)> {
+    let line = lines[start];
+    let idx = line.find(" Warning: ")?;
+    let head = &line[..idx];
+    let is_warning = head == "LaTeX"
+        || head == "LaTeX Font"
+        || head.starts_with("Package ")
+        || head.starts_with("Class ");
+    if !is_warning {
+        return None;
+    }
+    let source = head
+        .strip_prefix("Package ")
+        .or_else(|| head.strip_prefix("Class "));
+    let mut text = line.trim().to_string();
+    let mut consumed = 1;
+    while let Some(next) = lines.get(start + consumed) {
+        let next = next.trim_end();
+        if next.is_empty() {
+            break;
+        }
+        let continued = match source {
+            Some(pkg) => next.strip_prefix(&format!("({pkg})")),
+            None if head == "LaTeX Font" => next.strip_prefix("(Font)"),
+            None => Some(next),
+        };
+        match continued {
+            Some(rest) => {
+                text.push(' ');
+                text.push_str(rest.trim());
+                consumed += 1;
+            }
+            None => break,
+        }
+    }
+    Some((text, consumed))
+}
+
+/// `… on input line 12.` → 12.
+fn input_line(message: &str) -> Option<u32> {
+    let idx = message.rfind("input line ")?;
+    let rest = &message[idx + "input line ".len()..];
+    rest.chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+/// `… in paragraph at lines 12--14` / `… detected at line 12` → 12.
+fn box_line(line: &str) -> Option<u32> {
+    let idx = line
+        .rfind("at lines ")
+        .map(|i| i + "at lines ".len())
+        .or_else(|| line.rfind("at line ").map(|i| i + "at line ".len()))?;
+    line[idx..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        file: Option<&str>,
+        line: Option<u32>,
+        message: &str,
+        severity: Severity,
+        context: Option<&str>,
+    ) -> Diagnostic {
+        Diagnostic {
+            file: file.map(str::to_string),
+            line,
+            message: message.into(),
+            severity,
+            context: context.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn tex_errors_and_warnings_carry_their_file_and_line() {
+        let log = "This is XeTeX, Version 3.141592653\n\
+                   (./texput.tex\n\
+                   LaTeX2e <2023-11-01>\n\
+                   (/bundle/article.cls\n\
+                   Document Class: article 2023/05/17 v1.4n (see the manual)\n\
+                   ) (./intro.tex\n\
+                   ! Undefined control sequence.\n\
+                   l.3 \\foo\n\
+                   \x20        bar\n\
+                   )\n\
+                   LaTeX Warning: Reference `fig' on page 1 undefined on input line 12.\n\
+                   \n\
+                   Package hyperref Warning: Token not allowed in a PDF string,\n\
+                   (hyperref)                removing `\\x' on input line 20.\n\
+                   \n\
+                   Overfull \\hbox (12.0pt too wide) in paragraph at lines 30--32\n\
+                   []\\TU/lmr/m/n/10 Some text\n\
+                   ! Emergency stop.\n\
+                   )\n";
+        assert_eq!(
+            parse_log(log),
+            [
+                diagnostic(
+                    Some("intro.tex"),
+                    Some(3),
+                    "Undefined control sequence.",
+                    Severity::Error,
+                    Some("\\foo bar"),
+                ),
+                diagnostic(
+                    None,
+                    Some(12),
+                    "LaTeX Warning: Reference `fig' on page 1 undefined on input line 12.",
+                    Severity::Warning,
+                    None,
+                ),
+                diagnostic(
+                    None,
+                    Some(20),
+                    "Package hyperref Warning: Token not allowed in a PDF string, \
+                     removing `\\x' on input line 20.",
+                    Severity::Warning,
+                    None,
+                ),
+                diagnostic(
+                    None,
+                    Some(30),
+                    "Overfull \\hbox (12.0pt too wide) in paragraph at lines 30--32",
+                    Severity::Warning,
+                    Some("[]\\TU/lmr/m/n/10 Some text"),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrapped_latex_errors_are_joined() {
+        let log = "! LaTeX Error: Environment foo undefined, or the name\n\
+                   is misspelled.\n\
+                   \n\
+                   See the LaTeX manual or LaTeX Companion for explanation.\n\
+                   Type  H <return>  for immediate help.\n\
+                   \x20...\n\
+                   \n\
+                   l.5 \\begin{foo}\n";
+        let found = parse_log(log);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].message,
+            "LaTeX Error: Environment foo undefined, or the name is misspelled."
+        );
+        assert_eq!(found[0].line, Some(5));
+        assert_eq!(found[0].context.as_deref(), Some("\\begin{foo}"));
+    }
+
+    #[test]
+    fn bibtex_errors_point_into_the_bib_file() {
+        let blg = "Database file #1: refs.bib\n\
+                   Warning--empty journal in knuth84\n\
+                   I couldn't open database file other.bib\n\
+                   ---line 3 of file texput.aux\n\
+                   \x20: \\bibdata{refs,other\n\
+                   I was expecting a `,' or a `}'---line 12 of file refs.bib\n\
+                   \x20: @article{x\n";
+        assert_eq!(
+            parse_bibtex_log(blg),
+            [
+                diagnostic(
+                    None,
+                    None,
+                    "BibTeX: empty journal in knuth84",
+                    Severity::Warning,
+                    None
+                ),
+                diagnostic(
+                    None,
+                    None,
+                    "BibTeX: I couldn't open database file other.bib",
+                    Severity::Error,
+                    Some("\\bibdata{refs,other"),
+                ),
+                diagnostic(
+                    Some("refs.bib"),
+                    Some(12),
+                    "BibTeX: I was expecting a `,' or a `}'",
+                    Severity::Error,
+                    Some("@article{x"),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn makeindex_style_errors_keep_their_position() {
+        let ilg = "Scanning style file doc.ist....done (1 ignored).\n\
+                   ** Input style error (file = doc.ist, line = 3):\n\
+                   \x20  -- Unknown specifier `foo'.\n\
+                   !! Input index error (file = doc.idx, line = 7):\n\
+                   \x20  -- Extra `!' at position 4 of first argument.\n\
+                   ## Warning (input = doc.idx, line = 2; output = doc.ind, line = 5):\n\
+                   \x20  -- Unmatched range closing operator `)'.\n";
+        assert_eq!(
+            parse_makeindex_log(ilg),
+            [
+                diagnostic(
+                    Some("doc.ist"),
+                    Some(3),
+                    "makeindex: Unknown specifier `foo'.",
+                    Severity::Error,
+                    None,
+                ),
+                diagnostic(
+                    None,
+                    None,
+                    "makeindex: Extra `!' at position 4 of first argument.",
+                    Severity::Error,
+                    None,
+                ),
+                diagnostic(
+                    None,
+                    None,
+                    "makeindex: Unmatched range closing operator `)'.",
+                    Severity::Warning,
+                    None,
+                ),
+            ]
+        );
+    }
+}
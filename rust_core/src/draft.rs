@@ -0,0 +1,138 @@
+//! Draft compiles, for a PDF view that keeps up with typing.
+//!
+//! A draft ([`crate::CompileOptions::draft`]) gives up fidelity for speed:
+//! - `\includegraphics` draws a framed box with the file name (graphicx's
+//!   `draft`) instead of embedding the image
+//! - TeX stops after [`Draft::max_pages`] pages, or with the first page shipped
+//!   out once it has read [`Draft::until_line`] of the main file; nothing after
+//!   that is typeset or written
+//! - one TeX pass and no BibTeX or makeindex: references, citations and the
+//!   index come from the last full compile to the same output path, whose aux
+//!   files a draft reads but never replaces
+//! - xdvipdfmx leaves streams uncompressed
+//!
+//! Fonts are still subset, so a draft does not save that work: the embedded
+//! xdvipdfmx has no switch to embed them whole.
+//!
+//! The draft setup is read right after `\begin{document}`, on the same line, so
+//! the preamble (and its dumped format) is the one full compiles use.
+
+use serde::Deserialize;
+
+use crate::engine::incremental;
+
+/// In-memory file holding [`Draft::setup`].
+pub const DRAFT_INPUT: &str = "livelatex-draft.tex";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Draft {
+    /// Pages to typeset at most.
+    pub max_pages: Option<u32>,
+    /// 1-based line of the main file (the one being edited) to typeset up to.
+    pub until_line: Option<u32>,
+}
+
+impl Draft {
+    /// TeX that switches graphicx to draft and ends the run once a stop is reached.
+    ///
+    /// Pages are counted in `shipout/after`, where the stop is decided for
+    /// the page just shipped. Ending TeX there is not allowed (it is inside
+    /// the output routine), so the output routine ends with an `\aftergroup`
+    /// that runs `\end` back in the main vertical list; any page shipped after
+    /// the stop is discarded. Line numbers are only compared while no `\input`
+    /// file is open.
+    pub fn setup(&self) -> String {
+        let mut check = String::new();
+        if let Some(pages) = self.max_pages {
+            check += &format!(
+                "  \\ifnum\\livelatex@pages<{} \\else \\global\\livelatex@stoptrue \\fi\n",
+                pages.max(1)
+            );
+        }
+        if let Some(line) = self.until_line {
+            check += &format!(
+                "  \\ifnum\\livelatex@depth=\\z@ \\ifnum\\inputlineno<{line} \\else\n    \
+                 \\global\\livelatex@stoptrue \\fi\\fi\n"
+            );
+        }
+        format!(
+            r"\catcode`\@=11
+\ifdefined\Gin@drafttrue \Gin@drafttrue \fi
+\newif\iflivelatex@stop
+\newcount\livelatex@depth
+\newcount\livelatex@pages
+\AddToHook{{file/before}}{{\global\advance\livelatex@depth\@ne}}
+\AddToHook{{file/after}}{{\global\advance\livelatex@depth\m@ne}}
+\AddToHook{{shipout/before}}{{\iflivelatex@stop \DiscardShipoutBox \fi}}
+\AddToHook{{shipout/after}}{{\global\advance\livelatex@pages\@ne \livelatex@check}}
+\def\livelatex@check{{{check}}}
+\def\livelatex@end{{\deadcycles\z@ \csname @@end\endcsname}}
+\global\output\expandafter{{\the\output
+  \iflivelatex@stop \aftergroup\livelatex@end \fi}}
+\catcode`\@=12
+\endinput
+"
+        )
+    }
+
+    /// `source` with the setup read right after `\begin{document}`; unchanged
+    /// when there is none.
+    pub fn insert(source: &str) -> String {
+        const BEGIN: &str = "\\begin{document}";
+        match incremental::split_preamble(source) {
+            Some(p) => format!(
+                "{}{BEGIN}\\csname @@input\\endcsname {DRAFT_INPUT} {}",
+                p.text,
+                &p.body[BEGIN.len()..]
+            ),
+            None => source.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_is_read_on_the_begin_document_line() {
+        let source = "\\documentclass{article}\n\\begin{document}\nHi\n\\end{document}\n";
+        let drafted = Draft::insert(source);
+        assert_eq!(
+            drafted,
+            "\\documentclass{article}\n\\begin{document}\\csname @@input\\endcsname \
+             livelatex-draft.tex \nHi\n\\end{document}\n"
+        );
+        assert_eq!(drafted.lines().count(), source.lines().count());
+        assert_eq!(Draft::insert("Hi"), "Hi");
+    }
+
+    #[test]
+    fn setup_checks_only_the_stops_asked_for() {
+        let none = Draft::default().setup();
+        assert!(none.contains("\\def\\livelatex@check{}"), "{none}");
+        assert!(none.contains("\\Gin@drafttrue"));
+
+        let both = Draft {
+            max_pages: Some(0),
+            until_line: Some(40),
+        }
+        .setup();
+        assert!(both.contains("\\ifnum\\livelatex@pages<1 \\else"), "{both}");
+        assert!(both.contains("\\ifnum\\inputlineno<40 \\else"), "{both}");
+        assert!(both.ends_with("\\endinput\n"));
+    }
+
+    #[test]
+    fn options_come_from_json() {
+        let draft: Draft = serde_json::from_str(r#"{"maxPages": 5}"#).unwrap();
+        assert_eq!(
+            draft,
+            Draft {
+                max_pages: Some(5),
+                until_line: None
+            }
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Per-document state kept between compiles, so recompile-on-save starts warm.
+//!
+//! `cache/work/<doc>/` (one per output path) holds:
+//! - `preamble-<key>.fmt`: a format dumped right after the preamble. `<key>` hashes
+//!   the bundle digest and the preamble text, so any edit above `\begin{document}`
+//!   simply misses and a new one is dumped
+//! - `preamble-<key>.failed`: the preamble could not be dumped (XeTeX refuses to
+//!   dump native fonts, e.g. fontspec); it is compiled in full without retrying
+//! - `preamble-<key>.inputs`: project files the preamble read (a local `.sty`,
+//!   `\input{macros}`) with their hashes; the format is redumped when one changes
+//! - `aux-<key>/`: `.aux`, `.toc`, `.out`, … from the last successful run, including
+//!   the per-chapter `.aux` files of `\include` (which `\includeonly` relies on).
+//!   These are tied to the preamble that wrote them: a package dropped from the
+//!   preamble can leave commands in the `.aux` that would break the next run.
+//! - `bibtex-<key>`: what BibTeX last read (citations, styles, databases), so
+//!   the `.bbl` among the aux files is reused until one of those changes
+//! - `index-<key>`: the makeindex runs behind the `.ind`/`.gls`/… aux files, with
+//!   a hash of what each one read
+//! - `texput.synctex.gz`: SyncTeX of the last successful run, for [`crate::synctex`]
+//! - `shell/`: where shell escape commands of a trusted project run
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+/// Files carried from one run to the next; TeX rereads them on the following pass.
+/// BibTeX's and makeindex's logs come along with their output so skipped runs
+/// still report.
+pub const AUX_EXTENSIONS: &[&str] = &[
+    "aux", "toc", "lof", "lot", "out", "nav", "snm", "bbl", "blg", "ind", "ilg", "gls", "glg",
+    "acr", "alg", "nls", "nlg",
+];
+
+/// Preamble commands that `\immediate\openout` a file the body writes to; the
+/// stream does not survive a format dump.
+const OPENS_OUTPUT: &[&str] = &["\\makeindex", "\\makeglossaries", "\\makenomenclature"];
+
+/// Work dirs kept before the least recently compiled ones are dropped; each can
+/// hold a preamble format of several MB.
+const MAX_WORK_DIRS: usize = 8;
+const STAMP: &str = "last-used";
+
+pub fn is_aux_file(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUX_EXTENSIONS.contains(&e))
+}
+
+/// True for a relative path without `..` or a root, which stays inside any
+/// directory it is joined to.
+pub fn is_confined(name: &str) -> bool {
+    !name.is_empty()
+        && Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// First 16 hex digits of the SHA-256 of `parts`.
+pub fn key(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+/// Source split at `\begin{document}`.
+#[derive(Debug, Clone, Copy)]
+pub struct Preamble<'a> {
+    /// Everything before `\begin{document}`.
+    pub text: &'a str,
+    /// `\begin{document}` to the end.
+    pub body: &'a str,
+}
+
+impl Preamble<'_> {
+    /// False when the preamble opens an output stream (see [`OPENS_OUTPUT`]).
+    pub fn can_dump(&self) -> bool {
+        !OPENS_OUTPUT.iter().any(|cmd| self.text.contains(cmd))
+    }
+
+    /// The body, pushed down by the preamble's line count so TeX's `l.<n>`
+    /// numbers still match the editor.
+    pub fn padded_body(&self) -> String {
+        let lines = self.text.matches('\n').count();
+        let mut out = String::with_capacity(lines + self.body.len());
+        out.extend(std::iter::repeat_n('\n', lines));
+        out.push_str(self.body);
+        out
+    }
+}
+
+/// `None` when the source has no uncommented `\begin{document}`.
+pub fn split_preamble(source: &str) -> Option<Preamble<'_>> {
+    const BEGIN: &str = "\\begin{document}";
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
+        if let Some(pos) = line.find(BEGIN) {
+            if !is_commented(&line[..pos]) {
+                let at = line_start + pos;
+                return Some(Preamble {
+                    text: &source[..at],
+                    body: &source[at..],
+                });
+            }
+        }
+        line_start += line.len();
+    }
+    None
+}
+
+/// True if `prefix` contains an unescaped `%`.
+fn is_commented(prefix: &str) -> bool {
+    let mut escaped = false;
+    for c in prefix.chars() {
+        if c == '%' && !escaped {
+            return true;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    false
+}
+
+/// One makeindex run remembered between compiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexRecord {
+    /// Sorted file, e.g. `texput.ind`.
+    pub output: String,
+    /// Transcript, e.g. `texput.ilg`.
+    pub log: String,
+    /// [`key`] of the raw entries and style the output was made from.
+    pub input: String,
+}
+
+pub struct WorkDir {
+    root: PathBuf,
+}
+
+impl WorkDir {
+    /// Work dir for the document compiled to `output_path`; evicts the least
+    /// recently used ones beyond [`MAX_WORK_DIRS`].
+    pub fn for_document(cache_path: &Path, output_path: &Path) -> io::Result<Self> {
+        let parent = cache_path.join("work");
+        let root = parent.join(key(&[output_path.to_string_lossy().as_bytes()]));
+        fs::create_dir_all(&root)?;
+        fs::write(root.join(STAMP), b"")?;
+        evict(&parent, &root)?;
+        Ok(WorkDir { root })
+    }
+
+    /// Work dir of an already compiled document, without marking it as used.
+    pub fn existing(cache_path: &Path, output_path: &Path) -> Option<Self> {
+        let doc = key(&[output_path.to_string_lossy().as_bytes()]);
+        let root = cache_path.join("work").join(doc);
+        root.is_dir().then_some(WorkDir { root })
+    }
+
+    pub fn synctex_path(&self) -> PathBuf {
+        self.root.join("texput.synctex.gz")
+    }
+
+    pub fn store_synctex(&self, data: &[u8]) -> io::Result<()> {
+        let path = self.synctex_path();
+        let tmp = path.with_extension("gz.part");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)
+    }
+
+    /// Working directory for shell escape; the engine mirrors its files there.
+    pub fn shell_dir(&self) -> PathBuf {
+        self.root.join("shell")
+    }
+
+    pub fn preamble_format(&self, key: &str) -> PathBuf {
+        self.root.join(format!("preamble-{key}.fmt"))
+    }
+
+    pub fn preamble_failed(&self, key: &str) -> bool {
+        self.root.join(format!("preamble-{key}.failed")).is_file()
+    }
+
+    pub fn mark_preamble_failed(&self, key: &str) -> io::Result<()> {
+        self.remove_entries("preamble-")?;
+        fs::write(self.root.join(format!("preamble-{key}.failed")), b"")
+    }
+
+    /// Replaces any older preamble format. `inputs` are the project files the
+    /// preamble read, as (name, [`key`] of the contents).
+    pub fn store_preamble_format(
+        &self,
+        key: &str,
+        data: &[u8],
+        inputs: &[(String, String)],
+    ) -> io::Result<()> {
+        self.remove_entries("preamble-")?;
+        let path = self.preamble_format(key);
+        let tmp = path.with_extension("fmt.part");
+        fs::write(&tmp, data)?;
+        if !inputs.is_empty() {
+            let list: String = inputs
+                .iter()
+                .map(|(name, hash)| format!("{name}\t{hash}\n"))
+                .collect();
+            fs::write(self.root.join(format!("preamble-{key}.inputs")), list)?;
+        }
+        fs::rename(&tmp, &path)
+    }
+
+    /// Project files read while dumping the format under `key`.
+    pub fn preamble_inputs(&self, key: &str) -> Vec<(String, String)> {
+        fs::read_to_string(self.root.join(format!("preamble-{key}.inputs")))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| l.split_once('\t'))
+            .map(|(name, hash)| (name.to_string(), hash.to_string()))
+            .collect()
+    }
+
+    /// Input key of the BibTeX run whose `.bbl` is stored under `key`.
+    pub fn bibtex_input(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.root.join(format!("bibtex-{key}"))).ok()
+    }
+
+    /// Replaces any older BibTeX input key.
+    pub fn store_bibtex_input(&self, key: &str, input: &str) -> io::Result<()> {
+        self.remove_entries("bibtex-")?;
+        fs::write(self.root.join(format!("bibtex-{key}")), input)
+    }
+
+    /// makeindex runs whose outputs are stored under `key`.
+    pub fn index_records(&self, key: &str) -> Vec<IndexRecord> {
+        let Ok(text) = fs::read_to_string(self.root.join(format!("index-{key}"))) else {
+            return Vec::new();
+        };
+        text.lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                Some(IndexRecord {
+                    output: fields.next()?.to_string(),
+                    log: fields.next()?.to_string(),
+                    input: fields.next()?.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces any older makeindex records.
+    pub fn store_index_records(&self, key: &str, records: &[IndexRecord]) -> io::Result<()> {
+        self.remove_entries("index-")?;
+        if records.is_empty() {
+            return Ok(());
+        }
+        let text: String = records
+            .iter()
+            .map(|r| format!("{}\t{}\t{}\n", r.output, r.log, r.input))
+            .collect();
+        fs::write(self.root.join(format!("index-{key}")), text)
+    }
+
+    /// Aux files saved by [`WorkDir::store_aux`] under the same key; empty if none.
+    pub fn load_aux(&self, key: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let dir = self.root.join(format!("aux-{key}"));
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut files = Vec::new();
+        collect_aux(&dir, "", entries, &mut files)?;
+        Ok(files)
+    }
+
+    /// Replaces the saved aux files (for any key) with `files`.
+    pub fn store_aux(&self, key: &str, files: &[(String, Vec<u8>)]) -> io::Result<()> {
+        self.remove_entries("aux-")?;
+        let dir = self.root.join(format!("aux-{key}"));
+        fs::create_dir_all(&dir)?;
+        for (name, data) in files {
+            if !is_confined(name) {
+                continue;
+            }
+            let path = dir.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, data)?;
+        }
+        Ok(())
+    }
+
+    fn remove_entries(&self, prefix: &str) -> io::Result<()> {
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_name().to_string_lossy().starts_with(prefix) {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Aux files under `dir`, named relative to the aux root (`chapters/intro.aux`).
+fn collect_aux(
+    dir: &Path,
+    prefix: &str,
+    entries: fs::ReadDir,
+    files: &mut Vec<(String, Vec<u8>)>,
+) -> io::Result<()> {
+    for entry in entries {
+        let entry = entry?;
+        let name = format!("{prefix}{}", entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            let sub = dir.join(entry.file_name());
+            collect_aux(&sub, &format!("{name}/"), fs::read_dir(&sub)?, files)?;
+        } else if is_aux_file(&name) {
+            files.push((name, fs::read(entry.path())?));
+        }
+    }
+    Ok(())
+}
+
+fn evict(parent: &Path, keep: &Path) -> io::Result<()> {
+    let mut dirs: Vec<(SystemTime, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(parent)? {
+        let path = entry?.path();
+        if path == keep || !path.is_dir() {
+            continue;
+        }
+        let used = fs::metadata(path.join(STAMP))
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        dirs.push((used, path));
+    }
+    if dirs.len() < MAX_WORK_DIRS {
+        return Ok(());
+    }
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.0));
+    for (_, path) in dirs.drain(MAX_WORK_DIRS - 1..) {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_confined_refuses_roots_and_parent_dirs() {
+        assert!(is_confined("chapter.tex"));
+        assert!(is_confined("./figures/plot.pdf"));
+        assert!(!is_confined(""));
+        assert!(!is_confined("../secret.tex"));
+        assert!(!is_confined("figures/../../secret.tex"));
+        assert!(!is_confined("/etc/passwd"));
+    }
+}
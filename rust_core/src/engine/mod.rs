@@ -0,0 +1,84 @@
+//! Engine front: one call that turns LaTeX source into PDF bytes.
+//!
+//! The real implementation lives in [`xetex`] and is only compiled with the
+//! `tectonic` feature; without it [`typeset`] reports [`Error::EngineUnavailable`]
+//! so the rest of the crate (and its host tooling) still builds everywhere.
+//! [`incremental`] holds the per-document state that makes recompiles cheap,
+//! [`program`] whether a document is run as pdfLaTeX or XeLaTeX.
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "tectonic")]
+use std::sync::Mutex;
+
+#[cfg(not(feature = "tectonic"))]
+use crate::Error;
+use crate::Result;
+
+pub mod incremental;
+pub mod program;
+#[cfg(feature = "tectonic")]
+mod xetex;
+
+use crate::compile::CompileOptions;
+use crate::job::Monitor;
+use crate::sandbox::Denial;
+use incremental::WorkDir;
+use program::Program;
+
+/// Format file name handed to XeTeX; generated on first use from the bundle.
+pub const FORMAT_NAME: &str = "latex";
+
+/// Output of an engine run. TeX errors are not an `Err`: they leave `pdf` empty
+/// and set `failure`, so the log is still available for diagnostics.
+#[derive(Debug, Clone)]
+pub struct Typeset {
+    pub pdf: Option<Vec<u8>>,
+    /// Contents of the engine's `.log` for the last pass that ran.
+    pub log: String,
+    /// Engine-level failure ("XeTeX failed: …"), if the run stopped early.
+    pub failure: Option<String>,
+    /// BibTeX's `.blg` when BibTeX ran during this compile.
+    pub bibtex_log: Option<String>,
+    /// Transcripts of the index and glossary runs (`.ilg`, `.glg`, …).
+    pub index_logs: Vec<String>,
+    /// File accesses the sandbox refused, in the order the engines tried them.
+    pub denied: Vec<Denial>,
+}
+
+/// Cached `.fmt` files, keyed by bundle digest and format serial.
+pub fn formats_dir(cache_path: &Path) -> PathBuf {
+    cache_path.join("formats")
+}
+
+/// Held for a whole compile, not just one engine pass: with one compile at a
+/// time, [`crate::limits`] charges the memory the process grows by to it.
+#[cfg(feature = "tectonic")]
+static ENGINE: Mutex<()> = Mutex::new(());
+
+/// Runs one compile once no other holds the engine; the monitor's limits
+/// count from then.
+#[cfg(feature = "tectonic")]
+pub fn typeset(
+    latex_source: &str,
+    cache_path: &Path,
+    options: &CompileOptions,
+    program: Program,
+    work: &WorkDir,
+    monitor: &Monitor,
+) -> Result<Typeset> {
+    let _engine = ENGINE.lock().unwrap_or_else(|e| e.into_inner());
+    monitor.restart_limits();
+    xetex::typeset(latex_source, cache_path, options, program, work, monitor)
+}
+
+#[cfg(not(feature = "tectonic"))]
+pub fn typeset(
+    _latex_source: &str,
+    _cache_path: &Path,
+    _options: &CompileOptions,
+    _program: Program,
+    _work: &WorkDir,
+    _monitor: &Monitor,
+) -> Result<Typeset> {
+    Err(Error::EngineUnavailable)
+}
@@ -0,0 +1,284 @@
+//! Which TeX a document is written for.
+//!
+//! The embedded engine is XeTeX either way; the program decides how it runs:
+//! - [`Program::Pdftex`]: pdfLaTeX documents (`inputenc`, `fontenc`, Type 1
+//!   fonts). [`PDFTEX_COMPAT`] is read before the first line, so the pdfTeX
+//!   settings such documents make (`\pdfoutput=1`, `\pdfminorversion`,
+//!   `\pdfglyphtounicode`) are accepted and ignored instead of stopping TeX.
+//! - [`Program::Xetex`]: `fontspec`, `polyglossia`, `xeCJK` and the like.
+//!   OpenType fonts are found by name through fontconfig, or by file name in
+//!   the project, the bundle and the system font dirs ([`SYSTEM_FONT_DIRS`]).
+//!
+//! [`select`] picks one: the `program` compile option, else a
+//! `% !TEX program = xelatex` magic comment at the top of the file, else XeTeX
+//! if the document loads a Unicode-font package and pdfTeX otherwise.
+//!
+//! fontconfig reads its configuration once per process, so [`use_fontconfig`]
+//! points it at the system and bundle fonts before the first compile, whatever
+//! its program.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::{bundle_dir_path, used_packages};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::sandbox;
+
+/// In-memory file holding [`PDFTEX_COMPAT`]; `\input` at the start of line 1.
+pub const PDFTEX_COMPAT_INPUT: &str = "livelatex-pdftex.tex";
+
+/// pdfTeX primitives that pdfLaTeX documents set, for XeTeX. `\pdfoutput` is
+/// only defined until its first assignment or `\documentclass`: graphics,
+/// hyperref and iftex pick their pdfTeX drivers when it exists.
+pub const PDFTEX_COMPAT: &str = r"\catcode`\@=11
+\ifx\pdfoutput\@undefined
+  \def\livelatex@nopdfoutput{\let\pdfoutput\@undefined}
+  \def\pdfoutput{\afterassignment\livelatex@nopdfoutput\count@}
+  \let\livelatex@documentclass\documentclass
+  \def\documentclass{\livelatex@nopdfoutput
+    \let\documentclass\livelatex@documentclass\documentclass}
+\fi
+\ifx\pdfminorversion\@undefined \newcount\pdfminorversion \fi
+\ifx\pdfcompresslevel\@undefined \newcount\pdfcompresslevel \fi
+\ifx\pdfobjcompresslevel\@undefined \newcount\pdfobjcompresslevel \fi
+\ifx\pdfgentounicode\@undefined \newcount\pdfgentounicode \fi
+\ifx\pdfsuppresswarningpagegroup\@undefined \newcount\pdfsuppresswarningpagegroup \fi
+\ifx\pdfglyphtounicode\@undefined \let\pdfglyphtounicode\@gobbletwo \fi
+\catcode`\@=12
+\endinput
+";
+
+/// Where Android keeps its fonts; `/product/fonts` holds OEM additions.
+pub const SYSTEM_FONT_DIRS: &[&str] = &["/system/fonts", "/product/fonts", "/system/product/fonts"];
+
+/// Packages that only work with a Unicode engine.
+const UNICODE_PACKAGES: &[&str] = &[
+    "fontspec",
+    "polyglossia",
+    "unicode-math",
+    "xeCJK",
+    "xunicode",
+    "xltxtra",
+    "xgreek",
+    "bidi",
+    "arabxetex",
+];
+
+/// How many lines at the top of the file may hold magic comments.
+const MAGIC_COMMENT_LINES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Program {
+    #[serde(alias = "pdflatex")]
+    Pdftex,
+    #[serde(alias = "xelatex")]
+    Xetex,
+}
+
+impl Program {
+    /// Text the engine reads before the document; see [`PDFTEX_COMPAT`].
+    pub fn prelude(self) -> Option<&'static str> {
+        match self {
+            Program::Pdftex => Some(PDFTEX_COMPAT),
+            Program::Xetex => None,
+        }
+    }
+}
+
+/// The program for `source`, with a note when its magic comment names one that
+/// is not available. `requested` is the per-document setting.
+pub fn select(source: &str, requested: Option<Program>) -> (Program, Option<Diagnostic>) {
+    if let Some(program) = requested {
+        return (program, None);
+    }
+    let (magic, note) = match magic_comment(source) {
+        Some((line, name)) => parse_program(&name, line),
+        None => (None, None),
+    };
+    let program = magic.unwrap_or_else(|| {
+        let unicode = used_packages(source)
+            .iter()
+            .any(|u| UNICODE_PACKAGES.contains(&u.name.as_str()));
+        if unicode {
+            Program::Xetex
+        } else {
+            Program::Pdftex
+        }
+    });
+    (program, note)
+}
+
+/// `% !TEX program = xelatex` (or `TS-program`) among the comment lines the
+/// file starts with, as (1-based line, program name).
+fn magic_comment(source: &str) -> Option<(u32, String)> {
+    for (i, line) in source.lines().take(MAGIC_COMMENT_LINES).enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let comment = line.strip_prefix('%')?.trim_start_matches('%').trim_start();
+        let Some(rest) = comment
+            .strip_prefix("!TEX")
+            .or_else(|| comment.strip_prefix("!TeX"))
+        else {
+            continue;
+        };
+        let Some((key, value)) = rest.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        if key == "program" || key == "ts-program" {
+            return Some((i as u32 + 1, value.trim().to_ascii_lowercase()));
+        }
+    }
+    None
+}
+
+fn parse_program(name: &str, line: u32) -> (Option<Program>, Option<Diagnostic>) {
+    let note = |message: String| Diagnostic {
+        file: None,
+        line: Some(line),
+        message,
+        severity: Severity::Warning,
+        context: None,
+    };
+    match name {
+        "pdflatex" | "pdftex" | "latex" => (Some(Program::Pdftex), None),
+        "xelatex" | "xetex" => (Some(Program::Xetex), None),
+        "lualatex" | "luatex" => (
+            Some(Program::Xetex),
+            Some(note(format!(
+                "`{name}' is not available; compiling with XeLaTeX, which also loads fontspec"
+            ))),
+        ),
+        _ => (
+            None,
+            Some(note(format!(
+                "Unknown TeX program `{name}' in the magic comment; use pdflatex or xelatex"
+            ))),
+        ),
+    }
+}
+
+/// A font file TeX asked for by name (`[NotoSans-Regular.ttf]`, fontspec's
+/// `Extension`), in the system font dirs.
+pub fn system_font(name: &str) -> Option<PathBuf> {
+    if name.contains('/') || !sandbox::has_font_extension(name) {
+        return None;
+    }
+    SYSTEM_FONT_DIRS
+        .iter()
+        .map(|dir| Path::new(dir).join(name))
+        .find(|p| p.is_file())
+}
+
+/// Where fontconfig looks for fonts: the system font dirs and the installed bundle.
+pub fn font_dirs(cache_path: &Path) -> Vec<PathBuf> {
+    SYSTEM_FONT_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(Some(bundle_dir_path(cache_path)))
+        .collect()
+}
+
+/// Writes `fontconfig/fonts.conf` under `cache_path` and has fontconfig use it.
+/// Only the first call in a process has an effect, as fontconfig only reads its
+/// configuration once; an existing `FONTCONFIG_FILE` is left alone.
+pub fn use_fontconfig(cache_path: &Path) {
+    static CONFIGURED: OnceLock<()> = OnceLock::new();
+    CONFIGURED.get_or_init(|| {
+        if std::env::var_os("FONTCONFIG_FILE").is_some() {
+            return;
+        }
+        if let Ok(path) = write_fontconfig(cache_path) {
+            std::env::set_var("FONTCONFIG_FILE", path);
+        }
+    });
+}
+
+fn write_fontconfig(cache_path: &Path) -> io::Result<PathBuf> {
+    let dir = cache_path.join("fontconfig");
+    fs::create_dir_all(&dir)?;
+    let dirs = font_dirs(cache_path)
+        .into_iter()
+        .filter(|d| d.is_dir())
+        .map(|d| format!("  <dir>{}</dir>\n", xml_escape(&d.to_string_lossy())))
+        .collect::<String>();
+    let conf = format!(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE fontconfig SYSTEM \"fonts.dtd\">\n\
+         <fontconfig>\n{dirs}  <cachedir>{}</cachedir>\n</fontconfig>\n",
+        xml_escape(&dir.join("cache").to_string_lossy())
+    );
+    let path = dir.join("fonts.conf");
+    fs::write(&path, conf)?;
+    Ok(path)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_comment_is_read_from_the_leading_comments() {
+        let src = "\n% !TEX root = main.tex\n%!TeX TS-program = XeLaTeX \n\\documentclass{article}";
+        assert_eq!(magic_comment(src), Some((3, "xelatex".into())));
+        assert_eq!(
+            magic_comment("\\documentclass{article}\n% !TEX program = xelatex"),
+            None
+        );
+        assert_eq!(
+            magic_comment("% a note\n%% !TEX program=pdflatex"),
+            Some((2, "pdflatex".into()))
+        );
+    }
+
+    #[test]
+    fn requested_beats_magic_comment_beats_packages() {
+        let xe = "% !TEX program = xelatex\n\\documentclass{article}";
+        assert_eq!(select(xe, Some(Program::Pdftex)), (Program::Pdftex, None));
+        assert_eq!(select(xe, None), (Program::Xetex, None));
+
+        let fontspec = "\\documentclass{article}\n\\usepackage{fontspec}";
+        assert_eq!(select(fontspec, None).0, Program::Xetex);
+        let pdf = "% !TEX program = pdflatex\n\\usepackage{fontspec}";
+        assert_eq!(select(pdf, None).0, Program::Pdftex);
+        assert_eq!(select("\\usepackage[T1]{fontenc}", None).0, Program::Pdftex);
+    }
+
+    #[test]
+    fn unavailable_programs_get_a_note() {
+        let (program, note) = select("% !TEX program = lualatex\n", None);
+        assert_eq!(program, Program::Xetex);
+        let note = note.unwrap();
+        assert_eq!((note.line, note.severity), (Some(1), Severity::Warning));
+
+        let (program, note) = select("\n% !TEX program = context\n\\usepackage{xeCJK}", None);
+        assert_eq!(program, Program::Xetex);
+        assert!(note.unwrap().message.contains("`context'"));
+    }
+
+    #[test]
+    fn only_pdftex_reads_the_compat_prelude() {
+        assert_eq!(Program::Pdftex.prelude(), Some(PDFTEX_COMPAT));
+        assert_eq!(Program::Xetex.prelude(), None);
+        let program: Program = serde_json::from_str("\"pdflatex\"").unwrap();
+        assert_eq!(program, Program::Pdftex);
+    }
+
+    #[test]
+    fn only_plain_font_file_names_are_looked_up() {
+        assert_eq!(system_font("../fonts/Roboto-Regular.ttf"), None);
+        assert_eq!(system_font("Roboto-Regular.tex"), None);
+    }
+}
@@ -0,0 +1,1123 @@
+//! XeTeX + xdvipdfmx driver on top of the Tectonic engine crates.
+//!
+//! All engine I/O goes through [`Driver`]: the primary input is the in-memory
+//! source, intermediates (`.aux`, `.xdv`, `.log`, …) stay in a [`MemoryIo`],
+//! support files come from the bundle and formats from the format cache.
+//!
+//! With a [`WorkDir`] the run starts warm: the preamble is loaded from a dumped
+//! format and the previous run's aux files are preloaded, so an unchanged
+//! document typically needs a single TeX pass.
+//!
+//! A document whose `.aux` names `\bibdata` gets a BibTeX run after the TeX
+//! pass that wrote it, and again only when its citations, style or databases
+//! change. Raw index, glossary and nomenclature entries (`.idx`, `.glo`, `.nlo`,
+//! …) go through [`crate::makeindex`] the same way. Databases and styles are
+//! read from the project dir, if the app gave one, before the bundle.
+//!
+//! A draft ([`crate::draft`]) reads its setup after `\begin{document}`, runs TeX
+//! once and keeps the aux files of the last full compile.
+//!
+//! A pdfLaTeX document ([`Program::Pdftex`]) reads [`program::PDFTEX_COMPAT`] first; a
+//! XeLaTeX one may also load font files from the system font dirs.
+//!
+//! The [`Monitor`] is polled on every file open, and its [`Tripwire`] on every
+//! output write: refusing the open or failing the write of a shipped-out page
+//! are the only ways to stop a running engine, which then unwinds and frees its
+//! state. The same opens enforce [`crate::sandbox`]: names that would leave the project,
+//! the bundle or the in-memory outputs are refused and recorded.
+
+use std::fmt::Arguments;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use tectonic::io::format_cache::FormatCache;
+use tectonic::io::memory::MemoryFileInfo;
+use tectonic::io::{InputHandle, InputOrigin, IoProvider, MemoryIo, OpenResult, OutputHandle};
+use tectonic::status::{MessageKind, StatusBackend};
+use tectonic::unstable_opts::UnstableOptions;
+use tectonic::{BibtexEngine, TexEngine, TexOutcome, XdvipdfmxEngine};
+use tectonic_bridge_core::{
+    CoreBridgeLauncher, DriverHooks, SecuritySettings, SecurityStance, SystemRequestError,
+};
+use tectonic_bundles::{dir::DirBundle, zip::ZipBundle, Bundle};
+use tectonic_io_base::stdstreams::BufferedPrimaryIo;
+
+use super::incremental::{self, IndexRecord, Preamble, WorkDir};
+use super::program::{self, Program, PDFTEX_COMPAT_INPUT};
+use super::{formats_dir, Typeset, FORMAT_NAME};
+use crate::bundle::{bundle_dir_path, bundle_zip_path};
+use crate::compile::CompileOptions;
+use crate::draft::{Draft, DRAFT_INPUT};
+use crate::job::{Monitor, Stage, Tripwire};
+use crate::makeindex::{self, KeyOrder};
+use crate::reproducible;
+use crate::sandbox::{self, Access, Denial};
+use crate::{Error, Result};
+
+/// Name TeX sees for the primary input; the job name (and output names) derive from it.
+const INPUT_NAME: &str = "texput.tex";
+const JOB_NAME: &str = "texput";
+/// Upper bound on TeX passes while the aux files keep changing.
+const MAX_TEX_PASSES: usize = 4;
+/// Format name TeX is given when the document's preamble format is in use.
+const PREAMBLE_FORMAT: &str = "livelatex-preamble.fmt";
+/// In-memory file holding the preamble while its format is dumped.
+const PREAMBLE_INPUT: &str = "livelatex-preamble.tex";
+/// Files BibTeX and makeindex may read from the project dir.
+const PROJECT_EXTENSIONS: &[&str] = &["bib", "bst", "ist"];
+/// nomencl's makeindex style, from the bundle.
+const NOMENCL_STYLE: &str = "nomencl.ist";
+
+pub(super) fn typeset(
+    latex_source: &str,
+    cache_path: &Path,
+    options: &CompileOptions,
+    program: Program,
+    work: &WorkDir,
+    monitor: &Monitor,
+) -> Result<Typeset> {
+    monitor.check()?;
+    program::use_fontconfig(cache_path);
+    // Same line as the document's first, so TeX's line numbers still match the source.
+    let source = match program.prelude() {
+        Some(_) => format!("\\input {PDFTEX_COMPAT_INPUT} {latex_source}"),
+        None => latex_source.to_string(),
+    };
+    let source = match options.draft {
+        Some(_) => Draft::insert(&source),
+        None => source,
+    };
+    let mut status = Collector::default();
+    let mut bundle = open_bundle(cache_path)?;
+    let digest = bundle
+        .get_digest()
+        .map_err(|e| engine_error("bundle", &e, &status))?;
+
+    let shell_dir = options.trusted.then(|| work.shell_dir());
+    if let Some(dir) = &shell_dir {
+        // Files left by an earlier run would be read back as fresh output.
+        let _ = fs::remove_dir_all(dir);
+    }
+    let mut driver = Driver {
+        primary: BufferedPrimaryIo::from_text(&source),
+        format_primary: None,
+        mem: MemoryIo::new(true),
+        bundle,
+        formats: FormatCache::new(digest, formats_dir(cache_path)),
+        preamble_format: None,
+        project: options
+            .project_dir
+            .as_ref()
+            .map(|root| Project::new(root, options)),
+        bibtex_input: None,
+        index_style: options.index_style.clone(),
+        index_records: Vec::new(),
+        trusted: options.trusted,
+        font_dirs: program::font_dirs(cache_path),
+        program,
+        source_date_epoch: reproducible::source_date_epoch(options),
+        draft: options.draft.map(|d| d.setup()),
+        shell_dir,
+        denied: Vec::new(),
+        monitor,
+    };
+
+    driver.ensure_format(&mut status)?;
+
+    let preamble = incremental::split_preamble(&source);
+    let digest = digest.to_string();
+    let key = incremental::key(&[
+        digest.as_bytes(),
+        preamble.map_or("", |p| p.text).as_bytes(),
+    ]);
+    if let Some(p) = preamble.filter(Preamble::can_dump) {
+        if driver.ensure_preamble_format(&p, &key, work, &mut status) {
+            driver.primary = BufferedPrimaryIo::from_text(p.padded_body());
+            driver.preamble_format = Some(work.preamble_format(&key));
+        }
+    }
+    let aux = work.load_aux(&key)?;
+    if aux
+        .iter()
+        .any(|(name, _)| *name == format!("{JOB_NAME}.bbl"))
+    {
+        driver.bibtex_input = work.bibtex_input(&key);
+    }
+    driver.index_records = work.index_records(&key);
+    driver
+        .index_records
+        .retain(|r| aux.iter().any(|(name, _)| *name == r.output));
+    for (name, data) in aux {
+        driver.mem.files.borrow_mut().insert(
+            name,
+            MemoryFileInfo {
+                data,
+                unix_mtime: None,
+            },
+        );
+    }
+
+    // TeX and xdvipdfmx failures still come back as a `Typeset` so callers get the log.
+    let run = driver.run_passes(&mut status);
+    if matches!(run, Err(Error::Cancelled)) {
+        return Err(Error::Cancelled);
+    }
+    let log = driver
+        .file(&format!("{JOB_NAME}.log"))
+        .map(|b| String::from_utf8_lossy(&b).into_owned())
+        .unwrap_or_default();
+    let mut pdf = driver.file(&format!("{JOB_NAME}.pdf"));
+    if let (Some(pdf), Some(_)) = (&mut pdf, driver.source_date_epoch) {
+        reproducible::stabilize_id(pdf);
+    }
+    let failure = match (run, &pdf) {
+        (Err(e), _) => Some(e.to_string()),
+        (Ok(()), None) => Some("xdvipdfmx produced no PDF".to_string()),
+        (Ok(()), Some(_)) => None,
+    };
+    // Aux files from a failed run may be half-written, and a draft's stop
+    // before `\end{document}`; keep the last good ones.
+    if failure.is_none() {
+        if let Some(synctex) = driver.file(&format!("{JOB_NAME}.synctex.gz")) {
+            work.store_synctex(&synctex)?;
+        }
+    }
+    if failure.is_none() && driver.draft.is_none() {
+        work.store_aux(&key, &driver.aux_files())?;
+        if let Some(input) = &driver.bibtex_input {
+            work.store_bibtex_input(&key, input)?;
+        }
+        work.store_index_records(&key, &driver.index_records)?;
+    }
+    let bibtex_log = driver
+        .bibtex_input
+        .as_ref()
+        .and_then(|_| driver.file(&format!("{JOB_NAME}.blg")))
+        .map(|b| String::from_utf8_lossy(&b).into_owned());
+    let index_logs = driver
+        .index_records
+        .iter()
+        .filter_map(|r| driver.file(&r.log))
+        .map(|b| String::from_utf8_lossy(&b).into_owned())
+        .collect();
+    Ok(Typeset {
+        pdf,
+        log,
+        failure,
+        bibtex_log,
+        index_logs,
+        denied: driver.denied,
+    })
+}
+
+/// An installed `bundle/` wins over a side-loaded `bundle.zip`.
+fn open_bundle(cache_path: &Path) -> Result<Box<dyn Bundle>> {
+    let dir = bundle_dir_path(cache_path);
+    if dir.is_dir() {
+        return Ok(Box::new(DirBundle::new(&dir)));
+    }
+    let zip = bundle_zip_path(cache_path);
+    if zip.is_file() {
+        let bundle = ZipBundle::open(&zip).map_err(|e| Error::Engine {
+            engine: "bundle",
+            message: format!("{}: {e}", zip.display()),
+        })?;
+        return Ok(Box::new(bundle));
+    }
+    Err(Error::BundleMissing(cache_path.to_path_buf()))
+}
+
+fn engine_error(engine: &'static str, err: &tectonic_errors::Error, status: &Collector) -> Error {
+    let mut message = format!("{err:#}");
+    if let Some(last) = status.messages.last() {
+        message.push('\n');
+        message.push_str(last);
+    }
+    Error::Engine { engine, message }
+}
+
+struct Driver<'m> {
+    primary: BufferedPrimaryIo,
+    /// Set while dumping the format: replaces the primary input with `\input tectonic-format-*.tex`.
+    format_primary: Option<BufferedPrimaryIo>,
+    mem: MemoryIo,
+    bundle: Box<dyn Bundle>,
+    formats: FormatCache,
+    /// Dumped preamble for this document; TeX then only reads the body.
+    preamble_format: Option<PathBuf>,
+    /// Files on disk TeX may read, before the bundle.
+    project: Option<Project>,
+    /// [`Driver::bibtex_key`] of the inputs that produced the current `.bbl`.
+    bibtex_input: Option<String>,
+    /// `.ist` for `.idx` files, from [`CompileOptions::index_style`].
+    index_style: Option<String>,
+    /// makeindex runs behind the current index outputs.
+    index_records: Vec<IndexRecord>,
+    /// [`CompileOptions::trusted`]: reads may leave the project.
+    trusted: bool,
+    /// Where an untrusted compile may read fonts by absolute path.
+    font_dirs: Vec<PathBuf>,
+    program: Program,
+    /// Set for a reproducible compile; see [`crate::reproducible`].
+    source_date_epoch: Option<u64>,
+    /// [`Draft::setup`] of a draft compile.
+    draft: Option<String>,
+    /// Where shell escape runs; only set for a trusted project.
+    shell_dir: Option<PathBuf>,
+    denied: Vec<Denial>,
+    monitor: &'m Monitor<'m>,
+}
+
+impl Driver<'_> {
+    fn security(&self) -> SecuritySettings {
+        SecuritySettings::new(if self.shell_dir.is_some() {
+            SecurityStance::MaybeAllowInsecures
+        } else {
+            SecurityStance::DisableInsecures
+        })
+    }
+
+    fn deny(&mut self, access: Access, target: &str) {
+        let denial = Denial {
+            access,
+            target: target.to_string(),
+        };
+        if !self.denied.contains(&denial) {
+            self.denied.push(denial);
+        }
+    }
+
+    /// A name that is absolute or climbs out with `..`. Only font files in the
+    /// font dirs, or anything in a trusted project, are read; the rest is denied.
+    fn open_outside(&mut self, name: &str) -> OpenResult<InputHandle> {
+        let path = if self.trusted {
+            match &self.project {
+                Some(project) => project.base.join(name),
+                None => PathBuf::from(name),
+            }
+        } else {
+            match sandbox::font_path(name, &self.font_dirs) {
+                Some(path) => path,
+                None => {
+                    self.deny(Access::Read, name);
+                    return OpenResult::NotAvailable;
+                }
+            }
+        };
+        match File::open(path) {
+            Ok(f) => OpenResult::Ok(InputHandle::new_read_only(
+                name,
+                BufReader::new(f),
+                InputOrigin::Filesystem,
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => OpenResult::NotAvailable,
+            Err(e) => OpenResult::Err(e.into()),
+        }
+    }
+
+    /// Runs a `\write18` command of a trusted project in the shell dir, with
+    /// the in-memory files mirrored there; files it writes are read back.
+    fn run_shell(&self, command: &str, dir: &Path) -> io::Result<bool> {
+        for (name, file) in self.mem.files.borrow().iter() {
+            if !incremental::is_confined(name) {
+                continue;
+            }
+            let path = dir.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, &file.data)?;
+        }
+        fs::create_dir_all(dir)?;
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir)
+            .status()?;
+        let mut files = self.mem.files.borrow_mut();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let data = fs::read(entry.path())?;
+            if files.get(&name).map(|f| &f.data) != Some(&data) {
+                files.insert(
+                    name,
+                    MemoryFileInfo {
+                        data,
+                        unix_mtime: None,
+                    },
+                );
+            }
+        }
+        Ok(status.success())
+    }
+
+    /// Engine failure, or [`Error::Cancelled`] / [`Error::LimitExceeded`] if that
+    /// is why the engine stopped.
+    fn fail(
+        &self,
+        engine: &'static str,
+        err: &tectonic_errors::Error,
+        status: &Collector,
+    ) -> Error {
+        self.monitor
+            .check()
+            .err()
+            .unwrap_or_else(|| engine_error(engine, err, status))
+    }
+
+    /// `Err` from an I/O callback that makes the engine abort after a cancel or
+    /// once a limit is exceeded.
+    fn refuse_if_cancelled<T>(&self) -> Option<OpenResult<T>> {
+        let stop = self.monitor.check().err()?;
+        Some(OpenResult::Err(tectonic_errors::anyhow::anyhow!("{stop}")))
+    }
+
+    fn file(&self, name: &str) -> Option<Vec<u8>> {
+        self.mem.files.borrow().get(name).map(|f| f.data.clone())
+    }
+
+    /// Aux-type files currently in memory, sorted by name.
+    fn aux_files(&self) -> Vec<(String, Vec<u8>)> {
+        let mut files: Vec<_> = self
+            .mem
+            .files
+            .borrow()
+            .iter()
+            .filter(|(name, _)| incremental::is_aux_file(name))
+            .map(|(name, f)| (name.clone(), f.data.clone()))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn project_file(&self, name: &str) -> Option<PathBuf> {
+        self.project.as_ref()?.file(name)
+    }
+
+    /// True if every project file the preamble format was dumped from is unchanged.
+    fn preamble_inputs_current(&self, inputs: &[(String, String)]) -> bool {
+        inputs.iter().all(|(name, hash)| {
+            self.project_file(name)
+                .and_then(|p| std::fs::read(p).ok())
+                .is_some_and(|data| incremental::key(&[&data]) == *hash)
+        })
+    }
+
+    /// Hash of everything BibTeX's output depends on: the `\citation`,
+    /// `\bibstyle` and `\bibdata` lines of the `.aux` files and the databases
+    /// they name. `None` when the document has no `\bibdata`. Bundle files are
+    /// not hashed; the aux files are already keyed by the bundle digest.
+    fn bibtex_key(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        let mut databases = Vec::new();
+        for (name, data) in self.aux_files() {
+            if !name.ends_with(".aux") {
+                continue;
+            }
+            for line in String::from_utf8_lossy(&data).lines() {
+                if let Some(list) = line.strip_prefix("\\bibdata{") {
+                    let list = list.trim_end().trim_end_matches('}');
+                    databases.extend(list.split(',').map(|d| format!("{}.bib", d.trim())));
+                } else if !line.starts_with("\\citation{") && !line.starts_with("\\bibstyle{") {
+                    continue;
+                }
+                lines.push(line.to_string());
+            }
+        }
+        if databases.is_empty() {
+            return None;
+        }
+        let contents: Vec<Vec<u8>> = databases
+            .iter()
+            .map(|db| {
+                self.file(db)
+                    .or_else(|| self.project_file(db).and_then(|p| std::fs::read(p).ok()))
+                    .unwrap_or_default()
+            })
+            .collect();
+        let lines = lines.join("\n");
+        let mut parts: Vec<&[u8]> = vec![lines.as_bytes()];
+        parts.extend(contents.iter().map(Vec::as_slice));
+        Some(incremental::key(&parts))
+    }
+
+    /// Runs BibTeX if the bibliography inputs changed since the current `.bbl`
+    /// was written. The new `.bbl` changes the aux snapshot, which makes
+    /// [`Driver::run_passes`] run TeX again to pick it up.
+    fn bibliography_pass(&mut self, status: &mut Collector) -> Result<()> {
+        let Some(key) = self.bibtex_key() else {
+            return Ok(());
+        };
+        if self.bibtex_input.as_ref() == Some(&key) {
+            return Ok(());
+        }
+        self.monitor.check()?;
+        self.monitor.stage(Stage::Bibliography, 0);
+        let result = {
+            let security = self.security();
+            let mut launcher = CoreBridgeLauncher::new_with_security(self, status, security);
+            BibtexEngine::new().process(
+                &mut launcher,
+                &format!("{JOB_NAME}.aux"),
+                &UnstableOptions::default(),
+            )
+        };
+        // Database and style errors are in the `.blg`; TeX still runs and
+        // reports the citations that stayed undefined.
+        result.map_err(|e| self.fail("BibTeX", &e, status))?;
+        self.bibtex_input = Some(key);
+        Ok(())
+    }
+
+    /// What makeindex has to sort after this pass: every `.idx` (`\\makeindex`,
+    /// imakeidx's named indexes), the glossaries `\\@newglossary` declares in
+    /// the `.aux`, and nomencl's `.nlo`. Inputs TeX did not write are skipped.
+    fn index_runs(&self) -> Vec<IndexRun> {
+        let mut runs = Vec::new();
+        let names: Vec<String> = self.mem.files.borrow().keys().cloned().collect();
+        for name in &names {
+            if let Some(stem) = name.strip_suffix(".idx") {
+                runs.push(IndexRun {
+                    input: name.clone(),
+                    output: format!("{stem}.ind"),
+                    log: format!("{stem}.ilg"),
+                    style: self.index_style.clone(),
+                    order: KeyOrder::Word,
+                });
+            }
+        }
+
+        let aux = self
+            .file(&format!("{JOB_NAME}.aux"))
+            .map(|b| String::from_utf8_lossy(&b).into_owned())
+            .unwrap_or_default();
+        let arg = |line: &str, cmd: &str| -> Option<String> {
+            let rest = line.strip_prefix(cmd)?.strip_prefix('{')?;
+            Some(rest[..rest.find('}')?].trim_matches('"').to_string())
+        };
+        let style = aux.lines().find_map(|l| arg(l, "\\@istfilename"));
+        let order = match aux.lines().find_map(|l| arg(l, "\\@glsorder")).as_deref() {
+            Some("letter") => KeyOrder::Letter,
+            _ => KeyOrder::Word,
+        };
+        for line in aux.lines() {
+            // `\@newglossary{main}{glg}{gls}{glo}`: log, output and input extensions.
+            let Some(rest) = line.strip_prefix("\\@newglossary") else {
+                continue;
+            };
+            let fields: Vec<&str> = rest
+                .split('}')
+                .filter_map(|f| f.trim().strip_prefix('{'))
+                .collect();
+            if let [_, log, out, input, ..] = fields[..] {
+                runs.push(IndexRun {
+                    input: format!("{JOB_NAME}.{input}"),
+                    output: format!("{JOB_NAME}.{out}"),
+                    log: format!("{JOB_NAME}.{log}"),
+                    style: style.clone(),
+                    order,
+                });
+            }
+        }
+
+        let nomenclature = format!("{JOB_NAME}.nlo");
+        if names.contains(&nomenclature) {
+            runs.push(IndexRun {
+                input: nomenclature,
+                output: format!("{JOB_NAME}.nls"),
+                log: format!("{JOB_NAME}.nlg"),
+                style: Some(NOMENCL_STYLE.to_string()),
+                order: KeyOrder::Word,
+            });
+        }
+        runs.retain(|r| names.contains(&r.input));
+        runs
+    }
+
+    /// Contents of a support file as TeX would find it: memory, project dir, bundle.
+    fn read_input(&mut self, name: &str, status: &mut Collector) -> Option<Vec<u8>> {
+        let OpenResult::Ok(mut handle) = self.input_open_name(name, status) else {
+            return None;
+        };
+        let mut data = Vec::new();
+        handle.read_to_end(&mut data).ok()?;
+        Some(data)
+    }
+
+    /// Sorts every index whose raw entries or style changed since its output
+    /// was made. Like BibTeX's `.bbl`, a new output shows up in the aux
+    /// snapshot and [`Driver::run_passes`] runs TeX again.
+    fn index_pass(&mut self, status: &mut Collector) -> Result<()> {
+        let runs = self.index_runs();
+        // Indexes the document no longer makes stop being reported.
+        self.index_records
+            .retain(|r| runs.iter().any(|run| run.output == r.output));
+        for run in runs {
+            let Some(input) = self.file(&run.input) else {
+                continue;
+            };
+            let style = run
+                .style
+                .as_ref()
+                .map(|name| (name.clone(), self.read_input(name, status)));
+            let order = [run.order as u8];
+            let key = incremental::key(&[
+                &input,
+                style.as_ref().map_or(&b""[..], |(n, _)| n.as_bytes()),
+                style
+                    .as_ref()
+                    .and_then(|(_, d)| d.as_deref())
+                    .unwrap_or_default(),
+                &order,
+            ]);
+            let current = self
+                .index_records
+                .iter()
+                .any(|r| r.output == run.output && r.input == key);
+            if current && self.file(&run.output).is_some() {
+                continue;
+            }
+
+            self.monitor.check()?;
+            self.monitor.stage(Stage::Index, 0);
+            let (index, log) = sort_index(&run, &input, style.as_ref());
+            let mut files = self.mem.files.borrow_mut();
+            for (name, data) in [(&run.output, index), (&run.log, log)] {
+                files.insert(
+                    name.clone(),
+                    MemoryFileInfo {
+                        data: data.into_bytes(),
+                        unix_mtime: None,
+                    },
+                );
+            }
+            drop(files);
+            self.index_records.retain(|r| r.output != run.output);
+            self.index_records.push(IndexRecord {
+                output: run.output,
+                log: run.log,
+                input: key,
+            });
+        }
+        Ok(())
+    }
+
+    /// Dumps `latex.fmt` into the format cache unless one for this bundle already exists.
+    fn ensure_format(&mut self, status: &mut Collector) -> Result<()> {
+        if let OpenResult::Ok(_) = self.formats.input_open_format(FORMAT_NAME, status) {
+            return Ok(());
+        }
+
+        self.monitor.stage(Stage::Format, 0);
+        self.monitor.pause_limits();
+        self.format_primary = Some(BufferedPrimaryIo::from_text(format!(
+            "\\input tectonic-format-{FORMAT_NAME}.tex"
+        )));
+        let result = {
+            let security = self.security();
+            let mut launcher = CoreBridgeLauncher::new_with_security(self, status, security);
+            TexEngine::default()
+                .halt_on_error_mode(true)
+                .initex_mode(true)
+                .process(&mut launcher, "UNUSED.fmt", JOB_NAME)
+        };
+        self.format_primary = None;
+        match result {
+            Ok(TexOutcome::Errors) => {
+                return Err(Error::Engine {
+                    engine: "XeTeX",
+                    message: "errors while dumping the format".into(),
+                })
+            }
+            Ok(_) => {}
+            Err(e) => return Err(self.fail("XeTeX", &e, status)),
+        }
+
+        let dumped = self
+            .mem
+            .files
+            .borrow()
+            .iter()
+            .find(|(name, _)| name.ends_with(".fmt"))
+            .map(|(_, f)| f.data.clone());
+        let data = dumped.ok_or_else(|| Error::Engine {
+            engine: "XeTeX",
+            message: "format dump produced no .fmt".into(),
+        })?;
+        self.formats
+            .write_format(FORMAT_NAME, &data, status)
+            .map_err(|e| self.fail("XeTeX", &e, status))?;
+        self.mem.files.borrow_mut().clear();
+        self.monitor.restart_limits();
+        Ok(())
+    }
+
+    /// Dumps a format holding `preamble` on top of `latex.fmt`'s sources. Returns
+    /// false (and remembers it) when the preamble cannot be dumped; the caller
+    /// then compiles the whole source against the plain format.
+    ///
+    /// LaTeX's format file ends in `\dump`; the primary input redefines `\dump`
+    /// first, so at that point TeX reads the preamble and then dumps for real.
+    fn ensure_preamble_format(
+        &mut self,
+        preamble: &Preamble,
+        key: &str,
+        work: &WorkDir,
+        status: &mut Collector,
+    ) -> bool {
+        if work.preamble_format(key).is_file()
+            && self.preamble_inputs_current(&work.preamble_inputs(key))
+        {
+            return true;
+        }
+        if work.preamble_failed(key) || self.monitor.stopped() {
+            return false;
+        }
+
+        self.monitor.stage(Stage::Format, 0);
+        if let Some(project) = &mut self.project {
+            project.reads = Some(Vec::new());
+        }
+        self.mem.files.borrow_mut().insert(
+            PREAMBLE_INPUT.to_string(),
+            MemoryFileInfo {
+                data: format!("{}\n\\livelatexdump\n", preamble.text).into_bytes(),
+                unix_mtime: None,
+            },
+        );
+        self.format_primary = Some(BufferedPrimaryIo::from_text(format!(
+            "\\catcode`\\{{=1 \\catcode`\\}}=2 \\let\\livelatexdump\\dump \
+             \\def\\dump{{\\input {PREAMBLE_INPUT} }}\n\
+             \\input tectonic-format-{FORMAT_NAME}.tex\n"
+        )));
+        let result = {
+            let security = self.security();
+            let mut launcher = CoreBridgeLauncher::new_with_security(self, status, security);
+            TexEngine::default()
+                .halt_on_error_mode(true)
+                .initex_mode(true)
+                .process(&mut launcher, "UNUSED.fmt", JOB_NAME)
+        };
+        self.format_primary = None;
+        let dumped = self
+            .mem
+            .files
+            .borrow()
+            .iter()
+            .find(|(name, _)| name.ends_with(".fmt"))
+            .map(|(_, f)| f.data.clone());
+        self.mem.files.borrow_mut().clear();
+        let inputs: Vec<(String, String)> = self
+            .project
+            .as_mut()
+            .and_then(|p| p.reads.take())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(name, path)| {
+                let data = std::fs::read(path).ok()?;
+                Some((name, incremental::key(&[&data])))
+            })
+            .collect();
+
+        let stored = match (result, dumped) {
+            (Ok(TexOutcome::Spotless | TexOutcome::Warnings), Some(data)) => {
+                work.store_preamble_format(key, &data, &inputs).is_ok()
+            }
+            _ => false,
+        };
+        // A cancelled or stopped dump says nothing about the preamble; try again next time.
+        if !stored && !self.monitor.stopped() {
+            let _ = work.mark_preamble_failed(key);
+        }
+        stored
+    }
+
+    /// Reruns TeX until the aux files settle. With aux files preloaded from the
+    /// previous compile, an edit that moves no label or heading stops after one pass.
+    /// A draft always stops there.
+    fn run_passes(&mut self, status: &mut Collector) -> Result<()> {
+        let build_date = reproducible::build_date(self.source_date_epoch);
+        let mut previous = self.aux_files();
+        for pass in 1..=MAX_TEX_PASSES {
+            self.monitor.check()?;
+            self.monitor.stage(Stage::Tex, pass as u32);
+            self.tex_pass(build_date, status)?;
+            if let Some(pages) = self
+                .file(&format!("{JOB_NAME}.log"))
+                .and_then(|l| output_pages(&l))
+            {
+                self.monitor.pages(pages);
+            }
+            if self.draft.is_some() {
+                break;
+            }
+            self.bibliography_pass(status)?;
+            self.index_pass(status)?;
+            let current = self.aux_files();
+            if current == previous {
+                break;
+            }
+            previous = current;
+        }
+        self.monitor.check()?;
+        self.monitor.stage(Stage::Pdf, 0);
+        self.xdvipdfmx_pass(build_date, status)
+    }
+
+    fn tex_pass(&mut self, build_date: SystemTime, status: &mut Collector) -> Result<()> {
+        let format = match self.preamble_format {
+            Some(_) => PREAMBLE_FORMAT.to_string(),
+            None => format!("{FORMAT_NAME}.fmt"),
+        };
+        let result = {
+            let security = self.security();
+            let mut launcher = CoreBridgeLauncher::new_with_security(self, status, security);
+            TexEngine::default()
+                .halt_on_error_mode(true)
+                .shell_escape(self.shell_dir.is_some())
+                .synctex(true)
+                .build_date(build_date)
+                .process(&mut launcher, &format, INPUT_NAME)
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(self.fail("XeTeX", &e, status)),
+        }
+    }
+
+    fn xdvipdfmx_pass(&mut self, build_date: SystemTime, status: &mut Collector) -> Result<()> {
+        let result = {
+            let security = self.security();
+            let mut launcher = CoreBridgeLauncher::new_with_security(self, status, security);
+            XdvipdfmxEngine::default()
+                .build_date(build_date)
+                .enable_deterministic_tags(self.source_date_epoch.is_some())
+                .enable_compression(self.draft.is_none())
+                .process(
+                    &mut launcher,
+                    &format!("{JOB_NAME}.xdv"),
+                    &format!("{JOB_NAME}.pdf"),
+                )
+        };
+        result.map_err(|e| self.fail("xdvipdfmx", &e, status))
+    }
+}
+
+/// The document's folder on disk. Without a main file only bibliography and
+/// index support files are read from it; a multi-file project exposes everything.
+struct Project {
+    root: PathBuf,
+    /// Folder of the main file: relative names resolve here first, as they
+    /// would with TeX run from there.
+    base: PathBuf,
+    whole: bool,
+    /// Untrusted: a file whose symlinks lead out of [`Project::root`] is not read.
+    confined: bool,
+    /// Files read while dumping a preamble format, as (requested name, path).
+    reads: Option<Vec<(String, PathBuf)>>,
+}
+
+impl Project {
+    fn new(root: &Path, options: &CompileOptions) -> Self {
+        let base = options
+            .main_file
+            .as_deref()
+            .filter(|m| incremental::is_confined(m))
+            .and_then(|m| Path::new(m).parent())
+            .map_or_else(|| root.to_path_buf(), |dir| root.join(dir));
+        Project {
+            root: root.to_path_buf(),
+            base,
+            whole: options.main_file.is_some(),
+            confined: !options.trusted,
+            reads: None,
+        }
+    }
+
+    /// `name` on disk, if TeX may read it. Only relative names without `..`,
+    /// and in an untrusted project only files that are inside it once symlinks
+    /// are resolved, so nothing outside the project is reachable.
+    fn file(&self, name: &str) -> Option<PathBuf> {
+        let path = self.locate(name)?;
+        if !self.confined {
+            return Some(path);
+        }
+        sandbox::resolve_within(&path, std::slice::from_ref(&self.root))
+    }
+
+    /// The project file `name` stands for, wherever its symlinks lead.
+    fn locate(&self, name: &str) -> Option<PathBuf> {
+        if !incremental::is_confined(name) {
+            return None;
+        }
+        let allowed = self.whole
+            || Path::new(name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| PROJECT_EXTENSIONS.contains(&e));
+        if !allowed {
+            return None;
+        }
+        [&self.base, &self.root]
+            .into_iter()
+            .map(|dir| dir.join(name))
+            .find(|p| p.is_file())
+    }
+}
+
+/// One makeindex invocation, as `makeindex -s <style> -t <log> -o <output> <input>`.
+struct IndexRun {
+    input: String,
+    output: String,
+    log: String,
+    style: Option<String>,
+    order: KeyOrder,
+}
+
+/// Output and transcript of one index run. A missing or xindy style only
+/// yields a transcript saying so (and an empty index).
+fn sort_index(
+    run: &IndexRun,
+    input: &[u8],
+    style: Option<&(String, Option<Vec<u8>>)>,
+) -> (String, String) {
+    let style_error = |name: &str, message: &str| {
+        let log = format!("** Input style error (file = {name}, line = 0):\n   -- {message}\n");
+        (String::new(), log)
+    };
+    let style = match style {
+        Some((name, _)) if name.ends_with(".xdy") => {
+            return style_error(
+                name,
+                "xindy styles are not supported; load glossaries without the xindy option.",
+            )
+        }
+        Some((name, None)) => return style_error(name, &format!("Style file {name} not found.")),
+        Some((name, Some(data))) => Some((name.as_str(), String::from_utf8_lossy(data))),
+        None => None,
+    };
+    let input = String::from_utf8_lossy(input);
+    let output = makeindex::run(&makeindex::Job {
+        input_name: &run.input,
+        input: &input,
+        output_name: &run.output,
+        log_name: &run.log,
+        style: style.as_ref().map(|(name, text)| (*name, text.as_ref())),
+        order: run.order,
+    });
+    (output.index, output.log)
+}
+
+/// Returns from the enclosing function unless the provider reported `NotAvailable`.
+macro_rules! try_provider {
+    ($e:expr) => {
+        match $e {
+            OpenResult::NotAvailable => {}
+            r => return r,
+        }
+    };
+}
+
+impl IoProvider for Driver<'_> {
+    fn output_open_name(&mut self, name: &str) -> OpenResult<OutputHandle> {
+        if let Some(refused) = self.refuse_if_cancelled() {
+            return refused;
+        }
+        if !incremental::is_confined(name) {
+            self.deny(Access::Write, name);
+            return OpenResult::NotAvailable;
+        }
+        match self.mem.output_open_name(name) {
+            OpenResult::Ok(handle) => OpenResult::Ok(OutputHandle::new(
+                name,
+                Tripped {
+                    inner: handle,
+                    tripwire: self.monitor.tripwire(),
+                },
+            )),
+            other => other,
+        }
+    }
+
+    fn output_open_stdout(&mut self) -> OpenResult<OutputHandle> {
+        self.mem.output_open_stdout()
+    }
+
+    fn input_open_name(
+        &mut self,
+        name: &str,
+        status: &mut dyn StatusBackend,
+    ) -> OpenResult<InputHandle> {
+        if let Some(refused) = self.refuse_if_cancelled() {
+            return refused;
+        }
+        self.monitor.file(name);
+        try_provider!(self.mem.input_open_name(name, status));
+        let generated = match name {
+            PDFTEX_COMPAT_INPUT => self.program.prelude().map(str::to_string),
+            DRAFT_INPUT => self.draft.clone(),
+            _ => None,
+        };
+        if let Some(text) = generated {
+            return OpenResult::Ok(InputHandle::new_read_only(
+                name,
+                Cursor::new(text.into_bytes()),
+                InputOrigin::Other,
+            ));
+        }
+        if !incremental::is_confined(name) {
+            return self.open_outside(name);
+        }
+        if let Some(path) = self.project_file(name) {
+            if let Some(reads) = self.project.as_mut().and_then(|p| p.reads.as_mut()) {
+                reads.push((name.to_string(), path.clone()));
+            }
+            return match File::open(path) {
+                Ok(f) => OpenResult::Ok(InputHandle::new_read_only(
+                    name,
+                    BufReader::new(f),
+                    InputOrigin::Filesystem,
+                )),
+                Err(e) => OpenResult::Err(e.into()),
+            };
+        }
+        if self.project.as_ref().and_then(|p| p.locate(name)).is_some() {
+            // A symlink out of an untrusted project.
+            self.deny(Access::Read, name);
+            return OpenResult::NotAvailable;
+        }
+        try_provider!(self.bundle.input_open_name(name, status));
+        match program::system_font(name).filter(|_| self.program == Program::Xetex) {
+            Some(path) => match File::open(path) {
+                Ok(f) => OpenResult::Ok(InputHandle::new_read_only(
+                    name,
+                    BufReader::new(f),
+                    InputOrigin::Filesystem,
+                )),
+                Err(e) => OpenResult::Err(e.into()),
+            },
+            None => OpenResult::NotAvailable,
+        }
+    }
+
+    fn input_open_primary(&mut self, status: &mut dyn StatusBackend) -> OpenResult<InputHandle> {
+        match self.format_primary {
+            Some(ref mut p) => p.input_open_primary(status),
+            None => self.primary.input_open_primary(status),
+        }
+    }
+
+    fn input_open_format(
+        &mut self,
+        name: &str,
+        status: &mut dyn StatusBackend,
+    ) -> OpenResult<InputHandle> {
+        if let (PREAMBLE_FORMAT, Some(path)) = (name, &self.preamble_format) {
+            return match File::open(path) {
+                Ok(f) => OpenResult::Ok(InputHandle::new_read_only(
+                    name,
+                    BufReader::new(f),
+                    InputOrigin::Other,
+                )),
+                Err(e) => OpenResult::Err(e.into()),
+            };
+        }
+        self.formats.input_open_format(name, status)
+    }
+}
+
+impl DriverHooks for Driver<'_> {
+    fn io(&mut self) -> &mut dyn IoProvider {
+        self
+    }
+
+    fn sysrq_shell_escape(
+        &mut self,
+        command: &str,
+        status: &mut dyn StatusBackend,
+    ) -> std::result::Result<(), SystemRequestError> {
+        let Some(dir) = self.shell_dir.clone() else {
+            self.deny(Access::Shell, command);
+            return Err(SystemRequestError::NotAllowed);
+        };
+        match self.run_shell(command, &dir) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SystemRequestError::Failed),
+            Err(e) => {
+                status.report(
+                    MessageKind::Warning,
+                    format_args!("shell escape `{command}' failed: {e}"),
+                    None,
+                );
+                Err(SystemRequestError::Failed)
+            }
+        }
+    }
+}
+
+/// An engine output whose writes fail once the compile is cancelled or out of
+/// time. XeTeX aborts when a page cannot be written to the XDV, so even a loop
+/// that opens no file stops at its next shipout.
+struct Tripped {
+    inner: OutputHandle,
+    tripwire: Tripwire,
+}
+
+impl Write for Tripped {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.tripwire.tripped() {
+            return Err(io::Error::other("compile stopped"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `Output written on texput.xdv (12 pages, 3456 bytes).` → 12.
+fn output_pages(log: &[u8]) -> Option<u32> {
+    let log = String::from_utf8_lossy(log);
+    let line = log
+        .lines()
+        .rev()
+        .find(|l| l.starts_with("Output written on "))?;
+    let rest = &line[line.rfind('(')? + 1..];
+    rest.split(' ').next()?.parse().ok()
+}
+
+/// Keeps engine status messages so failures can carry more than "engine aborted".
+#[derive(Default)]
+struct Collector {
+    messages: Vec<String>,
+}
+
+impl StatusBackend for Collector {
+    fn report(&mut self, kind: MessageKind, args: Arguments, err: Option<&tectonic_errors::Error>) {
+        let prefix = match kind {
+            MessageKind::Note => "note",
+            MessageKind::Warning => "warning",
+            MessageKind::Error => "error",
+        };
+        let mut line = format!("{prefix}: {args}");
+        if let Some(e) = err {
+            line.push_str(&format!(" ({e:#})"));
+        }
+        self.messages.push(line);
+    }
+
+    fn dump_error_logs(&mut self, output: &[u8]) {
+        self.messages
+            .push(String::from_utf8_lossy(output).into_owned());
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use crate::limits::Exceeded;
+
+/// Errors surfaced by rust_core. The JNI layer turns these into strings.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JNI error: {0}")]
+    Jni(#[from] jni::errors::Error),
+
+    #[error("native code panicked")]
+    Panic,
+
+    #[error("invalid compile options: {0}")]
+    InvalidOptions(#[from] serde_json::Error),
+
+    #[error("{} is not inside the project", .0.display())]
+    OutsideProject(PathBuf),
+
+    #[error("compile cancelled")]
+    Cancelled,
+
+    /// A [`crate::limits::Limits`] value was reached; the engine was stopped.
+    #[error("compile stopped: {0} exceeded")]
+    LimitExceeded(Exceeded),
+
+    /// Built without the `tectonic` feature (e.g. a plain host build).
+    #[error("this build of rust_core has no TeX engine")]
+    EngineUnavailable,
+
+    #[error("no TeX bundle found under {}", .0.display())]
+    BundleMissing(PathBuf),
+
+    #[error("cannot read bundle archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    /// Bad manifest, hash mismatch, unknown package, …
+    #[error("invalid TeX bundle: {0}")]
+    InvalidBundle(String),
+
+    /// Unreadable file structure, no such page, page too large to render, …
+    #[error("cannot read PDF: {0}")]
+    InvalidPdf(String),
+
+    #[error("{engine} failed: {message}")]
+    Engine {
+        engine: &'static str,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,526 @@
+//! JNI exports for `com.omariskandarani.livelatexapp.LatexCompiler`.
+//!
+//! `LatexCompiler` is a Kotlin `object`, so every export receives the singleton
+//! instance as its second argument. Exports never unwind into the JVM: panics are
+//! caught and reported like any other failure.
+//!
+//! Compile jobs call back into Kotlin from their own thread through
+//! [`JniListener`], which keeps the listener alive with a global reference.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use jni::objects::{GlobalRef, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jboolean, jbyteArray, jdouble, jint, jlong, jstring, JNI_FALSE, JNI_TRUE};
+use jni::{JNIEnv, JavaVM};
+use serde::Serialize;
+
+use crate::bundle;
+use crate::job::{self, Listener, Progress};
+use crate::pdf;
+use crate::synctex::SyncTex;
+use crate::texlog;
+use crate::tikz;
+use crate::wordcount::{self, WordCountOptions};
+use crate::{
+    compile_detailed, compile_pdf, compile_project, compile_with, CompileOptions, CompileReport,
+    Error, Result,
+};
+
+fn read_string(env: &mut JNIEnv, s: &JString) -> Result<String> {
+    Ok(env.get_string(s)?.into())
+}
+
+/// Serializes `value` into a new Java string; `null` only if the JVM refuses the allocation.
+fn to_json_jstring(env: &mut JNIEnv, value: &impl Serialize) -> jstring {
+    let json = serde_json::to_string(value).unwrap_or_else(|_| "{}".into());
+    env.new_string(json)
+        .map(|s| s.into_raw())
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// `None` for a Java `null`.
+fn read_optional_string(env: &mut JNIEnv, s: &JString) -> Result<Option<String>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    read_string(env, s).map(Some)
+}
+
+fn read_string_array(env: &mut JNIEnv, array: &JObjectArray) -> Result<Vec<String>> {
+    let len = env.get_array_length(array)?;
+    let mut out = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let item = JString::from(env.get_object_array_element(array, i)?);
+        out.push(read_string(env, &item)?);
+    }
+    Ok(out)
+}
+
+/// JSON of the value, or `{"error": "…"}` if `f` failed or panicked.
+fn json_result<T: Serialize>(
+    env: &mut JNIEnv,
+    f: impl FnOnce(&mut JNIEnv) -> Result<T>,
+) -> jstring {
+    match caught(|| f(env)) {
+        Ok(value) => to_json_jstring(env, &value),
+        Err(e) => to_json_jstring(env, &serde_json::json!({ "error": e.to_string() })),
+    }
+}
+
+/// Runs `f`; errors and panics are returned as `Err` (a panic becomes [`Error::Panic`]).
+fn caught<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(Err(Error::Panic))
+}
+
+/// Runs `f`, mapping both errors and panics to `None`.
+fn guarded<T>(f: impl FnOnce() -> Result<T>) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => Some(v),
+        Ok(Err(_)) | Err(_) => None,
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_compilePdf<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    latex_source: JString<'local>,
+    output_path: JString<'local>,
+    cache_path: JString<'local>,
+) -> jboolean {
+    let ok = guarded(|| {
+        let source = read_string(&mut env, &latex_source)?;
+        let output = read_string(&mut env, &output_path)?;
+        let cache = read_string(&mut env, &cache_path)?;
+        compile_pdf(&source, Path::new(&output), Path::new(&cache))
+    });
+    if ok.is_some() {
+        JNI_TRUE
+    } else {
+        JNI_FALSE
+    }
+}
+
+/// JSON [`CompileReport`]: `pdfPath`, `errors`, `warnings`, `log`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_compilePdfDetailed<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    latex_source: JString<'local>,
+    output_path: JString<'local>,
+    cache_path: JString<'local>,
+) -> jstring {
+    let report = caught(|| {
+        let source = read_string(&mut env, &latex_source)?;
+        let output = read_string(&mut env, &output_path)?;
+        let cache = read_string(&mut env, &cache_path)?;
+        compile_detailed(&source, Path::new(&output), Path::new(&cache))
+    })
+    .unwrap_or_else(|e| CompileReport::from_error(&e));
+    to_json_jstring(&mut env, &report)
+}
+
+/// [`compilePdfDetailed`](Java_com_omariskandarani_livelatexapp_LatexCompiler_compilePdfDetailed)
+/// with JSON [`CompileOptions`] (`projectDir`, …).
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_compileWithOptions<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    latex_source: JString<'local>,
+    output_path: JString<'local>,
+    cache_path: JString<'local>,
+    options_json: JString<'local>,
+) -> jstring {
+    let report = caught(|| {
+        let source = read_string(&mut env, &latex_source)?;
+        let output = read_string(&mut env, &output_path)?;
+        let cache = read_string(&mut env, &cache_path)?;
+        let options = CompileOptions::from_json(&read_string(&mut env, &options_json)?)?;
+        compile_with(&source, Path::new(&output), Path::new(&cache), &options)
+    })
+    .unwrap_or_else(|e| CompileReport::from_error(&e));
+    to_json_jstring(&mut env, &report)
+}
+
+/// Compiles `mainFile` of the project at `projectRoot` from disk; JSON
+/// [`CompileReport`] whose diagnostics name the project file they are in.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_compileProject<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    project_root: JString<'local>,
+    main_file: JString<'local>,
+    output_path: JString<'local>,
+    cache_path: JString<'local>,
+    options_json: JString<'local>,
+) -> jstring {
+    let report = caught(|| {
+        let root = read_string(&mut env, &project_root)?;
+        let main = read_string(&mut env, &main_file)?;
+        let output = read_string(&mut env, &output_path)?;
+        let cache = read_string(&mut env, &cache_path)?;
+        let options = CompileOptions::from_json(&read_string(&mut env, &options_json)?)?;
+        compile_project(
+            Path::new(&root),
+            Path::new(&main),
+            Path::new(&output),
+            Path::new(&cache),
+            &options,
+        )
+    })
+    .unwrap_or_else(|e| CompileReport::from_error(&e));
+    to_json_jstring(&mut env, &report)
+}
+
+/// JSON [`bundle::InstallReport`]: `version`, `files`, `bytes`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_installBundle<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    zip_path: JString<'local>,
+    cache_path: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let zip = read_string(env, &zip_path)?;
+        let cache = read_string(env, &cache_path)?;
+        bundle::install(Path::new(&zip), Path::new(&cache))
+    })
+}
+
+/// JSON [`bundle::BundleInfo`]: `version`, `packages`, `bundleBytes`, `formatBytes`, `workBytes`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_listBundle<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    cache_path: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let cache = read_string(env, &cache_path)?;
+        bundle::list(Path::new(&cache))
+    })
+}
+
+/// JSON [`bundle::VerifyReport`]: `ok`, `missing`, `corrupt`, `extra`, ….
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_verifyBundle<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    cache_path: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let cache = read_string(env, &cache_path)?;
+        bundle::verify(Path::new(&cache))
+    })
+}
+
+/// JSON [`bundle::PruneReport`]: `removedFiles`, `freedBytes`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_pruneBundle<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    cache_path: JString<'local>,
+    packages: JObjectArray<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let cache = read_string(env, &cache_path)?;
+        let packages = read_string_array(env, &packages)?;
+        bundle::prune(Path::new(&cache), &packages)
+    })
+}
+
+/// Forwards job events to a Kotlin `NativeCompileListener`.
+struct JniListener {
+    vm: JavaVM,
+    listener: GlobalRef,
+}
+
+impl JniListener {
+    /// Runs `f` on the job thread's JNI env. A Kotlin exception thrown by the
+    /// callback is cleared so it cannot poison the next call.
+    fn with_env(&self, f: impl FnOnce(&mut JNIEnv) -> Result<()>) {
+        let Ok(mut env) = self.vm.attach_current_thread_permanently() else {
+            return;
+        };
+        let _ = f(&mut env);
+        if env.exception_check().unwrap_or(false) {
+            let _ = env.exception_clear();
+        }
+    }
+}
+
+impl Listener for JniListener {
+    fn progress(&self, progress: &Progress) {
+        self.with_env(|env| {
+            let stage = env.new_string(progress.stage.as_str())?;
+            let file = match &progress.file {
+                Some(f) => JObject::from(env.new_string(f)?),
+                None => JObject::null(),
+            };
+            env.call_method(
+                &self.listener,
+                "onProgress",
+                "(Ljava/lang/String;ILjava/lang/String;I)V",
+                &[
+                    JValue::Object(&stage),
+                    JValue::Int(progress.pass as i32),
+                    JValue::Object(&file),
+                    JValue::Int(progress.pages.map_or(-1, |p| p as i32)),
+                ],
+            )?;
+            Ok(())
+        });
+    }
+
+    fn finished(&self, result: Result<CompileReport>) {
+        self.with_env(|env| {
+            let report = match result {
+                Err(Error::Cancelled) => {
+                    env.call_method(&self.listener, "onCancelled", "()V", &[])?;
+                    return Ok(());
+                }
+                Ok(report) => report,
+                Err(e) => CompileReport::from_error(&e),
+            };
+            let json = serde_json::to_string(&report).unwrap_or_else(|_| "{}".into());
+            let json = env.new_string(json)?;
+            env.call_method(
+                &self.listener,
+                "onFinished",
+                "(Ljava/lang/String;)V",
+                &[JValue::Object(&json)],
+            )?;
+            Ok(())
+        });
+    }
+}
+
+/// Starts a background compile with JSON [`CompileOptions`]; events go to
+/// `listener`. Returns the job id.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_startCompile<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    latex_source: JString<'local>,
+    output_path: JString<'local>,
+    cache_path: JString<'local>,
+    options_json: JString<'local>,
+    listener: JObject<'local>,
+) -> jlong {
+    let started = caught(|| {
+        let source = read_string(&mut env, &latex_source)?;
+        let output = read_string(&mut env, &output_path)?;
+        let cache = read_string(&mut env, &cache_path)?;
+        let options = CompileOptions::from_json(&read_string(&mut env, &options_json)?)?;
+        let listener = JniListener {
+            vm: env.get_java_vm()?,
+            listener: env.new_global_ref(listener)?,
+        };
+        Ok(job::start(
+            source,
+            output.into(),
+            cache.into(),
+            options,
+            Box::new(listener),
+        ))
+    });
+    started.map_or(-1, |id| id as jlong)
+}
+
+/// False if the job already finished.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_cancelCompile<'local>(
+    _env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    job_id: jlong,
+) -> jboolean {
+    if job_id >= 0 && job::cancel(job_id as u64) {
+        JNI_TRUE
+    } else {
+        JNI_FALSE
+    }
+}
+
+/// JSON [`crate::synctex::PdfSpot`] for `line` of `file` (`null`: main document),
+/// or `null` when the PDF has no SyncTeX data or nothing was typeset there.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_synctexForward<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    cache_path: JString<'local>,
+    pdf_path: JString<'local>,
+    file: JString<'local>,
+    line: jint,
+    column: jint,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let cache = read_string(env, &cache_path)?;
+        let pdf = read_string(env, &pdf_path)?;
+        let file = read_optional_string(env, &file)?;
+        let Some(synctex) = SyncTex::for_document(Path::new(&cache), Path::new(&pdf))? else {
+            return Ok(None);
+        };
+        let column = u32::try_from(column).ok();
+        Ok(synctex.forward(file.as_deref(), line.max(1) as u32, column))
+    })
+}
+
+/// JSON [`crate::synctex::SourceSpot`] under `(x, y)` (PDF points from the page's
+/// top-left) on 1-based `page`, or `null`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_synctexInverse<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    cache_path: JString<'local>,
+    pdf_path: JString<'local>,
+    page: jint,
+    x: jdouble,
+    y: jdouble,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let cache = read_string(env, &cache_path)?;
+        let pdf = read_string(env, &pdf_path)?;
+        let Some(synctex) = SyncTex::for_document(Path::new(&cache), Path::new(&pdf))? else {
+            return Ok(None);
+        };
+        Ok(u32::try_from(page)
+            .ok()
+            .and_then(|page| synctex.inverse(page, x, y)))
+    })
+}
+
+/// JSON list of [`crate::pdf::PageSize`] (points, `/Rotate` applied) for the PDF
+/// at `pdf_path`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_pdfPageSizes<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    pdf_path: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let pdf = read_string(env, &pdf_path)?;
+        pdf::page_sizes(Path::new(&pdf))
+    })
+}
+
+/// RGBA pixels of 1-based `page` at `dpi`, sized as [`crate::pdf::pixel_size`]
+/// says, or `null` on failure.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_renderPdfPage<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    pdf_path: JString<'local>,
+    page: jint,
+    dpi: jdouble,
+) -> jbyteArray {
+    guarded(|| {
+        let pdf = read_string(&mut env, &pdf_path)?;
+        let page = u32::try_from(page).unwrap_or(0);
+        let bitmap = pdf::render_page(Path::new(&pdf), page, dpi)?;
+        Ok(env.byte_array_from_slice(&bitmap.pixels)?.into_raw())
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// JSON list of [`crate::pdf::TextWord`] on 1-based `page`, boxes in points from
+/// the page's top-left.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_pdfPageText<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    pdf_path: JString<'local>,
+    page: jint,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let pdf = read_string(env, &pdf_path)?;
+        pdf::page_text(Path::new(&pdf), u32::try_from(page).unwrap_or(0))
+    })
+}
+
+/// JSON list of [`crate::pdf::PageText`]: every page's lines with their boxes.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_pdfText<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    pdf_path: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let pdf = read_string(env, &pdf_path)?;
+        pdf::document_text(Path::new(&pdf))
+    })
+}
+
+/// JSON list of [`crate::pdf::SearchHit`] for `query` in the PDF at `pdf_path`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_pdfSearch<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    pdf_path: JString<'local>,
+    query: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let pdf = read_string(env, &pdf_path)?;
+        let query = read_string(env, &query)?;
+        pdf::search(Path::new(&pdf), &query)
+    })
+}
+
+/// JSON [`crate::wordcount::WordCount`] of the PDF at `pdf_path`;
+/// `options_json` is [`WordCountOptions`], blank for defaults.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_pdfWordCount<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    pdf_path: JString<'local>,
+    options_json: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let pdf = read_string(env, &pdf_path)?;
+        let options = WordCountOptions::from_json(&read_string(env, &options_json)?)?;
+        wordcount::count_pdf(Path::new(&pdf), &options)
+    })
+}
+
+/// JSON [`crate::tikz::TikzReport`] for the standalone TikZ document `tex_doc`;
+/// the SVG goes to `<projectDir>/.livelatex-cache/tikz/<sha1>.svg`.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_renderTikzSvg<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    tex_doc: JString<'local>,
+    project_dir: JString<'local>,
+    cache_path: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let doc = read_string(env, &tex_doc)?;
+        let project = read_string(env, &project_dir)?;
+        let cache = read_string(env, &cache_path)?;
+        tikz::render_svg(&doc, Path::new(&project), Path::new(&cache))
+    })
+}
+
+/// JSON [`crate::texlog::LogAnalysis`] of an engine log (`CompileReport.log`);
+/// `mainFile` (nullable) names the primary input, as in the compile options.
+#[no_mangle]
+pub extern "system" fn Java_com_omariskandarani_livelatexapp_LatexCompiler_analyzeTexLog<'local>(
+    mut env: JNIEnv<'local>,
+    _this: JObject<'local>,
+    log: JString<'local>,
+    main_file: JString<'local>,
+) -> jstring {
+    json_result(&mut env, |env| {
+        let log = read_string(env, &log)?;
+        let main = read_optional_string(env, &main_file)?;
+        Ok(texlog::analyze(&log, main.as_deref()))
+    })
+}
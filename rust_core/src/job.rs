@@ -0,0 +1,245 @@
+//! Background compile jobs: start one, follow its progress, cancel it.
+//!
+//! The engines cannot be interrupted mid-instruction, so cancellation is
+//! cooperative: [`Monitor`] is polled on every file the engine opens and between
+//! passes, and its [`Tripwire`] on every write to an engine output. A cancelled
+//! job drops its in-memory files and writes nothing to the output or work
+//! directories. The same polls enforce [`crate::limits`].
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use serde::Serialize;
+
+use crate::compile::{compile_monitored, CompileOptions, CompileReport};
+use crate::limits::{Exceeded, Limits, Meter};
+use crate::{Error, Result};
+
+pub type JobId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Dumping `latex.fmt` or the document's preamble format.
+    Format,
+    Tex,
+    /// BibTeX over the `.aux` citations, between TeX passes.
+    Bibliography,
+    /// makeindex over index, glossary and nomenclature entries, between TeX passes.
+    Index,
+    /// xdvipdfmx turning the XDV into PDF.
+    Pdf,
+}
+
+impl Stage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Format => "format",
+            Stage::Tex => "tex",
+            Stage::Bibliography => "bibliography",
+            Stage::Index => "index",
+            Stage::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub stage: Stage,
+    /// 1-based TeX pass; 0 outside [`Stage::Tex`].
+    pub pass: u32,
+    /// Last file the engine opened.
+    pub file: Option<String>,
+    /// Pages in the last finished pass.
+    pub pages: Option<u32>,
+}
+
+/// Receives a job's progress and outcome, on the job's own thread.
+pub trait Listener: Send {
+    fn progress(&self, progress: &Progress);
+    /// Called exactly once; `Err(Error::Cancelled)` after [`cancel`].
+    fn finished(&self, result: Result<CompileReport>);
+}
+
+/// What the engine driver reports to and polls while it runs.
+pub struct Monitor<'a> {
+    cancel: Option<Arc<AtomicBool>>,
+    listener: Option<&'a dyn Listener>,
+    progress: RefCell<Progress>,
+    meter: Meter,
+}
+
+impl<'a> Monitor<'a> {
+    pub fn new(cancel: Arc<AtomicBool>, listener: &'a dyn Listener) -> Self {
+        Monitor {
+            cancel: Some(cancel),
+            listener: Some(listener),
+            ..Monitor::silent()
+        }
+    }
+
+    /// For synchronous compiles: never cancelled, reports nowhere.
+    pub fn silent() -> Self {
+        Monitor {
+            cancel: None,
+            listener: None,
+            progress: RefCell::new(Progress {
+                stage: Stage::Tex,
+                pass: 0,
+                file: None,
+                pages: None,
+            }),
+            meter: Meter::new(Limits::unlimited()),
+        }
+    }
+
+    /// Stops the compile once it exceeds `limits`. The clock starts now and again
+    /// once the compile gets the engine, so time spent queued does not count.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.meter = Meter::new(limits);
+        self
+    }
+
+    /// See [`Meter::pause`].
+    pub fn pause_limits(&self) {
+        self.meter.pause();
+    }
+
+    /// Limits count from now; see [`Meter::restart`].
+    pub fn restart_limits(&self) {
+        self.meter.restart();
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+
+    /// What the engine's output writers check; see [`Tripwire`].
+    pub fn tripwire(&self) -> Tripwire {
+        Tripwire {
+            cancel: self.cancel.clone(),
+            timed_out: self.meter.timeout_flag(),
+        }
+    }
+
+    /// The limit the compile ran into, if any.
+    pub fn exceeded(&self) -> Option<Exceeded> {
+        self.meter.exceeded()
+    }
+
+    /// Cancelled or over a limit: the engine should stop.
+    pub fn stopped(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// `Err(Error::Cancelled)` once the job was cancelled,
+    /// `Err(Error::LimitExceeded)` once it ran into a limit.
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled() {
+            return Err(Error::Cancelled);
+        }
+        match self.meter.check() {
+            Some(limit) => Err(Error::LimitExceeded(limit)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn stage(&self, stage: Stage, pass: u32) {
+        self.update(|p| {
+            p.stage = stage;
+            p.pass = pass;
+            p.file = None;
+        });
+    }
+
+    pub fn file(&self, name: &str) {
+        self.update(|p| p.file = Some(name.to_string()));
+    }
+
+    pub fn pages(&self, pages: u32) {
+        self.meter.pages(pages);
+        self.update(|p| p.pages = Some(pages));
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Progress)) {
+        let Some(listener) = self.listener else {
+            return;
+        };
+        let snapshot = {
+            let mut p = self.progress.borrow_mut();
+            f(&mut p);
+            p.clone()
+        };
+        listener.progress(&snapshot);
+    }
+}
+
+/// The part of a [`Monitor`] that code running inside the engine can keep:
+/// cancellation and the timeout, without the listener or the memory reading.
+#[derive(Clone)]
+pub struct Tripwire {
+    cancel: Option<Arc<AtomicBool>>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Tripwire {
+    /// Cancelled or out of time: the engine should stop.
+    pub fn tripped(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+            || self
+                .cancel
+                .as_ref()
+                .is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+}
+
+fn jobs() -> &'static Mutex<HashMap<JobId, Arc<AtomicBool>>> {
+    static JOBS: OnceLock<Mutex<HashMap<JobId, Arc<AtomicBool>>>> = OnceLock::new();
+    JOBS.get_or_init(Default::default)
+}
+
+/// Compiles on a new thread. Jobs queue on the engine, so starting one while
+/// another runs is fine; cancel the stale one to let the new one through sooner.
+pub fn start(
+    latex_source: String,
+    output_path: PathBuf,
+    cache_path: PathBuf,
+    options: CompileOptions,
+    listener: Box<dyn Listener>,
+) -> JobId {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let cancel = Arc::new(AtomicBool::new(false));
+    jobs().lock().unwrap().insert(id, cancel.clone());
+
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let monitor =
+                Monitor::new(cancel.clone(), listener.as_ref()).with_limits(options.limits);
+            compile_monitored(&latex_source, &output_path, &cache_path, &options, &monitor)
+        }))
+        .unwrap_or(Err(Error::Panic));
+        jobs().lock().unwrap().remove(&id);
+        listener.finished(result);
+    });
+    id
+}
+
+/// Asks job `id` to stop; false if it already finished (or never existed).
+pub fn cancel(id: JobId) -> bool {
+    match jobs().lock().unwrap().get(&id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,47 @@
+//! Native LaTeX → PDF compiler for LiveLatex.
+//!
+//! The Android app loads this crate as `librust_core.so` (see `LatexCompiler.kt`).
+//! - [`bundle`] installs and maintains the offline TeX tree under `cachePath`
+//! - [`compile`] is the entry point shared by the JNI layer and host tooling
+//! - [`diagnostics`] turns the engine log into errors/warnings for the UI
+//! - [`texlog`] sorts a log into typed events and says whether TeX wants a rerun
+//! - [`bibliography`] prepares biblatex documents for the built-in BibTeX
+//! - [`makeindex`] sorts index, glossary and nomenclature entries between passes
+//! - [`synctex`] maps editor lines to PDF positions and back
+//! - [`pdf`] renders compiled pages to bitmaps and SVG and reads their text and bookmarks
+//! - [`wordcount`] counts the words of the typeset document, per page and section
+//! - [`tikz`] compiles the preview's TikZ figures to cached SVGs
+//! - [`sandbox`] decides what an untrusted document may read, write and run
+//! - [`job`] runs compiles in the background with progress and cancellation
+//! - [`limits`] bounds a compile's time, pages and memory
+//! - [`draft`] trades fidelity for speed in compiles for the live PDF view
+//! - [`reproducible`] makes a compile with a source date byte-identical across runs
+//! - [`engine`] drives the embedded XeTeX + xdvipdfmx engines (feature `tectonic`)
+//! - `ffi` holds the `Java_…` exports; it only converts arguments and results
+//!
+//! Everything the engine needs at run time (TeX bundle, cached formats) lives
+//! under the `cachePath` handed in by the app.
+
+pub mod bibliography;
+pub mod bundle;
+pub mod compile;
+pub mod diagnostics;
+pub mod draft;
+pub mod engine;
+mod error;
+mod ffi;
+pub mod job;
+pub mod limits;
+pub mod makeindex;
+pub mod pdf;
+pub mod reproducible;
+pub mod sandbox;
+pub mod synctex;
+pub mod texlog;
+pub mod tikz;
+pub mod wordcount;
+
+pub use compile::{
+    compile_detailed, compile_pdf, compile_project, compile_with, CompileOptions, CompileReport,
+};
+pub use error::{Error, Result};
@@ -0,0 +1,271 @@
+//! Time, page and memory limits for a compile.
+//!
+//! The engines run inside the app's process, so a runaway document cannot be
+//! killed. It is stopped the way a cancel stops it: [`crate::job::Monitor`]
+//! checks the limits on every file the engine opens and between passes, and
+//! refuses the open once one is exceeded. The compile then ends with a
+//! "limit exceeded" error instead of hanging or taking the app down.
+//!
+//! - time: wall clock since the compile got the engine. A watchdog thread
+//!   trips a flag at the deadline that the engine's output writers also check,
+//!   so a loop that opens no file still stops at its next shipped-out page.
+//! - pages: checked after each TeX pass, before more passes or the PDF
+//! - memory: growth of the process's resident set (`VmRSS`) since the compile
+//!   got the engine. Compiles hold the engine one at a time (see
+//!   [`crate::engine::typeset`]), so that growth is the running compile's.
+//!
+//! There is no limit on TeX's main memory or its other arrays: the engine
+//! hardcodes their sizes (main memory is 5,000,000 words, about 40 MB) and has
+//! no setting to feed a smaller one in, so a cap on them is not implemented. A
+//! document that overflows one stops with `TeX capacity exceeded`, reported
+//! like any other TeX error. A loop that neither opens a file nor ships out a
+//! page gives the engine no point at which to stop.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// How often the resident set is read at most; reading `/proc` on every file
+/// open would slow package loading.
+const MEMORY_POLL: Duration = Duration::from_millis(250);
+
+/// Per-compile limits from the app (`limits` in the compile options). 0 disables one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Limits {
+    pub timeout_secs: u64,
+    pub max_pages: u32,
+    /// MiB the compile may add to the process's resident memory.
+    pub max_memory_mb: u64,
+}
+
+impl Default for Limits {
+    /// Generous for a phone: a long thesis fits, a runaway document does not.
+    fn default() -> Self {
+        Limits {
+            timeout_secs: 120,
+            max_pages: 2000,
+            max_memory_mb: 768,
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Limits {
+            timeout_secs: 0,
+            max_pages: 0,
+            max_memory_mb: 0,
+        }
+    }
+}
+
+/// The limit a compile ran into, with its configured value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exceeded {
+    Time(u64),
+    Pages(u32),
+    Memory(u64),
+}
+
+impl fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exceeded::Time(secs) => write!(f, "time limit of {secs} s"),
+            Exceeded::Pages(pages) => write!(f, "page limit of {pages} pages"),
+            Exceeded::Memory(mb) => write!(f, "memory limit of {mb} MB"),
+        }
+    }
+}
+
+/// Sets a flag once a timeout has passed, from its own thread; dropping it
+/// first disarms it. Lets code that cannot poll the clock, like the engine's
+/// output writers, see the timeout.
+struct Watchdog {
+    _disarm: mpsc::Sender<()>,
+}
+
+impl Watchdog {
+    fn arm(timeout: Duration, tripped: Arc<AtomicBool>) -> Self {
+        let (disarm, disarmed) = mpsc::channel();
+        thread::spawn(move || {
+            if disarmed.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+                tripped.store(true, Ordering::Relaxed);
+            }
+        });
+        Watchdog { _disarm: disarm }
+    }
+}
+
+/// Tracks one compile against its [`Limits`]. Once exceeded it stays exceeded.
+pub struct Meter {
+    limits: Limits,
+    /// Set by the [`Watchdog`] when the timeout runs out.
+    timed_out: Arc<AtomicBool>,
+    watchdog: RefCell<Option<Watchdog>>,
+    /// Resident set in KiB when the compile started.
+    baseline_kb: Cell<Option<u64>>,
+    last_memory_poll: Cell<Instant>,
+    exceeded: Cell<Option<Exceeded>>,
+    paused: Cell<bool>,
+}
+
+impl Meter {
+    pub fn new(limits: Limits) -> Self {
+        let meter = Meter {
+            limits,
+            timed_out: Arc::new(AtomicBool::new(false)),
+            watchdog: RefCell::new(None),
+            baseline_kb: Cell::new(None),
+            last_memory_poll: Cell::new(Instant::now()),
+            exceeded: Cell::new(None),
+            paused: Cell::new(false),
+        };
+        meter.restart();
+        meter
+    }
+
+    /// Stops checking time and memory until [`Meter::restart`], e.g. during the
+    /// one-time `latex.fmt` dump, which should not count against the document.
+    pub fn pause(&self) {
+        // A timeout that already ran out still counts.
+        self.check();
+        self.paused.set(true);
+        self.watchdog.replace(None);
+    }
+
+    /// Starts the clock and the memory baseline again.
+    pub fn restart(&self) {
+        self.paused.set(false);
+        if self.exceeded.get().is_some() {
+            return;
+        }
+        self.timed_out.store(false, Ordering::Relaxed);
+        let secs = self.limits.timeout_secs;
+        self.watchdog.replace(
+            (secs > 0).then(|| Watchdog::arm(Duration::from_secs(secs), self.timed_out.clone())),
+        );
+        self.last_memory_poll.set(Instant::now());
+        self.baseline_kb
+            .set((self.limits.max_memory_mb > 0).then(resident_kb).flatten());
+    }
+
+    /// The flag the [`Watchdog`] sets on timeout, for code that cannot borrow the meter.
+    pub fn timeout_flag(&self) -> Arc<AtomicBool> {
+        self.timed_out.clone()
+    }
+
+    /// The limit this compile has exceeded, if any, checking time and memory now.
+    pub fn check(&self) -> Option<Exceeded> {
+        if self.exceeded.get().is_none() && !self.paused.get() {
+            self.exceeded
+                .set(self.over_time().or_else(|| self.over_memory()));
+        }
+        self.exceeded.get()
+    }
+
+    /// The limit already run into; unlike [`Meter::check`], looks at nothing new.
+    pub fn exceeded(&self) -> Option<Exceeded> {
+        self.exceeded.get()
+    }
+
+    /// Records the page count of a finished pass.
+    pub fn pages(&self, pages: u32) {
+        let max = self.limits.max_pages;
+        if max > 0 && pages > max && self.exceeded.get().is_none() {
+            self.exceeded.set(Some(Exceeded::Pages(max)));
+        }
+    }
+
+    fn over_time(&self) -> Option<Exceeded> {
+        self.timed_out
+            .load(Ordering::Relaxed)
+            .then_some(Exceeded::Time(self.limits.timeout_secs))
+    }
+
+    fn over_memory(&self) -> Option<Exceeded> {
+        let baseline = self.baseline_kb.get()?;
+        if self.last_memory_poll.get().elapsed() < MEMORY_POLL {
+            return None;
+        }
+        self.last_memory_poll.set(Instant::now());
+        let mb = self.limits.max_memory_mb;
+        let grown = resident_kb()?.saturating_sub(baseline);
+        (grown > mb * 1024).then_some(Exceeded::Memory(mb))
+    }
+}
+
+/// `VmRSS` of this process in KiB; `None` where `/proc` is unavailable.
+fn resident_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    line["VmRSS:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_limits_take_the_defaults() {
+        let limits: Limits = serde_json::from_str(r#"{"maxPages": 10}"#).unwrap();
+        assert_eq!(
+            limits,
+            Limits {
+                max_pages: 10,
+                ..Limits::default()
+            }
+        );
+    }
+
+    #[test]
+    fn zero_disables_a_limit() {
+        let limits: Limits =
+            serde_json::from_str(r#"{"timeoutSecs": 0, "maxPages": 0, "maxMemoryMb": 0}"#).unwrap();
+        assert_eq!(limits, Limits::unlimited());
+        let meter = Meter::new(limits);
+        meter.pages(100_000);
+        assert_eq!(meter.check(), None);
+    }
+
+    #[test]
+    fn pages_over_the_limit_stay_exceeded() {
+        let meter = Meter::new(Limits {
+            max_pages: 3,
+            ..Limits::unlimited()
+        });
+        meter.pages(3);
+        assert_eq!(meter.check(), None);
+        meter.pages(4);
+        meter.restart();
+        assert_eq!(meter.check(), Some(Exceeded::Pages(3)));
+    }
+
+    #[test]
+    fn watchdog_trips_the_timeout() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let _watchdog = Watchdog::arm(Duration::from_millis(10), flag.clone());
+        thread::sleep(Duration::from_millis(200));
+        assert!(flag.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn disarmed_watchdog_does_not_trip() {
+        let flag = Arc::new(AtomicBool::new(false));
+        drop(Watchdog::arm(Duration::from_millis(50), flag.clone()));
+        thread::sleep(Duration::from_millis(150));
+        assert!(!flag.load(Ordering::Relaxed));
+    }
+}
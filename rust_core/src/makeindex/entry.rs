@@ -0,0 +1,328 @@
+//! Reading `\indexentry{key}{page}` records from a raw index file.
+
+use std::cmp::Ordering;
+
+use super::style::Style;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Level {
+    /// What the entry sorts by (before `@`).
+    pub sort: String,
+    /// What is printed (after `@`; the sort key if there is no `@`).
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Range {
+    None,
+    /// `|(`
+    Open,
+    /// `|)`
+    Close,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Entry {
+    pub levels: Vec<Level>,
+    /// `textbf` for `|textbf`, `see{foo}` for `|see{foo}`.
+    pub encap: Option<String>,
+    pub range: Range,
+    pub page: Page,
+    /// 1-based line of the input file.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Page {
+    /// As written, e.g. `iv` or `A-3`.
+    pub text: String,
+    /// Per compositor-separated part: (rank in `page_precedence`, value).
+    pub parts: Vec<(usize, u32)>,
+}
+
+impl Page {
+    pub fn cmp(&self, other: &Page) -> Ordering {
+        self.parts.cmp(&other.parts)
+    }
+
+    /// True if `next` is the page right after this one: same prefix and type,
+    /// last part one higher.
+    pub fn precedes(&self, next: &Page) -> bool {
+        let (Some((&(rank, value), prefix)), Some((&(next_rank, next_value), next_prefix))) =
+            (self.parts.split_last(), next.parts.split_last())
+        else {
+            return false;
+        };
+        prefix == next_prefix && rank == next_rank && value.checked_add(1) == Some(next_value)
+    }
+}
+
+/// A record makeindex would reject, with the reason in makeindex's words.
+#[derive(Debug, Clone)]
+pub(super) struct Rejected {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Scans every `keyword{…}{…}` record of `input`.
+pub(super) fn scan(input: &str, style: &Style) -> (Vec<Entry>, Vec<Rejected>) {
+    let mut entries = Vec::new();
+    let mut rejected = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = input
+        .get(from..)
+        .and_then(|rest| rest.find(style.keyword.as_str()))
+    {
+        let start = from + pos;
+        let line = input[..start].matches('\n').count() + 1;
+        from = start + style.keyword.len();
+        let result = read_arg(input, &mut from, style)
+            .and_then(|key| Ok((key, read_arg(input, &mut from, style)?)))
+            .and_then(|(key, page)| parse_entry(&key, &page, line, style));
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(message) => rejected.push(Rejected { line, message }),
+        }
+        // Always move on, whatever the style's keyword and delimiters are.
+        if from <= start {
+            from = start + input[start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    (entries, rejected)
+}
+
+/// Reads a braced argument starting at `*at` (after optional blanks), honouring
+/// nesting, quote and escape characters. Leaves quote and escape characters in.
+fn read_arg(input: &str, at: &mut usize, style: &Style) -> Result<String, String> {
+    let rest = &input[*at..];
+    let trimmed = rest.trim_start_matches([' ', '\t']);
+    let mut chars = trimmed.char_indices();
+    match chars.next() {
+        Some((_, c)) if c == style.arg_open => {}
+        _ => {
+            *at += rest.len() - trimmed.len();
+            return Err(format!("Expected `{}'.", style.arg_open));
+        }
+    }
+    let offset = rest.len() - trimmed.len();
+    let mut depth = 0usize;
+    let mut out = String::new();
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' {
+            break;
+        }
+        if c == style.escape || c == style.quote {
+            out.push(c);
+            if let Some((_, next)) = chars.next() {
+                out.push(next);
+            }
+            continue;
+        }
+        if c == style.arg_close {
+            if depth == 0 {
+                *at += offset + i + c.len_utf8();
+                return Ok(out);
+            }
+            depth -= 1;
+        } else if c == style.arg_open {
+            depth += 1;
+        }
+        out.push(c);
+    }
+    *at += rest.len().min(offset + out.len() + 1);
+    Err(format!("Unmatched `{}'.", style.arg_open))
+}
+
+fn parse_entry(key: &str, page: &str, line: usize, style: &Style) -> Result<Entry, String> {
+    let mut levels = Vec::new();
+    let mut sort = String::new();
+    let mut display: Option<String> = None;
+    let mut encap_raw: Option<&str> = None;
+    let mut depth = 0usize;
+    let mut chars = key.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let target = display.as_mut().unwrap_or(&mut sort);
+        if c == style.escape {
+            // `\"a` stays as is; the character after the escape is never special.
+            target.push(c);
+            if let Some((_, next)) = chars.next() {
+                target.push(next);
+            }
+            continue;
+        }
+        if c == style.quote {
+            if let Some((_, next)) = chars.next() {
+                target.push(next);
+            }
+            continue;
+        }
+        if c == style.arg_open {
+            depth += 1;
+        } else if c == style.arg_close {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && c == style.level {
+            levels.push(finish_level(sort, display, i)?);
+            if levels.len() == 3 {
+                return Err("Too many levels.".into());
+            }
+            sort = String::new();
+            display = None;
+            continue;
+        } else if depth == 0 && c == style.actual {
+            if display.is_some() {
+                return Err(format!(
+                    "Extra `{}' at position {} of first argument.",
+                    style.actual,
+                    i + 1
+                ));
+            }
+            display = Some(String::new());
+            continue;
+        } else if depth == 0 && c == style.encap {
+            encap_raw = Some(&key[i + c.len_utf8()..]);
+            break;
+        }
+        target.push(c);
+    }
+    levels.push(finish_level(sort, display, key.len())?);
+
+    let (range, encap) = match encap_raw {
+        Some(raw) if raw.starts_with(style.range_open) => {
+            (Range::Open, &raw[style.range_open.len_utf8()..])
+        }
+        Some(raw) if raw.starts_with(style.range_close) => {
+            (Range::Close, &raw[style.range_close.len_utf8()..])
+        }
+        Some(raw) => (Range::None, raw),
+        None => (Range::None, ""),
+    };
+    let encap = unquote(encap, style);
+    Ok(Entry {
+        levels,
+        encap: (!encap.is_empty()).then_some(encap),
+        range,
+        page: parse_page(page.trim(), style)?,
+        line,
+    })
+}
+
+fn finish_level(sort: String, display: Option<String>, position: usize) -> Result<Level, String> {
+    if sort.is_empty() {
+        return Err(format!(
+            "Illegal null field at position {} of first argument.",
+            position + 1
+        ));
+    }
+    let display = match display {
+        Some(d) if !d.is_empty() => d,
+        _ => sort.clone(),
+    };
+    Ok(Level { sort, display })
+}
+
+fn unquote(text: &str, style: &Style) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == style.escape {
+            out.push(c);
+            out.extend(chars.next());
+        } else if c == style.quote {
+            out.extend(chars.next());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn parse_page(text: &str, style: &Style) -> Result<Page, String> {
+    let mut parts = Vec::new();
+    for part in text.split(style.page_compositor.as_str()) {
+        let (kind, value) =
+            page_part(part).ok_or_else(|| format!("Illegal page number {text}."))?;
+        let rank = style
+            .page_precedence
+            .find(kind)
+            .unwrap_or(style.page_precedence.len());
+        parts.push((rank, value));
+    }
+    Ok(Page {
+        text: text.to_string(),
+        parts,
+    })
+}
+
+/// Page number type (`r`, `R`, `n`, `a`, `A`) and value. Roman numerals win
+/// over letters, so `i` is 1 rather than the ninth letter.
+fn page_part(part: &str) -> Option<(char, u32)> {
+    if part.is_empty() {
+        return None;
+    }
+    if part.bytes().all(|b| b.is_ascii_digit()) {
+        return Some(('n', part.parse().ok()?));
+    }
+    if let Some(v) = roman(part, false) {
+        return Some(('r', v));
+    }
+    if let Some(v) = roman(part, true) {
+        return Some(('R', v));
+    }
+    let mut chars = part.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_lowercase() => Some(('a', c as u32 - 'a' as u32 + 1)),
+        (Some(c), None) if c.is_ascii_uppercase() => Some(('A', c as u32 - 'A' as u32 + 1)),
+        _ => None,
+    }
+}
+
+fn roman(text: &str, upper: bool) -> Option<u32> {
+    let digit = |c: char| {
+        let c = if upper {
+            c.to_ascii_lowercase()
+        } else if c.is_ascii_uppercase() {
+            return None;
+        } else {
+            c
+        };
+        match c {
+            'i' => Some(1),
+            'v' => Some(5),
+            'x' => Some(10),
+            'l' => Some(50),
+            'c' => Some(100),
+            'd' => Some(500),
+            'm' => Some(1000),
+            _ => None,
+        }
+    };
+    if upper && text.chars().any(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    let values: Vec<u32> = text.chars().map(digit).collect::<Option<_>>()?;
+    let mut total = 0;
+    for (i, &v) in values.iter().enumerate() {
+        if values.get(i + 1).is_some_and(|&next| next > v) {
+            total -= v as i64;
+        } else {
+            total += v as i64;
+        }
+    }
+    u32::try_from(total).ok().filter(|&v| v > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_moves_on_even_with_an_empty_keyword() {
+        let style = Style {
+            keyword: String::new(),
+            ..Style::default()
+        };
+        let (entries, rejected) = scan("{a}{1}", &style);
+        assert_eq!(entries.len(), 1);
+        assert!(rejected.len() <= "{a}{1}".len());
+    }
+}
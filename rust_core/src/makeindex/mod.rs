@@ -0,0 +1,516 @@
+//! A makeindex-compatible index processor, for `\makeindex`, `glossaries` and
+//! `nomencl` without an external binary.
+//!
+//! [`run`] reads the raw entries TeX wrote (`.idx`, `.glo`, `.nlo`, …), sorts
+//! and merges them, and writes the sorted file (`.ind`, `.gls`, `.nls`, …) the
+//! next TeX pass reads, laid out by a [`Style`] parsed from an `.ist` file.
+//! The transcript (`.ilg`, `.glg`, …) uses makeindex's message formats, which
+//! [`crate::diagnostics::parse_makeindex_log`] understands.
+//!
+//! Supported: up to three levels (`!`), sort keys (`@`), page encapsulators (`|`),
+//! explicit (`|(` … `|)`) and implicit page ranges, quoting, composite page
+//! numbers and group headings. Long lines are not wrapped; TeX does not care.
+
+mod entry;
+mod style;
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use entry::{Entry, Level, Page, Range};
+pub use style::Style;
+
+/// How sort keys are compared (makeindex's default, or its `-l` flag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyOrder {
+    /// Spaces count and sort before any other character.
+    #[default]
+    Word,
+    /// Spaces are ignored.
+    Letter,
+}
+
+/// One makeindex run; the names only appear in the transcript.
+#[derive(Debug, Clone)]
+pub struct Job<'a> {
+    pub input_name: &'a str,
+    pub input: &'a str,
+    pub output_name: &'a str,
+    pub log_name: &'a str,
+    /// Name and contents of the `.ist` style; makeindex's defaults otherwise.
+    pub style: Option<(&'a str, &'a str)>,
+    pub order: KeyOrder,
+}
+
+#[derive(Debug, Clone)]
+pub struct Output {
+    /// The sorted index.
+    pub index: String,
+    /// makeindex-style transcript.
+    pub log: String,
+}
+
+pub fn run(job: &Job) -> Output {
+    let mut log = String::from("This is makeindex (LiveLatex, makeindex-compatible).\n");
+    let style = match job.style {
+        Some((name, text)) => {
+            let (style, problems) = Style::parse(text);
+            let _ = writeln!(
+                log,
+                "Scanning style file {name}....done ({} ignored).",
+                problems.len()
+            );
+            for problem in &problems {
+                let (line, message) = problem.split_once(": ").unwrap_or(("0", problem));
+                let line = line.trim_start_matches("line ");
+                let _ = writeln!(
+                    log,
+                    "** Input style error (file = {name}, line = {line}):\n   -- {message}."
+                );
+            }
+            style
+        }
+        None => Style::default(),
+    };
+
+    let (mut entries, rejected) = entry::scan(job.input, &style);
+    let _ = writeln!(
+        log,
+        "Scanning input file {}....done ({} entries accepted, {} rejected).",
+        job.input_name,
+        entries.len(),
+        rejected.len()
+    );
+    for r in &rejected {
+        let _ = writeln!(
+            log,
+            "!! Input index error (file = {}, line = {}):\n   -- {}",
+            job.input_name, r.line, r.message
+        );
+    }
+
+    log.push_str("Sorting entries....");
+    // Stable: entries on the same page keep their input order, so a range
+    // opened and closed on one page stays well-formed.
+    entries
+        .sort_by(|a, b| compare_levels(&a.levels, &b.levels, job.order).then(a.page.cmp(&b.page)));
+    log.push_str("done.\n");
+
+    let mut writer = Writer {
+        style: &style,
+        out: String::new(),
+        warnings: Vec::new(),
+    };
+    writer.write(&entries, job.order);
+    let lines = writer.out.matches('\n').count();
+    let _ = writeln!(
+        log,
+        "Generating output file {}....done ({lines} lines written, {} warnings).",
+        job.output_name,
+        writer.warnings.len()
+    );
+    for (input_line, output_line, message) in &writer.warnings {
+        let _ = writeln!(
+            log,
+            "## Warning (input = {}, line = {input_line}; output = {}, line = {output_line}):\n   -- {message}",
+            job.input_name, job.output_name
+        );
+    }
+    let _ = writeln!(log, "Output written in {}.", job.output_name);
+    let _ = writeln!(log, "Transcript written in {}.", job.log_name);
+    Output {
+        index: writer.out,
+        log,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Class {
+    Symbol,
+    Number,
+    Letter,
+}
+
+fn class(key: &str) -> Class {
+    match key.chars().next() {
+        Some(c) if c.is_alphabetic() => Class::Letter,
+        Some(c) if c.is_ascii_digit() => Class::Number,
+        _ => Class::Symbol,
+    }
+}
+
+/// Symbols, then numbers (numerically), then letters ignoring case; exact
+/// spelling breaks ties.
+fn compare_keys(a: &str, b: &str, order: KeyOrder) -> Ordering {
+    let by_class = class(a).cmp(&class(b));
+    if by_class != Ordering::Equal {
+        return by_class;
+    }
+    if let (Ok(x), Ok(y)) = (a.parse::<u64>(), b.parse::<u64>()) {
+        return x.cmp(&y);
+    }
+    let folded = |s: &str| -> Vec<char> {
+        s.chars()
+            .filter(|c| order == KeyOrder::Word || *c != ' ')
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    folded(a).cmp(&folded(b)).then_with(|| a.cmp(b))
+}
+
+fn compare_levels(a: &[Level], b: &[Level], order: KeyOrder) -> Ordering {
+    for (x, y) in a.iter().zip(b) {
+        let ord = compare_keys(&x.sort, &y.sort, order).then_with(|| x.display.cmp(&y.display));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// A group heading changes with the class, and for letters with the initial.
+fn group_of(key: &str) -> (Class, Option<char>) {
+    let class = class(key);
+    let initial = match class {
+        Class::Letter => key
+            .chars()
+            .next()
+            .map(|c| c.to_uppercase().next().unwrap_or(c)),
+        _ => None,
+    };
+    (class, initial)
+}
+
+enum PageItem<'a> {
+    Single(&'a Page, Option<&'a str>),
+    Range(&'a Page, &'a Page, Option<&'a str>),
+    Suffixed(&'a Page, &'a str, Option<&'a str>),
+}
+
+struct Writer<'s> {
+    style: &'s Style,
+    out: String,
+    /// (input line, output line, message).
+    warnings: Vec<(usize, usize, String)>,
+}
+
+impl Writer<'_> {
+    fn write(&mut self, entries: &[Entry], order: KeyOrder) {
+        let style = self.style;
+        self.out.push_str(&style.preamble);
+        let mut previous: &[Level] = &[];
+        let mut group = None;
+        // Level and page presence of the item written last, for item_01/item_x1.
+        let mut last_item: Option<(usize, bool)> = None;
+
+        let mut i = 0;
+        while i < entries.len() {
+            let levels = &entries[i].levels;
+            let end = entries[i..]
+                .iter()
+                .position(|e| compare_levels(&e.levels, levels, order) != Ordering::Equal)
+                .map_or(entries.len(), |n| i + n);
+            let same = &entries[i..end];
+            i = end;
+
+            let this_group = group_of(&levels[0].sort);
+            if group != Some(this_group) {
+                if group.is_some() {
+                    self.out.push_str(&style.group_skip);
+                }
+                if style.headings_flag != 0 {
+                    self.out.push_str(&style.heading_prefix);
+                    self.out.push_str(&heading(style, this_group));
+                    self.out.push_str(&style.heading_suffix);
+                }
+                group = Some(this_group);
+                previous = &[];
+            }
+
+            let common = previous
+                .iter()
+                .zip(levels)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for (depth, level) in levels.iter().enumerate().skip(common) {
+                let item = match (depth, last_item) {
+                    (0, _) => &style.item_0,
+                    (1, Some((0, true))) => &style.item_01,
+                    (1, Some((0, false))) => &style.item_x1,
+                    (1, _) => &style.item_1,
+                    (_, Some((1, true))) => &style.item_12,
+                    (_, Some((1, false))) => &style.item_x2,
+                    _ => &style.item_2,
+                };
+                self.out.push_str(item);
+                self.out.push_str(&level.display);
+                let pages = if depth + 1 == levels.len() {
+                    self.pages(same)
+                } else {
+                    String::new()
+                };
+                if !pages.is_empty() {
+                    self.out.push_str(match depth {
+                        0 => &style.delim_0,
+                        1 => &style.delim_1,
+                        _ => &style.delim_2,
+                    });
+                    self.out.push_str(&pages);
+                    self.out.push_str(&style.delim_t);
+                }
+                last_item = Some((depth, !pages.is_empty()));
+            }
+            previous = levels;
+        }
+        self.out.push_str(&style.postamble);
+    }
+
+    /// Page list of one entry, with duplicates merged and ranges formed.
+    fn pages(&mut self, entries: &[Entry]) -> String {
+        let mut explicit: Vec<PageItem> = Vec::new();
+        let mut singles: Vec<(&Page, Option<&str>)> = Vec::new();
+        let mut open: Option<&Entry> = None;
+        let style = self.style;
+        let flush = |singles: &mut Vec<_>, items: &mut Vec<_>| {
+            collapse(singles, items, style);
+            singles.clear();
+        };
+        for e in entries {
+            let encap = e.encap.as_deref();
+            match (open, e.range) {
+                (Some(start), Range::Close) => {
+                    flush(&mut singles, &mut explicit);
+                    let start_encap = start.encap.as_deref();
+                    explicit.push(if start.page == e.page {
+                        PageItem::Single(&start.page, start_encap)
+                    } else {
+                        PageItem::Range(&start.page, &e.page, start_encap)
+                    });
+                    open = None;
+                }
+                (Some(start), Range::None)
+                    if encap.is_none() || encap == start.encap.as_deref() =>
+                {
+                    // Covered by the open range.
+                }
+                (Some(_), Range::Open) | (None, Range::Close) => {
+                    let message = if e.range == Range::Open {
+                        "Extra range opening operator `('."
+                    } else {
+                        "Unmatched range closing operator `)'."
+                    };
+                    self.warn(e.line, message);
+                    singles.push((&e.page, encap));
+                }
+                (None, Range::Open) => {
+                    flush(&mut singles, &mut explicit);
+                    open = Some(e);
+                }
+                (_, Range::None) => {
+                    if singles.last() != Some(&(&e.page, encap)) {
+                        singles.push((&e.page, encap));
+                    }
+                }
+            }
+        }
+        if let Some(start) = open {
+            self.warn(start.line, "Unmatched range opening operator `('.");
+            singles.push((&start.page, start.encap.as_deref()));
+            singles.sort_by(|a, b| a.0.cmp(b.0));
+        }
+        flush(&mut singles, &mut explicit);
+
+        let wrap = |text: String, encap: Option<&str>| match encap {
+            Some(encap) => format!(
+                "{}{encap}{}{text}{}",
+                style.encap_prefix, style.encap_infix, style.encap_suffix
+            ),
+            None => text,
+        };
+        explicit
+            .into_iter()
+            .map(|item| match item {
+                PageItem::Single(p, encap) => wrap(p.text.clone(), encap),
+                PageItem::Range(from, to, encap) => {
+                    wrap(format!("{}{}{}", from.text, style.delim_r, to.text), encap)
+                }
+                PageItem::Suffixed(p, suffix, encap) => wrap(format!("{}{suffix}", p.text), encap),
+            })
+            .collect::<Vec<_>>()
+            .join(&style.delim_n)
+    }
+
+    fn warn(&mut self, input_line: usize, message: &str) {
+        let output_line = self.out.matches('\n').count() + 1;
+        self.warnings
+            .push((input_line, output_line, message.to_string()));
+    }
+}
+
+/// Turns runs of consecutive pages with the same encapsulator into ranges (three
+/// or more pages), or into `suffix_2p`/`suffix_3p`/`suffix_mp` forms when set.
+fn collapse<'a>(
+    singles: &[(&'a Page, Option<&'a str>)],
+    items: &mut Vec<PageItem<'a>>,
+    style: &'a Style,
+) {
+    let mut i = 0;
+    while i < singles.len() {
+        let (first, encap) = singles[i];
+        let mut end = i + 1;
+        while end < singles.len()
+            && singles[end].1 == encap
+            && singles[end - 1].0.precedes(singles[end].0)
+        {
+            end += 1;
+        }
+        let run = end - i;
+        let last = singles[end - 1].0;
+        match run {
+            2 if !style.suffix_2p.is_empty() => {
+                items.push(PageItem::Suffixed(first, &style.suffix_2p, encap))
+            }
+            3 if !style.suffix_3p.is_empty() => {
+                items.push(PageItem::Suffixed(first, &style.suffix_3p, encap))
+            }
+            n if n >= 3 && !style.suffix_mp.is_empty() => {
+                items.push(PageItem::Suffixed(first, &style.suffix_mp, encap))
+            }
+            n if n >= 3 => items.push(PageItem::Range(first, last, encap)),
+            _ => {
+                items.extend(singles[i..end].iter().map(|&(p, e)| PageItem::Single(p, e)));
+            }
+        }
+        i = end;
+    }
+}
+
+fn heading(style: &Style, (class, initial): (Class, Option<char>)) -> String {
+    let positive = style.headings_flag > 0;
+    match (class, initial) {
+        (Class::Letter, Some(c)) if positive => c.to_string(),
+        (Class::Letter, Some(c)) => c.to_lowercase().to_string(),
+        (Class::Number, _) if positive => style.numhead_positive.clone(),
+        (Class::Number, _) => style.numhead_negative.clone(),
+        _ if positive => style.symhead_positive.clone(),
+        _ => style.symhead_negative.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job<'a>(input: &'a str, style: Option<&'a str>) -> Job<'a> {
+        Job {
+            input_name: "doc.idx",
+            input,
+            output_name: "doc.ind",
+            log_name: "doc.ilg",
+            style: style.map(|text| ("doc.ist", text)),
+            order: KeyOrder::Word,
+        }
+    }
+
+    #[test]
+    fn blank_keyword_is_rejected_not_looped_on() {
+        for ist in [
+            "keyword \"\"",
+            "keyword \"  \"",
+            "arg_open ' '",
+            "arg_close '\\t'",
+        ] {
+            let out = run(&job("\\indexentry{a}{1}", Some(ist)));
+            assert!(out.log.contains("cannot be blank"), "{ist}: {}", out.log);
+            assert!(out.index.contains("\\item a, 1"), "{ist}: {}", out.index);
+        }
+    }
+
+    fn index(input: &str) -> String {
+        run(&job(input, None)).index
+    }
+
+    #[test]
+    fn symbols_numbers_and_letters_sort_in_groups() {
+        let out = index(
+            "\\indexentry{beta}{1}\n\\indexentry{Alpha}{2}\n\\indexentry{10}{3}\n\
+             \\indexentry{9}{4}\n\\indexentry{+}{5}\n\\indexentry{alpha}{6}\n",
+        );
+        assert_eq!(
+            out,
+            "\\begin{theindex}\n\
+             \n  \\item +, 5\
+             \n\n  \\indexspace\n\
+             \n  \\item 9, 4\
+             \n  \\item 10, 3\
+             \n\n  \\indexspace\n\
+             \n  \\item Alpha, 2\
+             \n  \\item alpha, 6\
+             \n\n  \\indexspace\n\
+             \n  \\item beta, 1\
+             \n\n\\end{theindex}\n"
+        );
+    }
+
+    #[test]
+    fn sub_entries_and_sort_keys() {
+        let out = index(
+            "\\indexentry{fruit!banana}{2}\n\\indexentry{fruit!apple}{3}\n\
+             \\indexentry{fruit}{1}\n\\indexentry{zeta@\\textit{$\\zeta$}}{4}\n\
+             \\indexentry{fruit!apple!green}{5}\n",
+        );
+        assert!(
+            out.contains(
+                "\\item fruit, 1\n    \\subitem apple, 3\n      \\subsubitem green, 5\
+                 \n    \\subitem banana, 2"
+            ),
+            "{out}"
+        );
+        assert!(out.contains("\\item \\textit{$\\zeta$}, 4"), "{out}");
+    }
+
+    #[test]
+    fn pages_are_merged_into_ranges() {
+        let out = index(
+            "\\indexentry{a}{3}\n\\indexentry{a}{1}\n\\indexentry{a}{2}\n\\indexentry{a}{3}\n\
+             \\indexentry{a}{5}\n\\indexentry{a}{6}\n\\indexentry{a|textbf}{9}\n\
+             \\indexentry{a|(}{11}\n\\indexentry{a}{12}\n\\indexentry{a|)}{14}\n",
+        );
+        assert!(
+            out.contains("\\item a, 1--3, 5, 6, \\textbf{9}, 11--14"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn unmatched_range_is_a_warning() {
+        let out = run(&job("\\indexentry{a|)}{2}\n", None));
+        assert!(out.index.contains("\\item a, 2"), "{}", out.index);
+        assert!(
+            out.log
+                .contains("## Warning (input = doc.idx, line = 1; output = doc.ind, line = 3):"),
+            "{}",
+            out.log
+        );
+        assert!(out.log.contains("Unmatched range closing operator"));
+    }
+
+    #[test]
+    fn letter_order_ignores_spaces() {
+        let input = "\\indexentry{ice cream}{1}\n\\indexentry{iceberg}{2}\n";
+        let mut letter = job(input, None);
+        letter.order = KeyOrder::Letter;
+        let word = index(input);
+        assert!(word.find("ice cream") < word.find("iceberg"), "{word}");
+        let out = run(&letter).index;
+        assert!(out.find("iceberg") < out.find("ice cream"), "{out}");
+    }
+
+    #[test]
+    fn style_changes_the_layout() {
+        let ist = "preamble \"<ix>\"\npostamble \"</ix>\\n\"\nheadings_flag 1\n\
+                   heading_prefix \"[\"\nheading_suffix \"]\"\ndelim_0 \" -- \"\n";
+        let out = run(&job("\\indexentry{x}{7}\n", Some(ist))).index;
+        assert_eq!(out, "<ix>[X]\n  \\item x -- 7</ix>\n");
+    }
+}
@@ -0,0 +1,289 @@
+//! makeindex `.ist` style files: `key "string"`, `key 'c'` or `key 123` per line,
+//! `%` comments. Unknown keys are reported and ignored, like makeindex does.
+
+/// Every parameter makeindex reads, with its built-in default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    // Input.
+    pub keyword: String,
+    pub arg_open: char,
+    pub arg_close: char,
+    pub range_open: char,
+    pub range_close: char,
+    pub level: char,
+    pub actual: char,
+    pub encap: char,
+    pub quote: char,
+    pub escape: char,
+    pub page_compositor: String,
+
+    // Output.
+    pub preamble: String,
+    pub postamble: String,
+    pub group_skip: String,
+    /// 0: no group headings; > 0 upper case; < 0 lower case.
+    pub headings_flag: i32,
+    pub heading_prefix: String,
+    pub heading_suffix: String,
+    pub symhead_positive: String,
+    pub symhead_negative: String,
+    pub numhead_positive: String,
+    pub numhead_negative: String,
+    pub item_0: String,
+    pub item_1: String,
+    pub item_2: String,
+    pub item_01: String,
+    pub item_x1: String,
+    pub item_12: String,
+    pub item_x2: String,
+    pub delim_0: String,
+    pub delim_1: String,
+    pub delim_2: String,
+    pub delim_n: String,
+    pub delim_r: String,
+    pub delim_t: String,
+    pub encap_prefix: String,
+    pub encap_infix: String,
+    pub encap_suffix: String,
+    pub suffix_2p: String,
+    pub suffix_3p: String,
+    pub suffix_mp: String,
+    /// Order of page number types: `r`oman, `R`oman, `n`umeric, `a`lpha, `A`lpha.
+    pub page_precedence: String,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            keyword: "\\indexentry".into(),
+            arg_open: '{',
+            arg_close: '}',
+            range_open: '(',
+            range_close: ')',
+            level: '!',
+            actual: '@',
+            encap: '|',
+            quote: '"',
+            escape: '\\',
+            page_compositor: "-".into(),
+
+            preamble: "\\begin{theindex}\n".into(),
+            postamble: "\n\n\\end{theindex}\n".into(),
+            group_skip: "\n\n  \\indexspace\n".into(),
+            headings_flag: 0,
+            heading_prefix: String::new(),
+            heading_suffix: String::new(),
+            symhead_positive: "Symbols".into(),
+            symhead_negative: "symbols".into(),
+            numhead_positive: "Numbers".into(),
+            numhead_negative: "numbers".into(),
+            item_0: "\n  \\item ".into(),
+            item_1: "\n    \\subitem ".into(),
+            item_2: "\n      \\subsubitem ".into(),
+            item_01: "\n    \\subitem ".into(),
+            item_x1: "\n    \\subitem ".into(),
+            item_12: "\n      \\subsubitem ".into(),
+            item_x2: "\n      \\subsubitem ".into(),
+            delim_0: ", ".into(),
+            delim_1: ", ".into(),
+            delim_2: ", ".into(),
+            delim_n: ", ".into(),
+            delim_r: "--".into(),
+            delim_t: String::new(),
+            encap_prefix: "\\".into(),
+            encap_infix: "{".into(),
+            encap_suffix: "}".into(),
+            suffix_2p: String::new(),
+            suffix_3p: String::new(),
+            suffix_mp: String::new(),
+            page_precedence: "rRnaA".into(),
+        }
+    }
+}
+
+enum Value {
+    Str(String),
+    Char(char),
+    Int(i32),
+}
+
+impl Style {
+    /// Applies `text` on top of the defaults. Returns the style and one message
+    /// per line that could not be used.
+    pub fn parse(text: &str) -> (Style, Vec<String>) {
+        let mut style = Style::default();
+        let mut problems = Vec::new();
+        let mut rest = text;
+        let mut line = 1;
+        loop {
+            rest = skip_blank(rest, &mut line);
+            if rest.is_empty() {
+                break;
+            }
+            let key_len = rest
+                .find(|c: char| c.is_whitespace() || c == '%')
+                .unwrap_or(rest.len());
+            let key = &rest[..key_len];
+            rest = skip_blank(&rest[key_len..], &mut line);
+            let (value, after) = match read_value(rest) {
+                Some(v) => v,
+                None => {
+                    problems.push(format!("line {line}: No value for `{key}'"));
+                    rest = rest.split_once('\n').map_or("", |(_, r)| r);
+                    line += 1;
+                    continue;
+                }
+            };
+            line += rest[..rest.len() - after.len()].matches('\n').count();
+            rest = after;
+            if let Err(e) = style.set(key, value) {
+                problems.push(format!("line {line}: {e}"));
+            }
+        }
+        (style, problems)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> std::result::Result<(), String> {
+        // Entries are found by these; a blank one would match everywhere.
+        let blank = match &value {
+            Value::Str(s) => key == "keyword" && s.trim().is_empty(),
+            Value::Char(c) => matches!(key, "arg_open" | "arg_close") && c.is_whitespace(),
+            Value::Int(_) => false,
+        };
+        if blank {
+            return Err(format!("Specifier `{key}' cannot be blank"));
+        }
+        let char_field = match key {
+            "arg_open" => Some(&mut self.arg_open),
+            "arg_close" => Some(&mut self.arg_close),
+            "range_open" => Some(&mut self.range_open),
+            "range_close" => Some(&mut self.range_close),
+            "level" => Some(&mut self.level),
+            "actual" => Some(&mut self.actual),
+            "encap" => Some(&mut self.encap),
+            "quote" => Some(&mut self.quote),
+            "escape" => Some(&mut self.escape),
+            _ => None,
+        };
+        if let Some(field) = char_field {
+            return match value {
+                Value::Char(c) => {
+                    *field = c;
+                    Ok(())
+                }
+                _ => Err(format!("Specifier `{key}' takes a character")),
+            };
+        }
+        if key == "headings_flag" {
+            return match value {
+                Value::Int(n) => {
+                    self.headings_flag = n;
+                    Ok(())
+                }
+                _ => Err(format!("Specifier `{key}' takes a number")),
+            };
+        }
+        // Line wrapping is irrelevant to TeX; accepted for compatibility.
+        if matches!(key, "line_max" | "indent_length" | "indent_space") {
+            return Ok(());
+        }
+        let field = match key {
+            "keyword" => &mut self.keyword,
+            "page_compositor" => &mut self.page_compositor,
+            "preamble" => &mut self.preamble,
+            "postamble" => &mut self.postamble,
+            "group_skip" => &mut self.group_skip,
+            "heading_prefix" | "lethead_prefix" => &mut self.heading_prefix,
+            "heading_suffix" | "lethead_suffix" => &mut self.heading_suffix,
+            "symhead_positive" => &mut self.symhead_positive,
+            "symhead_negative" => &mut self.symhead_negative,
+            "numhead_positive" => &mut self.numhead_positive,
+            "numhead_negative" => &mut self.numhead_negative,
+            "item_0" => &mut self.item_0,
+            "item_1" => &mut self.item_1,
+            "item_2" => &mut self.item_2,
+            "item_01" => &mut self.item_01,
+            "item_x1" => &mut self.item_x1,
+            "item_12" => &mut self.item_12,
+            "item_x2" => &mut self.item_x2,
+            "delim_0" => &mut self.delim_0,
+            "delim_1" => &mut self.delim_1,
+            "delim_2" => &mut self.delim_2,
+            "delim_n" => &mut self.delim_n,
+            "delim_r" => &mut self.delim_r,
+            "delim_t" => &mut self.delim_t,
+            "encap_prefix" => &mut self.encap_prefix,
+            "encap_infix" => &mut self.encap_infix,
+            "encap_suffix" => &mut self.encap_suffix,
+            "suffix_2p" => &mut self.suffix_2p,
+            "suffix_3p" => &mut self.suffix_3p,
+            "suffix_mp" => &mut self.suffix_mp,
+            "page_precedence" => &mut self.page_precedence,
+            "setpage_prefix" | "setpage_suffix" => return Ok(()),
+            _ => return Err(format!("Unknown specifier `{key}'")),
+        };
+        match value {
+            Value::Str(s) => {
+                *field = s;
+                Ok(())
+            }
+            _ => Err(format!("Specifier `{key}' takes a string")),
+        }
+    }
+}
+
+/// Skips whitespace and `%` comments, counting newlines.
+fn skip_blank<'a>(mut text: &'a str, line: &mut usize) -> &'a str {
+    loop {
+        let trimmed = text.trim_start();
+        *line += text[..text.len() - trimmed.len()].matches('\n').count();
+        text = trimmed;
+        match text.strip_prefix('%') {
+            Some(comment) => text = comment.split_once('\n').map_or("", |(_, r)| r),
+            None => return text,
+        }
+        *line += 1;
+    }
+}
+
+/// `"…"` where `\n` and `\t` are control characters and `\` otherwise quotes
+/// the next character (`\\`, `\"`, `\%`); `'c'`; or a signed integer.
+fn read_value(text: &str) -> Option<(Value, &str)> {
+    let mut chars = text.char_indices();
+    match chars.next()?.1 {
+        '"' => {
+            let mut out = String::new();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => return Some((Value::Str(out), &text[i + 1..])),
+                    '\\' => match chars.next()?.1 {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        c => out.push(c),
+                    },
+                    c => out.push(c),
+                }
+            }
+            None
+        }
+        '\'' => {
+            let (_, mut c) = chars.next()?;
+            if c == '\\' {
+                c = match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    c => c,
+                };
+            }
+            let (i, close) = chars.next()?;
+            (close == '\'').then(|| (Value::Char(c), &text[i + 1..]))
+        }
+        _ => {
+            let end = text
+                .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+                .unwrap_or(text.len());
+            let n = text[..end].parse().ok()?;
+            Some((Value::Int(n), &text[end..]))
+        }
+    }
+}
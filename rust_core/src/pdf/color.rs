@@ -0,0 +1,1200 @@
+//! Color spaces, functions, shadings and image XObjects, all resolved to sRGB.
+//!
+//! ICC profiles are not applied: an `ICCBased` space is read as the device
+//! space with the same number of components. Images in `DCTDecode` go through
+//! [`super::jpeg`]; other codecs (`JPXDecode`, `JBIG2Decode`, `CCITTFaxDecode`)
+//! are drawn as a flat gray box.
+
+use std::rc::Rc;
+
+use super::file::PdfFile;
+use super::jpeg;
+use super::object::{Dict, Lexer, Object, Token};
+use super::raster::Image;
+
+/// Limit on image samples, so a corrupt `Width`/`Height` cannot exhaust memory.
+const MAX_IMAGE_PIXELS: usize = 64 << 20;
+
+#[derive(Debug, Clone)]
+pub enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// CIE L*a*b* with its white point.
+    Lab([f64; 3]),
+    Indexed {
+        base: Box<ColorSpace>,
+        hival: usize,
+        lookup: Vec<u8>,
+    },
+    /// `Separation` or `DeviceN`, through the tint transform.
+    Tinted {
+        components: usize,
+        alternate: Box<ColorSpace>,
+        tint: Option<Rc<Function>>,
+    },
+    /// The base space is used by uncolored tiling patterns only.
+    Pattern,
+}
+
+impl ColorSpace {
+    pub fn parse(file: &PdfFile, obj: &Object, resources: Option<&Dict>) -> ColorSpace {
+        Self::parse_at_depth(file, obj, resources, 0)
+    }
+
+    fn parse_at_depth(
+        file: &PdfFile,
+        obj: &Object,
+        resources: Option<&Dict>,
+        depth: usize,
+    ) -> ColorSpace {
+        if depth > 8 {
+            return ColorSpace::Gray;
+        }
+        let obj = file.resolve(obj);
+        match &*obj {
+            Object::Name(n) => match n.as_str() {
+                "DeviceGray" | "G" | "CalGray" => ColorSpace::Gray,
+                "DeviceRGB" | "RGB" | "CalRGB" => ColorSpace::Rgb,
+                "DeviceCMYK" | "CMYK" => ColorSpace::Cmyk,
+                "Pattern" => ColorSpace::Pattern,
+                name => {
+                    // A name in the page's `/ColorSpace` resources.
+                    let named = resources
+                        .and_then(|r| file.dict(r.get("ColorSpace")?))
+                        .and_then(|cs| cs.get(name).cloned());
+                    match named {
+                        Some(o) => Self::parse_at_depth(file, &o, None, depth + 1),
+                        None => ColorSpace::Gray,
+                    }
+                }
+            },
+            Object::Array(items) => {
+                let family = items.first().and_then(Object::as_name).unwrap_or("");
+                let arg = |i: usize| items.get(i).map(|o| file.resolve(o));
+                match family {
+                    "CalGray" => ColorSpace::Gray,
+                    "CalRGB" => ColorSpace::Rgb,
+                    "Lab" => {
+                        let white = arg(1)
+                            .and_then(|d| file.dict(&d))
+                            .and_then(|d| d.get("WhitePoint").cloned())
+                            .and_then(|w| {
+                                let v: Vec<f64> =
+                                    w.as_array()?.iter().filter_map(Object::as_f64).collect();
+                                (v.len() == 3).then(|| [v[0], v[1], v[2]])
+                            })
+                            .unwrap_or([0.9505, 1.0, 1.089]);
+                        ColorSpace::Lab(white)
+                    }
+                    "ICCBased" => {
+                        let dict = arg(1)
+                            .and_then(|s| s.as_dict().cloned())
+                            .unwrap_or_default();
+                        if let Some(alt) = dict.get("Alternate") {
+                            return Self::parse_at_depth(file, alt, resources, depth + 1);
+                        }
+                        match file.number(&dict, "N").unwrap_or(3.0) as usize {
+                            1 => ColorSpace::Gray,
+                            4 => ColorSpace::Cmyk,
+                            _ => ColorSpace::Rgb,
+                        }
+                    }
+                    "Indexed" | "I" => {
+                        let base = items
+                            .get(1)
+                            .map(|b| Self::parse_at_depth(file, b, resources, depth + 1))
+                            .unwrap_or(ColorSpace::Rgb);
+                        let hival =
+                            arg(2).and_then(|h| h.as_i64()).unwrap_or(0).clamp(0, 255) as usize;
+                        let lookup = match items.get(3) {
+                            Some(o) => match &*file.resolve(o) {
+                                Object::Str(s) => s.clone(),
+                                Object::Stream(_) => file.stream_data(o).unwrap_or_default(),
+                                _ => Vec::new(),
+                            },
+                            None => Vec::new(),
+                        };
+                        ColorSpace::Indexed {
+                            base: Box::new(base),
+                            hival,
+                            lookup,
+                        }
+                    }
+                    "Separation" | "DeviceN" => {
+                        let components = if family == "Separation" {
+                            1
+                        } else {
+                            arg(1)
+                                .and_then(|n| n.as_array().map(<[Object]>::len))
+                                .unwrap_or(1)
+                        };
+                        let alternate = items
+                            .get(2)
+                            .map(|a| Self::parse_at_depth(file, a, resources, depth + 1))
+                            .unwrap_or(ColorSpace::Gray);
+                        let tint = items
+                            .get(3)
+                            .and_then(|f| Function::parse(file, f))
+                            .map(Rc::new);
+                        ColorSpace::Tinted {
+                            components,
+                            alternate: Box::new(alternate),
+                            tint,
+                        }
+                    }
+                    "Pattern" => ColorSpace::Pattern,
+                    _ => Self::parse_at_depth(
+                        file,
+                        &Object::Name(family.to_string()),
+                        resources,
+                        depth + 1,
+                    ),
+                }
+            }
+            _ => ColorSpace::Gray,
+        }
+    }
+
+    pub fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } | ColorSpace::Pattern => 1,
+            ColorSpace::Rgb | ColorSpace::Lab(_) => 3,
+            ColorSpace::Cmyk => 4,
+            ColorSpace::Tinted { components, .. } => *components,
+        }
+    }
+
+    /// The color a newly selected space starts with.
+    pub fn initial(&self) -> Vec<f64> {
+        match self {
+            ColorSpace::Cmyk => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Tinted { components, .. } => vec![1.0; *components],
+            ColorSpace::Lab(_) => vec![0.0, 0.0, 0.0],
+            _ => vec![0.0; self.components()],
+        }
+    }
+
+    /// The decode range images use by default.
+    fn default_decode(&self, bpc: usize) -> Vec<(f64, f64)> {
+        match self {
+            ColorSpace::Indexed { .. } => vec![(0.0, ((1usize << bpc) - 1) as f64)],
+            ColorSpace::Lab(_) => vec![(0.0, 100.0), (-100.0, 100.0), (-100.0, 100.0)],
+            _ => vec![(0.0, 1.0); self.components()],
+        }
+    }
+
+    pub fn to_rgb(&self, c: &[f64]) -> [u8; 3] {
+        let at = |i: usize| c.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        let byte = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        match self {
+            ColorSpace::Gray | ColorSpace::Pattern => {
+                let g = byte(at(0));
+                [g, g, g]
+            }
+            ColorSpace::Rgb => [byte(at(0)), byte(at(1)), byte(at(2))],
+            ColorSpace::Cmyk => {
+                let k = at(3);
+                [
+                    byte((1.0 - at(0)) * (1.0 - k)),
+                    byte((1.0 - at(1)) * (1.0 - k)),
+                    byte((1.0 - at(2)) * (1.0 - k)),
+                ]
+            }
+            ColorSpace::Lab(white) => lab_to_rgb(c, white),
+            ColorSpace::Indexed {
+                base,
+                hival,
+                lookup,
+                ..
+            } => {
+                let index =
+                    (c.first().copied().unwrap_or(0.0).round().max(0.0) as usize).min(*hival);
+                let n = base.components();
+                let entry: Vec<f64> = (0..n)
+                    .map(|k| lookup.get(index * n + k).copied().unwrap_or(0) as f64 / 255.0)
+                    .collect();
+                // Lab lookups are stored scaled to bytes over their ranges.
+                if let ColorSpace::Lab(_) = **base {
+                    let ranges = base.default_decode(8);
+                    let scaled: Vec<f64> = entry
+                        .iter()
+                        .zip(ranges)
+                        .map(|(v, (lo, hi))| lo + v * (hi - lo))
+                        .collect();
+                    return base.to_rgb(&scaled);
+                }
+                base.to_rgb(&entry)
+            }
+            ColorSpace::Tinted {
+                alternate, tint, ..
+            } => match tint {
+                Some(f) => alternate.to_rgb(&f.eval(c)),
+                // No usable tint transform: show the tint as darkness.
+                None => {
+                    let g = byte(1.0 - at(0));
+                    [g, g, g]
+                }
+            },
+        }
+    }
+}
+
+fn lab_to_rgb(c: &[f64], white: &[f64; 3]) -> [u8; 3] {
+    let (l, a, b) = (
+        c.first().copied().unwrap_or(0.0),
+        c.get(1).copied().unwrap_or(0.0),
+        c.get(2).copied().unwrap_or(0.0),
+    );
+    let fy = (l + 16.0) / 116.0;
+    let (fx, fz) = (fy + a / 500.0, fy - b / 200.0);
+    let g = |t: f64| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            108.0 / 841.0 * (t - 4.0 / 29.0)
+        }
+    };
+    let (x, y, z) = (white[0] * g(fx), white[1] * g(fy), white[2] * g(fz));
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let gr = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let bl = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    let gamma = |v: f64| {
+        let v = v.clamp(0.0, 1.0);
+        let s = if v <= 0.0031308 {
+            12.92 * v
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        };
+        (s * 255.0).round() as u8
+    };
+    [gamma(r), gamma(gr), gamma(bl)]
+}
+
+/// PDF function types 0 (sampled), 2 (exponential), 3 (stitching) and 4
+/// (PostScript calculator).
+#[derive(Debug)]
+pub enum Function {
+    Sampled {
+        domain: Vec<(f64, f64)>,
+        range: Vec<(f64, f64)>,
+        size: Vec<usize>,
+        encode: Vec<(f64, f64)>,
+        decode: Vec<(f64, f64)>,
+        samples: Vec<f64>,
+    },
+    Exponential {
+        domain: (f64, f64),
+        c0: Vec<f64>,
+        c1: Vec<f64>,
+        n: f64,
+    },
+    Stitching {
+        domain: (f64, f64),
+        functions: Vec<Function>,
+        bounds: Vec<f64>,
+        encode: Vec<(f64, f64)>,
+    },
+    PostScript {
+        domain: Vec<(f64, f64)>,
+        range: Vec<(f64, f64)>,
+        program: Vec<PsOp>,
+    },
+    /// An array of 1-output functions, one per output.
+    Multi(Vec<Function>),
+}
+
+fn pairs(v: &[f64]) -> Vec<(f64, f64)> {
+    v.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+}
+
+impl Function {
+    pub fn parse(file: &PdfFile, obj: &Object) -> Option<Function> {
+        Self::parse_at_depth(file, obj, 0)
+    }
+
+    fn parse_at_depth(file: &PdfFile, obj: &Object, depth: usize) -> Option<Function> {
+        if depth > 8 {
+            return None;
+        }
+        let resolved = file.resolve(obj);
+        if let Object::Array(items) = &*resolved {
+            return items
+                .iter()
+                .map(|f| Self::parse_at_depth(file, f, depth + 1))
+                .collect::<Option<Vec<_>>>()
+                .map(Function::Multi);
+        }
+        let dict = resolved.as_dict()?.clone();
+        let nums = |key: &str| -> Vec<f64> {
+            file.lookup(&dict, key)
+                .as_array()
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(|o| file.resolve(o).as_f64())
+                .collect()
+        };
+        let domain = pairs(&nums("Domain"));
+        let first_domain = domain.first().copied().unwrap_or((0.0, 1.0));
+        match file.number(&dict, "FunctionType")? as i64 {
+            0 => {
+                let size: Vec<usize> = nums("Size").iter().map(|&s| s.max(1.0) as usize).collect();
+                let range = pairs(&nums("Range"));
+                let bps = file.number(&dict, "BitsPerSample").unwrap_or(8.0) as usize;
+                let mut encode = pairs(&nums("Encode"));
+                if encode.len() < size.len() {
+                    encode = size.iter().map(|&s| (0.0, (s - 1) as f64)).collect();
+                }
+                let mut decode = pairs(&nums("Decode"));
+                if decode.len() < range.len() {
+                    decode = range.clone();
+                }
+                let data = file.stream_data(obj)?;
+                let count = size.iter().product::<usize>() * range.len();
+                let max = ((1u64 << bps.min(32)) - 1) as f64;
+                let samples = read_samples(&data, bps, count)
+                    .into_iter()
+                    .map(|v| v as f64 / max)
+                    .collect();
+                Some(Function::Sampled {
+                    domain,
+                    range,
+                    size,
+                    encode,
+                    decode,
+                    samples,
+                })
+            }
+            2 => {
+                let c0 = Some(nums("C0"))
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or(vec![0.0]);
+                let c1 = Some(nums("C1"))
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or(vec![1.0]);
+                Some(Function::Exponential {
+                    domain: first_domain,
+                    c0,
+                    c1,
+                    n: file.number(&dict, "N").unwrap_or(1.0),
+                })
+            }
+            3 => {
+                let functions = file
+                    .lookup(&dict, "Functions")
+                    .as_array()?
+                    .iter()
+                    .map(|f| Self::parse_at_depth(file, f, depth + 1))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Function::Stitching {
+                    domain: first_domain,
+                    functions,
+                    bounds: nums("Bounds"),
+                    encode: pairs(&nums("Encode")),
+                })
+            }
+            4 => {
+                let code = file.stream_data(obj)?;
+                Some(Function::PostScript {
+                    domain,
+                    range: pairs(&nums("Range")),
+                    program: parse_ps(&mut Lexer::new(&code, 0)),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn eval(&self, input: &[f64]) -> Vec<f64> {
+        match self {
+            Function::Sampled {
+                domain,
+                range,
+                size,
+                encode,
+                decode,
+                samples,
+            } => {
+                // Multilinear interpolation would be more exact; the nearest
+                // lower sample with linear blending along the first input is
+                // what one-input shadings need.
+                let outputs = range.len();
+                let mut index = 0usize;
+                let mut stride = 1usize;
+                let mut frac = 0.0;
+                for (i, &(d0, d1)) in domain.iter().enumerate() {
+                    let x = input
+                        .get(i)
+                        .copied()
+                        .unwrap_or(0.0)
+                        .clamp(d0.min(d1), d0.max(d1));
+                    let (e0, e1) = encode.get(i).copied().unwrap_or((0.0, 0.0));
+                    let e = if d1 == d0 {
+                        e0
+                    } else {
+                        e0 + (x - d0) * (e1 - e0) / (d1 - d0)
+                    };
+                    let e = e.clamp(0.0, (size[i] - 1) as f64);
+                    let lower = e.floor() as usize;
+                    if i == 0 {
+                        frac = e - lower as f64;
+                    }
+                    index += lower * stride;
+                    stride *= size[i];
+                }
+                (0..outputs)
+                    .map(|o| {
+                        let s0 = samples.get(index * outputs + o).copied().unwrap_or(0.0);
+                        let s1 = if frac > 0.0 {
+                            samples
+                                .get((index + 1) * outputs + o)
+                                .copied()
+                                .unwrap_or(s0)
+                        } else {
+                            s0
+                        };
+                        let s = s0 + (s1 - s0) * frac;
+                        let (lo, hi) = decode.get(o).copied().unwrap_or((0.0, 1.0));
+                        let (r0, r1) = range[o];
+                        (lo + s * (hi - lo)).clamp(r0.min(r1), r0.max(r1))
+                    })
+                    .collect()
+            }
+            Function::Exponential { domain, c0, c1, n } => {
+                let x = input
+                    .first()
+                    .copied()
+                    .unwrap_or(0.0)
+                    .clamp(domain.0, domain.1);
+                let xn = x.powf(*n);
+                c0.iter().zip(c1).map(|(a, b)| a + xn * (b - a)).collect()
+            }
+            Function::Stitching {
+                domain,
+                functions,
+                bounds,
+                encode,
+            } => {
+                let x = input
+                    .first()
+                    .copied()
+                    .unwrap_or(0.0)
+                    .clamp(domain.0, domain.1);
+                let k = bounds
+                    .iter()
+                    .take_while(|&&b| x >= b)
+                    .count()
+                    .min(functions.len().saturating_sub(1));
+                let lo = if k == 0 { domain.0 } else { bounds[k - 1] };
+                let hi = bounds.get(k).copied().unwrap_or(domain.1);
+                let (e0, e1) = encode.get(k).copied().unwrap_or((0.0, 1.0));
+                let t = if hi == lo {
+                    e0
+                } else {
+                    e0 + (x - lo) * (e1 - e0) / (hi - lo)
+                };
+                functions.get(k).map_or_else(Vec::new, |f| f.eval(&[t]))
+            }
+            Function::PostScript {
+                domain,
+                range,
+                program,
+            } => {
+                let mut stack: Vec<f64> = domain
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(d0, d1))| input.get(i).copied().unwrap_or(0.0).clamp(d0, d1))
+                    .collect();
+                run_ps(program, &mut stack, 0);
+                let n = range.len();
+                let start = stack.len().saturating_sub(n);
+                stack[start..]
+                    .iter()
+                    .zip(range)
+                    .map(|(v, &(r0, r1))| v.clamp(r0, r1))
+                    .collect()
+            }
+            Function::Multi(fs) => fs.iter().flat_map(|f| f.eval(input)).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PsOp {
+    Num(f64),
+    Op(String),
+    If(Vec<PsOp>),
+    IfElse(Vec<PsOp>, Vec<PsOp>),
+}
+
+/// `{ … }` including the braces.
+fn parse_ps(lexer: &mut Lexer) -> Vec<PsOp> {
+    let mut out = Vec::new();
+    // Skip to the opening brace.
+    loop {
+        match lexer.next_token() {
+            Some(Token::Keyword(k)) if k == "{" => break,
+            None => return out,
+            _ => {}
+        }
+    }
+    let mut blocks: Vec<Vec<PsOp>> = Vec::new();
+    while let Some(token) = lexer.next_token() {
+        match token {
+            Token::Int(n) => out.push(PsOp::Num(n as f64)),
+            Token::Real(r) => out.push(PsOp::Num(r)),
+            Token::Keyword(k) => match k.as_str() {
+                "{" => {
+                    lexer.pos -= 1;
+                    blocks.push(parse_ps(lexer));
+                }
+                "}" => return out,
+                "if" => {
+                    if let Some(b) = blocks.pop() {
+                        out.push(PsOp::If(b));
+                    }
+                }
+                "ifelse" => {
+                    if let (Some(b), Some(a)) = (blocks.pop(), blocks.pop()) {
+                        out.push(PsOp::IfElse(a, b));
+                    }
+                }
+                _ => out.push(PsOp::Op(k)),
+            },
+            _ => {}
+        }
+    }
+    out
+}
+
+fn run_ps(program: &[PsOp], s: &mut Vec<f64>, depth: usize) {
+    if depth > 16 {
+        return;
+    }
+    let pop = |s: &mut Vec<f64>| s.pop().unwrap_or(0.0);
+    let truth = |b: bool| if b { 1.0 } else { 0.0 };
+    for op in program {
+        match op {
+            PsOp::Num(n) => s.push(*n),
+            PsOp::If(block) => {
+                if pop(s) != 0.0 {
+                    run_ps(block, s, depth + 1);
+                }
+            }
+            PsOp::IfElse(a, b) => {
+                let block = if pop(s) != 0.0 { a } else { b };
+                run_ps(block, s, depth + 1);
+            }
+            PsOp::Op(name) => {
+                let name = name.as_str();
+                match name {
+                    "abs" | "neg" | "ceiling" | "floor" | "round" | "truncate" | "sqrt" | "sin"
+                    | "cos" | "ln" | "log" | "cvi" | "cvr" | "not" => {
+                        let a = pop(s);
+                        s.push(match name {
+                            "abs" => a.abs(),
+                            "neg" => -a,
+                            "ceiling" => a.ceil(),
+                            "floor" => a.floor(),
+                            "round" => (a + 0.5).floor(),
+                            "truncate" | "cvi" => a.trunc(),
+                            "sqrt" => a.max(0.0).sqrt(),
+                            "sin" => a.to_radians().sin(),
+                            "cos" => a.to_radians().cos(),
+                            "ln" => a.ln(),
+                            "log" => a.log10(),
+                            "not" => truth(a == 0.0),
+                            _ => a,
+                        });
+                    }
+                    "add" | "sub" | "mul" | "div" | "idiv" | "mod" | "exp" | "atan" | "eq"
+                    | "ne" | "gt" | "ge" | "lt" | "le" | "and" | "or" | "xor" | "bitshift" => {
+                        let b = pop(s);
+                        let a = pop(s);
+                        s.push(match name {
+                            "add" => a + b,
+                            "sub" => a - b,
+                            "mul" => a * b,
+                            "div" => {
+                                if b == 0.0 {
+                                    0.0
+                                } else {
+                                    a / b
+                                }
+                            }
+                            "idiv" => {
+                                if b == 0.0 {
+                                    0.0
+                                } else {
+                                    (a / b).trunc()
+                                }
+                            }
+                            "mod" => {
+                                if b == 0.0 {
+                                    0.0
+                                } else {
+                                    a % b
+                                }
+                            }
+                            "exp" => a.powf(b),
+                            "atan" => a.atan2(b).to_degrees().rem_euclid(360.0),
+                            "eq" => truth(a == b),
+                            "ne" => truth(a != b),
+                            "gt" => truth(a > b),
+                            "ge" => truth(a >= b),
+                            "lt" => truth(a < b),
+                            "le" => truth(a <= b),
+                            "and" => ((a as i64) & (b as i64)) as f64,
+                            "or" => ((a as i64) | (b as i64)) as f64,
+                            "xor" => ((a as i64) ^ (b as i64)) as f64,
+                            "bitshift" => {
+                                let (a, b) = (a as i64, b as i64);
+                                (if b >= 0 {
+                                    a << b.min(63)
+                                } else {
+                                    a >> (-b).min(63)
+                                }) as f64
+                            }
+                            _ => a,
+                        });
+                    }
+                    "true" => s.push(1.0),
+                    "false" => s.push(0.0),
+                    "pop" => {
+                        s.pop();
+                    }
+                    "dup" => {
+                        let a = s.last().copied().unwrap_or(0.0);
+                        s.push(a);
+                    }
+                    "exch" => {
+                        let n = s.len();
+                        if n >= 2 {
+                            s.swap(n - 1, n - 2);
+                        }
+                    }
+                    "copy" => {
+                        let n = pop(s).max(0.0) as usize;
+                        let start = s.len().saturating_sub(n);
+                        let copied: Vec<f64> = s[start..].to_vec();
+                        s.extend(copied);
+                    }
+                    "index" => {
+                        let n = pop(s).max(0.0) as usize;
+                        let v = s.len().checked_sub(n + 1).map_or(0.0, |i| s[i]);
+                        s.push(v);
+                    }
+                    "roll" => {
+                        let j = pop(s) as i64;
+                        let n = pop(s).max(0.0) as usize;
+                        let len = s.len();
+                        if n > 0 && n <= len {
+                            s[len - n..].rotate_right(j.rem_euclid(n as i64) as usize);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Shading types 1 (function-based), 2 (axial) and 3 (radial). Mesh shadings
+/// (4–7) paint their `Background`, if any.
+pub struct Shading {
+    kind: ShadingKind,
+    space: ColorSpace,
+    function: Option<Function>,
+    pub background: Option<[u8; 3]>,
+    /// Region outside of which nothing is painted, in shading space.
+    pub bbox: Option<[f64; 4]>,
+}
+
+enum ShadingKind {
+    Function {
+        domain: [f64; 4],
+        matrix: super::raster::Matrix,
+    },
+    Axial {
+        coords: [f64; 4],
+        domain: (f64, f64),
+        extend: (bool, bool),
+    },
+    Radial {
+        coords: [f64; 6],
+        domain: (f64, f64),
+        extend: (bool, bool),
+    },
+    Unsupported,
+}
+
+impl Shading {
+    pub fn parse(file: &PdfFile, obj: &Object, resources: Option<&Dict>) -> Option<Shading> {
+        let dict = file.dict(obj)?;
+        let nums = |key: &str| -> Vec<f64> {
+            file.lookup(&dict, key)
+                .as_array()
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(|o| file.resolve(o).as_f64())
+                .collect()
+        };
+        let space = ColorSpace::parse(file, dict.get("ColorSpace")?, resources);
+        let function = dict.get("Function").and_then(|f| Function::parse(file, f));
+        let background = match nums("Background") {
+            v if v.len() == space.components() => Some(space.to_rgb(&v)),
+            _ => None,
+        };
+        let bbox = match nums("BBox")[..] {
+            [a, b, c, d] => Some([a.min(c), b.min(d), a.max(c), b.max(d)]),
+            _ => None,
+        };
+        let extend = match file.lookup(&dict, "Extend").as_array() {
+            Some([Object::Bool(a), Object::Bool(b)]) => (*a, *b),
+            _ => (false, false),
+        };
+        let domain = match nums("Domain")[..] {
+            [a, b] => (a, b),
+            _ => (0.0, 1.0),
+        };
+        let kind = match file.number(&dict, "ShadingType")? as i64 {
+            1 => {
+                let d = match nums("Domain")[..] {
+                    [a, b, c, e] => [a, b, c, e],
+                    _ => [0.0, 1.0, 0.0, 1.0],
+                };
+                let matrix = match nums("Matrix")[..] {
+                    [a, b, c, d, e, f] => super::raster::Matrix([a, b, c, d, e, f]),
+                    _ => super::raster::Matrix::IDENTITY,
+                };
+                ShadingKind::Function { domain: d, matrix }
+            }
+            2 => match nums("Coords")[..] {
+                [a, b, c, d] => ShadingKind::Axial {
+                    coords: [a, b, c, d],
+                    domain,
+                    extend,
+                },
+                _ => ShadingKind::Unsupported,
+            },
+            3 => match nums("Coords")[..] {
+                [a, b, c, d, e, f] => ShadingKind::Radial {
+                    coords: [a, b, c, d, e, f],
+                    domain,
+                    extend,
+                },
+                _ => ShadingKind::Unsupported,
+            },
+            _ => ShadingKind::Unsupported,
+        };
+        Some(Shading {
+            kind,
+            space,
+            function,
+            background,
+            bbox,
+        })
+    }
+
+    fn color(&self, t: &[f64]) -> [u8; 3] {
+        match &self.function {
+            Some(f) => self.space.to_rgb(&f.eval(t)),
+            None => self.space.to_rgb(t),
+        }
+    }
+
+    /// Color at `(x, y)` in shading space; `None` outside the painted area.
+    pub fn color_at(&self, x: f64, y: f64) -> Option<[u8; 3]> {
+        if let Some([x0, y0, x1, y1]) = self.bbox {
+            if x < x0 || x > x1 || y < y0 || y > y1 {
+                return None;
+            }
+        }
+        match &self.kind {
+            ShadingKind::Function { domain, matrix } => {
+                let (u, v) = matrix.invert()?.apply(x, y);
+                if u < domain[0] || u > domain[1] || v < domain[2] || v > domain[3] {
+                    return self.background;
+                }
+                Some(self.color(&[u, v]))
+            }
+            ShadingKind::Axial {
+                coords,
+                domain,
+                extend,
+            } => {
+                let [x0, y0, x1, y1] = *coords;
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let len2 = dx * dx + dy * dy;
+                let s = if len2 == 0.0 {
+                    0.0
+                } else {
+                    ((x - x0) * dx + (y - y0) * dy) / len2
+                };
+                let s = extended(s, *extend).or(self.background.map(|_| f64::NAN))?;
+                if s.is_nan() {
+                    return self.background;
+                }
+                Some(self.color(&[domain.0 + s * (domain.1 - domain.0)]))
+            }
+            ShadingKind::Radial {
+                coords,
+                domain,
+                extend,
+            } => {
+                let s = radial_parameter(coords, x, y, *extend);
+                match s {
+                    Some(s) => Some(self.color(&[domain.0 + s * (domain.1 - domain.0)])),
+                    None => self.background,
+                }
+            }
+            ShadingKind::Unsupported => self.background,
+        }
+    }
+}
+
+fn extended(s: f64, extend: (bool, bool)) -> Option<f64> {
+    if s < 0.0 {
+        extend.0.then_some(0.0)
+    } else if s > 1.0 {
+        extend.1.then_some(1.0)
+    } else {
+        Some(s)
+    }
+}
+
+/// Largest `s` whose circle (interpolated between the two circles) passes
+/// through `(x, y)`, as the spec prescribes.
+fn radial_parameter(c: &[f64; 6], x: f64, y: f64, extend: (bool, bool)) -> Option<f64> {
+    let [x0, y0, r0, x1, y1, r1] = *c;
+    let (cdx, cdy, dr) = (x1 - x0, y1 - y0, r1 - r0);
+    let (px, py) = (x - x0, y - y0);
+    let a = cdx * cdx + cdy * cdy - dr * dr;
+    let b = px * cdx + py * cdy + r0 * dr;
+    let cc = px * px + py * py - r0 * r0;
+    let candidates: Vec<f64> = if a.abs() < 1e-9 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        vec![cc / (2.0 * b)]
+    } else {
+        let disc = b * b - a * cc;
+        if disc < 0.0 {
+            return None;
+        }
+        let sq = disc.sqrt();
+        let (s1, s2) = ((b + sq) / a, (b - sq) / a);
+        vec![s1.max(s2), s1.min(s2)]
+    };
+    for s in candidates {
+        if r0 + s * dr < 0.0 {
+            continue;
+        }
+        if let Some(e) = extended(s, extend) {
+            return Some(e);
+        }
+    }
+    None
+}
+
+/// Unpacks `count` samples of `bits` bits each (rows are not padded here).
+fn read_samples(data: &[u8], bits: usize, count: usize) -> Vec<u32> {
+    let mut out = Vec::with_capacity(count.min(data.len() * 8));
+    let (mut acc, mut have) = (0u64, 0usize);
+    for &b in data {
+        acc = acc << 8 | b as u64;
+        have += 8;
+        while have >= bits && out.len() < count {
+            have -= bits;
+            out.push(((acc >> have) & ((1u64 << bits) - 1)) as u32);
+        }
+        acc &= (1u64 << have) - 1;
+        if out.len() >= count {
+            break;
+        }
+    }
+    out
+}
+
+/// A decoded image or stencil mask, ready for [`super::raster::Canvas::draw_image`].
+pub fn decode_image(
+    file: &PdfFile,
+    obj: &Object,
+    resources: Option<&Dict>,
+    fill: [u8; 3],
+) -> Option<Image> {
+    let stream = file.resolve(obj);
+    let dict = stream.as_dict()?.clone();
+    decode_image_with(file, &dict, obj, resources, fill, None)
+}
+
+/// Image from an inline `BI … ID … EI` block, its abbreviations expanded.
+pub fn decode_inline_image(
+    file: &PdfFile,
+    dict: &Dict,
+    data: Vec<u8>,
+    resources: Option<&Dict>,
+    fill: [u8; 3],
+) -> Option<Image> {
+    decode_image_with(file, dict, &Object::Null, resources, fill, Some(data))
+}
+
+fn decode_image_with(
+    file: &PdfFile,
+    dict: &Dict,
+    obj: &Object,
+    resources: Option<&Dict>,
+    fill: [u8; 3],
+    inline: Option<Vec<u8>>,
+) -> Option<Image> {
+    let num = |keys: &[&str]| keys.iter().find_map(|k| file.number(dict, k));
+    let width = num(&["Width", "W"])?.max(0.0) as usize;
+    let height = num(&["Height", "H"])?.max(0.0) as usize;
+    if width == 0 || height == 0 || width.saturating_mul(height) > MAX_IMAGE_PIXELS {
+        return None;
+    }
+    let is_mask = matches!(
+        ["ImageMask", "IM"].iter().find_map(|k| dict.get(*k)),
+        Some(Object::Bool(true))
+    );
+    let filters: Vec<String> = match ["Filter", "F"]
+        .iter()
+        .find_map(|k| dict.get(*k))
+        .map(|f| file.resolve(f))
+    {
+        Some(f) => match &*f {
+            Object::Name(n) => vec![n.clone()],
+            Object::Array(a) => a
+                .iter()
+                .filter_map(|x| x.as_name().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    let data = match inline {
+        Some(raw) => {
+            let stream = super::object::Stream {
+                dict: expand_inline_keys(dict),
+                data: raw,
+            };
+            super::file::decode_stream(&stream).ok()?
+        }
+        None => file.stream_data(obj)?,
+    };
+    let last_filter = filters.last().map(String::as_str);
+    let decode_array: Vec<f64> = ["Decode", "D"]
+        .iter()
+        .find_map(|k| dict.get(*k))
+        .map(|d| file.resolve(d))
+        .and_then(|d| {
+            d.as_array()
+                .map(|a| a.iter().filter_map(Object::as_f64).collect())
+        })
+        .unwrap_or_default();
+
+    let mut rgba = vec![255u8; width * height * 4];
+    if is_mask {
+        let inverted = decode_array.first() == Some(&1.0);
+        let samples = read_rows(&data, 1, 1, width, height);
+        for (i, &s) in samples.iter().enumerate() {
+            // Sample 0 paints unless the decode array is [1 0].
+            let paint = (s == 0) != inverted;
+            let px = &mut rgba[i * 4..i * 4 + 4];
+            px[..3].copy_from_slice(&fill);
+            px[3] = if paint { 255 } else { 0 };
+        }
+        return Some(Image {
+            width,
+            height,
+            rgba,
+        });
+    }
+
+    let space = ["ColorSpace", "CS"]
+        .iter()
+        .find_map(|k| dict.get(*k))
+        .map(|cs| ColorSpace::parse(file, &expand_inline_name(cs), resources));
+    match last_filter {
+        Some("DCTDecode" | "DCT") => {
+            let decoded = jpeg::decode(&data);
+            match decoded {
+                Some(j) if j.width == width && j.height == height => {
+                    let cmyk_inverted = j.components == 4 && j.adobe_inverted;
+                    for i in 0..width * height {
+                        let px = &j.pixels[i * j.components..(i + 1) * j.components];
+                        let color = match j.components {
+                            1 => [px[0]; 3],
+                            3 => [px[0], px[1], px[2]],
+                            4 => {
+                                let c: Vec<f64> = px
+                                    .iter()
+                                    .map(|&v| {
+                                        let v = v as f64 / 255.0;
+                                        if cmyk_inverted {
+                                            1.0 - v
+                                        } else {
+                                            v
+                                        }
+                                    })
+                                    .collect();
+                                ColorSpace::Cmyk.to_rgb(&c)
+                            }
+                            _ => [128; 3],
+                        };
+                        rgba[i * 4..i * 4 + 3].copy_from_slice(&color);
+                    }
+                }
+                _ => gray_box(&mut rgba),
+            }
+        }
+        Some("JPXDecode" | "JBIG2Decode" | "CCITTFaxDecode" | "CCF") => gray_box(&mut rgba),
+        _ => {
+            let space = space.unwrap_or(ColorSpace::Gray);
+            let bpc = num(&["BitsPerComponent", "BPC"]).unwrap_or(8.0) as usize;
+            if !matches!(bpc, 1 | 2 | 4 | 8 | 16) {
+                return None;
+            }
+            let n = space.components();
+            let samples = read_rows(&data, bpc, n, width, height);
+            let max = ((1u32 << bpc) - 1) as f64;
+            let ranges: Vec<(f64, f64)> = if decode_array.len() >= 2 * n {
+                pairs(&decode_array)
+            } else {
+                space.default_decode(bpc)
+            };
+            let color_key = color_key_mask(file, dict, n);
+            let mut comps = vec![0.0; n];
+            // Small palettes of 8-bit gray/RGB need no per-pixel float math.
+            for i in 0..width * height {
+                let raw = &samples[i * n..(i + 1) * n];
+                for (k, c) in comps.iter_mut().enumerate() {
+                    let (lo, hi) = ranges.get(k).copied().unwrap_or((0.0, 1.0));
+                    *c = lo + raw[k] as f64 * (hi - lo) / max;
+                }
+                let color = space.to_rgb(&comps);
+                let px = &mut rgba[i * 4..i * 4 + 4];
+                px[..3].copy_from_slice(&color);
+                if let Some(key) = &color_key {
+                    if raw
+                        .iter()
+                        .zip(key)
+                        .all(|(&v, &(lo, hi))| (lo..=hi).contains(&v))
+                    {
+                        px[3] = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    // Soft mask, or a stencil `Mask` stream.
+    let smask = dict.get("SMask").map(|s| (s, false)).or_else(|| {
+        dict.get("Mask")
+            .filter(|m| matches!(&*file.resolve(m), Object::Stream(_)))
+            .map(|m| (m, true))
+    });
+    if let Some((mask_obj, stencil)) = smask {
+        let mask = if stencil {
+            // Stencil mask: 1 marks the area to leave out, decoded like a mask image.
+            decode_image(file, mask_obj, resources, [0, 0, 0]).map(|mut m| {
+                for px in m.rgba.chunks_exact_mut(4) {
+                    px[0] = px[3];
+                }
+                m
+            })
+        } else {
+            decode_image(file, mask_obj, resources, [0, 0, 0])
+        };
+        if let Some(mask) = mask {
+            for y in 0..height {
+                let my = y * mask.height / height;
+                for x in 0..width {
+                    let mx = x * mask.width / width;
+                    let m = mask.rgba[(my * mask.width + mx) * 4];
+                    let a = &mut rgba[(y * width + x) * 4 + 3];
+                    *a = (*a as u16 * m as u16 / 255) as u8;
+                }
+            }
+        }
+    }
+    Some(Image {
+        width,
+        height,
+        rgba,
+    })
+}
+
+/// A flat placeholder for images in codecs this renderer does not decode.
+fn gray_box(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        px.copy_from_slice(&[204, 204, 204, 255]);
+    }
+}
+
+/// `(min, max)` per component from a color-key `Mask` array.
+fn color_key_mask(file: &PdfFile, dict: &Dict, n: usize) -> Option<Vec<(u32, u32)>> {
+    let mask = file.lookup(dict, "Mask");
+    let values: Vec<u32> = mask
+        .as_array()?
+        .iter()
+        .filter_map(|v| v.as_i64())
+        .map(|v| v.max(0) as u32)
+        .collect();
+    (values.len() >= 2 * n).then(|| values.chunks_exact(2).map(|p| (p[0], p[1])).collect())
+}
+
+/// Samples of an image whose rows are padded to whole bytes.
+fn read_rows(data: &[u8], bpc: usize, components: usize, width: usize, height: usize) -> Vec<u32> {
+    let row_bytes = (bpc * components * width).div_ceil(8);
+    let per_row = components * width;
+    let mut out = Vec::with_capacity(per_row * height);
+    for y in 0..height {
+        let start = (y * row_bytes).min(data.len());
+        let end = (start + row_bytes).min(data.len());
+        let mut row = read_samples(&data[start..end], bpc, per_row);
+        row.resize(per_row, 0);
+        out.extend(row);
+    }
+    out
+}
+
+/// Full names for the abbreviated keys of inline images, for the filter code.
+fn expand_inline_keys(dict: &Dict) -> Dict {
+    dict.iter()
+        .map(|(k, v)| {
+            let key = match k.as_str() {
+                "F" => "Filter",
+                "DP" => "DecodeParms",
+                other => other,
+            };
+            let value = match v {
+                Object::Name(_) => expand_inline_name(v),
+                Object::Array(a) => Object::Array(a.iter().map(expand_inline_name).collect()),
+                _ => v.clone(),
+            };
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+fn expand_inline_name(obj: &Object) -> Object {
+    let Object::Name(n) = obj else {
+        return obj.clone();
+    };
+    let full = match n.as_str() {
+        "G" => "DeviceGray",
+        "RGB" => "DeviceRGB",
+        "CMYK" => "DeviceCMYK",
+        "I" => "Indexed",
+        "AHx" => "ASCIIHexDecode",
+        "A85" => "ASCII85Decode",
+        "LZW" => "LZWDecode",
+        "Fl" => "FlateDecode",
+        "RL" => "RunLengthDecode",
+        "CCF" => "CCITTFaxDecode",
+        "DCT" => "DCTDecode",
+        other => other,
+    };
+    Object::Name(full.to_string())
+}
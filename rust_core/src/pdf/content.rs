@@ -0,0 +1,845 @@
+//! Content stream interpreter: graphics state, paths, text, images, shadings
+//! and form XObjects, reported to a [`Device`] in device space.
+//!
+//! Transparency groups, soft masks and blend modes are ignored (constant alpha
+//! is honoured); tiling patterns paint a flat tint.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::color::{decode_image, decode_inline_image, ColorSpace, Shading};
+use super::file::PdfFile;
+use super::font::Font;
+use super::object::{Dict, Lexer, Object, Token};
+use super::raster::{FillRule, Image, LineCap, LineJoin, Matrix, Path, StrokeStyle};
+
+/// Nesting limit for form XObjects and Type 3 glyph procedures.
+const MAX_DEPTH: usize = 12;
+/// Tint for tiling patterns, which are not rasterized.
+const PATTERN_TINT: [u8; 3] = [191, 191, 191];
+
+/// What a fill or stroke paints with.
+pub enum Fill {
+    Solid([u8; 3]),
+    /// A shading and the map from device to shading space.
+    Shading(Rc<Shading>, Matrix),
+}
+
+/// One shown glyph, for the text layer. Positions are in device space.
+pub struct Glyph {
+    pub text: Option<String>,
+    /// Start of the baseline and the point the advance width reaches.
+    pub origin: (f64, f64),
+    pub end: (f64, f64),
+    /// Corners of the box from descent to ascent over the advance.
+    pub quad: [(f64, f64); 4],
+    /// Font size in device units.
+    pub size: f64,
+}
+
+/// Receives what a page draws. Clips stack with `save`/`restore`.
+pub trait Device {
+    /// `false` skips outlines, images and shadings (text extraction).
+    fn wants_paint(&self) -> bool {
+        true
+    }
+    fn save(&mut self) {}
+    fn restore(&mut self) {}
+    fn clip(&mut self, _path: &Path, _rule: FillRule) {}
+    fn fill(&mut self, _path: &Path, _rule: FillRule, _fill: &Fill, _alpha: f32) {}
+    fn stroke(&mut self, _path: &Path, _style: &StrokeStyle, _fill: &Fill, _alpha: f32) {}
+    /// `m` maps the unit square to device space.
+    fn image(&mut self, _image: &Image, _m: &Matrix, _alpha: f32, _smooth: bool) {}
+    fn glyph(&mut self, _glyph: &Glyph) {}
+}
+
+#[derive(Clone)]
+enum PatternFill {
+    Shading(Rc<Shading>, Matrix),
+    Flat([u8; 3]),
+}
+
+#[derive(Clone)]
+struct Color {
+    space: Rc<ColorSpace>,
+    components: Vec<f64>,
+    pattern: Option<PatternFill>,
+}
+
+impl Color {
+    fn new(space: ColorSpace) -> Color {
+        Color {
+            components: space.initial(),
+            space: Rc::new(space),
+            pattern: None,
+        }
+    }
+
+    fn fill(&self) -> Fill {
+        match &self.pattern {
+            Some(PatternFill::Shading(s, m)) => Fill::Shading(s.clone(), *m),
+            Some(PatternFill::Flat(rgb)) => Fill::Solid(*rgb),
+            None => Fill::Solid(self.rgb()),
+        }
+    }
+
+    fn rgb(&self) -> [u8; 3] {
+        self.space.to_rgb(&self.components)
+    }
+}
+
+#[derive(Clone)]
+struct GState {
+    ctm: Matrix,
+    fill: Color,
+    stroke: Color,
+    fill_alpha: f32,
+    stroke_alpha: f32,
+    line_width: f64,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f64,
+    dash: Vec<f64>,
+    dash_phase: f64,
+    font: Option<Rc<Font>>,
+    font_size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    h_scale: f64,
+    leading: f64,
+    rise: f64,
+    render_mode: i64,
+}
+
+impl GState {
+    fn new(ctm: Matrix) -> GState {
+        GState {
+            ctm,
+            fill: Color::new(ColorSpace::Gray),
+            stroke: Color::new(ColorSpace::Gray),
+            fill_alpha: 1.0,
+            stroke_alpha: 1.0,
+            line_width: 1.0,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 10.0,
+            dash: Vec::new(),
+            dash_phase: 0.0,
+            font: None,
+            font_size: 1.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            h_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+        }
+    }
+}
+
+pub struct Interpreter<'a, D: Device> {
+    file: &'a PdfFile,
+    device: &'a mut D,
+    /// Device area `sh` paints when the shading has no `BBox`.
+    bounds: (f64, f64),
+    fonts: HashMap<u32, Rc<Font>>,
+    state: GState,
+    stack: Vec<GState>,
+    /// Default space of the content stream being run, which patterns use.
+    base: Matrix,
+    path: Path,
+    current: (f64, f64),
+    pending_clip: Option<FillRule>,
+    tm: Matrix,
+    tlm: Matrix,
+    /// Glyph outlines collected by clipping text render modes until `ET`.
+    text_clip: Path,
+    depth: usize,
+    /// Saved states that belong to enclosing streams, out of reach of `Q`.
+    frame_floor: usize,
+}
+
+impl<'a, D: Device> Interpreter<'a, D> {
+    /// `ctm` maps default user space to device space, `bounds` is the device size.
+    pub fn new(file: &'a PdfFile, device: &'a mut D, ctm: Matrix, bounds: (f64, f64)) -> Self {
+        Interpreter {
+            file,
+            device,
+            bounds,
+            fonts: HashMap::new(),
+            state: GState::new(ctm),
+            stack: Vec::new(),
+            base: ctm,
+            path: Path::default(),
+            current: (0.0, 0.0),
+            pending_clip: None,
+            tm: Matrix::IDENTITY,
+            tlm: Matrix::IDENTITY,
+            text_clip: Path::default(),
+            depth: 0,
+            frame_floor: 0,
+        }
+    }
+
+    pub fn run(&mut self, content: &[u8], resources: &Dict) {
+        let mut lexer = Lexer::new(content, 0);
+        let mut operands: Vec<Object> = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            match token {
+                Token::Keyword(op) => match op.as_str() {
+                    "true" => operands.push(Object::Bool(true)),
+                    "false" => operands.push(Object::Bool(false)),
+                    "null" => operands.push(Object::Null),
+                    "BI" => {
+                        self.inline_image(&mut lexer, resources);
+                        operands.clear();
+                    }
+                    _ => {
+                        self.execute(&op, &operands, resources);
+                        operands.clear();
+                    }
+                },
+                token => {
+                    if let Some(obj) = lexer.object_from(token) {
+                        operands.push(obj);
+                    }
+                }
+            }
+        }
+        // Unbalanced `q` in a page or form must not leak into what follows.
+        while self.stack.len() > self.frame_floor {
+            self.restore();
+        }
+    }
+
+    fn save(&mut self) {
+        self.stack.push(self.state.clone());
+        self.device.save();
+    }
+
+    fn restore(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+            self.device.restore();
+        }
+    }
+
+    fn execute(&mut self, op: &str, args: &[Object], resources: &Dict) {
+        let n = |i: usize| args.get(i).and_then(Object::as_f64).unwrap_or(0.0);
+        let nums = || args.iter().filter_map(Object::as_f64).collect::<Vec<f64>>();
+        let name = || args.first().and_then(Object::as_name).unwrap_or("");
+        match op {
+            "q" => self.save(),
+            "Q" if self.stack.len() > self.frame_floor => self.restore(),
+            "cm" => {
+                let m = Matrix([n(0), n(1), n(2), n(3), n(4), n(5)]);
+                self.state.ctm = m.then(&self.state.ctm);
+            }
+            "w" => self.state.line_width = n(0),
+            "J" => {
+                self.state.cap = match n(0) as i64 {
+                    1 => LineCap::Round,
+                    2 => LineCap::Square,
+                    _ => LineCap::Butt,
+                }
+            }
+            "j" => {
+                self.state.join = match n(0) as i64 {
+                    1 => LineJoin::Round,
+                    2 => LineJoin::Bevel,
+                    _ => LineJoin::Miter,
+                }
+            }
+            "M" => self.state.miter_limit = n(0),
+            "d" => {
+                self.state.dash = args
+                    .first()
+                    .and_then(Object::as_array)
+                    .map(|a| a.iter().filter_map(Object::as_f64).collect())
+                    .unwrap_or_default();
+                self.state.dash_phase = n(1);
+            }
+            "gs" => self.ext_gstate(name(), resources),
+
+            "m" => {
+                self.path.move_to(n(0), n(1));
+                self.current = (n(0), n(1));
+            }
+            "l" => {
+                self.path.line_to(n(0), n(1));
+                self.current = (n(0), n(1));
+            }
+            "c" => {
+                self.path.curve_to(n(0), n(1), n(2), n(3), n(4), n(5));
+                self.current = (n(4), n(5));
+            }
+            "v" => {
+                let (x, y) = self.current;
+                self.path.curve_to(x, y, n(0), n(1), n(2), n(3));
+                self.current = (n(2), n(3));
+            }
+            "y" => {
+                self.path.curve_to(n(0), n(1), n(2), n(3), n(2), n(3));
+                self.current = (n(2), n(3));
+            }
+            "h" => self.path.close(),
+            "re" => {
+                self.path.rect(n(0), n(1), n(2), n(3));
+                self.current = (n(0), n(1));
+            }
+            "S" => self.paint(None, true),
+            "s" => {
+                self.path.close();
+                self.paint(None, true);
+            }
+            "f" | "F" => self.paint(Some(FillRule::NonZero), false),
+            "f*" => self.paint(Some(FillRule::EvenOdd), false),
+            "B" => self.paint(Some(FillRule::NonZero), true),
+            "B*" => self.paint(Some(FillRule::EvenOdd), true),
+            "b" => {
+                self.path.close();
+                self.paint(Some(FillRule::NonZero), true);
+            }
+            "b*" => {
+                self.path.close();
+                self.paint(Some(FillRule::EvenOdd), true);
+            }
+            "n" => self.paint(None, false),
+            "W" => self.pending_clip = Some(FillRule::NonZero),
+            "W*" => self.pending_clip = Some(FillRule::EvenOdd),
+
+            "BT" => {
+                self.tm = Matrix::IDENTITY;
+                self.tlm = Matrix::IDENTITY;
+            }
+            "ET" if !self.text_clip.is_empty() => {
+                let clip = std::mem::take(&mut self.text_clip);
+                self.device.clip(&clip, FillRule::NonZero);
+            }
+            "Tc" => self.state.char_spacing = n(0),
+            "Tw" => self.state.word_spacing = n(0),
+            "Tz" => self.state.h_scale = n(0) / 100.0,
+            "TL" => self.state.leading = n(0),
+            "Ts" => self.state.rise = n(0),
+            "Tr" => self.state.render_mode = n(0) as i64,
+            "Tf" => {
+                self.state.font = self.font(resources, name());
+                self.state.font_size = n(1);
+            }
+            "Td" => self.next_line(n(0), n(1)),
+            "TD" => {
+                self.state.leading = -n(1);
+                self.next_line(n(0), n(1));
+            }
+            "Tm" => {
+                self.tlm = Matrix([n(0), n(1), n(2), n(3), n(4), n(5)]);
+                self.tm = self.tlm;
+            }
+            "T*" => self.next_line(0.0, -self.state.leading),
+            "Tj" => {
+                if let Some(s) = args.first().and_then(Object::as_bytes) {
+                    self.show(s, resources);
+                }
+            }
+            "'" => {
+                self.next_line(0.0, -self.state.leading);
+                if let Some(s) = args.first().and_then(Object::as_bytes) {
+                    self.show(s, resources);
+                }
+            }
+            "\"" => {
+                self.state.word_spacing = n(0);
+                self.state.char_spacing = n(1);
+                self.next_line(0.0, -self.state.leading);
+                if let Some(s) = args.get(2).and_then(Object::as_bytes) {
+                    self.show(s, resources);
+                }
+            }
+            "TJ" => {
+                let items = args.first().and_then(Object::as_array).unwrap_or(&[]);
+                for item in items {
+                    match item {
+                        Object::Str(s) => self.show(s, resources),
+                        other => {
+                            let adjust = other.as_f64().unwrap_or(0.0);
+                            let tx = -adjust / 1000.0 * self.state.font_size * self.state.h_scale;
+                            self.tm = Matrix::translate(tx, 0.0).then(&self.tm);
+                        }
+                    }
+                }
+            }
+
+            "g" => self.state.fill = color_of(ColorSpace::Gray, nums()),
+            "G" => self.state.stroke = color_of(ColorSpace::Gray, nums()),
+            "rg" => self.state.fill = color_of(ColorSpace::Rgb, nums()),
+            "RG" => self.state.stroke = color_of(ColorSpace::Rgb, nums()),
+            "k" => self.state.fill = color_of(ColorSpace::Cmyk, nums()),
+            "K" => self.state.stroke = color_of(ColorSpace::Cmyk, nums()),
+            "cs" | "CS" => {
+                let space = match args.first() {
+                    Some(obj) => ColorSpace::parse(self.file, obj, Some(resources)),
+                    None => ColorSpace::Gray,
+                };
+                let color = Color::new(space);
+                if op == "cs" {
+                    self.state.fill = color;
+                } else {
+                    self.state.stroke = color;
+                }
+            }
+            "sc" | "scn" | "SC" | "SCN" => {
+                let fill = op.starts_with('s');
+                let pattern = match args.last() {
+                    Some(Object::Name(p)) => Some(self.pattern(p, &nums(), fill, resources)),
+                    _ => None,
+                };
+                let color = if fill {
+                    &mut self.state.fill
+                } else {
+                    &mut self.state.stroke
+                };
+                let values = nums();
+                if !values.is_empty() {
+                    color.components = values;
+                }
+                color.pattern = pattern;
+            }
+
+            "Do" => self.xobject(name(), resources),
+            "sh" => self.shade(name(), resources),
+            _ => {}
+        }
+    }
+
+    fn ext_gstate(&mut self, name: &str, resources: &Dict) {
+        let Some(gs) = self
+            .resource(resources, "ExtGState", name)
+            .and_then(|o| self.file.dict(&o))
+        else {
+            return;
+        };
+        for (key, value) in &gs {
+            let value = self.file.resolve(value);
+            match key.as_str() {
+                "LW" => self.state.line_width = value.as_f64().unwrap_or(1.0),
+                "LC" => self.execute("J", &[(*value).clone()], resources),
+                "LJ" => self.execute("j", &[(*value).clone()], resources),
+                "ML" => self.state.miter_limit = value.as_f64().unwrap_or(10.0),
+                "D" => {
+                    if let Some([dash, phase]) = value.as_array() {
+                        self.execute("d", &[dash.clone(), phase.clone()], resources);
+                    }
+                }
+                "CA" => {
+                    self.state.stroke_alpha = value.as_f64().unwrap_or(1.0).clamp(0.0, 1.0) as f32
+                }
+                "ca" => {
+                    self.state.fill_alpha = value.as_f64().unwrap_or(1.0).clamp(0.0, 1.0) as f32
+                }
+                "Font" => {
+                    if let Some([font, size]) = value.as_array() {
+                        if let Some(dict) = self.file.dict(font) {
+                            let key = match font {
+                                Object::Ref(num, _) => Some(*num),
+                                _ => None,
+                            };
+                            self.state.font = Some(self.load_font(key, &dict));
+                            self.state.font_size = size.as_f64().unwrap_or(1.0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn resource(&self, resources: &Dict, category: &str, name: &str) -> Option<Object> {
+        self.file.dict(resources.get(category)?)?.get(name).cloned()
+    }
+
+    fn font(&mut self, resources: &Dict, name: &str) -> Option<Rc<Font>> {
+        let obj = self.resource(resources, "Font", name)?;
+        let key = match obj {
+            Object::Ref(num, _) => Some(num),
+            _ => None,
+        };
+        let dict = self.file.dict(&obj)?;
+        Some(self.load_font(key, &dict))
+    }
+
+    fn load_font(&mut self, key: Option<u32>, dict: &Dict) -> Rc<Font> {
+        if let Some(font) = key.and_then(|k| self.fonts.get(&k)) {
+            return font.clone();
+        }
+        let font = Rc::new(Font::load(self.file, dict));
+        if let Some(k) = key {
+            self.fonts.insert(k, font.clone());
+        }
+        font
+    }
+
+    fn pattern(&self, name: &str, tint: &[f64], fill: bool, resources: &Dict) -> PatternFill {
+        let file = self.file;
+        let Some(obj) = self.resource(resources, "Pattern", name) else {
+            return PatternFill::Flat(PATTERN_TINT);
+        };
+        let Some(dict) = file.dict(&obj) else {
+            return PatternFill::Flat(PATTERN_TINT);
+        };
+        let matrix = match file.lookup(&dict, "Matrix").as_array() {
+            Some(m) if m.len() == 6 => {
+                let v: Vec<f64> = m.iter().map(|x| x.as_f64().unwrap_or(0.0)).collect();
+                Matrix([v[0], v[1], v[2], v[3], v[4], v[5]])
+            }
+            _ => Matrix::IDENTITY,
+        };
+        match file.number(&dict, "PatternType").unwrap_or(1.0) as i64 {
+            2 => {
+                let shading = dict
+                    .get("Shading")
+                    .and_then(|s| Shading::parse(file, s, Some(resources)));
+                let inverse = matrix.then(&self.base).invert();
+                match (shading, inverse) {
+                    (Some(s), Some(inv)) => PatternFill::Shading(Rc::new(s), inv),
+                    _ => PatternFill::Flat(PATTERN_TINT),
+                }
+            }
+            _ => {
+                // Uncolored tiling patterns carry their color in the operands.
+                let uncolored = file.number(&dict, "PaintType") == Some(2.0);
+                let color = if fill {
+                    &self.state.fill
+                } else {
+                    &self.state.stroke
+                };
+                match (uncolored, color.space.as_ref()) {
+                    (true, ColorSpace::Pattern) if !tint.is_empty() => {
+                        let space = match tint.len() {
+                            1 => ColorSpace::Gray,
+                            4 => ColorSpace::Cmyk,
+                            _ => ColorSpace::Rgb,
+                        };
+                        PatternFill::Flat(space.to_rgb(tint))
+                    }
+                    _ => PatternFill::Flat(PATTERN_TINT),
+                }
+            }
+        }
+    }
+
+    fn stroke_style(&self) -> StrokeStyle {
+        let scale = self.state.ctm.scale_factor();
+        StrokeStyle {
+            width: self.state.line_width * scale,
+            cap: self.state.cap,
+            join: self.state.join,
+            miter_limit: self.state.miter_limit,
+            dash: self.state.dash.iter().map(|d| d * scale).collect(),
+            dash_phase: self.state.dash_phase * scale,
+        }
+    }
+
+    fn paint(&mut self, fill: Option<FillRule>, stroke: bool) {
+        let path = std::mem::take(&mut self.path);
+        if path.is_empty() {
+            self.pending_clip = None;
+            return;
+        }
+        let device_path = path.transform(&self.state.ctm);
+        if self.device.wants_paint() {
+            if let Some(rule) = fill {
+                let paint = self.state.fill.fill();
+                self.device
+                    .fill(&device_path, rule, &paint, self.state.fill_alpha);
+            }
+            if stroke {
+                let paint = self.state.stroke.fill();
+                let style = self.stroke_style();
+                self.device
+                    .stroke(&device_path, &style, &paint, self.state.stroke_alpha);
+            }
+        }
+        if let Some(rule) = self.pending_clip.take() {
+            self.device.clip(&device_path, rule);
+        }
+    }
+
+    fn next_line(&mut self, tx: f64, ty: f64) {
+        self.tlm = Matrix::translate(tx, ty).then(&self.tlm);
+        self.tm = self.tlm;
+    }
+
+    fn show(&mut self, bytes: &[u8], resources: &Dict) {
+        let Some(font) = self.state.font.clone() else {
+            return;
+        };
+        let (size, th) = (self.state.font_size, self.state.h_scale);
+        for (code, is_space) in font.codes(bytes) {
+            let advance = font.width(code);
+            let trm = Matrix([size * th, 0.0, 0.0, size, 0.0, self.state.rise])
+                .then(&self.tm)
+                .then(&self.state.ctm);
+            self.glyph(&font, code, advance, &trm, resources);
+            let spacing = self.state.char_spacing
+                + if is_space {
+                    self.state.word_spacing
+                } else {
+                    0.0
+                };
+            let tx = (advance * size + spacing) * th;
+            self.tm = Matrix::translate(tx, 0.0).then(&self.tm);
+        }
+    }
+
+    fn glyph(&mut self, font: &Rc<Font>, code: u32, advance: f64, trm: &Matrix, resources: &Dict) {
+        let corner = |x: f64, y: f64| trm.apply(x, y);
+        let (vx, vy) = trm.apply_vector(0.0, 1.0);
+        self.device.glyph(&Glyph {
+            text: font.unicode(code),
+            origin: corner(0.0, 0.0),
+            end: corner(advance, 0.0),
+            quad: [
+                corner(0.0, font.descent),
+                corner(advance, font.descent),
+                corner(advance, font.ascent),
+                corner(0.0, font.ascent),
+            ],
+            size: vx.hypot(vy),
+        });
+        let mode = self.state.render_mode;
+        if !self.device.wants_paint() || mode == 3 {
+            return;
+        }
+        if font.is_type3() {
+            if let Some(proc_obj) = font.type3_proc(code) {
+                if let Some(data) = self.file.stream_data(proc_obj) {
+                    let ctm = font.matrix.then(trm);
+                    let res = font.resources.clone().unwrap_or_else(|| resources.clone());
+                    self.run_nested(&data, &res, ctm, None);
+                }
+            }
+            return;
+        }
+        let Some(outline) = font.outline(code) else {
+            return;
+        };
+        let path = outline.transform(trm);
+        if matches!(mode, 0 | 2 | 4 | 6) {
+            let paint = self.state.fill.fill();
+            self.device
+                .fill(&path, FillRule::NonZero, &paint, self.state.fill_alpha);
+        }
+        if matches!(mode, 1 | 2 | 5 | 6) {
+            let paint = self.state.stroke.fill();
+            let style = self.stroke_style();
+            self.device
+                .stroke(&path, &style, &paint, self.state.stroke_alpha);
+        }
+        if mode >= 4 {
+            self.text_clip.extend_transformed(&outline, trm);
+        }
+    }
+
+    /// Runs a form or glyph procedure in its own saved state, clipped to `bbox`.
+    fn run_nested(
+        &mut self,
+        content: &[u8],
+        resources: &Dict,
+        ctm: Matrix,
+        bbox: Option<[f64; 4]>,
+    ) {
+        if self.depth >= MAX_DEPTH {
+            return;
+        }
+        let saved_text = (self.tm, self.tlm);
+        let saved_path = std::mem::take(&mut self.path);
+        let saved_base = self.base;
+        let saved_floor = self.frame_floor;
+        self.save();
+        self.state.ctm = ctm;
+        self.base = ctm;
+        if let Some([x0, y0, x1, y1]) = bbox {
+            let mut clip = Path::default();
+            clip.rect(x0, y0, x1 - x0, y1 - y0);
+            self.device.clip(&clip.transform(&ctm), FillRule::NonZero);
+        }
+        self.depth += 1;
+        self.frame_floor = self.stack.len();
+        self.run(content, resources);
+        self.depth -= 1;
+        self.frame_floor = saved_floor;
+        self.restore();
+        self.base = saved_base;
+        self.path = saved_path;
+        (self.tm, self.tlm) = saved_text;
+    }
+
+    fn xobject(&mut self, name: &str, resources: &Dict) {
+        let file = self.file;
+        let Some(obj) = self.resource(resources, "XObject", name) else {
+            return;
+        };
+        let Some(dict) = file.dict(&obj) else {
+            return;
+        };
+        match file.lookup(&dict, "Subtype").as_name() {
+            Some("Image") => {
+                if !self.device.wants_paint() {
+                    return;
+                }
+                let fill = self.state.fill.rgb();
+                if let Some(image) = decode_image(file, &obj, Some(resources), fill) {
+                    let smooth = image.width >= 16 && image.height >= 16;
+                    self.device
+                        .image(&image, &self.state.ctm, self.state.fill_alpha, smooth);
+                }
+            }
+            Some("Form") => {
+                let Some(data) = file.stream_data(&obj) else {
+                    return;
+                };
+                let matrix = match numbers(file, &dict, "Matrix")[..] {
+                    [a, b, c, d, e, f] => Matrix([a, b, c, d, e, f]),
+                    _ => Matrix::IDENTITY,
+                };
+                let bbox = match numbers(file, &dict, "BBox")[..] {
+                    [a, b, c, d] => Some([a.min(c), b.min(d), a.max(c), b.max(d)]),
+                    _ => None,
+                };
+                let res = file
+                    .dict(&file.lookup(&dict, "Resources"))
+                    .unwrap_or_else(|| resources.clone());
+                let ctm = matrix.then(&self.state.ctm);
+                self.run_nested(&data, &res, ctm, bbox);
+            }
+            _ => {}
+        }
+    }
+
+    fn shade(&mut self, name: &str, resources: &Dict) {
+        if !self.device.wants_paint() {
+            return;
+        }
+        let Some(obj) = self.resource(resources, "Shading", name) else {
+            return;
+        };
+        let (Some(shading), Some(inverse)) = (
+            Shading::parse(self.file, &obj, Some(resources)),
+            self.state.ctm.invert(),
+        ) else {
+            return;
+        };
+        let mut area = Path::default();
+        match shading.bbox {
+            Some([x0, y0, x1, y1]) => {
+                area.rect(x0, y0, x1 - x0, y1 - y0);
+                area = area.transform(&self.state.ctm);
+            }
+            None => area.rect(0.0, 0.0, self.bounds.0, self.bounds.1),
+        }
+        let paint = Fill::Shading(Rc::new(shading), inverse);
+        self.device
+            .fill(&area, FillRule::NonZero, &paint, self.state.fill_alpha);
+    }
+
+    /// `BI <entries> ID <data> EI`; the lexer is left after `EI`.
+    fn inline_image(&mut self, lexer: &mut Lexer, resources: &Dict) {
+        let mut dict = Dict::new();
+        loop {
+            match lexer.next_token() {
+                Some(Token::Name(key)) => {
+                    if let Some(value) = lexer.object() {
+                        dict.insert(key, value);
+                    }
+                }
+                Some(Token::Keyword(k)) if k == "ID" => break,
+                Some(_) => {}
+                None => return,
+            }
+        }
+        let data = lexer.data;
+        // One whitespace byte separates `ID` from the data.
+        let start = (lexer.pos + 1).min(data.len());
+        let file = self.file;
+        let num = |keys: &[&str]| keys.iter().find_map(|k| file.number(&dict, k));
+        let filtered = dict.contains_key("F") || dict.contains_key("Filter");
+        // Unfiltered data has a known length; otherwise look for `EI` between whitespace.
+        let known = (!filtered).then(|| {
+            let w = num(&["W", "Width"]).unwrap_or(0.0) as usize;
+            let h = num(&["H", "Height"]).unwrap_or(0.0) as usize;
+            let is_mask = matches!(
+                dict.get("IM").or(dict.get("ImageMask")),
+                Some(Object::Bool(true))
+            );
+            let bpc = if is_mask {
+                1
+            } else {
+                num(&["BPC", "BitsPerComponent"]).unwrap_or(8.0) as usize
+            };
+            let comps = match dict
+                .get("CS")
+                .or(dict.get("ColorSpace"))
+                .and_then(Object::as_name)
+            {
+                _ if is_mask => 1,
+                Some("RGB" | "DeviceRGB") => 3,
+                Some("CMYK" | "DeviceCMYK") => 4,
+                _ => 1,
+            };
+            (w * comps * bpc).div_ceil(8) * h
+        });
+        let end = match known {
+            Some(len) if start + len <= data.len() => start + len,
+            _ => {
+                let mut i = start;
+                loop {
+                    if i + 2 > data.len() {
+                        break data.len();
+                    }
+                    if &data[i..i + 2] == b"EI"
+                        && (i == start || super::object::is_whitespace(data[i - 1]))
+                        && data
+                            .get(i + 2)
+                            .is_none_or(|&b| super::object::is_whitespace(b))
+                    {
+                        break i;
+                    }
+                    i += 1;
+                }
+            }
+        };
+        let image_data = data[start..end].to_vec();
+        // Skip past `EI`.
+        lexer.pos = end;
+        while let Some(token) = lexer.next_token() {
+            if token == Token::Keyword("EI".into()) {
+                break;
+            }
+        }
+        if !self.device.wants_paint() {
+            return;
+        }
+        let fill = self.state.fill.rgb();
+        if let Some(image) = decode_inline_image(file, &dict, image_data, Some(resources), fill) {
+            self.device
+                .image(&image, &self.state.ctm, self.state.fill_alpha, false);
+        }
+    }
+}
+
+fn color_of(space: ColorSpace, components: Vec<f64>) -> Color {
+    Color {
+        space: Rc::new(space),
+        components,
+        pattern: None,
+    }
+}
+
+fn numbers(file: &PdfFile, dict: &Dict, key: &str) -> Vec<f64> {
+    file.lookup(dict, key)
+        .as_array()
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|o| file.resolve(o).as_f64())
+        .collect()
+}
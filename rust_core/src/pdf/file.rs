@@ -0,0 +1,699 @@
+//! Cross-reference tables and streams, object streams, stream filters.
+//!
+//! A file whose xref cannot be read is reconstructed by scanning for `N G obj`
+//! headers, which also covers the offsets a truncated or hand-edited file gets wrong.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Read;
+use std::rc::Rc;
+
+use flate2::read::ZlibDecoder;
+
+use super::object::{Dict, Lexer, Object, Stream, Token};
+use crate::{Error, Result};
+
+/// Nested `Prev` chains and object streams deeper than this are cut off.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    Offset(usize),
+    /// Index within the object stream with the given number.
+    Compressed(u32, usize),
+}
+
+pub struct PdfFile {
+    data: Vec<u8>,
+    xref: HashMap<u32, Entry>,
+    pub trailer: Dict,
+    cache: RefCell<HashMap<u32, Rc<Object>>>,
+}
+
+impl PdfFile {
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        let mut file = PdfFile {
+            data,
+            xref: HashMap::new(),
+            trailer: Dict::new(),
+            cache: RefCell::new(HashMap::new()),
+        };
+        if !file.read_xref_chain() || !file.trailer.contains_key("Root") {
+            file.reconstruct()?;
+        }
+        Ok(file)
+    }
+
+    fn read_xref_chain(&mut self) -> bool {
+        let Some(mut offset) = self.startxref() else {
+            return false;
+        };
+        let mut seen = Vec::new();
+        while !seen.contains(&offset) && seen.len() < MAX_DEPTH {
+            seen.push(offset);
+            let Some(trailer) = self.read_xref_section(offset) else {
+                return false;
+            };
+            // Hybrid files: the table's trailer points at an xref stream as well.
+            if let Some(stm) = trailer.get("XRefStm").and_then(Object::as_i64) {
+                self.read_xref_section(stm as usize);
+            }
+            let prev = trailer.get("Prev").and_then(Object::as_i64);
+            for (k, v) in trailer {
+                self.trailer.entry(k).or_insert(v);
+            }
+            match prev {
+                Some(p) if p >= 0 => offset = p as usize,
+                _ => break,
+            }
+        }
+        true
+    }
+
+    fn startxref(&self) -> Option<usize> {
+        let tail_start = self.data.len().saturating_sub(1024);
+        let tail = &self.data[tail_start..];
+        let at = rfind(tail, b"startxref")?;
+        let mut lexer = Lexer::new(tail, at + 9);
+        match lexer.next_token()? {
+            Token::Int(n) if n >= 0 && (n as usize) < self.data.len() => Some(n as usize),
+            _ => None,
+        }
+    }
+
+    /// Reads the table or stream at `offset`; entries already known (from a
+    /// newer section) win. Returns its trailer.
+    fn read_xref_section(&mut self, offset: usize) -> Option<Dict> {
+        let mut lexer = Lexer::new(&self.data, offset);
+        match lexer.next_token()? {
+            Token::Keyword(k) if k == "xref" => self.read_xref_table(lexer.pos),
+            Token::Int(_) => {
+                let (_, obj) = self.parse_indirect_at(offset)?;
+                let Object::Stream(stream) = obj else {
+                    return None;
+                };
+                self.read_xref_stream(&stream)?;
+                Some(stream.dict)
+            }
+            _ => None,
+        }
+    }
+
+    fn read_xref_table(&mut self, pos: usize) -> Option<Dict> {
+        let mut lexer = Lexer::new(&self.data, pos);
+        let mut entries = Vec::new();
+        loop {
+            match lexer.next_token()? {
+                Token::Int(start) => {
+                    let Token::Int(count) = lexer.next_token()? else {
+                        return None;
+                    };
+                    for i in 0..count.max(0) {
+                        let (Token::Int(off), Token::Int(_), Token::Keyword(kind)) = (
+                            lexer.next_token()?,
+                            lexer.next_token()?,
+                            lexer.next_token()?,
+                        ) else {
+                            return None;
+                        };
+                        if kind == "n" && off > 0 {
+                            entries.push(((start + i) as u32, Entry::Offset(off as usize)));
+                        }
+                    }
+                }
+                Token::Keyword(k) if k == "trailer" => {
+                    let Token::DictOpen = lexer.next_token()? else {
+                        return None;
+                    };
+                    let trailer = lexer.dict_body()?;
+                    for (num, entry) in entries {
+                        self.xref.entry(num).or_insert(entry);
+                    }
+                    return Some(trailer);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn read_xref_stream(&mut self, stream: &Stream) -> Option<()> {
+        let data = decode_stream(stream).ok()?;
+        let widths: Vec<usize> = stream
+            .dict
+            .get("W")?
+            .as_array()?
+            .iter()
+            .map(|w| w.as_i64().unwrap_or(0).max(0) as usize)
+            .collect();
+        if widths.len() < 3 {
+            return None;
+        }
+        let size = stream
+            .dict
+            .get("Size")
+            .and_then(Object::as_i64)
+            .unwrap_or(0);
+        let index: Vec<i64> = match stream.dict.get("Index").and_then(Object::as_array) {
+            Some(a) => a.iter().filter_map(Object::as_i64).collect(),
+            None => vec![0, size],
+        };
+        let row = widths.iter().sum::<usize>();
+        if row == 0 {
+            return None;
+        }
+        let mut rows = data.chunks_exact(row);
+        for pair in index.chunks_exact(2) {
+            for num in pair[0]..pair[0] + pair[1] {
+                let Some(r) = rows.next() else {
+                    return Some(());
+                };
+                let field = |i: usize| {
+                    let start: usize = widths[..i].iter().sum();
+                    r[start..start + widths[i]]
+                        .iter()
+                        .fold(0usize, |acc, &b| acc << 8 | b as usize)
+                };
+                let kind = if widths[0] == 0 { 1 } else { field(0) };
+                let entry = match kind {
+                    1 => Entry::Offset(field(1)),
+                    2 => Entry::Compressed(field(1) as u32, field(2)),
+                    _ => continue,
+                };
+                self.xref.entry(num as u32).or_insert(entry);
+            }
+        }
+        Some(())
+    }
+
+    /// Rebuilds the xref from every `N G obj` in the file; the last definition
+    /// of a number wins, as an incremental update would have it.
+    fn reconstruct(&mut self) -> Result<()> {
+        self.xref.clear();
+        let mut trailer = Dict::new();
+        let mut pos = 0;
+        while let Some(at) = find(&self.data[pos..], b"obj") {
+            let end = pos + at;
+            pos = end + 3;
+            if let Some((num, start)) = object_header_before(&self.data, end) {
+                self.xref.insert(num, Entry::Offset(start));
+            }
+        }
+        let mut pos = 0;
+        while let Some(at) = find(&self.data[pos..], b"trailer") {
+            let mut lexer = Lexer::new(&self.data, pos + at + 7);
+            pos += at + 7;
+            if let Some(Token::DictOpen) = lexer.next_token() {
+                if let Some(d) = lexer.dict_body() {
+                    trailer.extend(d);
+                }
+            }
+        }
+        // Object streams and cross-reference streams hold the rest.
+        let offsets: Vec<(u32, usize)> = self
+            .xref
+            .iter()
+            .filter_map(|(&n, e)| match e {
+                Entry::Offset(o) => Some((n, *o)),
+                Entry::Compressed(..) => None,
+            })
+            .collect();
+        for (num, offset) in offsets {
+            let Some((_, Object::Stream(s))) = self.parse_indirect_at(offset) else {
+                continue;
+            };
+            match s.dict.get("Type").and_then(Object::as_name) {
+                Some("ObjStm") => {
+                    let Ok(data) = decode_stream(&s) else {
+                        continue;
+                    };
+                    for (i, (n, _)) in object_stream_index(&s.dict, &data).iter().enumerate() {
+                        self.xref.entry(*n).or_insert(Entry::Compressed(num, i));
+                    }
+                }
+                Some("XRef") => {
+                    for (k, v) in s.dict {
+                        if matches!(k.as_str(), "Root" | "Info" | "Encrypt" | "ID") {
+                            trailer.entry(k).or_insert(v);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !trailer.contains_key("Root") {
+            // No trailer at all: look for the catalog itself.
+            let catalog = self.xref.keys().copied().find(|&n| {
+                self.get(n)
+                    .as_dict()
+                    .and_then(|d| d.get("Type"))
+                    .and_then(Object::as_name)
+                    == Some("Catalog")
+            });
+            match catalog {
+                Some(n) => {
+                    trailer.insert("Root".into(), Object::Ref(n, 0));
+                }
+                None => return Err(Error::InvalidPdf("no document catalog".into())),
+            }
+        }
+        self.trailer = trailer;
+        self.cache.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// `N G obj … endobj` at `offset`, with the stream data attached.
+    fn parse_indirect_at(&self, offset: usize) -> Option<(u32, Object)> {
+        let mut lexer = Lexer::new(&self.data, offset);
+        let (Token::Int(num), Token::Int(_), Token::Keyword(k)) = (
+            lexer.next_token()?,
+            lexer.next_token()?,
+            lexer.next_token()?,
+        ) else {
+            return None;
+        };
+        if k != "obj" {
+            return None;
+        }
+        let obj = lexer.object()?;
+        let Object::Dict(dict) = obj else {
+            return Some((num as u32, obj));
+        };
+        match lexer.next_token() {
+            Some(Token::Keyword(k)) if k == "stream" => {}
+            _ => return Some((num as u32, Object::Dict(dict))),
+        }
+        let mut start = lexer.pos;
+        if self.data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if self.data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+        let declared = match dict.get("Length") {
+            Some(Object::Ref(n, _)) => {
+                // Avoid recursion into ourselves for a self-referencing length.
+                if *n == num as u32 {
+                    None
+                } else {
+                    self.get(*n).as_i64()
+                }
+            }
+            Some(o) => o.as_i64(),
+            None => None,
+        };
+        let end = declared
+            .map(|len| start + len.max(0) as usize)
+            .filter(|&end| {
+                end <= self.data.len()
+                    && find(
+                        &self.data[end..(end + 32).min(self.data.len())],
+                        b"endstream",
+                    )
+                    .is_some()
+            })
+            .or_else(|| {
+                let at = find(&self.data[start..], b"endstream")?;
+                let mut end = start + at;
+                // The EOL before `endstream` is not part of the data.
+                if end > start && self.data[end - 1] == b'\n' {
+                    end -= 1;
+                }
+                if end > start && self.data[end - 1] == b'\r' {
+                    end -= 1;
+                }
+                Some(end)
+            })?;
+        Some((
+            num as u32,
+            Object::Stream(Stream {
+                dict,
+                data: self.data[start..end].to_vec(),
+            }),
+        ))
+    }
+
+    /// Object `num`, or `Null` when it does not exist.
+    pub fn get(&self, num: u32) -> Rc<Object> {
+        self.get_at_depth(num, 0)
+    }
+
+    fn get_at_depth(&self, num: u32, depth: usize) -> Rc<Object> {
+        if let Some(obj) = self.cache.borrow().get(&num) {
+            return obj.clone();
+        }
+        // Guards against reference cycles through `Length` or object streams.
+        self.cache.borrow_mut().insert(num, Rc::new(Object::Null));
+        let obj = match self.xref.get(&num) {
+            Some(Entry::Offset(off)) => self
+                .parse_indirect_at(*off)
+                .filter(|(n, _)| *n == num)
+                .map(|(_, o)| o),
+            Some(&Entry::Compressed(stream_num, index)) if depth < MAX_DEPTH => {
+                self.in_object_stream(stream_num, index, depth)
+            }
+            _ => None,
+        };
+        let obj = Rc::new(obj.unwrap_or(Object::Null));
+        self.cache.borrow_mut().insert(num, obj.clone());
+        obj
+    }
+
+    fn in_object_stream(&self, stream_num: u32, index: usize, depth: usize) -> Option<Object> {
+        let container = self.get_at_depth(stream_num, depth + 1);
+        let Object::Stream(stream) = &*container else {
+            return None;
+        };
+        let data = decode_stream(stream).ok()?;
+        let entries = object_stream_index(&stream.dict, &data);
+        let first = stream.dict.get("First").and_then(Object::as_i64)? as usize;
+        let &(_, offset) = entries.get(index)?;
+        Lexer::new(&data, first + offset).object()
+    }
+
+    /// Follows a reference; other objects are returned as they are.
+    pub fn resolve(&self, obj: &Object) -> Rc<Object> {
+        let mut current = Rc::new(obj.clone());
+        for _ in 0..MAX_DEPTH {
+            let Object::Ref(n, _) = *current else {
+                return current;
+            };
+            current = self.get(n);
+        }
+        Rc::new(Object::Null)
+    }
+
+    /// `dict[key]`, resolved.
+    pub fn lookup(&self, dict: &Dict, key: &str) -> Rc<Object> {
+        match dict.get(key) {
+            Some(o) => self.resolve(o),
+            None => Rc::new(Object::Null),
+        }
+    }
+
+    /// A resolved dictionary (or a stream's dictionary), if `obj` is one.
+    pub fn dict(&self, obj: &Object) -> Option<Dict> {
+        self.resolve(obj).as_dict().cloned()
+    }
+
+    pub fn number(&self, dict: &Dict, key: &str) -> Option<f64> {
+        self.lookup(dict, key).as_f64()
+    }
+
+    /// Decoded data of the stream `obj` refers to.
+    pub fn stream_data(&self, obj: &Object) -> Option<Vec<u8>> {
+        match &*self.resolve(obj) {
+            Object::Stream(s) => decode_stream(s).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// `(object number, offset)` pairs from the header of an object stream.
+fn object_stream_index(dict: &Dict, data: &[u8]) -> Vec<(u32, usize)> {
+    let count = dict.get("N").and_then(Object::as_i64).unwrap_or(0).max(0) as usize;
+    let mut lexer = Lexer::new(data, 0);
+    let mut out = Vec::with_capacity(count);
+    for _ in 0..count {
+        match (lexer.next_token(), lexer.next_token()) {
+            (Some(Token::Int(n)), Some(Token::Int(off))) => out.push((n as u32, off as usize)),
+            _ => break,
+        }
+    }
+    out
+}
+
+/// For `obj` ending at `end`, the object number and the offset of `N G obj`.
+fn object_header_before(data: &[u8], end: usize) -> Option<(u32, usize)> {
+    if data.get(end + 3).is_some_and(|b| b.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let mut i = end;
+    let mut numbers = Vec::new();
+    for _ in 0..2 {
+        while i > 0 && super::object::is_whitespace(data[i - 1]) {
+            i -= 1;
+        }
+        let stop = i;
+        while i > 0 && data[i - 1].is_ascii_digit() {
+            i -= 1;
+        }
+        if i == stop {
+            return None;
+        }
+        numbers.push(
+            std::str::from_utf8(&data[i..stop])
+                .ok()?
+                .parse::<u32>()
+                .ok()?,
+        );
+    }
+    Some((numbers[1], i))
+}
+
+/// Applies the stream's filters. Image codecs (`DCTDecode`, `JPXDecode`, …)
+/// are left in place; the caller sees them in the dictionary.
+pub fn decode_stream(stream: &Stream) -> Result<Vec<u8>> {
+    let filters: Vec<String> = match stream.dict.get("Filter") {
+        Some(Object::Name(n)) => vec![n.clone()],
+        Some(Object::Array(a)) => a
+            .iter()
+            .filter_map(|f| f.as_name().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let params: Vec<Option<&Dict>> = match stream.dict.get("DecodeParms") {
+        Some(Object::Dict(d)) => vec![Some(d)],
+        Some(Object::Array(a)) => a.iter().map(Object::as_dict).collect(),
+        _ => Vec::new(),
+    };
+    let mut data = stream.data.clone();
+    for (i, filter) in filters.iter().enumerate() {
+        let params = params.get(i).copied().flatten();
+        data = match filter.as_str() {
+            "FlateDecode" | "Fl" => {
+                let mut out = Vec::new();
+                // A truncated or slightly corrupt stream still yields its prefix.
+                let _ = ZlibDecoder::new(&data[..]).read_to_end(&mut out);
+                if out.is_empty() && !data.is_empty() {
+                    return Err(Error::InvalidPdf("corrupt Flate stream".into()));
+                }
+                unpredict(out, params)
+            }
+            "LZWDecode" | "LZW" => {
+                let early = params
+                    .and_then(|p| p.get("EarlyChange"))
+                    .and_then(Object::as_i64)
+                    .unwrap_or(1);
+                unpredict(lzw_decode(&data, early != 0), params)
+            }
+            "ASCIIHexDecode" | "AHx" => {
+                // The lexer's hex string reader, fed as `<…>`.
+                let mut bytes = Vec::with_capacity(data.len() + 2);
+                bytes.push(b'<');
+                bytes.extend(data.iter().take_while(|&&b| b != b'>'));
+                bytes.push(b'>');
+                match Lexer::new(&bytes, 0).next_token() {
+                    Some(Token::Str(s)) => s,
+                    _ => Vec::new(),
+                }
+            }
+            "ASCII85Decode" | "A85" => ascii85_decode(&data),
+            "RunLengthDecode" | "RL" => run_length_decode(&data),
+            _ => return Ok(data),
+        };
+    }
+    Ok(data)
+}
+
+/// Undoes PNG (`Predictor` ≥ 10) and TIFF (`Predictor` 2, 8-bit) predictors.
+fn unpredict(data: Vec<u8>, params: Option<&Dict>) -> Vec<u8> {
+    let Some(params) = params else {
+        return data;
+    };
+    let get = |k: &str, default: i64| {
+        params
+            .get(k)
+            .and_then(Object::as_i64)
+            .unwrap_or(default)
+            .max(0) as usize
+    };
+    let predictor = get("Predictor", 1);
+    if predictor < 2 {
+        return data;
+    }
+    let colors = get("Colors", 1).max(1);
+    let bpc = get("BitsPerComponent", 8).max(1);
+    let columns = get("Columns", 1).max(1);
+    let bpp = (colors * bpc).div_ceil(8).max(1);
+    let row_len = (colors * bpc * columns).div_ceil(8);
+    if predictor == 2 {
+        let mut data = data;
+        if bpc == 8 {
+            for row in data.chunks_mut(row_len) {
+                for i in bpp..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - bpp]);
+                }
+            }
+        }
+        return data;
+    }
+    let mut out = Vec::with_capacity(data.len());
+    let mut prev = vec![0u8; row_len];
+    for chunk in data.chunks(row_len + 1) {
+        let (kind, row) = (chunk[0], &chunk[1..]);
+        let mut cur = row.to_vec();
+        cur.resize(row_len, 0);
+        for i in 0..row_len {
+            let left = if i >= bpp { cur[i - bpp] } else { 0 };
+            let up = prev[i];
+            let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+            cur[i] = match kind {
+                1 => cur[i].wrapping_add(left),
+                2 => cur[i].wrapping_add(up),
+                3 => cur[i].wrapping_add(((left as u16 + up as u16) / 2) as u8),
+                4 => cur[i].wrapping_add(paeth(left, up, up_left)),
+                _ => cur[i],
+            };
+        }
+        out.extend_from_slice(&cur);
+        prev = cur;
+    }
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn ascii85_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut group = [0u8; 5];
+    let mut n = 0;
+    for &b in data {
+        match b {
+            b'~' => break,
+            b'z' if n == 0 => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group[n] = b - b'!';
+                n += 1;
+                if n == 5 {
+                    let v = group
+                        .iter()
+                        .fold(0u32, |acc, &d| acc.wrapping_mul(85).wrapping_add(d as u32));
+                    out.extend_from_slice(&v.to_be_bytes());
+                    n = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+    if n > 1 {
+        for d in group.iter_mut().skip(n) {
+            *d = 84;
+        }
+        let v = group
+            .iter()
+            .fold(0u32, |acc, &d| acc.wrapping_mul(85).wrapping_add(d as u32));
+        out.extend_from_slice(&v.to_be_bytes()[..n - 1]);
+    }
+    out
+}
+
+fn run_length_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while let Some(&len) = data.get(i) {
+        i += 1;
+        match len {
+            128 => break,
+            0..=127 => {
+                let end = (i + len as usize + 1).min(data.len());
+                out.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            _ => {
+                if let Some(&b) = data.get(i) {
+                    out.extend(std::iter::repeat_n(b, 257 - len as usize));
+                }
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn lzw_decode(data: &[u8], early_change: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let reset = |table: &mut Vec<Vec<u8>>| {
+        table.clear();
+        table.extend((0..=255u8).map(|b| vec![b]));
+        table.push(Vec::new());
+        table.push(Vec::new());
+    };
+    reset(&mut table);
+    let mut width = 9;
+    let mut prev: Option<Vec<u8>> = None;
+    let (mut acc, mut bits) = (0u32, 0);
+    for &b in data {
+        acc = acc << 8 | b as u32;
+        bits += 8;
+        while bits >= width {
+            let code = (acc >> (bits - width)) as usize & ((1 << width) - 1);
+            bits -= width;
+            match code {
+                256 => {
+                    reset(&mut table);
+                    width = 9;
+                    prev = None;
+                    continue;
+                }
+                257 => return out,
+                _ => {}
+            }
+            let entry = match (table.get(code), &prev) {
+                (Some(e), _) if code < table.len() => e.clone(),
+                (_, Some(p)) => {
+                    let mut e = p.clone();
+                    e.push(p[0]);
+                    e
+                }
+                _ => return out,
+            };
+            out.extend_from_slice(&entry);
+            if let Some(mut p) = prev.take() {
+                p.push(entry[0]);
+                table.push(p);
+            }
+            prev = Some(entry);
+            let limit = table.len() + early_change as usize;
+            width = match limit {
+                0..=511 => 9,
+                512..=1023 => 10,
+                1024..=2047 => 11,
+                _ => 12,
+            };
+        }
+    }
+    out
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
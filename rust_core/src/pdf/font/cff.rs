@@ -0,0 +1,777 @@
+//! Compact Font Format (`FontFile3` with `Type1C`, `CIDFontType0C` or
+//! `OpenType`): name- and CID-keyed fonts, Type 2 charstrings.
+
+use super::encoding::{standard, STANDARD_STRINGS};
+use crate::pdf::raster::{Matrix, Path};
+
+/// Nesting limit for subroutine calls (the spec's is 10).
+const MAX_CALL_DEPTH: usize = 10;
+const MAX_STACK: usize = 513;
+
+type Range = std::ops::Range<usize>;
+
+pub struct Cff {
+    data: Vec<u8>,
+    charstrings: Vec<Range>,
+    global_subrs: Vec<Range>,
+    /// Local subrs per font DICT (one for name-keyed fonts).
+    local_subrs: Vec<Vec<Range>>,
+    /// Font DICT of each glyph, for CID-keyed fonts.
+    fd_select: Vec<u8>,
+    /// SID (name-keyed) or CID of each glyph.
+    charset: Vec<u16>,
+    strings: Vec<Range>,
+    /// Built-in encoding: glyph for each code.
+    encoding: [u16; 256],
+    pub is_cid: bool,
+    pub font_matrix: Matrix,
+}
+
+impl Cff {
+    pub fn parse(data: Vec<u8>) -> Option<Cff> {
+        let data = match data.get(..4) {
+            Some(b"OTTO") => super::truetype::table(&data, b"CFF ")?.to_vec(),
+            _ => data,
+        };
+        let header_size = *data.get(2)? as usize;
+        let (_names, pos) = read_index(&data, header_size)?;
+        let (top_dicts, pos) = read_index(&data, pos)?;
+        let (strings, pos) = read_index(&data, pos)?;
+        let (global_subrs, _) = read_index(&data, pos)?;
+        let top = parse_dict(&data[top_dicts.first()?.clone()]);
+        let get = |op: u16| {
+            top.iter()
+                .find(|(o, _)| *o == op)
+                .map(|(_, v)| v.as_slice())
+        };
+        let charstrings_at = *get(17)?.first()? as usize;
+        let (charstrings, _) = read_index(&data, charstrings_at)?;
+        let glyphs = charstrings.len();
+        let font_matrix = match get(1207) {
+            Some(&[a, b, c, d, e, f]) => Matrix([a, b, c, d, e, f]),
+            _ => Matrix([0.001, 0.0, 0.0, 0.001, 0.0, 0.0]),
+        };
+        let is_cid = get(1230).is_some();
+        let charset = match get(15).and_then(|v| v.first()).map(|&v| v as usize) {
+            None | Some(0) => (0..glyphs as u16).collect(),
+            // Expert charsets: rare enough to map glyphs to themselves.
+            Some(1) | Some(2) => (0..glyphs as u16).collect(),
+            Some(at) => read_charset(&data, at, glyphs)?,
+        };
+        let mut local_subrs = Vec::new();
+        let mut fd_select = Vec::new();
+        let mut font_matrix = font_matrix;
+        if is_cid {
+            let fd_array_at = *get(1236)?.first()? as usize;
+            let (fds, _) = read_index(&data, fd_array_at)?;
+            for fd in &fds {
+                let dict = parse_dict(&data[fd.clone()]);
+                local_subrs.push(private_subrs(&data, &dict).unwrap_or_default());
+                // A font DICT matrix is applied on top of the top DICT's; TeX
+                // fonts leave both at the default, so only take it when it differs.
+                if let Some((_, v)) = dict.iter().find(|(o, _)| *o == 1207) {
+                    if let &[a, b, c, d, e, f] = v.as_slice() {
+                        if fds.len() == 1 && a != 0.001 {
+                            font_matrix = Matrix([a, b, c, d, e, f]);
+                        }
+                    }
+                }
+            }
+            fd_select = match get(1237).and_then(|v| v.first()) {
+                Some(&at) => read_fd_select(&data, at as usize, glyphs).unwrap_or_default(),
+                None => Vec::new(),
+            };
+        } else {
+            local_subrs.push(private_subrs(&data, &top).unwrap_or_default());
+        }
+        let mut cff = Cff {
+            data,
+            charstrings,
+            global_subrs,
+            local_subrs,
+            fd_select,
+            charset,
+            strings,
+            encoding: [0; 256],
+            is_cid,
+            font_matrix,
+        };
+        if !is_cid {
+            let at = get(16).and_then(|v| v.first()).map_or(0, |&v| v as usize);
+            cff.read_encoding(at);
+        }
+        Some(cff)
+    }
+
+    fn read_encoding(&mut self, at: usize) {
+        if at <= 1 {
+            // Standard (0) or Expert (1); only Standard is built in.
+            for code in 0..=255u8 {
+                if let Some(gid) = standard(code).and_then(|name| self.gid_for_name(name)) {
+                    self.encoding[code as usize] = gid;
+                }
+            }
+            return;
+        }
+        let data = &self.data;
+        let Some(&format) = data.get(at) else {
+            return;
+        };
+        let mut pos = at + 1;
+        let mut gid = 1u16;
+        match format & 0x7f {
+            0 => {
+                let n = data.get(pos).copied().unwrap_or(0) as usize;
+                pos += 1;
+                for &code in data.get(pos..pos + n).unwrap_or(&[]) {
+                    self.encoding[code as usize] = gid;
+                    gid += 1;
+                }
+                pos += n;
+            }
+            1 => {
+                let n = data.get(pos).copied().unwrap_or(0) as usize;
+                pos += 1;
+                for r in data.get(pos..pos + 2 * n).unwrap_or(&[]).chunks_exact(2) {
+                    for code in r[0] as usize..=(r[0] as usize + r[1] as usize).min(255) {
+                        self.encoding[code] = gid;
+                        gid += 1;
+                    }
+                }
+                pos += 2 * n;
+            }
+            _ => return,
+        }
+        if format & 0x80 != 0 {
+            let n = data.get(pos).copied().unwrap_or(0) as usize;
+            pos += 1;
+            let sups: Vec<(u8, u16)> = data
+                .get(pos..pos + 3 * n)
+                .unwrap_or(&[])
+                .chunks_exact(3)
+                .map(|s| (s[0], u16::from_be_bytes([s[1], s[2]])))
+                .collect();
+            for (code, sid) in sups {
+                if let Some(g) = self.charset.iter().position(|&s| s == sid) {
+                    self.encoding[code as usize] = g as u16;
+                }
+            }
+        }
+    }
+
+    fn name(&self, sid: u16) -> Option<&str> {
+        match STANDARD_STRINGS.get(sid as usize) {
+            Some(s) => Some(s),
+            None => {
+                let r = self.strings.get(sid as usize - STANDARD_STRINGS.len())?;
+                std::str::from_utf8(&self.data[r.clone()]).ok()
+            }
+        }
+    }
+
+    /// Glyph name of `gid` in a name-keyed font.
+    pub fn glyph_name(&self, gid: u16) -> Option<&str> {
+        if self.is_cid {
+            return None;
+        }
+        self.name(*self.charset.get(gid as usize)?)
+    }
+
+    pub fn gid_for_name(&self, name: &str) -> Option<u16> {
+        if self.is_cid {
+            return None;
+        }
+        (0..self.charset.len())
+            .find(|&g| self.name(self.charset[g]) == Some(name))
+            .map(|g| g as u16)
+    }
+
+    /// Glyph for `code` in the font's built-in encoding.
+    pub fn gid_for_code(&self, code: u8) -> Option<u16> {
+        Some(self.encoding[code as usize]).filter(|&g| g != 0)
+    }
+
+    /// For CID-keyed fonts through the charset; otherwise CID = GID.
+    pub fn gid_for_cid(&self, cid: u32) -> Option<u16> {
+        if !self.is_cid {
+            return (cid < self.charstrings.len() as u32).then_some(cid as u16);
+        }
+        if cid == 0 {
+            return Some(0);
+        }
+        self.charset
+            .iter()
+            .position(|&c| c as u32 == cid)
+            .map(|g| g as u16)
+    }
+
+    /// Outline of `gid` in glyph units (apply [`Cff::font_matrix`]).
+    pub fn outline(&self, gid: u16) -> Option<Path> {
+        let code = self.charstrings.get(gid as usize)?.clone();
+        let fd = self.fd_select.get(gid as usize).copied().unwrap_or(0) as usize;
+        let mut cs = Charstring {
+            cff: self,
+            fd,
+            path: Path::default(),
+            x: 0.0,
+            y: 0.0,
+            stack: Vec::new(),
+            stems: 0,
+            width_parsed: false,
+            open: false,
+            seac_depth: 0,
+            transient: [0.0; 32],
+        };
+        cs.run(&self.data[code], 0)?;
+        if cs.open {
+            cs.path.close();
+        }
+        Some(cs.path)
+    }
+}
+
+fn read_index(data: &[u8], pos: usize) -> Option<(Vec<Range>, usize)> {
+    let count = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+    if count == 0 {
+        return Some((Vec::new(), pos + 2));
+    }
+    let off_size = *data.get(pos + 2)? as usize;
+    if !(1..=4).contains(&off_size) {
+        return None;
+    }
+    let offsets_at = pos + 3;
+    let base = offsets_at + (count + 1) * off_size - 1;
+    let offset = |i: usize| -> Option<usize> {
+        let b = data.get(offsets_at + i * off_size..offsets_at + (i + 1) * off_size)?;
+        Some(b.iter().fold(0usize, |acc, &x| acc << 8 | x as usize))
+    };
+    let mut ranges = Vec::with_capacity(count);
+    for i in 0..count {
+        let (start, end) = (base + offset(i)?, base + offset(i + 1)?);
+        if start > end || end > data.len() {
+            return None;
+        }
+        ranges.push(start..end);
+    }
+    Some((ranges, base + offset(count)?))
+}
+
+/// `(operator, operands)`; two-byte operators are `1200 + b1`.
+fn parse_dict(data: &[u8]) -> Vec<(u16, Vec<f64>)> {
+    let mut out = Vec::new();
+    let mut operands = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let b0 = data[i];
+        match b0 {
+            0..=21 => {
+                let op = if b0 == 12 {
+                    i += 1;
+                    1200 + *data.get(i).unwrap_or(&0) as u16
+                } else {
+                    b0 as u16
+                };
+                out.push((op, std::mem::take(&mut operands)));
+                i += 1;
+            }
+            28 | 29 | 32..=254 => {
+                let (v, len) = read_number(&data[i..]);
+                operands.push(v);
+                i += len;
+            }
+            30 => {
+                let (v, len) = read_real(&data[i + 1..]);
+                operands.push(v);
+                i += 1 + len;
+            }
+            _ => i += 1,
+        }
+    }
+    out
+}
+
+/// An integer operand shared by DICTs and charstrings: `(value, bytes)`.
+fn read_number(data: &[u8]) -> (f64, usize) {
+    let at = |i: usize| *data.get(i).unwrap_or(&0) as i32;
+    match data[0] {
+        28 => ((at(1) << 8 | at(2)) as i16 as f64, 3),
+        29 => ((at(1) << 24 | at(2) << 16 | at(3) << 8 | at(4)) as f64, 5),
+        b @ 32..=246 => (b as f64 - 139.0, 1),
+        b @ 247..=250 => (((b as i32 - 247) * 256 + at(1) + 108) as f64, 2),
+        b @ 251..=254 => ((-(b as i32 - 251) * 256 - at(1) - 108) as f64, 2),
+        _ => (0.0, 1),
+    }
+}
+
+fn read_real(data: &[u8]) -> (f64, usize) {
+    let mut s = String::new();
+    for (i, &b) in data.iter().enumerate() {
+        for nibble in [b >> 4, b & 0xf] {
+            match nibble {
+                0..=9 => s.push((b'0' + nibble) as char),
+                0xa => s.push('.'),
+                0xb => s.push('E'),
+                0xc => s.push_str("E-"),
+                0xe => s.push('-'),
+                0xf => return (s.parse().unwrap_or(0.0), i + 1),
+                _ => {}
+            }
+        }
+    }
+    (s.parse().unwrap_or(0.0), data.len())
+}
+
+fn read_charset(data: &[u8], at: usize, glyphs: usize) -> Option<Vec<u16>> {
+    let mut out = vec![0u16];
+    let format = *data.get(at)?;
+    let mut pos = at + 1;
+    let u16_at =
+        |p: usize| -> Option<u16> { Some(u16::from_be_bytes([*data.get(p)?, *data.get(p + 1)?])) };
+    while out.len() < glyphs {
+        match format {
+            0 => {
+                out.push(u16_at(pos)?);
+                pos += 2;
+            }
+            1 | 2 => {
+                let first = u16_at(pos)?;
+                let left = if format == 1 {
+                    *data.get(pos + 2)? as u16
+                } else {
+                    u16_at(pos + 2)?
+                };
+                pos += if format == 1 { 3 } else { 4 };
+                for i in 0..=left {
+                    out.push(first.wrapping_add(i));
+                }
+            }
+            _ => return None,
+        }
+    }
+    out.truncate(glyphs);
+    Some(out)
+}
+
+fn read_fd_select(data: &[u8], at: usize, glyphs: usize) -> Option<Vec<u8>> {
+    match *data.get(at)? {
+        0 => Some(data.get(at + 1..at + 1 + glyphs)?.to_vec()),
+        3 => {
+            let n = u16::from_be_bytes([*data.get(at + 1)?, *data.get(at + 2)?]) as usize;
+            let mut out = vec![0u8; glyphs];
+            for i in 0..n {
+                let r = data.get(at + 3 + i * 3..at + 3 + i * 3 + 5)?;
+                let first = u16::from_be_bytes([r[0], r[1]]) as usize;
+                let next = u16::from_be_bytes([r[3], r[4]]) as usize;
+                for slot in out.iter_mut().take(next.min(glyphs)).skip(first) {
+                    *slot = r[2];
+                }
+            }
+            Some(out)
+        }
+        _ => None,
+    }
+}
+
+fn private_subrs(data: &[u8], dict: &[(u16, Vec<f64>)]) -> Option<Vec<Range>> {
+    let (_, private) = dict.iter().find(|(o, _)| *o == 18)?;
+    let (size, at) = (*private.first()? as usize, *private.get(1)? as usize);
+    let private_dict = parse_dict(data.get(at..at + size)?);
+    let (_, subrs) = private_dict.iter().find(|(o, _)| *o == 19)?;
+    Some(read_index(data, at + *subrs.first()? as usize)?.0)
+}
+
+fn bias(count: usize) -> usize {
+    match count {
+        0..=1239 => 107,
+        1240..=33899 => 1131,
+        _ => 32768,
+    }
+}
+
+struct Charstring<'a> {
+    cff: &'a Cff,
+    fd: usize,
+    path: Path,
+    x: f64,
+    y: f64,
+    stack: Vec<f64>,
+    stems: usize,
+    width_parsed: bool,
+    open: bool,
+    seac_depth: usize,
+    transient: [f64; 32],
+}
+
+impl Charstring<'_> {
+    /// Drops the advance width a charstring may put before its first operator.
+    fn take_width(&mut self, expected_even: bool) {
+        if !self.width_parsed {
+            self.width_parsed = true;
+            let odd = self.stack.len() % 2 == 1;
+            if odd == expected_even && !self.stack.is_empty() {
+                self.stack.remove(0);
+            }
+        }
+    }
+
+    fn move_to(&mut self, dx: f64, dy: f64) {
+        if self.open {
+            self.path.close();
+        }
+        self.x += dx;
+        self.y += dy;
+        self.path.move_to(self.x, self.y);
+        self.open = true;
+    }
+
+    fn line_to(&mut self, dx: f64, dy: f64) {
+        self.x += dx;
+        self.y += dy;
+        self.path.line_to(self.x, self.y);
+    }
+
+    fn curve_to(&mut self, d: [f64; 6]) {
+        let (x1, y1) = (self.x + d[0], self.y + d[1]);
+        let (x2, y2) = (x1 + d[2], y1 + d[3]);
+        let (x3, y3) = (x2 + d[4], y2 + d[5]);
+        self.path.curve_to(x1, y1, x2, y2, x3, y3);
+        self.x = x3;
+        self.y = y3;
+    }
+
+    /// `Some(true)` after `endchar`.
+    fn run(&mut self, code: &[u8], depth: usize) -> Option<bool> {
+        if depth > MAX_CALL_DEPTH {
+            return None;
+        }
+        let mut i = 0;
+        while i < code.len() {
+            let b0 = code[i];
+            if b0 == 28 || b0 >= 32 {
+                let (v, len) = if b0 == 255 {
+                    let b = code.get(i + 1..i + 5)?;
+                    (
+                        i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64 / 65536.0,
+                        5,
+                    )
+                } else {
+                    read_number(&code[i..])
+                };
+                if self.stack.len() < MAX_STACK {
+                    self.stack.push(v);
+                }
+                i += len;
+                continue;
+            }
+            i += 1;
+            match b0 {
+                1 | 3 | 18 | 23 => {
+                    self.take_width(true);
+                    self.stems += self.stack.len() / 2;
+                    self.stack.clear();
+                }
+                19 | 20 => {
+                    self.take_width(true);
+                    self.stems += self.stack.len() / 2;
+                    self.stack.clear();
+                    i += self.stems.div_ceil(8);
+                }
+                21 => {
+                    self.take_width(false);
+                    let n = self.stack.len();
+                    if n >= 2 {
+                        self.move_to(self.stack[n - 2], self.stack[n - 1]);
+                    }
+                    self.stack.clear();
+                }
+                22 | 4 => {
+                    self.take_width(true);
+                    let v = self.stack.last().copied().unwrap_or(0.0);
+                    if b0 == 22 {
+                        self.move_to(v, 0.0);
+                    } else {
+                        self.move_to(0.0, v);
+                    }
+                    self.stack.clear();
+                }
+                5 => {
+                    let args = std::mem::take(&mut self.stack);
+                    for p in args.chunks_exact(2) {
+                        self.line_to(p[0], p[1]);
+                    }
+                }
+                6 | 7 => {
+                    let args = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 6;
+                    for &d in &args {
+                        if horizontal {
+                            self.line_to(d, 0.0);
+                        } else {
+                            self.line_to(0.0, d);
+                        }
+                        horizontal = !horizontal;
+                    }
+                }
+                8 => {
+                    let args = std::mem::take(&mut self.stack);
+                    for c in args.chunks_exact(6) {
+                        self.curve_to([c[0], c[1], c[2], c[3], c[4], c[5]]);
+                    }
+                }
+                24 => {
+                    let args = std::mem::take(&mut self.stack);
+                    let curves = args.len().saturating_sub(2) / 6;
+                    for c in args.chunks_exact(6).take(curves) {
+                        self.curve_to([c[0], c[1], c[2], c[3], c[4], c[5]]);
+                    }
+                    if let [dx, dy] = args[curves * 6..] {
+                        self.line_to(dx, dy);
+                    }
+                }
+                25 => {
+                    let args = std::mem::take(&mut self.stack);
+                    let lines = args.len().saturating_sub(6) / 2;
+                    for p in args.chunks_exact(2).take(lines) {
+                        self.line_to(p[0], p[1]);
+                    }
+                    if let [a, b, c, d, e, f] = args[lines * 2..] {
+                        self.curve_to([a, b, c, d, e, f]);
+                    }
+                }
+                26 | 27 => {
+                    let mut args = std::mem::take(&mut self.stack);
+                    let mut first = 0.0;
+                    if args.len() % 4 == 1 {
+                        first = args.remove(0);
+                    }
+                    for (n, c) in args.chunks_exact(4).enumerate() {
+                        let extra = if n == 0 { first } else { 0.0 };
+                        if b0 == 26 {
+                            self.curve_to([extra, c[0], c[1], c[2], 0.0, c[3]]);
+                        } else {
+                            self.curve_to([c[0], extra, c[1], c[2], c[3], 0.0]);
+                        }
+                    }
+                }
+                30 | 31 => {
+                    let args = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 31;
+                    let mut k = 0;
+                    while k + 4 <= args.len() {
+                        let last = if args.len() - (k + 4) == 1 {
+                            args[k + 4]
+                        } else {
+                            0.0
+                        };
+                        let c = &args[k..k + 4];
+                        if horizontal {
+                            self.curve_to([c[0], 0.0, c[1], c[2], last, c[3]]);
+                        } else {
+                            self.curve_to([0.0, c[0], c[1], c[2], c[3], last]);
+                        }
+                        horizontal = !horizontal;
+                        k += 4;
+                    }
+                }
+                10 | 29 => {
+                    let index = self.stack.pop()? as isize;
+                    let subrs = if b0 == 10 {
+                        self.cff.local_subrs.get(self.fd)?
+                    } else {
+                        &self.cff.global_subrs
+                    };
+                    let n = (index + bias(subrs.len()) as isize) as usize;
+                    let range = subrs.get(n)?.clone();
+                    if self.run(&self.cff.data[range], depth + 1)? {
+                        return Some(true);
+                    }
+                }
+                11 => return Some(false),
+                14 => {
+                    if !self.width_parsed {
+                        self.width_parsed = true;
+                        if self.stack.len() == 1 || self.stack.len() == 5 {
+                            self.stack.remove(0);
+                        }
+                    }
+                    if let [adx, ady, bchar, achar] = self.stack[..] {
+                        self.seac(adx, ady, bchar as u8, achar as u8);
+                    }
+                    if self.open {
+                        self.path.close();
+                        self.open = false;
+                    }
+                    return Some(true);
+                }
+                12 => {
+                    let op = *code.get(i)?;
+                    i += 1;
+                    self.escape(op)?;
+                }
+                _ => self.stack.clear(),
+            }
+        }
+        Some(false)
+    }
+
+    /// Accented character: base and accent glyph by StandardEncoding code.
+    fn seac(&mut self, adx: f64, ady: f64, bchar: u8, achar: u8) {
+        if self.seac_depth > 0 {
+            return;
+        }
+        let glyph = |code: u8| standard(code).and_then(|n| self.cff.gid_for_name(n));
+        let (Some(base), Some(accent)) = (glyph(bchar), glyph(achar)) else {
+            return;
+        };
+        for (gid, offset) in [(base, (0.0, 0.0)), (accent, (adx, ady))] {
+            let Some(range) = self.cff.charstrings.get(gid as usize).cloned() else {
+                continue;
+            };
+            let mut sub = Charstring {
+                cff: self.cff,
+                fd: self.fd,
+                path: Path::default(),
+                x: 0.0,
+                y: 0.0,
+                stack: Vec::new(),
+                stems: 0,
+                width_parsed: false,
+                open: false,
+                seac_depth: 1,
+                transient: [0.0; 32],
+            };
+            if sub.run(&self.cff.data[range], 0).is_some() {
+                if sub.open {
+                    sub.path.close();
+                }
+                self.path
+                    .extend_transformed(&sub.path, &Matrix::translate(offset.0, offset.1));
+            }
+        }
+    }
+
+    fn escape(&mut self, op: u8) -> Option<()> {
+        let s = &mut self.stack;
+        match op {
+            // flex, hflex, flex1, hflex1
+            35 => {
+                let a = std::mem::take(s);
+                if a.len() >= 12 {
+                    self.curve_to([a[0], a[1], a[2], a[3], a[4], a[5]]);
+                    self.curve_to([a[6], a[7], a[8], a[9], a[10], a[11]]);
+                }
+            }
+            34 => {
+                let a = std::mem::take(s);
+                if a.len() >= 7 {
+                    let y0 = self.y;
+                    self.curve_to([a[0], 0.0, a[1], a[2], a[3], 0.0]);
+                    self.curve_to([a[4], 0.0, a[5], y0 - self.y, a[6], 0.0]);
+                }
+            }
+            36 => {
+                let a = std::mem::take(s);
+                if a.len() >= 9 {
+                    let y0 = self.y;
+                    self.curve_to([a[0], a[1], a[2], a[3], a[4], 0.0]);
+                    let dy = y0 - (self.y + a[7]);
+                    self.curve_to([a[5], 0.0, a[6], a[7], a[8], dy]);
+                }
+            }
+            37 => {
+                let a = std::mem::take(s);
+                if a.len() >= 11 {
+                    let dx: f64 = a[0] + a[2] + a[4] + a[6] + a[8];
+                    let dy: f64 = a[1] + a[3] + a[5] + a[7] + a[9];
+                    let (x0, y0) = (self.x, self.y);
+                    self.curve_to([a[0], a[1], a[2], a[3], a[4], a[5]]);
+                    let (lx, ly) = if dx.abs() > dy.abs() {
+                        (a[10], y0 - (self.y + a[7] + a[9]))
+                    } else {
+                        (x0 - (self.x + a[6] + a[8]), a[10])
+                    };
+                    self.curve_to([a[6], a[7], a[8], a[9], lx, ly]);
+                }
+            }
+            // Arithmetic and storage.
+            3 => binary(s, |a, b| f64::from(a != 0.0 && b != 0.0)),
+            4 => binary(s, |a, b| f64::from(a != 0.0 || b != 0.0)),
+            5 => unary(s, |a| f64::from(a == 0.0)),
+            9 => unary(s, f64::abs),
+            10 => binary(s, |a, b| a + b),
+            11 => binary(s, |a, b| a - b),
+            12 => binary(s, |a, b| if b == 0.0 { 0.0 } else { a / b }),
+            14 => unary(s, |a| -a),
+            15 => binary(s, |a, b| f64::from(a == b)),
+            18 => {
+                s.pop();
+            }
+            20 => {
+                let i = s.pop()? as usize;
+                let v = s.pop()?;
+                if let Some(slot) = self.transient.get_mut(i) {
+                    *slot = v;
+                }
+            }
+            21 => {
+                let i = s.pop()? as usize;
+                s.push(self.transient.get(i).copied().unwrap_or(0.0));
+            }
+            22 => {
+                let v2 = s.pop()?;
+                let v1 = s.pop()?;
+                let b = s.pop()?;
+                let a = s.pop()?;
+                s.push(if v1 <= v2 { a } else { b });
+            }
+            23 => s.push(0.5),
+            24 => binary(s, |a, b| a * b),
+            26 => unary(s, |a| a.max(0.0).sqrt()),
+            27 => {
+                let v = *s.last()?;
+                s.push(v);
+            }
+            28 => {
+                let n = s.len();
+                if n >= 2 {
+                    s.swap(n - 1, n - 2);
+                }
+            }
+            29 => {
+                let i = s.pop()?;
+                let n = s.len();
+                let v = if i < 0.0 {
+                    *s.last()?
+                } else {
+                    *s.get(n.checked_sub(1 + i as usize)?)?
+                };
+                s.push(v);
+            }
+            30 => {
+                let j = s.pop()? as isize;
+                let n = s.pop()? as usize;
+                let len = s.len();
+                if n > 0 && n <= len {
+                    let part = &mut s[len - n..];
+                    let shift = j.rem_euclid(n as isize) as usize;
+                    part.rotate_right(shift);
+                }
+            }
+            // dotsection and hint replacement ops carry nothing we draw.
+            _ => s.clear(),
+        }
+        Some(())
+    }
+}
+
+fn unary(s: &mut Vec<f64>, f: impl Fn(f64) -> f64) {
+    if let Some(a) = s.pop() {
+        s.push(f(a));
+    }
+}
+
+fn binary(s: &mut Vec<f64>, f: impl Fn(f64, f64) -> f64) {
+    if let (Some(b), Some(a)) = (s.pop(), s.pop()) {
+        s.push(f(a, b));
+    }
+}
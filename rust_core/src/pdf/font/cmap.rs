@@ -0,0 +1,235 @@
+//! CMaps: embedded Type 0 encodings (`cidrange`/`cidchar`) and ToUnicode maps
+//! (`bfrange`/`bfchar`). Of the predefined CMaps only `Identity-H`/`-V` are
+//! known; any other name is read as Identity too.
+
+use std::collections::HashMap;
+
+use crate::pdf::object::{Lexer, Token};
+
+#[derive(Debug, Clone)]
+struct CodeRange {
+    low: Vec<u8>,
+    high: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    /// First destination; later codes increment its last UTF-16 unit.
+    Start(Vec<u16>),
+    Each(Vec<String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CMap {
+    codespace: Vec<CodeRange>,
+    cid_single: HashMap<u32, u32>,
+    /// `(low, high, first CID)`.
+    cid_ranges: Vec<(u32, u32, u32)>,
+    text_single: HashMap<u32, String>,
+    text_ranges: Vec<(u32, u32, Target)>,
+    /// No `cidrange`/`cidchar` at all: the code is the CID.
+    identity: bool,
+}
+
+impl CMap {
+    /// Two-byte codes mapping to the same CID.
+    pub fn identity() -> CMap {
+        CMap {
+            codespace: vec![CodeRange {
+                low: vec![0, 0],
+                high: vec![0xff, 0xff],
+            }],
+            identity: true,
+            ..CMap::default()
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> CMap {
+        let mut cmap = CMap::default();
+        let mut lexer = Lexer::new(data, 0);
+        let mut operands: Vec<Token> = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            let Token::Keyword(k) = &token else {
+                operands.push(token);
+                continue;
+            };
+            match k.as_str() {
+                "begincodespacerange"
+                | "begincidrange"
+                | "begincidchar"
+                | "beginbfchar"
+                | "beginbfrange" => operands.clear(),
+                "endcodespacerange" => {
+                    for pair in operands.chunks_exact(2) {
+                        if let (Token::Str(low), Token::Str(high)) = (&pair[0], &pair[1]) {
+                            cmap.codespace.push(CodeRange {
+                                low: low.clone(),
+                                high: high.clone(),
+                            });
+                        }
+                    }
+                    operands.clear();
+                }
+                "endcidrange" => {
+                    for t in operands.chunks_exact(3) {
+                        if let (Token::Str(low), Token::Str(high), Token::Int(cid)) =
+                            (&t[0], &t[1], &t[2])
+                        {
+                            cmap.cid_ranges
+                                .push((be(low), be(high), (*cid).max(0) as u32));
+                        }
+                    }
+                    operands.clear();
+                }
+                "endcidchar" => {
+                    for t in operands.chunks_exact(2) {
+                        if let (Token::Str(code), Token::Int(cid)) = (&t[0], &t[1]) {
+                            cmap.cid_single.insert(be(code), (*cid).max(0) as u32);
+                        }
+                    }
+                    operands.clear();
+                }
+                "endbfchar" => {
+                    for t in operands.chunks_exact(2) {
+                        match (&t[0], &t[1]) {
+                            (Token::Str(code), Token::Str(dst)) => {
+                                cmap.text_single.insert(be(code), utf16(dst));
+                            }
+                            (Token::Str(code), Token::Name(name)) => {
+                                if let Some(s) = super::encoding::glyph_name_to_unicode(name) {
+                                    cmap.text_single.insert(be(code), s);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    operands.clear();
+                }
+                "endbfrange" => {
+                    cmap.read_bf_ranges(&operands);
+                    operands.clear();
+                }
+                _ => operands.clear(),
+            }
+        }
+        cmap.identity = cmap.cid_single.is_empty() && cmap.cid_ranges.is_empty();
+        cmap
+    }
+
+    fn read_bf_ranges(&mut self, tokens: &[Token]) {
+        let mut i = 0;
+        while i + 2 < tokens.len() {
+            let (Token::Str(low), Token::Str(high)) = (&tokens[i], &tokens[i + 1]) else {
+                i += 1;
+                continue;
+            };
+            let (low, high) = (be(low), be(high));
+            match &tokens[i + 2] {
+                Token::Str(dst) => {
+                    self.text_ranges.push((
+                        low,
+                        high,
+                        Target::Start(dst.chunks(2).map(unit).collect()),
+                    ));
+                    i += 3;
+                }
+                Token::ArrayOpen => {
+                    let mut each = Vec::new();
+                    i += 3;
+                    while i < tokens.len() && tokens[i] != Token::ArrayClose {
+                        if let Token::Str(dst) = &tokens[i] {
+                            each.push(utf16(dst));
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                    self.text_ranges.push((low, high, Target::Each(each)));
+                }
+                _ => i += 3,
+            }
+        }
+    }
+
+    /// Splits the next code off `bytes`: `(code, byte length)`.
+    pub fn next_code(&self, bytes: &[u8]) -> (u32, usize) {
+        for len in 1..=4 {
+            if bytes.len() < len {
+                break;
+            }
+            let candidate = &bytes[..len];
+            let matches = self.codespace.iter().any(|r| {
+                r.low.len() == len
+                    && candidate
+                        .iter()
+                        .zip(r.low.iter().zip(&r.high))
+                        .all(|(b, (lo, hi))| lo <= b && b <= hi)
+            });
+            if matches {
+                return (be(candidate), len);
+            }
+        }
+        // Outside every range: use the shortest code length.
+        let len = self
+            .codespace
+            .iter()
+            .map(|r| r.low.len())
+            .min()
+            .unwrap_or(1)
+            .clamp(1, bytes.len().max(1));
+        (be(&bytes[..len.min(bytes.len())]), len)
+    }
+
+    pub fn cid(&self, code: u32) -> u32 {
+        if self.identity {
+            return code;
+        }
+        if let Some(&cid) = self.cid_single.get(&code) {
+            return cid;
+        }
+        self.cid_ranges
+            .iter()
+            .find(|(lo, hi, _)| (*lo..=*hi).contains(&code))
+            .map_or(0, |(lo, _, start)| start + (code - lo))
+    }
+
+    pub fn unicode(&self, code: u32) -> Option<String> {
+        if let Some(s) = self.text_single.get(&code) {
+            return Some(s.clone());
+        }
+        let (lo, _, target) = self
+            .text_ranges
+            .iter()
+            .find(|(lo, hi, _)| (*lo..=*hi).contains(&code))?;
+        let offset = code - lo;
+        match target {
+            Target::Start(units) => {
+                let mut units = units.clone();
+                let last = units.last_mut()?;
+                *last = last.wrapping_add(offset as u16);
+                Some(String::from_utf16_lossy(&units))
+            }
+            Target::Each(each) => each.get(offset as usize).cloned(),
+        }
+    }
+
+    pub fn has_unicode(&self) -> bool {
+        !self.text_single.is_empty() || !self.text_ranges.is_empty()
+    }
+}
+
+fn be(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |acc, &b| acc << 8 | b as u32)
+}
+
+fn unit(pair: &[u8]) -> u16 {
+    match pair {
+        [hi, lo] => u16::from_be_bytes([*hi, *lo]),
+        [b] => *b as u16,
+        _ => 0,
+    }
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2).map(unit).collect();
+    String::from_utf16_lossy(&units)
+}
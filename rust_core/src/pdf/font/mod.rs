@@ -0,0 +1,542 @@
+//! PDF fonts: from a character code in a string to an advance width, an outline
+//! and the Unicode text it stands for.
+//!
+//! Embedded programs are read for simple (Type 1, Type1C, TrueType), composite
+//! (CIDFontType0/2 behind a Type 0 font) and Type 3 fonts. Fonts that are not
+//! embedded keep their widths and text but draw nothing.
+
+mod cff;
+mod cmap;
+pub mod encoding;
+mod truetype;
+mod type1;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cff::Cff;
+use cmap::CMap;
+use truetype::TrueType;
+use type1::Type1;
+
+use super::file::PdfFile;
+use super::object::{Dict, Object};
+use super::raster::{Matrix, Path};
+
+enum Program {
+    Cff(Box<Cff>),
+    Type1(Type1),
+    TrueType(TrueType),
+    None,
+}
+
+/// How codes are split and mapped to glyphs.
+enum Kind {
+    Simple {
+        /// Glyph name per code from `/Encoding` and its `/Differences`.
+        names: Vec<Option<String>>,
+        /// Font flags say the font is symbolic (TrueType cmap lookup differs).
+        symbolic: bool,
+        first_char: u32,
+        widths: Vec<f64>,
+    },
+    Composite {
+        cmap: CMap,
+        /// `None` for `Identity`.
+        cid_to_gid: Option<Vec<u16>>,
+        default_width: f64,
+        widths: HashMap<u32, f64>,
+    },
+    Type3 {
+        first_char: u32,
+        widths: Vec<f64>,
+        names: Vec<Option<String>>,
+        procs: Dict,
+    },
+}
+
+pub struct Font {
+    kind: Kind,
+    program: Program,
+    to_unicode: Option<CMap>,
+    missing_width: f64,
+    /// Glyph space to text space.
+    pub matrix: Matrix,
+    /// Resources the glyph procedures of a Type 3 font use.
+    pub resources: Option<Dict>,
+    /// Above and below the baseline, in text space units (per 1 unit font size).
+    pub ascent: f64,
+    pub descent: f64,
+    outlines: RefCell<HashMap<u32, Option<Rc<Path>>>>,
+}
+
+impl Font {
+    pub fn load(file: &PdfFile, dict: &Dict) -> Font {
+        let subtype = file.lookup(dict, "Subtype");
+        match subtype.as_name() {
+            Some("Type0") => Self::load_composite(file, dict),
+            Some("Type3") => Self::load_type3(file, dict),
+            _ => Self::load_simple(file, dict),
+        }
+    }
+
+    fn load_simple(file: &PdfFile, dict: &Dict) -> Font {
+        let descriptor = file.dict(dict.get("FontDescriptor").unwrap_or(&Object::Null));
+        let program = descriptor
+            .as_ref()
+            .map_or(Program::None, |d| load_program(file, d));
+        let flags = descriptor
+            .as_ref()
+            .and_then(|d| file.number(d, "Flags"))
+            .unwrap_or(0.0) as u32;
+        let symbolic = flags & 4 != 0;
+        let first_char = file.number(dict, "FirstChar").unwrap_or(0.0).max(0.0) as u32;
+        let widths = numbers(file, &file.lookup(dict, "Widths"));
+        // The standard 14 fonts may come without widths; half an em keeps
+        // their words apart in the text layer.
+        let missing_width = if widths.is_empty() { 500.0 } else { 0.0 };
+        let names = simple_encoding(file, dict, &program, symbolic);
+        let matrix = match &program {
+            Program::Cff(c) => c.font_matrix,
+            Program::Type1(t) => t.font_matrix,
+            Program::TrueType(t) => Matrix::scale(1.0 / t.units_per_em, 1.0 / t.units_per_em),
+            Program::None => Matrix::scale(0.001, 0.001),
+        };
+        let mut font = Font {
+            kind: Kind::Simple {
+                names,
+                symbolic,
+                first_char,
+                widths,
+            },
+            program,
+            to_unicode: None,
+            missing_width,
+            matrix,
+            resources: None,
+            ascent: 0.8,
+            descent: -0.2,
+            outlines: RefCell::new(HashMap::new()),
+        };
+        font.read_common(file, dict, descriptor.as_ref());
+        font
+    }
+
+    fn load_composite(file: &PdfFile, dict: &Dict) -> Font {
+        let cmap = match &*file.lookup(dict, "Encoding") {
+            Object::Stream(_) => dict
+                .get("Encoding")
+                .and_then(|e| file.stream_data(e))
+                .map_or_else(CMap::identity, |d| CMap::parse(&d)),
+            _ => CMap::identity(),
+        };
+        let descendant = file
+            .lookup(dict, "DescendantFonts")
+            .as_array()
+            .and_then(|a| a.first())
+            .and_then(|d| file.dict(d))
+            .unwrap_or_default();
+        let descriptor = file.dict(descendant.get("FontDescriptor").unwrap_or(&Object::Null));
+        let program = descriptor
+            .as_ref()
+            .map_or(Program::None, |d| load_program(file, d));
+        let cid_to_gid = match descendant.get("CIDToGIDMap") {
+            Some(o) => file.stream_data(o).map(|d| {
+                d.chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect()
+            }),
+            None => None,
+        };
+        let default_width = file.number(&descendant, "DW").unwrap_or(1000.0);
+        let widths = cid_widths(file, &file.lookup(&descendant, "W"));
+        let matrix = match &program {
+            Program::Cff(c) => c.font_matrix,
+            Program::TrueType(t) => Matrix::scale(1.0 / t.units_per_em, 1.0 / t.units_per_em),
+            _ => Matrix::scale(0.001, 0.001),
+        };
+        let mut font = Font {
+            kind: Kind::Composite {
+                cmap,
+                cid_to_gid,
+                default_width,
+                widths,
+            },
+            program,
+            to_unicode: None,
+            missing_width: default_width,
+            matrix,
+            resources: None,
+            ascent: 0.8,
+            descent: -0.2,
+            outlines: RefCell::new(HashMap::new()),
+        };
+        font.read_common(file, dict, descriptor.as_ref());
+        font
+    }
+
+    fn load_type3(file: &PdfFile, dict: &Dict) -> Font {
+        let matrix = match numbers(file, &file.lookup(dict, "FontMatrix"))[..] {
+            [a, b, c, d, e, f] => Matrix([a, b, c, d, e, f]),
+            _ => Matrix::scale(0.001, 0.001),
+        };
+        let first_char = file.number(dict, "FirstChar").unwrap_or(0.0).max(0.0) as u32;
+        let widths = numbers(file, &file.lookup(dict, "Widths"));
+        let names = simple_encoding(file, dict, &Program::None, true);
+        let procs = file
+            .dict(dict.get("CharProcs").unwrap_or(&Object::Null))
+            .unwrap_or_default();
+        let resources = file.dict(dict.get("Resources").unwrap_or(&Object::Null));
+        let mut font = Font {
+            kind: Kind::Type3 {
+                first_char,
+                widths,
+                names,
+                procs,
+            },
+            program: Program::None,
+            to_unicode: None,
+            missing_width: 0.0,
+            matrix,
+            resources,
+            ascent: 0.8,
+            descent: -0.2,
+            outlines: RefCell::new(HashMap::new()),
+        };
+        font.read_common(file, dict, None);
+        font
+    }
+
+    fn read_common(&mut self, file: &PdfFile, dict: &Dict, descriptor: Option<&Dict>) {
+        self.to_unicode = dict
+            .get("ToUnicode")
+            .and_then(|t| file.stream_data(t))
+            .map(|d| CMap::parse(&d))
+            .filter(CMap::has_unicode);
+        if let Some(d) = descriptor {
+            if let Some(w) = file.number(d, "MissingWidth") {
+                self.missing_width = w;
+            }
+            let ascent = file.number(d, "Ascent").unwrap_or(0.0);
+            let descent = file.number(d, "Descent").unwrap_or(0.0);
+            // Some producers write zeros; keep the defaults then.
+            if ascent > 0.0 {
+                self.ascent = ascent / 1000.0;
+            }
+            if descent < 0.0 {
+                self.descent = descent / 1000.0;
+            }
+        }
+    }
+
+    /// Splits a shown string into codes; the flag marks single-byte code 32,
+    /// the only one word spacing (`Tw`) applies to.
+    pub fn codes(&self, bytes: &[u8]) -> Vec<(u32, bool)> {
+        match &self.kind {
+            Kind::Composite { cmap, .. } => {
+                let mut out = Vec::new();
+                let mut rest = bytes;
+                while !rest.is_empty() {
+                    let (code, len) = cmap.next_code(rest);
+                    out.push((code, len == 1 && code == 32));
+                    rest = &rest[len.min(rest.len()).max(1)..];
+                }
+                out
+            }
+            _ => bytes.iter().map(|&b| (b as u32, b == 32)).collect(),
+        }
+    }
+
+    /// Horizontal advance of `code` in text space units (per 1 unit font size).
+    pub fn width(&self, code: u32) -> f64 {
+        match &self.kind {
+            Kind::Simple {
+                first_char, widths, ..
+            } => {
+                code.checked_sub(*first_char)
+                    .and_then(|i| widths.get(i as usize))
+                    .copied()
+                    .unwrap_or(self.missing_width)
+                    / 1000.0
+            }
+            Kind::Composite {
+                cmap,
+                default_width,
+                widths,
+                ..
+            } => widths.get(&cmap.cid(code)).unwrap_or(default_width) / 1000.0,
+            Kind::Type3 {
+                first_char, widths, ..
+            } => {
+                let w = code
+                    .checked_sub(*first_char)
+                    .and_then(|i| widths.get(i as usize))
+                    .copied()
+                    .unwrap_or(0.0);
+                self.matrix.apply_vector(w, 0.0).0
+            }
+        }
+    }
+
+    /// Glyph procedure of a Type 3 font for `code`.
+    pub fn type3_proc(&self, code: u32) -> Option<&Object> {
+        let Kind::Type3 { names, procs, .. } = &self.kind else {
+            return None;
+        };
+        let name = names.get(code as usize)?.as_ref()?;
+        procs.get(name)
+    }
+
+    pub fn is_type3(&self) -> bool {
+        matches!(self.kind, Kind::Type3 { .. })
+    }
+
+    /// Outline of `code` in text space (per 1 unit font size); `None` when the
+    /// font is not embedded or has no such glyph.
+    pub fn outline(&self, code: u32) -> Option<Rc<Path>> {
+        if let Some(cached) = self.outlines.borrow().get(&code) {
+            return cached.clone();
+        }
+        let path = self
+            .glyph_outline(code)
+            .map(|p| Rc::new(p.transform(&self.matrix)));
+        self.outlines.borrow_mut().insert(code, path.clone());
+        path
+    }
+
+    fn glyph_outline(&self, code: u32) -> Option<Path> {
+        match (&self.kind, &self.program) {
+            (Kind::Simple { names, .. }, Program::Cff(cff)) => {
+                let by_name = names
+                    .get(code as usize)
+                    .and_then(|n| n.as_deref())
+                    .and_then(|n| cff.gid_for_name(n));
+                let gid = by_name.or_else(|| cff.gid_for_code(code as u8))?;
+                cff.outline(gid)
+            }
+            (Kind::Simple { names, .. }, Program::Type1(t1)) => {
+                let name = names
+                    .get(code as usize)
+                    .and_then(|n| n.as_deref())
+                    .filter(|n| t1.has_glyph(n))
+                    .or_else(|| t1.name_for_code(code as u8))?;
+                t1.outline(name)
+            }
+            (
+                Kind::Simple {
+                    names, symbolic, ..
+                },
+                Program::TrueType(tt),
+            ) => {
+                let gid = simple_truetype_gid(tt, code, names, *symbolic)?;
+                tt.outline(gid)
+            }
+            (
+                Kind::Composite {
+                    cmap, cid_to_gid, ..
+                },
+                program,
+            ) => {
+                let cid = cmap.cid(code);
+                match program {
+                    Program::Cff(cff) => cff.outline(cff.gid_for_cid(cid)?),
+                    Program::TrueType(tt) => {
+                        let gid = match cid_to_gid {
+                            Some(map) => *map.get(cid as usize)?,
+                            None => cid as u16,
+                        };
+                        tt.outline(gid)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The text `code` stands for: ToUnicode first, then the glyph name.
+    pub fn unicode(&self, code: u32) -> Option<String> {
+        if let Some(s) = self.to_unicode.as_ref().and_then(|m| m.unicode(code)) {
+            return Some(s);
+        }
+        match (&self.kind, &self.program) {
+            (Kind::Simple { names, .. }, program) | (Kind::Type3 { names, .. }, program) => {
+                let name =
+                    names
+                        .get(code as usize)
+                        .and_then(|n| n.clone())
+                        .or_else(|| match program {
+                            Program::Cff(cff) => cff
+                                .gid_for_code(code as u8)
+                                .and_then(|g| cff.glyph_name(g))
+                                .map(str::to_string),
+                            Program::Type1(t1) => t1.name_for_code(code as u8).map(str::to_string),
+                            _ => None,
+                        });
+                match name {
+                    Some(n) => encoding::glyph_name_to_unicode(&n),
+                    None => char::from_u32(code)
+                        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+                        .map(String::from),
+                }
+            }
+            (Kind::Composite { .. }, _) => None,
+        }
+    }
+}
+
+fn load_program(file: &PdfFile, descriptor: &Dict) -> Program {
+    if let Some(data) = descriptor
+        .get("FontFile3")
+        .and_then(|f| file.stream_data(f))
+    {
+        let subtype = descriptor
+            .get("FontFile3")
+            .map(|f| file.resolve(f))
+            .and_then(|f| f.as_dict().and_then(|d| d.get("Subtype")).cloned());
+        let is_opentype_tt = subtype.as_ref().and_then(Object::as_name) == Some("OpenType")
+            && data.get(..4) != Some(b"OTTO");
+        if is_opentype_tt {
+            return TrueType::parse(data).map_or(Program::None, Program::TrueType);
+        }
+        return Cff::parse(data).map_or(Program::None, |c| Program::Cff(Box::new(c)));
+    }
+    if let Some(data) = descriptor
+        .get("FontFile2")
+        .and_then(|f| file.stream_data(f))
+    {
+        return TrueType::parse(data).map_or(Program::None, Program::TrueType);
+    }
+    if let Some(data) = descriptor.get("FontFile").and_then(|f| file.stream_data(f)) {
+        return Type1::parse(&data).map_or(Program::None, Program::Type1);
+    }
+    Program::None
+}
+
+fn numbers(file: &PdfFile, obj: &Object) -> Vec<f64> {
+    obj.as_array()
+        .unwrap_or(&[])
+        .iter()
+        .map(|o| file.resolve(o).as_f64().unwrap_or(0.0))
+        .collect()
+}
+
+/// `W` array: `c [w1 w2 …]` and `c_first c_last w`.
+fn cid_widths(file: &PdfFile, obj: &Object) -> HashMap<u32, f64> {
+    let mut out = HashMap::new();
+    let items: Vec<Rc<Object>> = obj
+        .as_array()
+        .unwrap_or(&[])
+        .iter()
+        .map(|o| file.resolve(o))
+        .collect();
+    let mut i = 0;
+    while i + 1 < items.len() {
+        let Some(first) = items[i].as_i64() else {
+            i += 1;
+            continue;
+        };
+        let first = first.max(0) as u32;
+        if let Some(list) = items[i + 1].as_array() {
+            for (k, w) in list.iter().enumerate() {
+                out.insert(first + k as u32, file.resolve(w).as_f64().unwrap_or(0.0));
+            }
+            i += 2;
+        } else if let (Some(last), Some(w)) = (
+            items[i + 1].as_i64(),
+            items.get(i + 2).and_then(|o| o.as_f64()),
+        ) {
+            // Bounded so a bogus range cannot allocate without limit.
+            for cid in first..=(last.max(0) as u32).min(first + 65535) {
+                out.insert(cid, w);
+            }
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Glyph names per code: the base encoding (or, for symbolic and Type 3 fonts,
+/// none, so the font's own encoding applies) with `/Differences` on top.
+fn simple_encoding(
+    file: &PdfFile,
+    dict: &Dict,
+    program: &Program,
+    symbolic: bool,
+) -> Vec<Option<String>> {
+    let mut names: Vec<Option<String>> = vec![None; 256];
+    let enc = file.lookup(dict, "Encoding");
+    let (base, differences) = match &*enc {
+        Object::Name(n) => (Some(n.clone()), None),
+        Object::Dict(d) => (
+            d.get("BaseEncoding")
+                .and_then(Object::as_name)
+                .map(str::to_string),
+            d.get("Differences").map(|o| file.resolve(o)),
+        ),
+        _ => (None, None),
+    };
+    let table: Option<fn(u8) -> Option<&'static str>> = match base.as_deref() {
+        Some("WinAnsiEncoding") | Some("MacRomanEncoding") => Some(encoding::win_ansi),
+        Some("StandardEncoding") => Some(encoding::standard),
+        // A non-symbolic TrueType font without an encoding is read as Standard.
+        None if !symbolic && matches!(program, Program::TrueType(_)) => Some(encoding::standard),
+        _ => None,
+    };
+    if let Some(table) = table {
+        for code in 0..=255u8 {
+            names[code as usize] = table(code).map(str::to_string);
+        }
+    }
+    if let Some(diffs) = differences.as_deref().and_then(Object::as_array) {
+        let mut code = 0usize;
+        for item in diffs {
+            match item {
+                Object::Int(n) => code = (*n).max(0) as usize,
+                Object::Name(n) => {
+                    if let Some(slot) = names.get_mut(code) {
+                        *slot = Some(n.clone());
+                    }
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    names
+}
+
+fn simple_truetype_gid(
+    tt: &TrueType,
+    code: u32,
+    names: &[Option<String>],
+    symbolic: bool,
+) -> Option<u16> {
+    if tt.has_cmap(3, 0) && (symbolic || !tt.has_cmap(3, 1)) {
+        for base in [0, 0xf000, 0xf100, 0xf200] {
+            if let Some(g) = tt.lookup(3, 0, base + code) {
+                return Some(g);
+            }
+        }
+    }
+    if tt.has_cmap(3, 1) {
+        let unicode = names
+            .get(code as usize)
+            .and_then(|n| n.as_deref())
+            .and_then(encoding::glyph_name_to_unicode)
+            .and_then(|s| s.chars().next())
+            .map(|c| c as u32)
+            .unwrap_or(code);
+        if let Some(g) = tt.lookup(3, 1, unicode) {
+            return Some(g);
+        }
+    }
+    if let Some(g) = tt.lookup(1, 0, code) {
+        return Some(g);
+    }
+    // No usable cmap: codes are glyph ids, as in many subsetted fonts.
+    (code < tt.glyph_count() as u32).then_some(code as u16)
+}
@@ -0,0 +1,340 @@
+//! TrueType outlines (`FontFile2`): `glyf`/`loca`, composite glyphs and the
+//! `cmap` subtables simple TrueType fonts are looked up through.
+
+use crate::pdf::raster::{Matrix, Path};
+
+/// Composite glyphs nested deeper than this are cut off.
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]))
+}
+
+fn i16_at(data: &[u8], pos: usize) -> Option<i16> {
+    u16_at(data, pos).map(|v| v as i16)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// The table `tag` of an sfnt (TrueType or OpenType) font.
+pub fn table<'a>(data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = u16_at(data, 4)? as usize;
+    (0..count).find_map(|i| {
+        let rec = 12 + i * 16;
+        if data.get(rec..rec + 4)? != tag {
+            return None;
+        }
+        let offset = u32_at(data, rec + 8)? as usize;
+        let len = u32_at(data, rec + 12)? as usize;
+        data.get(offset..offset.checked_add(len)?)
+            .or_else(|| data.get(offset..))
+    })
+}
+
+pub struct TrueType {
+    data: Vec<u8>,
+    glyf: std::ops::Range<usize>,
+    loca: Vec<u32>,
+    pub units_per_em: f64,
+    /// `(platform, encoding, offset of the subtable)`.
+    cmaps: Vec<(u16, u16, usize)>,
+}
+
+impl TrueType {
+    pub fn parse(data: Vec<u8>) -> Option<TrueType> {
+        let range_of = |tag: &[u8; 4]| -> Option<std::ops::Range<usize>> {
+            let t = table(&data, tag)?;
+            let start = t.as_ptr() as usize - data.as_ptr() as usize;
+            Some(start..start + t.len())
+        };
+        let head = table(&data, b"head")?;
+        let units_per_em = u16_at(head, 18).filter(|&u| u > 0).unwrap_or(1000) as f64;
+        let long_loca = i16_at(head, 50)? != 0;
+        let glyphs = table(&data, b"maxp")
+            .and_then(|m| u16_at(m, 4))
+            .unwrap_or(0) as usize;
+        let loca_table = table(&data, b"loca")?;
+        let entries = if glyphs > 0 {
+            glyphs + 1
+        } else if long_loca {
+            loca_table.len() / 4
+        } else {
+            loca_table.len() / 2
+        };
+        let loca: Vec<u32> = (0..entries)
+            .map_while(|i| {
+                if long_loca {
+                    u32_at(loca_table, i * 4)
+                } else {
+                    u16_at(loca_table, i * 2).map(|v| v as u32 * 2)
+                }
+            })
+            .collect();
+        let glyf = range_of(b"glyf")?;
+        let mut cmaps = Vec::new();
+        if let Some(cmap) = range_of(b"cmap") {
+            let c = &data[cmap.clone()];
+            let n = u16_at(c, 2).unwrap_or(0) as usize;
+            for i in 0..n {
+                let rec = 4 + i * 8;
+                if let (Some(p), Some(e), Some(off)) =
+                    (u16_at(c, rec), u16_at(c, rec + 2), u32_at(c, rec + 4))
+                {
+                    cmaps.push((p, e, cmap.start + off as usize));
+                }
+            }
+        }
+        Some(TrueType {
+            data,
+            glyf,
+            loca,
+            units_per_em,
+            cmaps,
+        })
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.loca.len().saturating_sub(1)
+    }
+
+    pub fn has_cmap(&self, platform: u16, encoding: u16) -> bool {
+        self.cmaps
+            .iter()
+            .any(|&(p, e, _)| p == platform && e == encoding)
+    }
+
+    /// Glyph for `code` in the `(platform, encoding)` subtable.
+    pub fn lookup(&self, platform: u16, encoding: u16, code: u32) -> Option<u16> {
+        let &(_, _, at) = self
+            .cmaps
+            .iter()
+            .find(|&&(p, e, _)| p == platform && e == encoding)?;
+        let d = &self.data;
+        let gid = match u16_at(d, at)? {
+            0 => *d.get(at + 6 + code as usize)? as u16,
+            4 => {
+                let segs = u16_at(d, at + 6)? as usize / 2;
+                let ends = at + 14;
+                let starts = ends + segs * 2 + 2;
+                let deltas = starts + segs * 2;
+                let range_offsets = deltas + segs * 2;
+                let seg = (0..segs)
+                    .find(|&s| u16_at(d, ends + s * 2).is_some_and(|e| e as u32 >= code))?;
+                let start = u16_at(d, starts + seg * 2)? as u32;
+                if code < start {
+                    return None;
+                }
+                let delta = u16_at(d, deltas + seg * 2)?;
+                let ro = u16_at(d, range_offsets + seg * 2)? as usize;
+                if ro == 0 {
+                    (code as u16).wrapping_add(delta)
+                } else {
+                    let at = range_offsets + seg * 2 + ro + (code - start) as usize * 2;
+                    match u16_at(d, at)? {
+                        0 => 0,
+                        g => g.wrapping_add(delta),
+                    }
+                }
+            }
+            6 => {
+                let first = u16_at(d, at + 6)? as u32;
+                let count = u16_at(d, at + 8)? as u32;
+                if code < first || code >= first + count {
+                    return None;
+                }
+                u16_at(d, at + 10 + (code - first) as usize * 2)?
+            }
+            12 => {
+                let groups = u32_at(d, at + 12)? as usize;
+                (0..groups).find_map(|g| {
+                    let rec = at + 16 + g * 12;
+                    let (start, end, first) =
+                        (u32_at(d, rec)?, u32_at(d, rec + 4)?, u32_at(d, rec + 8)?);
+                    (start..=end)
+                        .contains(&code)
+                        .then(|| (first + code - start) as u16)
+                })?
+            }
+            _ => return None,
+        };
+        Some(gid).filter(|&g| g != 0)
+    }
+
+    /// Outline of `gid` in font units (divide by [`TrueType::units_per_em`]).
+    pub fn outline(&self, gid: u16) -> Option<Path> {
+        let mut path = Path::default();
+        self.append_glyph(gid, &Matrix::IDENTITY, &mut path, 0)?;
+        Some(path)
+    }
+
+    fn glyph_data(&self, gid: u16) -> Option<&[u8]> {
+        let start = *self.loca.get(gid as usize)? as usize;
+        let end = *self.loca.get(gid as usize + 1)? as usize;
+        if end <= start {
+            return Some(&[]);
+        }
+        self.data
+            .get(self.glyf.start + start..self.glyf.start + end.min(self.glyf.len()))
+    }
+
+    fn append_glyph(&self, gid: u16, m: &Matrix, path: &mut Path, depth: usize) -> Option<()> {
+        let g = self.glyph_data(gid)?;
+        if g.is_empty() {
+            return Some(());
+        }
+        let contours = i16_at(g, 0)?;
+        if contours >= 0 {
+            self.append_simple(g, contours as usize, m, path)
+        } else if depth < MAX_COMPONENT_DEPTH {
+            self.append_composite(g, m, path, depth)
+        } else {
+            None
+        }
+    }
+
+    fn append_simple(&self, g: &[u8], contours: usize, m: &Matrix, path: &mut Path) -> Option<()> {
+        let ends: Vec<usize> = (0..contours)
+            .map(|i| u16_at(g, 10 + i * 2).map(|v| v as usize))
+            .collect::<Option<_>>()?;
+        let points = ends.last().map_or(0, |&e| e + 1);
+        let instructions = u16_at(g, 10 + contours * 2)? as usize;
+        let mut pos = 12 + contours * 2 + instructions;
+        let mut flags = Vec::with_capacity(points);
+        while flags.len() < points {
+            let f = *g.get(pos)?;
+            pos += 1;
+            flags.push(f);
+            if f & 8 != 0 {
+                let repeat = *g.get(pos)?;
+                pos += 1;
+                for _ in 0..repeat {
+                    flags.push(f);
+                }
+            }
+        }
+        flags.truncate(points);
+        let mut read_coords = |short: u8, same: u8| -> Option<Vec<f64>> {
+            let mut v = 0i32;
+            let mut out = Vec::with_capacity(points);
+            for &f in &flags {
+                if f & short != 0 {
+                    let d = *g.get(pos)? as i32;
+                    pos += 1;
+                    v += if f & same != 0 { d } else { -d };
+                } else if f & same == 0 {
+                    v += i16_at(g, pos)? as i32;
+                    pos += 2;
+                }
+                out.push(v as f64);
+            }
+            Some(out)
+        };
+        let xs = read_coords(2, 16)?;
+        let ys = read_coords(4, 32)?;
+        let mut start = 0;
+        for &end in &ends {
+            if end < start || end >= points {
+                break;
+            }
+            let contour: Vec<((f64, f64), bool)> = (start..=end)
+                .map(|i| (m.apply(xs[i], ys[i]), flags[i] & 1 != 0))
+                .collect();
+            append_contour(path, &contour);
+            start = end + 1;
+        }
+        Some(())
+    }
+
+    fn append_composite(&self, g: &[u8], m: &Matrix, path: &mut Path, depth: usize) -> Option<()> {
+        let mut pos = 10;
+        loop {
+            let flags = u16_at(g, pos)?;
+            let gid = u16_at(g, pos + 2)?;
+            pos += 4;
+            let (dx, dy) = if flags & 1 != 0 {
+                let v = (i16_at(g, pos)? as f64, i16_at(g, pos + 2)? as f64);
+                pos += 4;
+                v
+            } else {
+                let v = (*g.get(pos)? as i8 as f64, *g.get(pos + 1)? as i8 as f64);
+                pos += 2;
+                v
+            };
+            let f2dot14 = |p: usize| i16_at(g, p).map(|v| v as f64 / 16384.0);
+            let (a, b, c, d) = if flags & 8 != 0 {
+                let s = f2dot14(pos)?;
+                pos += 2;
+                (s, 0.0, 0.0, s)
+            } else if flags & 0x40 != 0 {
+                let v = (f2dot14(pos)?, 0.0, 0.0, f2dot14(pos + 2)?);
+                pos += 4;
+                v
+            } else if flags & 0x80 != 0 {
+                let v = (
+                    f2dot14(pos)?,
+                    f2dot14(pos + 2)?,
+                    f2dot14(pos + 4)?,
+                    f2dot14(pos + 6)?,
+                );
+                pos += 8;
+                v
+            } else {
+                (1.0, 0.0, 0.0, 1.0)
+            };
+            // Point-matching placement (ARGS_ARE_XY_VALUES unset) is rare; the
+            // component is then drawn unshifted.
+            let (dx, dy) = if flags & 2 != 0 { (dx, dy) } else { (0.0, 0.0) };
+            let component = Matrix([a, b, c, d, dx, dy]).then(m);
+            self.append_glyph(gid, &component, path, depth + 1)?;
+            if flags & 0x20 == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+/// One quadratic contour: on-curve flags per point, implied on-curve points
+/// between two off-curve ones.
+fn append_contour(path: &mut Path, points: &[((f64, f64), bool)]) {
+    let n = points.len();
+    if n == 0 {
+        return;
+    }
+    let mid = |a: (f64, f64), b: (f64, f64)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    // Start on an on-curve point, or between the last and first off-curve ones.
+    let first_on = points.iter().position(|p| p.1);
+    let (start, order): (_, Vec<usize>) = match first_on {
+        Some(i) => (points[i].0, (1..=n).map(|k| (i + k) % n).collect()),
+        None => (mid(points[n - 1].0, points[0].0), (0..n).collect()),
+    };
+    path.move_to(start.0, start.1);
+    let mut current = start;
+    let mut control: Option<(f64, f64)> = None;
+    for i in order {
+        let (p, on) = points[i];
+        match (on, control) {
+            (true, None) => {
+                path.line_to(p.0, p.1);
+                current = p;
+            }
+            (true, Some(c)) => {
+                path.quad_to(current.0, current.1, c.0, c.1, p.0, p.1);
+                current = p;
+                control = None;
+            }
+            (false, None) => control = Some(p),
+            (false, Some(c)) => {
+                let m = mid(c, p);
+                path.quad_to(current.0, current.1, c.0, c.1, m.0, m.1);
+                current = m;
+                control = Some(p);
+            }
+        }
+    }
+    if let Some(c) = control {
+        path.quad_to(current.0, current.1, c.0, c.1, start.0, start.1);
+    }
+    path.close();
+}
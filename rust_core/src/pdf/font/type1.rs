@@ -0,0 +1,487 @@
+//! Type 1 fonts (`FontFile`): eexec decryption, the built-in encoding and
+//! Type 1 charstrings, including flex and `seac` accents. Hints are ignored.
+
+use std::collections::HashMap;
+
+use super::encoding::standard;
+use crate::pdf::raster::{Matrix, Path};
+
+const EEXEC_KEY: u16 = 55665;
+const CHARSTRING_KEY: u16 = 4330;
+const MAX_CALL_DEPTH: usize = 10;
+
+pub struct Type1 {
+    charstrings: HashMap<String, Vec<u8>>,
+    subrs: Vec<Vec<u8>>,
+    /// Built-in encoding: glyph name for each code.
+    encoding: HashMap<u8, String>,
+    pub font_matrix: Matrix,
+}
+
+impl Type1 {
+    pub fn parse(data: &[u8]) -> Option<Type1> {
+        let eexec = find(data, b"eexec")?;
+        let clear = &data[..eexec];
+        let mut rest = &data[eexec + 5..];
+        while let Some((&b, tail)) = rest.split_first() {
+            if !b.is_ascii_whitespace() {
+                break;
+            }
+            rest = tail;
+        }
+        let is_hex = rest.len() >= 4 && rest[..4].iter().all(u8::is_ascii_hexdigit);
+        let binary: Vec<u8> = if is_hex {
+            let digits: Vec<u8> = rest
+                .iter()
+                .filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8))
+                .collect();
+            digits.chunks_exact(2).map(|p| p[0] << 4 | p[1]).collect()
+        } else {
+            rest.to_vec()
+        };
+        let private = decrypt(&binary, EEXEC_KEY, 4);
+
+        let len_iv = find(&private, b"/lenIV")
+            .and_then(|at| read_int(&private, &mut (at + 6)))
+            .unwrap_or(4);
+        let subrs = read_subrs(&private, len_iv);
+        let charstrings = read_charstrings(&private, len_iv);
+        let encoding = read_encoding(clear);
+        let font_matrix = find(clear, b"/FontMatrix")
+            .and_then(|at| {
+                let open = at + find(&clear[at..], b"[")? + 1;
+                let close = open + find(&clear[open..], b"]")?;
+                let values: Vec<f64> = std::str::from_utf8(&clear[open..close])
+                    .ok()?
+                    .split_whitespace()
+                    .filter_map(|v| v.parse().ok())
+                    .collect();
+                match values[..] {
+                    [a, b, c, d, e, f] => Some(Matrix([a, b, c, d, e, f])),
+                    _ => None,
+                }
+            })
+            .unwrap_or(Matrix([0.001, 0.0, 0.0, 0.001, 0.0, 0.0]));
+        Some(Type1 {
+            charstrings,
+            subrs,
+            encoding,
+            font_matrix,
+        })
+    }
+
+    pub fn has_glyph(&self, name: &str) -> bool {
+        self.charstrings.contains_key(name)
+    }
+
+    /// Glyph name for `code` in the font's built-in encoding.
+    pub fn name_for_code(&self, code: u8) -> Option<&str> {
+        self.encoding.get(&code).map(String::as_str)
+    }
+
+    /// Outline of the glyph `name` in glyph units (apply [`Type1::font_matrix`]).
+    pub fn outline(&self, name: &str) -> Option<Path> {
+        let code = self.charstrings.get(name)?;
+        let mut cs = Charstring::new(self);
+        cs.run(code, 0)?;
+        cs.finish();
+        Some(cs.path)
+    }
+}
+
+fn decrypt(data: &[u8], key: u16, skip: usize) -> Vec<u8> {
+    let mut r = key;
+    let out: Vec<u8> = data
+        .iter()
+        .map(|&c| {
+            let p = c ^ (r >> 8) as u8;
+            r = (c as u16)
+                .wrapping_add(r)
+                .wrapping_mul(52845)
+                .wrapping_add(22719);
+            p
+        })
+        .collect();
+    out.get(skip..).unwrap_or(&[]).to_vec()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn skip_space(data: &[u8], pos: &mut usize) {
+    while data.get(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+}
+
+fn read_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    skip_space(data, pos);
+    let start = *pos;
+    while data
+        .get(*pos)
+        .is_some_and(|b| !b.is_ascii_whitespace() && (*pos == start || !b"/[]{}".contains(b)))
+    {
+        *pos += 1;
+    }
+    (*pos > start).then(|| &data[start..*pos])
+}
+
+fn read_int(data: &[u8], pos: &mut usize) -> Option<i64> {
+    std::str::from_utf8(read_token(data, pos)?)
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// `n RD <n bytes>` (the `RD` token may be spelled `-|`): the decrypted bytes.
+fn read_binary(data: &[u8], pos: &mut usize, len_iv: i64) -> Option<Vec<u8>> {
+    let len = read_int(data, pos)?.max(0) as usize;
+    read_token(data, pos)?;
+    *pos += 1;
+    let bytes = data.get(*pos..*pos + len)?;
+    *pos += len;
+    Some(if len_iv < 0 {
+        bytes.to_vec()
+    } else {
+        decrypt(bytes, CHARSTRING_KEY, len_iv as usize)
+    })
+}
+
+fn read_subrs(private: &[u8], len_iv: i64) -> Vec<Vec<u8>> {
+    let Some(at) = find(private, b"/Subrs") else {
+        return Vec::new();
+    };
+    let mut pos = at + 6;
+    let count = read_int(private, &mut pos).unwrap_or(0).clamp(0, 65536) as usize;
+    let mut subrs = vec![Vec::new(); count];
+    for _ in 0..count {
+        let Some(dup) = find(&private[pos..], b"dup") else {
+            break;
+        };
+        pos += dup + 3;
+        let Some(index) = read_int(private, &mut pos) else {
+            break;
+        };
+        let Some(bytes) = read_binary(private, &mut pos, len_iv) else {
+            break;
+        };
+        if let Some(slot) = subrs.get_mut(index as usize) {
+            *slot = bytes;
+        }
+    }
+    subrs
+}
+
+fn read_charstrings(private: &[u8], len_iv: i64) -> HashMap<String, Vec<u8>> {
+    let mut out = HashMap::new();
+    let Some(at) = find(private, b"/CharStrings") else {
+        return out;
+    };
+    let mut pos = at + 12;
+    // Skip `count dict dup begin`.
+    while let Some(slash) = private[pos..].iter().position(|&b| b == b'/') {
+        let before = &private[pos..pos + slash];
+        if find(before, b"end").is_some() && !out.is_empty() {
+            break;
+        }
+        pos += slash + 1;
+        let Some(name) = read_token(private, &mut pos) else {
+            break;
+        };
+        let name = String::from_utf8_lossy(name).into_owned();
+        match read_binary(private, &mut pos, len_iv) {
+            Some(bytes) => {
+                out.insert(name, bytes);
+            }
+            None => break,
+        }
+    }
+    out
+}
+
+fn read_encoding(clear: &[u8]) -> HashMap<u8, String> {
+    let mut out = HashMap::new();
+    let Some(at) = find(clear, b"/Encoding") else {
+        return out;
+    };
+    let rest = &clear[at + 9..];
+    let mut pos = 0;
+    skip_space(rest, &mut pos);
+    if rest[pos..].starts_with(b"StandardEncoding") {
+        for code in 0..=255u8 {
+            if let Some(name) = standard(code) {
+                out.insert(code, name.to_string());
+            }
+        }
+        return out;
+    }
+    let end = find(rest, b"readonly def")
+        .or_else(|| find(rest, b" def"))
+        .unwrap_or(rest.len());
+    let body = &rest[..end];
+    let mut pos = 0;
+    while let Some(dup) = find(&body[pos..], b"dup ") {
+        pos += dup + 4;
+        let Some(code) = read_int(body, &mut pos) else {
+            continue;
+        };
+        if let Some(name) = read_token(body, &mut pos).and_then(|t| t.strip_prefix(b"/")) {
+            if let Ok(code) = u8::try_from(code) {
+                out.insert(code, String::from_utf8_lossy(name).into_owned());
+            }
+        }
+    }
+    out
+}
+
+struct Charstring<'a> {
+    font: &'a Type1,
+    path: Path,
+    x: f64,
+    y: f64,
+    stack: Vec<f64>,
+    /// Results of `callothersubr` waiting for `pop`.
+    ps_stack: Vec<f64>,
+    flex: Option<Vec<(f64, f64)>>,
+    open: bool,
+    in_seac: bool,
+}
+
+impl<'a> Charstring<'a> {
+    fn new(font: &'a Type1) -> Self {
+        Charstring {
+            font,
+            path: Path::default(),
+            x: 0.0,
+            y: 0.0,
+            stack: Vec::new(),
+            ps_stack: Vec::new(),
+            flex: None,
+            open: false,
+            in_seac: false,
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.open {
+            self.path.close();
+            self.open = false;
+        }
+    }
+
+    fn move_to(&mut self, dx: f64, dy: f64) {
+        self.x += dx;
+        self.y += dy;
+        if let Some(points) = &mut self.flex {
+            points.push((self.x, self.y));
+            return;
+        }
+        self.finish();
+        self.path.move_to(self.x, self.y);
+        self.open = true;
+    }
+
+    fn line_to(&mut self, dx: f64, dy: f64) {
+        self.x += dx;
+        self.y += dy;
+        self.path.line_to(self.x, self.y);
+    }
+
+    fn curve_to(&mut self, d: [f64; 6]) {
+        let (x1, y1) = (self.x + d[0], self.y + d[1]);
+        let (x2, y2) = (x1 + d[2], y1 + d[3]);
+        let (x3, y3) = (x2 + d[4], y2 + d[5]);
+        self.path.curve_to(x1, y1, x2, y2, x3, y3);
+        self.x = x3;
+        self.y = y3;
+    }
+
+    fn run(&mut self, code: &[u8], depth: usize) -> Option<bool> {
+        if depth > MAX_CALL_DEPTH {
+            return None;
+        }
+        let mut i = 0;
+        while i < code.len() {
+            let v = code[i] as i32;
+            i += 1;
+            if v >= 32 {
+                let at = |k: usize| *code.get(k).unwrap_or(&0) as i32;
+                let n = match v {
+                    32..=246 => v - 139,
+                    247..=250 => {
+                        i += 1;
+                        (v - 247) * 256 + at(i - 1) + 108
+                    }
+                    251..=254 => {
+                        i += 1;
+                        -(v - 251) * 256 - at(i - 1) - 108
+                    }
+                    _ => {
+                        i += 4;
+                        at(i - 4) << 24 | at(i - 3) << 16 | at(i - 2) << 8 | at(i - 1)
+                    }
+                };
+                self.stack.push(n as f64);
+                continue;
+            }
+            let s = std::mem::take(&mut self.stack);
+            match v {
+                13 => {
+                    // hsbw: side bearing, width.
+                    if let [sbx, _] = s[..] {
+                        self.x = sbx;
+                        self.y = 0.0;
+                    }
+                }
+                9 if self.open => {
+                    self.path.close();
+                    self.open = false;
+                }
+                21 => {
+                    if let [dx, dy] = s[..] {
+                        self.move_to(dx, dy);
+                    }
+                }
+                22 => self.move_to(s.first().copied().unwrap_or(0.0), 0.0),
+                4 => self.move_to(0.0, s.first().copied().unwrap_or(0.0)),
+                5 => {
+                    if let [dx, dy] = s[..] {
+                        self.line_to(dx, dy);
+                    }
+                }
+                6 => self.line_to(s.first().copied().unwrap_or(0.0), 0.0),
+                7 => self.line_to(0.0, s.first().copied().unwrap_or(0.0)),
+                8 => {
+                    if let [a, b, c, d, e, f] = s[..] {
+                        self.curve_to([a, b, c, d, e, f]);
+                    }
+                }
+                30 => {
+                    if let [dy1, dx2, dy2, dx3] = s[..] {
+                        self.curve_to([0.0, dy1, dx2, dy2, dx3, 0.0]);
+                    }
+                }
+                31 => {
+                    if let [dx1, dx2, dy2, dy3] = s[..] {
+                        self.curve_to([dx1, 0.0, dx2, dy2, 0.0, dy3]);
+                    }
+                }
+                10 => {
+                    let mut s = s;
+                    let index = s.pop()? as usize;
+                    self.stack = s;
+                    let subr = self.font.subrs.get(index)?;
+                    if self.run(subr, depth + 1)? {
+                        return Some(true);
+                    }
+                }
+                11 => {
+                    self.stack = s;
+                    return Some(false);
+                }
+                14 => {
+                    self.finish();
+                    return Some(true);
+                }
+                12 => {
+                    let op = *code.get(i)?;
+                    i += 1;
+                    self.escape(op, s)?;
+                }
+                // hstem, vstem: hints.
+                _ => {}
+            }
+        }
+        Some(false)
+    }
+
+    fn escape(&mut self, op: u8, mut s: Vec<f64>) -> Option<()> {
+        match op {
+            6 => {
+                if let [asb, adx, ady, bchar, achar] = s[..] {
+                    self.seac(asb, adx, ady, bchar as u8, achar as u8);
+                }
+            }
+            7 => {
+                // sbw
+                if let [sbx, sby, _, _] = s[..] {
+                    self.x = sbx;
+                    self.y = sby;
+                }
+            }
+            12 => {
+                let b = s.pop()?;
+                let a = s.pop()?;
+                s.push(if b == 0.0 { 0.0 } else { a / b });
+                self.stack = s;
+            }
+            16 => {
+                // callothersubr: othersubr# and its args are on the stack.
+                let index = s.pop()? as i32;
+                let n = s.pop()? as usize;
+                let args = s.split_off(s.len().saturating_sub(n));
+                self.stack = s;
+                match index {
+                    1 => self.flex = Some(Vec::new()),
+                    0 => {
+                        let points = self.flex.take().unwrap_or_default();
+                        if points.len() >= 7 {
+                            for c in [&points[1..4], &points[4..7]] {
+                                self.path
+                                    .curve_to(c[0].0, c[0].1, c[1].0, c[1].1, c[2].0, c[2].1);
+                            }
+                            let (x, y) = points[6];
+                            self.x = x;
+                            self.y = y;
+                        }
+                        // The charstring pops the end point for setcurrentpoint.
+                        self.ps_stack = vec![self.y, self.x];
+                    }
+                    2 => {}
+                    _ => {
+                        // Hint replacement (3) and unknown othersubrs hand
+                        // their arguments back, in reverse for `pop`.
+                        self.ps_stack = args.into_iter().collect();
+                    }
+                }
+            }
+            17 => {
+                let v = self.ps_stack.pop().unwrap_or(0.0);
+                s.push(v);
+                self.stack = s;
+            }
+            33 => {
+                if let [x, y] = s[..] {
+                    self.x = x;
+                    self.y = y;
+                }
+            }
+            // dotsection, vstem3, hstem3.
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn seac(&mut self, asb: f64, adx: f64, ady: f64, bchar: u8, achar: u8) {
+        if self.in_seac {
+            return;
+        }
+        let (Some(base), Some(accent)) = (standard(bchar), standard(achar)) else {
+            return;
+        };
+        // The accent's own `hsbw` already offsets it by `asb`.
+        for (name, dx, dy) in [(base, 0.0, 0.0), (accent, adx - asb, ady)] {
+            let Some(code) = self.font.charstrings.get(name) else {
+                continue;
+            };
+            let mut sub = Charstring::new(self.font);
+            sub.in_seac = true;
+            if sub.run(code, 0).is_some() {
+                sub.finish();
+                self.path
+                    .extend_transformed(&sub.path, &Matrix::translate(dx, dy));
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+//! Baseline (sequential Huffman) JPEG decoding for `DCTDecode` images.
+//! Progressive and arithmetic-coded files return `None` and are drawn as
+//! placeholders.
+
+/// Decoded samples, `components` bytes per pixel: gray, RGB or CMYK.
+pub struct Jpeg {
+    pub width: usize,
+    pub height: usize,
+    pub components: usize,
+    pub pixels: Vec<u8>,
+    /// Adobe CMYK files store inverted ink values.
+    pub adobe_inverted: bool,
+}
+
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// Frames larger than this are refused rather than allocated.
+const MAX_PIXELS: usize = 64 << 20;
+
+#[derive(Clone, Default)]
+struct Huffman {
+    /// Largest code of each length, or -1.
+    max_code: [i32; 17],
+    /// Index into `values` of the first code of each length, minus that code.
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: Vec<u8>) -> Huffman {
+        let mut h = Huffman {
+            max_code: [-1; 17],
+            offset: [0; 17],
+            values,
+        };
+        let (mut code, mut k) = (0i32, 0i32);
+        for len in 1..=16 {
+            let n = counts[len - 1] as i32;
+            if n > 0 {
+                h.offset[len] = k - code;
+                code += n;
+                k += n;
+                h.max_code[len] = code - 1;
+            }
+            code <<= 1;
+        }
+        h
+    }
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    left: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.left == 0 {
+            let mut b = self.data.get(self.pos).copied().unwrap_or(0);
+            if b == 0xFF {
+                match self.data.get(self.pos + 1) {
+                    Some(0) => self.pos += 2,
+                    // A marker ends the entropy-coded data: feed zeros.
+                    _ => b = 0,
+                }
+            } else if self.pos < self.data.len() {
+                self.pos += 1;
+            }
+            self.acc = b as u32;
+            self.left = 8;
+        }
+        self.left -= 1;
+        (self.acc >> self.left) & 1
+    }
+
+    fn receive(&mut self, n: u8) -> i32 {
+        let mut v = 0i32;
+        for _ in 0..n {
+            v = v << 1 | self.bit() as i32;
+        }
+        v
+    }
+
+    fn decode(&mut self, h: &Huffman) -> Option<u8> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = code << 1 | self.bit() as i32;
+            if code <= h.max_code[len] {
+                return h.values.get((h.offset[len] + code) as usize).copied();
+            }
+        }
+        None
+    }
+
+    /// Byte-aligns and skips an `RSTn` marker.
+    fn restart(&mut self) {
+        self.left = 0;
+        if self.data.get(self.pos) == Some(&0xFF)
+            && self
+                .data
+                .get(self.pos + 1)
+                .is_some_and(|m| (0xD0..=0xD7).contains(m))
+        {
+            self.pos += 2;
+        }
+    }
+}
+
+fn extend(v: i32, n: u8) -> i32 {
+    if n == 0 {
+        0
+    } else if v < 1 << (n - 1) {
+        v - (1 << n) + 1
+    } else {
+        v
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    dc: usize,
+    ac: usize,
+    pred: i32,
+    /// Samples for whole blocks, `stride` wide.
+    plane: Vec<u8>,
+    stride: usize,
+}
+
+struct Frame {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    h_max: usize,
+    v_max: usize,
+    mcus_x: usize,
+    mcus_y: usize,
+}
+
+pub fn decode(data: &[u8]) -> Option<Jpeg> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut quant = [[0u16; 64]; 4];
+    let mut dc_tables: [Huffman; 4] = Default::default();
+    let mut ac_tables: [Huffman; 4] = Default::default();
+    let mut frame: Option<Frame> = None;
+    let mut restart_interval = 0usize;
+    let mut adobe: Option<u8> = None;
+    let mut pos = 2;
+    let idct = IdctTable::new();
+    loop {
+        while data.get(pos).is_some_and(|&b| b != 0xFF) {
+            pos += 1;
+        }
+        while data.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        // A file cut short keeps what was decoded.
+        if pos >= data.len() {
+            break;
+        }
+        let marker = data[pos];
+        pos += 1;
+        if matches!(marker, 0x00 | 0x01 | 0xD0..=0xD8) {
+            continue;
+        }
+        if marker == 0xD9 {
+            break;
+        }
+        let len = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        let segment = data.get(pos + 2..pos + len.max(2))?;
+        pos += len;
+        match marker {
+            0xC0 | 0xC1 => {
+                let height = u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]) as usize;
+                let width = u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]) as usize;
+                let count = *segment.get(5)? as usize;
+                if width == 0 || height == 0 || width * height > MAX_PIXELS || segment[0] != 8 {
+                    return None;
+                }
+                let mut components = Vec::with_capacity(count);
+                for i in 0..count {
+                    let c = segment.get(6 + i * 3..9 + i * 3)?;
+                    components.push(Component {
+                        id: c[0],
+                        h: ((c[1] >> 4) as usize).clamp(1, 4),
+                        v: ((c[1] & 15) as usize).clamp(1, 4),
+                        quant: (c[2] & 3) as usize,
+                        dc: 0,
+                        ac: 0,
+                        pred: 0,
+                        plane: Vec::new(),
+                        stride: 0,
+                    });
+                }
+                let h_max = components.iter().map(|c| c.h).max()?;
+                let v_max = components.iter().map(|c| c.v).max()?;
+                let mcus_x = width.div_ceil(8 * h_max);
+                let mcus_y = height.div_ceil(8 * v_max);
+                for c in &mut components {
+                    c.stride = mcus_x * c.h * 8;
+                    c.plane = vec![0; c.stride * mcus_y * c.v * 8];
+                }
+                frame = Some(Frame {
+                    width,
+                    height,
+                    components,
+                    h_max,
+                    v_max,
+                    mcus_x,
+                    mcus_y,
+                });
+            }
+            // Progressive, lossless, hierarchical and arithmetic-coded frames.
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return None,
+            0xC4 => {
+                let mut p = 0;
+                while p < segment.len() {
+                    let class = segment[p] >> 4;
+                    let index = (segment[p] & 3) as usize;
+                    let counts = segment.get(p + 1..p + 17)?;
+                    let total: usize = counts.iter().map(|&c| c as usize).sum();
+                    let values = segment.get(p + 17..p + 17 + total)?.to_vec();
+                    let table = Huffman::new(counts, values);
+                    if class == 0 {
+                        dc_tables[index] = table;
+                    } else {
+                        ac_tables[index] = table;
+                    }
+                    p += 17 + total;
+                }
+            }
+            0xDB => {
+                let mut p = 0;
+                while p < segment.len() {
+                    let wide = segment[p] >> 4 != 0;
+                    let index = (segment[p] & 3) as usize;
+                    p += 1;
+                    for q in quant[index].iter_mut() {
+                        *q = if wide {
+                            let v = u16::from_be_bytes([*segment.get(p)?, *segment.get(p + 1)?]);
+                            p += 2;
+                            v
+                        } else {
+                            p += 1;
+                            *segment.get(p - 1)? as u16
+                        };
+                    }
+                }
+            }
+            0xDD => {
+                restart_interval =
+                    u16::from_be_bytes([*segment.first()?, *segment.get(1)?]) as usize;
+            }
+            0xEE if segment.starts_with(b"Adobe") => adobe = segment.get(11).copied(),
+            0xDA => {
+                let frame = frame.as_mut()?;
+                let count = *segment.first()? as usize;
+                let mut scan = Vec::with_capacity(count);
+                for i in 0..count {
+                    let id = *segment.get(1 + i * 2)?;
+                    let tables = *segment.get(2 + i * 2)?;
+                    let index = frame.components.iter().position(|c| c.id == id)?;
+                    let c = &mut frame.components[index];
+                    c.dc = (tables >> 4 & 3) as usize;
+                    c.ac = (tables & 3) as usize;
+                    scan.push(index);
+                }
+                let mut bits = Bits {
+                    data,
+                    pos,
+                    acc: 0,
+                    left: 0,
+                };
+                let tables = Tables {
+                    quant: &quant,
+                    dc: &dc_tables,
+                    ac: &ac_tables,
+                    idct: &idct,
+                };
+                decode_scan(frame, &scan, &mut bits, &tables, restart_interval);
+                pos = bits.pos;
+            }
+            _ => {}
+        }
+    }
+    let frame = frame?;
+    Some(finish(frame, adobe))
+}
+
+struct Tables<'a> {
+    quant: &'a [[u16; 64]; 4],
+    dc: &'a [Huffman; 4],
+    ac: &'a [Huffman; 4],
+    idct: &'a IdctTable,
+}
+
+fn decode_scan(frame: &mut Frame, scan: &[usize], bits: &mut Bits, t: &Tables, restart: usize) {
+    for &i in scan {
+        frame.components[i].pred = 0;
+    }
+    // One component alone is coded block by block over its own size.
+    let units: Vec<(usize, usize)> = if let [only] = scan {
+        let c = &frame.components[*only];
+        let w = (frame.width * c.h).div_ceil(frame.h_max).div_ceil(8);
+        let h = (frame.height * c.v).div_ceil(frame.v_max).div_ceil(8);
+        (0..h).flat_map(|y| (0..w).map(move |x| (x, y))).collect()
+    } else {
+        (0..frame.mcus_y)
+            .flat_map(|y| (0..frame.mcus_x).map(move |x| (x, y)))
+            .collect()
+    };
+    for (n, (ux, uy)) in units.into_iter().enumerate() {
+        if restart > 0 && n > 0 && n % restart == 0 {
+            bits.restart();
+            for &i in scan {
+                frame.components[i].pred = 0;
+            }
+        }
+        for &i in scan {
+            let c = &mut frame.components[i];
+            let (bw, bh) = if scan.len() == 1 { (1, 1) } else { (c.h, c.v) };
+            for by in 0..bh {
+                for bx in 0..bw {
+                    let coefficients = match decode_block(c, bits, t) {
+                        Some(b) => b,
+                        None => return,
+                    };
+                    let x0 = (ux * bw + bx) * 8;
+                    let y0 = (uy * bh + by) * 8;
+                    t.idct.apply(&coefficients, &mut c.plane, c.stride, x0, y0);
+                }
+            }
+        }
+    }
+}
+
+fn decode_block(c: &mut Component, bits: &mut Bits, t: &Tables) -> Option<[f32; 64]> {
+    let q = &t.quant[c.quant];
+    let mut out = [0f32; 64];
+    let s = bits.decode(&t.dc[c.dc])?;
+    c.pred += extend(bits.receive(s), s);
+    out[0] = c.pred as f32 * q[0] as f32;
+    let mut k = 1;
+    while k < 64 {
+        let rs = bits.decode(&t.ac[c.ac])?;
+        let (run, size) = ((rs >> 4) as usize, rs & 15);
+        if size == 0 {
+            if run == 15 {
+                k += 16;
+                continue;
+            }
+            break;
+        }
+        k += run;
+        if k > 63 {
+            break;
+        }
+        out[ZIGZAG[k]] = extend(bits.receive(size), size) as f32 * q[k] as f32;
+        k += 1;
+    }
+    Some(out)
+}
+
+/// Separable float inverse DCT.
+struct IdctTable([[f32; 8]; 8]);
+
+impl IdctTable {
+    fn new() -> IdctTable {
+        let mut t = [[0f32; 8]; 8];
+        for (x, row) in t.iter_mut().enumerate() {
+            for (u, v) in row.iter_mut().enumerate() {
+                let cu = if u == 0 {
+                    std::f32::consts::FRAC_1_SQRT_2
+                } else {
+                    1.0
+                };
+                *v = cu / 2.0 * (((2 * x + 1) * u) as f32 * std::f32::consts::PI / 16.0).cos();
+            }
+        }
+        IdctTable(t)
+    }
+
+    fn apply(&self, block: &[f32; 64], plane: &mut [u8], stride: usize, x0: usize, y0: usize) {
+        let mut rows = [0f32; 64];
+        for v in 0..8 {
+            for x in 0..8 {
+                rows[v * 8 + x] = (0..8).map(|u| self.0[x][u] * block[v * 8 + u]).sum();
+            }
+        }
+        for y in 0..8 {
+            for x in 0..8 {
+                let s: f32 = (0..8).map(|v| self.0[y][v] * rows[v * 8 + x]).sum();
+                if let Some(p) = plane.get_mut((y0 + y) * stride + x0 + x) {
+                    *p = (s + 128.0).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+fn finish(frame: Frame, adobe: Option<u8>) -> Jpeg {
+    let (width, height) = (frame.width, frame.height);
+    let n = frame.components.len();
+    let mut pixels = vec![0u8; width * height * n];
+    for y in 0..height {
+        for x in 0..width {
+            let at = (y * width + x) * n;
+            for (k, c) in frame.components.iter().enumerate() {
+                let cx = x * c.h / frame.h_max;
+                let cy = y * c.v / frame.v_max;
+                pixels[at + k] = c.plane.get(cy * c.stride + cx).copied().unwrap_or(0);
+            }
+        }
+    }
+    // YCbCr unless an Adobe marker says otherwise; YCCK only when it says so.
+    let transform = match (n, adobe) {
+        (3, Some(t)) => t != 0,
+        (3, None) => !frame.components.iter().map(|c| c.id).eq(*b"RGB"),
+        (4, Some(t)) => t == 2,
+        _ => false,
+    };
+    if transform {
+        for px in pixels.chunks_exact_mut(n) {
+            let (y, cb, cr) = (px[0] as f32, px[1] as f32 - 128.0, px[2] as f32 - 128.0);
+            let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+            let (r, g, b) = (
+                clamp(y + 1.402 * cr),
+                clamp(y - 0.344_136 * cb - 0.714_136 * cr),
+                clamp(y + 1.772 * cb),
+            );
+            if n == 4 {
+                px[0] = 255 - r;
+                px[1] = 255 - g;
+                px[2] = 255 - b;
+            } else {
+                px[0] = r;
+                px[1] = g;
+                px[2] = b;
+            }
+        }
+    }
+    Jpeg {
+        width,
+        height,
+        components: n,
+        pixels,
+        adobe_inverted: n == 4 && adobe.is_some(),
+    }
+}
@@ -0,0 +1,554 @@
+//! Pure-CPU PDF rendering for the in-app viewer: page sizes, page bitmaps, SVG,
+//! the text layer and bookmarks.
+//!
+//! Bitmaps are RGBA, 4 bytes per pixel, rows top to bottom — the layout
+//! `Bitmap.copyPixelsFromBuffer` expects for `ARGB_8888`. A page is
+//! `ceil(points × dpi / 72)` pixels on each side ([`pixel_size`]), with
+//! `/Rotate` applied. Text positions are points from the page's top-left
+//! corner, like [`crate::synctex`].
+//!
+//! Aimed at what the compiler produces (Type 1/CFF/TrueType/Type 3 fonts,
+//! vector graphics, axial and radial shadings, Flate and baseline JPEG images).
+//! Fonts that are not embedded are not drawn; annotations, transparency groups
+//! and soft masks are ignored; tiling patterns are a flat tint; JPEG 2000,
+//! JBIG2, CCITT and progressive JPEG images are gray boxes.
+
+mod color;
+mod content;
+mod file;
+mod font;
+mod jpeg;
+mod object;
+mod outline;
+mod raster;
+mod svg;
+mod text;
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::{Error, Result};
+use content::{Device, Fill, Interpreter};
+use file::PdfFile;
+use object::{Dict, Object};
+use raster::{Canvas, FillRule, Image, Mask, Matrix, Paint, StrokeStyle};
+use svg::SvgWriter;
+use text::TextCollector;
+
+pub use outline::OutlineItem;
+pub use text::{PageText, SearchHit, TextBox, TextLine, TextWord};
+
+/// Largest bitmap [`Document::render_page`] allocates, in pixels.
+pub const MAX_PIXELS: u64 = 32 << 20;
+/// Page tree nesting beyond this is treated as corrupt.
+const MAX_TREE_DEPTH: usize = 64;
+
+/// Page size in PDF points, after `/Rotate`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A rendered page: `width × height` RGBA pixels.
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Pixels a length of `points` covers at `dpi`; at least 1.
+pub fn pixel_size(points: f64, dpi: f64) -> u32 {
+    ((points * dpi / 72.0).ceil() as u32).max(1)
+}
+
+struct Page {
+    /// Object number, for destinations that point here.
+    id: Option<u32>,
+    dict: Dict,
+    resources: Dict,
+    /// Crop box, `[x0 y0 x1 y1]` normalized.
+    bbox: [f64; 4],
+    /// 0, 90, 180 or 270.
+    rotate: i64,
+}
+
+impl Page {
+    fn size(&self) -> PageSize {
+        let (w, h) = (self.bbox[2] - self.bbox[0], self.bbox[3] - self.bbox[1]);
+        if self.rotate % 180 == 0 {
+            PageSize {
+                width: w,
+                height: h,
+            }
+        } else {
+            PageSize {
+                width: h,
+                height: w,
+            }
+        }
+    }
+
+    /// Default user space to device space with `scale` pixels per point.
+    fn device_matrix(&self, scale: f64) -> Matrix {
+        let (w, h) = (
+            (self.bbox[2] - self.bbox[0]) * scale,
+            (self.bbox[3] - self.bbox[1]) * scale,
+        );
+        let upright =
+            Matrix::translate(-self.bbox[0], -self.bbox[3]).then(&Matrix::scale(scale, -scale));
+        let rotation = match self.rotate {
+            90 => Matrix([0.0, 1.0, -1.0, 0.0, h, 0.0]),
+            180 => Matrix([-1.0, 0.0, 0.0, -1.0, w, h]),
+            270 => Matrix([0.0, -1.0, 1.0, 0.0, 0.0, w]),
+            _ => Matrix::IDENTITY,
+        };
+        upright.then(&rotation)
+    }
+}
+
+/// A parsed PDF and its page list.
+pub struct Document {
+    file: PdfFile,
+    pages: Vec<Page>,
+}
+
+impl Document {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::parse(std::fs::read(path)?)
+    }
+
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        let file = PdfFile::parse(data)?;
+        let root = file
+            .dict(&file.lookup(&file.trailer, "Root"))
+            .ok_or_else(|| Error::InvalidPdf("no document catalog".into()))?;
+        let mut pages = Vec::new();
+        let mut seen = HashSet::new();
+        let inherited = Inherited::default();
+        if let Some(Object::Ref(num, _)) = root.get("Pages") {
+            seen.insert(*num);
+        }
+        let tree = file.lookup(&root, "Pages");
+        collect_pages(&file, &tree, &inherited, &mut seen, &mut pages, 0);
+        if pages.is_empty() {
+            return Err(Error::InvalidPdf("no pages".into()));
+        }
+        Ok(Document { file, pages })
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.pages.len() as u32
+    }
+
+    pub fn page_sizes(&self) -> Vec<PageSize> {
+        self.pages.iter().map(Page::size).collect()
+    }
+
+    /// `page` is 1-based.
+    fn page(&self, page: u32) -> Result<&Page> {
+        (page as usize)
+            .checked_sub(1)
+            .and_then(|i| self.pages.get(i))
+            .ok_or_else(|| Error::InvalidPdf(format!("no page {page}")))
+    }
+
+    /// Rasterizes `page` (1-based) at `dpi` on a white background.
+    pub fn render_page(&self, page: u32, dpi: f64) -> Result<Bitmap> {
+        let p = self.page(page)?;
+        let size = p.size();
+        let (width, height) = (pixel_size(size.width, dpi), pixel_size(size.height, dpi));
+        if dpi.is_nan() || dpi <= 0.0 || width as u64 * height as u64 > MAX_PIXELS {
+            return Err(Error::InvalidPdf(format!(
+                "page {page} is too large to render at {dpi} dpi"
+            )));
+        }
+        let mut painter = Painter {
+            canvas: Canvas::new(width as usize, height as usize),
+            clip: None,
+            saved: Vec::new(),
+        };
+        self.run_page(p, &mut painter, dpi / 72.0, (width as f64, height as f64));
+        Ok(Bitmap {
+            width,
+            height,
+            pixels: painter.canvas.pixels,
+        })
+    }
+
+    /// Words on `page` (1-based) in the order they are drawn.
+    pub fn page_text(&self, page: u32) -> Result<Vec<TextWord>> {
+        let p = self.page(page)?;
+        let size = p.size();
+        let mut collector = TextCollector::default();
+        self.run_page(p, &mut collector, 1.0, (size.width, size.height));
+        Ok(collector.finish())
+    }
+
+    /// The text of every page, as lines.
+    pub fn text(&self) -> Vec<PageText> {
+        (1..=self.page_count())
+            .map(|page| PageText {
+                page,
+                lines: text::lines(self.page_text(page).unwrap_or_default()),
+            })
+            .collect()
+    }
+
+    /// Occurrences of `query` in the text, in page order, ignoring case; any
+    /// run of whitespace, line breaks included, matches one space.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        self.text()
+            .iter()
+            .flat_map(|page| text::search(page, query))
+            .collect()
+    }
+
+    /// `page` (1-based) as a standalone SVG document sized in points. Element
+    /// ids start with `id_prefix`, so pages stay distinct when inlined together.
+    pub fn page_svg(&self, page: u32, id_prefix: &str) -> Result<String> {
+        let p = self.page(page)?;
+        let size = p.size();
+        let mut writer = SvgWriter::new(id_prefix);
+        self.run_page(p, &mut writer, 1.0, (size.width, size.height));
+        Ok(writer.finish(size.width, size.height))
+    }
+
+    fn run_page<D: Device>(&self, page: &Page, device: &mut D, scale: f64, bounds: (f64, f64)) {
+        let ctm = page.device_matrix(scale);
+        let content = page_content(&self.file, &page.dict);
+        let mut interpreter = Interpreter::new(&self.file, device, ctm, bounds);
+        interpreter.run(&content, &page.resources);
+    }
+}
+
+#[derive(Clone, Default)]
+struct Inherited {
+    resources: Option<Dict>,
+    media_box: Option<[f64; 4]>,
+    crop_box: Option<[f64; 4]>,
+    rotate: Option<i64>,
+}
+
+fn rect(file: &PdfFile, dict: &Dict, key: &str) -> Option<[f64; 4]> {
+    let values: Vec<f64> = file
+        .lookup(dict, key)
+        .as_array()?
+        .iter()
+        .filter_map(|v| file.resolve(v).as_f64())
+        .collect();
+    match values[..] {
+        [a, b, c, d] if a != c && b != d => Some([a.min(c), b.min(d), a.max(c), b.max(d)]),
+        _ => None,
+    }
+}
+
+fn collect_pages(
+    file: &PdfFile,
+    node: &Object,
+    inherited: &Inherited,
+    seen: &mut HashSet<u32>,
+    pages: &mut Vec<Page>,
+    depth: usize,
+) {
+    let Some(dict) = file.dict(node) else {
+        return;
+    };
+    let mut here = inherited.clone();
+    if let Some(res) = file.dict(&file.lookup(&dict, "Resources")) {
+        here.resources = Some(res);
+    }
+    if let Some(r) = rect(file, &dict, "MediaBox") {
+        here.media_box = Some(r);
+    }
+    if let Some(r) = rect(file, &dict, "CropBox") {
+        here.crop_box = Some(r);
+    }
+    if let Some(r) = file.lookup(&dict, "Rotate").as_i64() {
+        here.rotate = Some(r);
+    }
+    let kids = file.lookup(&dict, "Kids");
+    match kids.as_array() {
+        Some(kids) if depth < MAX_TREE_DEPTH => {
+            for kid in kids {
+                if let Object::Ref(num, _) = kid {
+                    if !seen.insert(*num) {
+                        continue;
+                    }
+                }
+                collect_pages(file, kid, &here, seen, pages, depth + 1);
+            }
+        }
+        Some(_) => {}
+        None => {
+            let media = here.media_box.unwrap_or([0.0, 0.0, 612.0, 792.0]);
+            // The crop box never extends past the media box.
+            let bbox = match here.crop_box {
+                Some(c) => [
+                    c[0].max(media[0]),
+                    c[1].max(media[1]),
+                    c[2].min(media[2]),
+                    c[3].min(media[3]),
+                ],
+                None => media,
+            };
+            let bbox = if bbox[2] > bbox[0] && bbox[3] > bbox[1] {
+                bbox
+            } else {
+                media
+            };
+            pages.push(Page {
+                id: match node {
+                    Object::Ref(num, _) => Some(*num),
+                    _ => None,
+                },
+                dict,
+                resources: here.resources.unwrap_or_default(),
+                bbox,
+                rotate: here.rotate.unwrap_or(0).rem_euclid(360) / 90 * 90,
+            });
+        }
+    }
+}
+
+/// The page's content streams, joined.
+fn page_content(file: &PdfFile, page: &Dict) -> Vec<u8> {
+    let contents = page.get("Contents").cloned().unwrap_or(Object::Null);
+    let parts = match &*file.resolve(&contents) {
+        Object::Array(items) => items.clone(),
+        _ => vec![contents],
+    };
+    let mut out = Vec::new();
+    for part in &parts {
+        if let Some(data) = file.stream_data(part) {
+            out.extend_from_slice(&data);
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+/// A [`Device`] that draws onto a [`Canvas`].
+struct Painter {
+    canvas: Canvas,
+    clip: Option<Rc<Mask>>,
+    saved: Vec<Option<Rc<Mask>>>,
+}
+
+fn with_paint(fill: &Fill, draw: impl FnOnce(&Paint)) {
+    match fill {
+        Fill::Solid(rgb) => draw(&Paint::Solid(*rgb)),
+        Fill::Shading(shading, inverse) => {
+            let shader = |x: f64, y: f64| {
+                let (u, v) = inverse.apply(x, y);
+                shading.color_at(u, v)
+            };
+            draw(&Paint::Shader(&shader));
+        }
+    }
+}
+
+impl Device for Painter {
+    fn save(&mut self) {
+        self.saved.push(self.clip.clone());
+    }
+
+    fn restore(&mut self) {
+        if let Some(clip) = self.saved.pop() {
+            self.clip = clip;
+        }
+    }
+
+    fn clip(&mut self, path: &raster::Path, rule: FillRule) {
+        self.clip = Some(self.canvas.clip(path, rule, self.clip.as_ref()));
+    }
+
+    fn fill(&mut self, path: &raster::Path, rule: FillRule, fill: &Fill, alpha: f32) {
+        let (canvas, clip) = (&mut self.canvas, self.clip.as_ref());
+        with_paint(fill, |paint| canvas.fill(path, rule, paint, alpha, clip));
+    }
+
+    fn stroke(&mut self, path: &raster::Path, style: &StrokeStyle, fill: &Fill, alpha: f32) {
+        let (canvas, clip) = (&mut self.canvas, self.clip.as_ref());
+        with_paint(fill, |paint| canvas.stroke(path, style, paint, alpha, clip));
+    }
+
+    fn image(&mut self, image: &Image, m: &Matrix, alpha: f32, smooth: bool) {
+        self.canvas
+            .draw_image(image, m, alpha, smooth, self.clip.as_ref());
+    }
+}
+
+/// Page sizes of the PDF at `path`.
+pub fn page_sizes(path: &Path) -> Result<Vec<PageSize>> {
+    Ok(Document::open(path)?.page_sizes())
+}
+
+/// Renders one page (1-based) of the PDF at `path`.
+pub fn render_page(path: &Path, page: u32, dpi: f64) -> Result<Bitmap> {
+    Document::open(path)?.render_page(page, dpi)
+}
+
+/// The text layer of one page (1-based) of the PDF at `path`.
+pub fn page_text(path: &Path, page: u32) -> Result<Vec<TextWord>> {
+    Document::open(path)?.page_text(page)
+}
+
+/// The text of every page of the PDF at `path`; see [`Document::text`].
+pub fn document_text(path: &Path) -> Result<Vec<PageText>> {
+    Ok(Document::open(path)?.text())
+}
+
+/// Occurrences of `query` in the PDF at `path`; see [`Document::search`].
+pub fn search(path: &Path, query: &str) -> Result<Vec<SearchHit>> {
+    Ok(Document::open(path)?.search(query))
+}
+
+/// One page (1-based) of the PDF at `path` as SVG; see [`Document::page_svg`].
+pub fn page_svg(path: &Path, page: u32, id_prefix: &str) -> Result<String> {
+    Document::open(path)?.page_svg(page, id_prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PDF of `objects` (numbered from 1, the first being the catalog) with
+    /// a cross-reference table.
+    fn pdf(objects: &[&str]) -> Vec<u8> {
+        let mut out = b"%PDF-1.5\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, body) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n{body}\nendobj\n", i + 1).bytes());
+        }
+        let xref = out.len();
+        out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            out.extend(format!("{offset:010} 00000 n \n").bytes());
+        }
+        out.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .bytes(),
+        );
+        out
+    }
+
+    fn stream(content: &str) -> String {
+        format!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            content.len()
+        )
+    }
+
+    /// Two pages, the second turned by `/Rotate`; a red box on the first.
+    fn two_pages() -> Vec<u8> {
+        pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /MediaBox [0 0 200 100] >>",
+            "<< /Type /Page /Parent 2 0 R /Contents 5 0 R >>",
+            "<< /Type /Page /Parent 2 0 R /Rotate 90 >>",
+            &stream("1 0 0 rg 10 10 50 30 re f"),
+        ])
+    }
+
+    fn pixel(bitmap: &Bitmap, x: u32, y: u32) -> &[u8] {
+        let at = ((y * bitmap.width + x) * 4) as usize;
+        &bitmap.pixels[at..at + 4]
+    }
+
+    #[test]
+    fn pages_inherit_their_box_and_turn_with_rotate() {
+        let doc = Document::parse(two_pages()).unwrap();
+        assert_eq!(doc.page_count(), 2);
+        assert_eq!(
+            doc.page_sizes(),
+            [
+                PageSize {
+                    width: 200.0,
+                    height: 100.0
+                },
+                PageSize {
+                    width: 100.0,
+                    height: 200.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_render_top_down_on_white() {
+        let doc = Document::parse(two_pages()).unwrap();
+        let bitmap = doc.render_page(1, 144.0).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (400, 200));
+        assert_eq!(bitmap.pixels.len(), 400 * 200 * 4);
+        // The box spans y 10..40 in PDF space, which is 120..180 from the top at 2x.
+        assert_eq!(pixel(&bitmap, 70, 150), [255, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 70, 50), [255, 255, 255, 255]);
+        assert_eq!(pixel(&bitmap, 300, 150), [255, 255, 255, 255]);
+
+        assert!(doc.render_page(3, 72.0).is_err());
+        assert!(doc.render_page(1, 0.0).is_err());
+        assert!(doc.render_page(1, 1e6).is_err());
+    }
+
+    #[test]
+    fn svg_is_sized_in_points() {
+        let doc = Document::parse(two_pages()).unwrap();
+        let svg = doc.page_svg(1, "p1-").unwrap();
+        assert!(
+            svg.contains("width=\"200pt\" height=\"100pt\" viewBox=\"0 0 200 100\""),
+            "{svg}"
+        );
+        assert!(svg.ends_with("</svg>\n"), "{svg}");
+    }
+
+    #[test]
+    fn a_file_without_a_usable_xref_is_reconstructed() {
+        let mut data = two_pages();
+        let at = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+        data.truncate(at);
+        let doc = Document::parse(data).unwrap();
+        assert_eq!(doc.page_count(), 2);
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        assert!(Document::parse(b"not a pdf".to_vec()).is_err());
+    }
+
+    #[test]
+    fn text_is_extracted_and_searched() {
+        let glyph = stream("500 0 0 0 500 700 d1 0 0 500 700 re f");
+        let data = pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 100] \
+             /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>",
+            "<< /Type /Font /Subtype /Type3 /FontBBox [0 0 500 700] \
+             /FontMatrix [0.001 0 0 0.001 0 0] /CharProcs << /a 6 0 R /b 6 0 R >> \
+             /Encoding << /Type /Encoding /Differences [97 /a /b] >> \
+             /FirstChar 97 /LastChar 98 /Widths [500 500] >>",
+            &stream("BT /F1 10 Tf 20 50 Td (abba ab) Tj ET"),
+            &glyph,
+        ]);
+        let doc = Document::parse(data).unwrap();
+        let words: Vec<String> = doc
+            .page_text(1)
+            .unwrap()
+            .into_iter()
+            .map(|w| w.text)
+            .collect();
+        assert_eq!(words, ["abba", "ab"]);
+        let hits = doc.search("AB");
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|h| h.page == 1));
+    }
+}
@@ -0,0 +1,374 @@
+//! PDF objects and the tokenizer shared by the file parser and content streams.
+
+use std::collections::HashMap;
+
+pub type Dict = HashMap<String, Object>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Real(f64),
+    /// Literal or hex string, undecoded bytes.
+    Str(Vec<u8>),
+    Name(String),
+    Array(Vec<Object>),
+    Dict(Dict),
+    Stream(Stream),
+    Ref(u32, u16),
+}
+
+/// A stream with its raw (still filtered) data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub dict: Dict,
+    pub data: Vec<u8>,
+}
+
+impl Object {
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Object::Int(n) => Some(n as f64),
+            Object::Real(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Object::Int(n) => Some(n),
+            Object::Real(r) => Some(r as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Object::Name(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Object]> {
+        match self {
+            Object::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// The dictionary of a dict or of a stream.
+    pub fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Object::Dict(d) => Some(d),
+            Object::Stream(s) => Some(&s.dict),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Object::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// One lexical token. Delimiters of compound objects are separate tokens so the
+/// same lexer serves content streams, where operators are bare keywords.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Int(i64),
+    Real(f64),
+    Str(Vec<u8>),
+    Name(String),
+    ArrayOpen,
+    ArrayClose,
+    DictOpen,
+    DictClose,
+    /// `true`, `R`, `obj`, `BT`, `Tj`, …
+    Keyword(String),
+}
+
+pub fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+pub fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+pub struct Lexer<'a> {
+    pub data: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(data: &'a [u8], pos: usize) -> Self {
+        Lexer { data, pos }
+    }
+
+    pub fn skip_whitespace(&mut self) {
+        while let Some(&b) = self.data.get(self.pos) {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(&b) = self.data.get(self.pos) {
+                    if b == b'\r' || b == b'\n' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.skip_whitespace();
+        let &b = self.data.get(self.pos)?;
+        match b {
+            b'[' => {
+                self.pos += 1;
+                Some(Token::ArrayOpen)
+            }
+            b']' => {
+                self.pos += 1;
+                Some(Token::ArrayClose)
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Some(Token::DictOpen)
+            }
+            b'>' if self.data.get(self.pos + 1) == Some(&b'>') => {
+                self.pos += 2;
+                Some(Token::DictClose)
+            }
+            b'<' => Some(Token::Str(self.hex_string())),
+            b'(' => Some(Token::Str(self.literal_string())),
+            b'/' => {
+                self.pos += 1;
+                Some(Token::Name(self.name()))
+            }
+            b'{' | b'}' | b')' | b'>' => {
+                // PostScript calculator braces, or stray delimiters.
+                self.pos += 1;
+                Some(Token::Keyword((b as char).to_string()))
+            }
+            b'+' | b'-' | b'.' | b'0'..=b'9' => Some(self.number()),
+            _ => {
+                let start = self.pos;
+                while let Some(&b) = self.data.get(self.pos) {
+                    if is_whitespace(b) || is_delimiter(b) {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                Some(Token::Keyword(
+                    String::from_utf8_lossy(&self.data[start..self.pos]).into_owned(),
+                ))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Token {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(&b) = self.data.get(self.pos) {
+            if !(b.is_ascii_digit() || b == b'.' || b == b'-') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or("0");
+        if let Ok(n) = text.parse::<i64>() {
+            return Token::Int(n);
+        }
+        // Tolerates the `--1` and `1.2.3` some producers write.
+        let cleaned: String = text
+            .trim_start_matches(['+', '-'])
+            .chars()
+            .scan(false, |dot, c| {
+                if c == '.' {
+                    if *dot {
+                        return Some(None);
+                    }
+                    *dot = true;
+                }
+                Some(Some(c))
+            })
+            .flatten()
+            .filter(|c| *c != '-')
+            .collect();
+        let value = cleaned.parse::<f64>().unwrap_or(0.0);
+        Token::Real(if text.starts_with('-') { -value } else { value })
+    }
+
+    fn name(&mut self) -> String {
+        let mut out = Vec::new();
+        while let Some(&b) = self.data.get(self.pos) {
+            if is_whitespace(b) || is_delimiter(b) {
+                break;
+            }
+            self.pos += 1;
+            if b == b'#' {
+                let hex = self.data.get(self.pos..self.pos + 2);
+                if let Some(v) = hex
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                {
+                    out.push(v);
+                    self.pos += 2;
+                    continue;
+                }
+            }
+            out.push(b);
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        let mut high: Option<u8> = None;
+        while let Some(&b) = self.data.get(self.pos) {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            let Some(v) = (b as char).to_digit(16) else {
+                continue;
+            };
+            match high.take() {
+                Some(h) => out.push(h << 4 | v as u8),
+                None => high = Some(v as u8),
+            }
+        }
+        if let Some(h) = high {
+            out.push(h << 4);
+        }
+        out
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while let Some(&b) = self.data.get(self.pos) {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    out.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(b);
+                }
+                b'\\' => {
+                    let Some(&e) = self.data.get(self.pos) else {
+                        break;
+                    };
+                    self.pos += 1;
+                    match e {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(8),
+                        b'f' => out.push(12),
+                        b'\r' => {
+                            if self.data.get(self.pos) == Some(&b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        b'0'..=b'7' => {
+                            let mut v = (e - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.data.get(self.pos) {
+                                    Some(&d @ b'0'..=b'7') => {
+                                        v = v * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(v as u8);
+                        }
+                        _ => out.push(e),
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+        out
+    }
+
+    /// A complete object; `N G R` references are recognised. `None` at the end of
+    /// input or on a keyword that is not a value (returned via `keyword`).
+    pub fn object(&mut self) -> Option<Object> {
+        let token = self.next_token()?;
+        self.object_from(token)
+    }
+
+    pub fn object_from(&mut self, token: Token) -> Option<Object> {
+        Some(match token {
+            Token::Int(n) => {
+                // Look ahead for `G R`.
+                let save = self.pos;
+                if let (Some(Token::Int(gen)), Some(Token::Keyword(k))) =
+                    (self.next_token(), self.next_token())
+                {
+                    if k == "R" && n >= 0 {
+                        return Some(Object::Ref(n as u32, gen as u16));
+                    }
+                }
+                self.pos = save;
+                Object::Int(n)
+            }
+            Token::Real(r) => Object::Real(r),
+            Token::Str(s) => Object::Str(s),
+            Token::Name(n) => Object::Name(n),
+            Token::ArrayOpen => {
+                let mut items = Vec::new();
+                loop {
+                    match self.next_token()? {
+                        Token::ArrayClose => break,
+                        t => items.push(self.object_from(t)?),
+                    }
+                }
+                Object::Array(items)
+            }
+            Token::DictOpen => Object::Dict(self.dict_body()?),
+            Token::Keyword(k) => match k.as_str() {
+                "true" => Object::Bool(true),
+                "false" => Object::Bool(false),
+                "null" => Object::Null,
+                _ => return None,
+            },
+            Token::ArrayClose | Token::DictClose => return None,
+        })
+    }
+
+    /// Entries up to and including `>>`.
+    pub fn dict_body(&mut self) -> Option<Dict> {
+        let mut dict = Dict::new();
+        loop {
+            match self.next_token()? {
+                Token::DictClose => return Some(dict),
+                Token::Name(key) => {
+                    let value = self.object()?;
+                    dict.insert(key, value);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Bookmarks (`/Outlines`) and where their destinations point.
+//!
+//! hyperref writes one bookmark per sectioning command, with a named
+//! destination a little above the heading. Names are looked up in the
+//! catalog's `/Names /Dests` tree, then in the older `/Dests` dictionary.
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use super::object::{Dict, Object};
+use super::Document;
+
+/// Bookmarks beyond this are ignored, as are deeper levels.
+const MAX_ITEMS: usize = 10_000;
+const MAX_LEVEL: u32 = 32;
+/// Name tree nesting beyond this is treated as corrupt.
+const MAX_TREE_DEPTH: usize = 32;
+
+/// A bookmark, in outline order.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutlineItem {
+    pub title: String,
+    /// 1 for top-level bookmarks.
+    pub level: u32,
+    /// 1-based page of the destination.
+    pub page: u32,
+    /// Top of the destination in points from the page's top edge; 0 when it
+    /// is the whole page.
+    pub y: f64,
+}
+
+impl Document {
+    /// The bookmarks whose destinations are pages of this file.
+    pub fn outline(&self) -> Vec<OutlineItem> {
+        let Some(root) = self.catalog() else {
+            return Vec::new();
+        };
+        let Some(outlines) = self.file.dict(&self.file.lookup(&root, "Outlines")) else {
+            return Vec::new();
+        };
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        self.outline_level(&root, &outlines, 1, &mut seen, &mut items);
+        items
+    }
+
+    fn catalog(&self) -> Option<Dict> {
+        self.file
+            .dict(&self.file.lookup(&self.file.trailer, "Root"))
+    }
+
+    /// Appends the children of `parent`, depth first.
+    fn outline_level(
+        &self,
+        root: &Dict,
+        parent: &Dict,
+        level: u32,
+        seen: &mut HashSet<u32>,
+        items: &mut Vec<OutlineItem>,
+    ) {
+        let mut next = parent.get("First").cloned();
+        while let Some(node) = next.take() {
+            if let Object::Ref(num, _) = node {
+                if !seen.insert(num) {
+                    return;
+                }
+            }
+            if items.len() >= MAX_ITEMS {
+                return;
+            }
+            let Some(item) = self.file.dict(&node) else {
+                return;
+            };
+            let dest = match item.get("Dest") {
+                Some(dest) => Some(dest.clone()),
+                None => self
+                    .file
+                    .dict(&self.file.lookup(&item, "A"))
+                    .filter(|a| self.file.lookup(a, "S").as_name() == Some("GoTo"))
+                    .and_then(|a| a.get("D").cloned()),
+            };
+            if let Some((page, y)) = dest.and_then(|d| self.destination(root, &d)) {
+                let title = self.file.lookup(&item, "Title");
+                items.push(OutlineItem {
+                    title: text_string(title.as_bytes().unwrap_or_default()),
+                    level,
+                    page,
+                    y,
+                });
+            }
+            if level < MAX_LEVEL {
+                self.outline_level(root, &item, level + 1, seen, items);
+            }
+            next = item.get("Next").cloned();
+        }
+    }
+
+    /// 1-based page and top (points from the top edge) of an explicit or
+    /// named destination.
+    fn destination(&self, root: &Dict, dest: &Object) -> Option<(u32, f64)> {
+        let dest = self.file.resolve(dest);
+        let explicit = match &*dest {
+            Object::Array(_) => dest,
+            Object::Str(name) => self.named_destination(root, name)?,
+            Object::Name(name) => {
+                let dests = self.file.dict(&self.file.lookup(root, "Dests"))?;
+                self.file.lookup(&dests, name)
+            }
+            _ => return None,
+        };
+        // A named destination may be a dictionary holding the array under `D`.
+        let explicit = match explicit.as_dict() {
+            Some(d) => self.file.lookup(d, "D"),
+            None => explicit,
+        };
+        let parts = explicit.as_array()?;
+        let Some(Object::Ref(target, _)) = parts.first() else {
+            return None;
+        };
+        let index = self.pages.iter().position(|p| p.id == Some(*target))?;
+        let page = &self.pages[index];
+        let number = |i: usize| parts.get(i).and_then(|o| self.file.resolve(o).as_f64());
+        let (left, top) = match parts.get(1).and_then(Object::as_name) {
+            Some("XYZ") => (number(2), number(3)),
+            Some("FitH" | "FitBH") => (None, number(2)),
+            Some("FitR") => (number(2), number(5)),
+            _ => (None, None),
+        };
+        let y = match top {
+            Some(top) => {
+                let m = page.device_matrix(1.0);
+                m.apply(left.unwrap_or(page.bbox[0]), top).1.max(0.0)
+            }
+            None => 0.0,
+        };
+        Some((index as u32 + 1, y))
+    }
+
+    /// `name` in the catalog's `/Names /Dests` tree.
+    fn named_destination(&self, root: &Dict, name: &[u8]) -> Option<Rc<Object>> {
+        let names = self.file.dict(&self.file.lookup(root, "Names"))?;
+        let tree = self.file.dict(&self.file.lookup(&names, "Dests"))?;
+        self.name_tree_lookup(&tree, name, 0)
+    }
+
+    fn name_tree_lookup(&self, node: &Dict, name: &[u8], depth: usize) -> Option<Rc<Object>> {
+        if let Some(limits) = self.file.lookup(node, "Limits").as_array() {
+            let limit = |i: usize| limits.get(i).map(|o| self.file.resolve(o));
+            if let (Some(first), Some(last)) = (limit(0), limit(1)) {
+                if let (Some(first), Some(last)) = (first.as_bytes(), last.as_bytes()) {
+                    if name < first || name > last {
+                        return None;
+                    }
+                }
+            }
+        }
+        if let Some(pairs) = self.file.lookup(node, "Names").as_array() {
+            for pair in pairs.chunks(2) {
+                if let [key, value] = pair {
+                    if self.file.resolve(key).as_bytes() == Some(name) {
+                        return Some(self.file.resolve(value));
+                    }
+                }
+            }
+        }
+        if depth >= MAX_TREE_DEPTH {
+            return None;
+        }
+        let kids = self.file.lookup(node, "Kids");
+        kids.as_array()?.iter().find_map(|kid| {
+            let kid = self.file.dict(kid)?;
+            self.name_tree_lookup(&kid, name, depth + 1)
+        })
+    }
+}
+
+/// A PDF text string: UTF-16BE or UTF-8 with a byte order mark, else
+/// PDFDocEncoding, read as Latin-1 (they differ only in rarely used slots).
+pub(crate) fn text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(utf8).into_owned();
+    }
+    bytes.iter().map(|&b| b as char).collect()
+}
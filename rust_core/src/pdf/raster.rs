@@ -0,0 +1,839 @@
+//! Anti-aliased scanline rasterizer: paths, strokes, clip masks and images on
+//! an RGBA canvas.
+//!
+//! Paths are flattened to polygons in device space. Each pixel row is sampled on
+//! [`SUBSAMPLES`] sub-scanlines with exact horizontal coverage, which keeps
+//! vertical glyph stems crisp. Strokes are turned into polygons (one per
+//! segment, join and cap, all wound the same way) and filled with the nonzero rule.
+
+use std::rc::Rc;
+
+/// Sub-scanlines per pixel row.
+const SUBSAMPLES: usize = 8;
+/// Maximum distance in pixels between a curve and its flattened polygon.
+const FLATNESS: f64 = 0.2;
+/// Strokes thinner than this many pixels are drawn this wide, so hairlines and
+/// thin rules stay visible at low zoom.
+const MIN_STROKE_WIDTH: f64 = 0.8;
+
+/// Affine transform `[a b c d e f]` mapping `(x, y)` to
+/// `(a·x + c·y + e, b·x + d·y + f)`, as in PDF.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix(pub [f64; 6]);
+
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    pub fn translate(x: f64, y: f64) -> Matrix {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    pub fn scale(x: f64, y: f64) -> Matrix {
+        Matrix([x, 0.0, 0.0, y, 0.0, 0.0])
+    }
+
+    /// `self` applied first, then `next`.
+    pub fn then(&self, next: &Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = next.0;
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// Like [`Matrix::apply`] without the translation.
+    pub fn apply_vector(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, ..] = self.0;
+        (a * x + c * y, b * x + d * y)
+    }
+
+    pub fn invert(&self) -> Option<Matrix> {
+        let [a, b, c, d, e, f] = self.0;
+        let det = a * d - b * c;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        Some(Matrix([
+            d / det,
+            -b / det,
+            -c / det,
+            a / det,
+            (c * f - d * e) / det,
+            (b * e - a * f) / det,
+        ]))
+    }
+
+    /// Geometric mean scale factor, for line widths.
+    pub fn scale_factor(&self) -> f64 {
+        let [a, b, c, d, ..] = self.0;
+        (a * d - b * c).abs().sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    CurveTo(f64, f64, f64, f64, f64, f64),
+    Close,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    pub segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.segments.push(Segment::MoveTo(x, y));
+    }
+
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.segments.push(Segment::LineTo(x, y));
+    }
+
+    pub fn curve_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) {
+        self.segments.push(Segment::CurveTo(x1, y1, x2, y2, x3, y3));
+    }
+
+    /// A quadratic curve from `(x0, y0)`, as the equivalent cubic.
+    pub fn quad_to(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, x2: f64, y2: f64) {
+        self.curve_to(
+            x0 + 2.0 / 3.0 * (x1 - x0),
+            y0 + 2.0 / 3.0 * (y1 - y0),
+            x2 + 2.0 / 3.0 * (x1 - x2),
+            y2 + 2.0 / 3.0 * (y1 - y2),
+            x2,
+            y2,
+        );
+    }
+
+    pub fn close(&mut self) {
+        self.segments.push(Segment::Close);
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.move_to(x, y);
+        self.line_to(x + w, y);
+        self.line_to(x + w, y + h);
+        self.line_to(x, y + h);
+        self.close();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn transform(&self, m: &Matrix) -> Path {
+        let segments = self
+            .segments
+            .iter()
+            .map(|s| match *s {
+                Segment::MoveTo(x, y) => {
+                    let (x, y) = m.apply(x, y);
+                    Segment::MoveTo(x, y)
+                }
+                Segment::LineTo(x, y) => {
+                    let (x, y) = m.apply(x, y);
+                    Segment::LineTo(x, y)
+                }
+                Segment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                    let (x1, y1) = m.apply(x1, y1);
+                    let (x2, y2) = m.apply(x2, y2);
+                    let (x3, y3) = m.apply(x3, y3);
+                    Segment::CurveTo(x1, y1, x2, y2, x3, y3)
+                }
+                Segment::Close => Segment::Close,
+            })
+            .collect();
+        Path { segments }
+    }
+
+    /// Append `other` transformed by `m`.
+    pub fn extend_transformed(&mut self, other: &Path, m: &Matrix) {
+        self.segments.extend(other.transform(m).segments);
+    }
+
+    /// `(min_x, min_y, max_x, max_y)` of all points, control points included.
+    pub fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let mut points = self.segments.iter().flat_map(|s| match *s {
+            Segment::MoveTo(x, y) | Segment::LineTo(x, y) => vec![(x, y)],
+            Segment::CurveTo(x1, y1, x2, y2, x3, y3) => vec![(x1, y1), (x2, y2), (x3, y3)],
+            Segment::Close => vec![],
+        });
+        let (x, y) = points.next()?;
+        Some(points.fold((x, y, x, y), |(x0, y0, x1, y1), (x, y)| {
+            (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+        }))
+    }
+
+    /// Polygons of the subpaths, curves flattened to [`FLATNESS`].
+    fn flatten(&self) -> Vec<Polyline> {
+        let mut out = Vec::new();
+        let mut current = Polyline::default();
+        let mut start = (0.0, 0.0);
+        let mut last = (0.0, 0.0);
+        for s in &self.segments {
+            match *s {
+                Segment::MoveTo(x, y) => {
+                    if current.points.len() > 1 {
+                        out.push(std::mem::take(&mut current));
+                    }
+                    current.points.clear();
+                    current.closed = false;
+                    current.points.push((x, y));
+                    start = (x, y);
+                    last = start;
+                }
+                Segment::LineTo(x, y) => {
+                    if current.points.is_empty() {
+                        current.points.push(last);
+                    }
+                    current.points.push((x, y));
+                    last = (x, y);
+                }
+                Segment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                    if current.points.is_empty() {
+                        current.points.push(last);
+                    }
+                    flatten_cubic(&mut current.points, last, (x1, y1), (x2, y2), (x3, y3));
+                    last = (x3, y3);
+                }
+                Segment::Close => {
+                    if !current.points.is_empty() {
+                        current.closed = true;
+                        out.push(std::mem::take(&mut current));
+                    }
+                    current.points.push(start);
+                    last = start;
+                }
+            }
+        }
+        if current.points.len() > 1 || (current.points.len() == 1 && current.closed) {
+            out.push(current);
+        }
+        out
+    }
+}
+
+fn flatten_cubic(
+    out: &mut Vec<(f64, f64)>,
+    p0: (f64, f64),
+    p1: (f64, f64),
+    p2: (f64, f64),
+    p3: (f64, f64),
+) {
+    let dd = f64::max(
+        (p0.0 - 2.0 * p1.0 + p2.0).hypot(p0.1 - 2.0 * p1.1 + p2.1),
+        (p1.0 - 2.0 * p2.0 + p3.0).hypot(p1.1 - 2.0 * p2.1 + p3.1),
+    );
+    let n = (0.75 * dd / FLATNESS).sqrt().ceil().clamp(1.0, 256.0) as usize;
+    for i in 1..=n {
+        let t = i as f64 / n as f64;
+        let mt = 1.0 - t;
+        let a = mt * mt * mt;
+        let b = 3.0 * mt * mt * t;
+        let c = 3.0 * mt * t * t;
+        let d = t * t * t;
+        out.push((
+            a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+            a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+        ));
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Polyline {
+    points: Vec<(f64, f64)>,
+    closed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Stroke parameters in device pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f64,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub miter_limit: f64,
+    pub dash: Vec<f64>,
+    pub dash_phase: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    x_top: f64,
+    y_top: f64,
+    y_bottom: f64,
+    dx_dy: f64,
+    winding: i32,
+}
+
+/// Calls `emit(y, x0, coverage)` for each pixel row the polygons touch within
+/// `width × height`; `coverage[i]` belongs to pixel `x0 + i`.
+fn rasterize(
+    polys: &[Polyline],
+    rule: FillRule,
+    width: usize,
+    height: usize,
+    mut emit: impl FnMut(usize, usize, &[f32]),
+) {
+    let mut edges = Vec::new();
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for poly in polys {
+        let n = poly.points.len();
+        if n < 2 {
+            continue;
+        }
+        for i in 0..n {
+            let (x0, y0) = poly.points[i];
+            let (x1, y1) = poly.points[(i + 1) % n];
+            if !(x0.is_finite() && y0.is_finite() && x1.is_finite() && y1.is_finite()) {
+                return;
+            }
+            min_x = min_x.min(x0);
+            max_x = max_x.max(x0);
+            min_y = min_y.min(y0);
+            max_y = max_y.max(y0);
+            if y0 == y1 {
+                continue;
+            }
+            let (top, bottom, winding) = if y0 < y1 {
+                ((x0, y0), (x1, y1), 1)
+            } else {
+                ((x1, y1), (x0, y0), -1)
+            };
+            edges.push(Edge {
+                x_top: top.0,
+                y_top: top.1,
+                y_bottom: bottom.1,
+                dx_dy: (bottom.0 - top.0) / (bottom.1 - top.1),
+                winding,
+            });
+        }
+    }
+    if edges.is_empty() {
+        return;
+    }
+    let x0 = min_x.floor().max(0.0) as usize;
+    let x1 = (max_x.ceil().max(0.0) as usize).min(width);
+    let y0 = min_y.floor().max(0.0) as usize;
+    let y1 = (max_y.ceil().max(0.0) as usize).min(height);
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+    edges.sort_by(|a, b| a.y_top.total_cmp(&b.y_top));
+    let span = x1 - x0;
+    let mut acc = vec![0f32; span + 1];
+    let mut run = vec![0f32; span + 2];
+    let mut active: Vec<Edge> = Vec::new();
+    let mut next_edge = 0;
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    let weight = 1.0 / SUBSAMPLES as f32;
+    for y in y0..y1 {
+        acc.iter_mut().for_each(|v| *v = 0.0);
+        run.iter_mut().for_each(|v| *v = 0.0);
+        let mut touched = false;
+        for s in 0..SUBSAMPLES {
+            let sy = y as f64 + (s as f64 + 0.5) / SUBSAMPLES as f64;
+            while next_edge < edges.len() && edges[next_edge].y_top <= sy {
+                active.push(edges[next_edge]);
+                next_edge += 1;
+            }
+            active.retain(|e| e.y_bottom > sy);
+            crossings.clear();
+            crossings.extend(
+                active
+                    .iter()
+                    .filter(|e| e.y_top <= sy)
+                    .map(|e| (e.x_top + (sy - e.y_top) * e.dx_dy, e.winding)),
+            );
+            if crossings.is_empty() {
+                continue;
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            let mut start = 0.0;
+            for &(x, w) in &crossings {
+                let was_inside = inside(winding, rule);
+                winding += w;
+                let is_inside = inside(winding, rule);
+                if !was_inside && is_inside {
+                    start = x;
+                } else if was_inside && !is_inside {
+                    add_span(&mut acc, &mut run, start - x0 as f64, x - x0 as f64, weight);
+                    touched = true;
+                }
+            }
+        }
+        if !touched {
+            continue;
+        }
+        let mut running = 0.0;
+        for i in 0..span {
+            running += run[i];
+            acc[i] = (acc[i] + running).min(1.0);
+        }
+        emit(y, x0, &acc[..span]);
+    }
+}
+
+fn inside(winding: i32, rule: FillRule) -> bool {
+    match rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    }
+}
+
+/// Adds coverage `weight` for `[xs, xe)`; whole pixels go through the `run`
+/// difference array.
+fn add_span(acc: &mut [f32], run: &mut [f32], xs: f64, xe: f64, weight: f32) {
+    let limit = (acc.len() - 1) as f64;
+    let xs = xs.clamp(0.0, limit);
+    let xe = xe.clamp(0.0, limit);
+    if xe <= xs {
+        return;
+    }
+    let (i0, i1) = (xs.floor() as usize, xe.floor() as usize);
+    if i0 == i1 {
+        acc[i0] += (xe - xs) as f32 * weight;
+        return;
+    }
+    acc[i0] += (i0 as f64 + 1.0 - xs) as f32 * weight;
+    if i1 > i0 + 1 {
+        run[i0 + 1] += weight;
+        run[i1] -= weight;
+    }
+    acc[i1] += (xe - i1 as f64) as f32 * weight;
+}
+
+/// Turns flattened subpaths into fill polygons covering the stroke.
+fn stroke_polygons(polys: &[Polyline], style: &StrokeStyle) -> Vec<Polyline> {
+    let hw = style.width.max(MIN_STROKE_WIDTH) / 2.0;
+    let mut out = Vec::new();
+    let dashed;
+    let polys = if style.dash.iter().any(|&d| d > 0.0) {
+        dashed = dash(polys, &style.dash, style.dash_phase);
+        &dashed
+    } else {
+        polys
+    };
+    for poly in polys {
+        let mut pts = poly.points.clone();
+        pts.dedup_by(|a, b| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        if poly.closed && pts.len() > 1 && pts.first() == pts.last() {
+            pts.pop();
+        }
+        if pts.len() == 1 {
+            // A zero-length subpath shows its caps only.
+            let (x, y) = pts[0];
+            match style.cap {
+                LineCap::Round => out.push(circle(x, y, hw)),
+                LineCap::Square => out.push(oriented(vec![
+                    (x - hw, y - hw),
+                    (x + hw, y - hw),
+                    (x + hw, y + hw),
+                    (x - hw, y + hw),
+                ])),
+                LineCap::Butt => {}
+            }
+            continue;
+        }
+        let n = pts.len();
+        let segments = if poly.closed { n } else { n - 1 };
+        for i in 0..segments {
+            let a = pts[i];
+            let b = pts[(i + 1) % n];
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let len = dx.hypot(dy);
+            let (nx, ny) = (-dy / len * hw, dx / len * hw);
+            let (mut a, mut b) = (a, b);
+            if !poly.closed && style.cap == LineCap::Square {
+                let (ex, ey) = (dx / len * hw, dy / len * hw);
+                if i == 0 {
+                    a = (a.0 - ex, a.1 - ey);
+                }
+                if i == segments - 1 {
+                    b = (b.0 + ex, b.1 + ey);
+                }
+            }
+            out.push(oriented(vec![
+                (a.0 + nx, a.1 + ny),
+                (b.0 + nx, b.1 + ny),
+                (b.0 - nx, b.1 - ny),
+                (a.0 - nx, a.1 - ny),
+            ]));
+        }
+        // Joins at interior vertices (and the start of a closed path).
+        let joins = if poly.closed { 0..n } else { 1..n - 1 };
+        for i in joins {
+            let prev = pts[(i + n - 1) % n];
+            let p = pts[i];
+            let next = pts[(i + 1) % n];
+            out.extend(join(prev, p, next, hw, style));
+        }
+        if !poly.closed && style.cap == LineCap::Round {
+            out.push(circle(pts[0].0, pts[0].1, hw));
+            out.push(circle(pts[n - 1].0, pts[n - 1].1, hw));
+        }
+    }
+    out
+}
+
+fn join(
+    prev: (f64, f64),
+    p: (f64, f64),
+    next: (f64, f64),
+    hw: f64,
+    style: &StrokeStyle,
+) -> Option<Polyline> {
+    let (d0x, d0y) = (p.0 - prev.0, p.1 - prev.1);
+    let (d1x, d1y) = (next.0 - p.0, next.1 - p.1);
+    let (l0, l1) = (d0x.hypot(d0y), d1x.hypot(d1y));
+    if l0 == 0.0 || l1 == 0.0 {
+        return None;
+    }
+    let (u0, u1) = ((d0x / l0, d0y / l0), (d1x / l1, d1y / l1));
+    let cross = u0.0 * u1.1 - u0.1 * u1.0;
+    if cross.abs() < 1e-9 && u0.0 * u1.0 + u0.1 * u1.1 > 0.0 {
+        return None;
+    }
+    if style.join == LineJoin::Round {
+        return Some(circle(p.0, p.1, hw));
+    }
+    // Outer side of the turn.
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let a = (p.0 - u0.1 * hw * side, p.1 + u0.0 * hw * side);
+    let b = (p.0 - u1.1 * hw * side, p.1 + u1.0 * hw * side);
+    // Miter length / line width = 1 / sin(φ/2), φ the angle between the
+    // segments; sin(φ/2)² = (1 + u0·u1) / 2.
+    let sin_half = ((1.0 + u0.0 * u1.0 + u0.1 * u1.1) / 2.0).max(0.0).sqrt();
+    let mut points = vec![p, a];
+    if style.join == LineJoin::Miter && sin_half > 1e-9 && 1.0 / sin_half <= style.miter_limit {
+        let (mx, my) = (a.0 + b.0 - 2.0 * p.0, a.1 + b.1 - 2.0 * p.1);
+        let ml = mx.hypot(my);
+        if ml > 1e-9 {
+            let dist = hw / sin_half;
+            points.push((p.0 + mx / ml * dist, p.1 + my / ml * dist));
+        }
+    }
+    points.push(b);
+    Some(oriented(points))
+}
+
+fn circle(x: f64, y: f64, r: f64) -> Polyline {
+    let n = (r * 2.0).ceil().clamp(8.0, 64.0) as usize;
+    oriented(
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / n as f64 * std::f64::consts::TAU;
+                (x + r * t.cos(), y + r * t.sin())
+            })
+            .collect(),
+    )
+}
+
+/// A closed polygon with positive winding, so overlapping stroke pieces union
+/// under the nonzero rule.
+fn oriented(mut points: Vec<(f64, f64)>) -> Polyline {
+    let n = points.len();
+    let area: f64 = (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    if area < 0.0 {
+        points.reverse();
+    }
+    Polyline {
+        points,
+        closed: true,
+    }
+}
+
+fn dash(polys: &[Polyline], pattern: &[f64], phase: f64) -> Vec<Polyline> {
+    let total: f64 = pattern.iter().sum();
+    let mut out = Vec::new();
+    for poly in polys {
+        let mut pts = poly.points.clone();
+        if poly.closed && pts.len() > 1 {
+            pts.push(pts[0]);
+        }
+        // Position in the pattern.
+        let mut index = 0;
+        let mut left = pattern[0];
+        let mut offset = phase.rem_euclid(total.max(1e-9));
+        while offset > 0.0 {
+            if offset >= left {
+                offset -= left;
+                index = (index + 1) % pattern.len();
+                left = pattern[index];
+            } else {
+                left -= offset;
+                offset = 0.0;
+            }
+        }
+        let mut current = Polyline::default();
+        if index % 2 == 0 {
+            current.points.push(pts[0]);
+        }
+        for w in pts.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let len = (b.0 - a.0).hypot(b.1 - a.1);
+            let mut pos = 0.0;
+            while len - pos > left {
+                pos += left;
+                let t = pos / len;
+                let p = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                if index % 2 == 0 {
+                    current.points.push(p);
+                    out.push(std::mem::take(&mut current));
+                } else {
+                    current.points.push(p);
+                }
+                index = (index + 1) % pattern.len();
+                left = pattern[index];
+                if left <= 0.0 && index % 2 == 1 {
+                    // Zero-length gaps would loop forever.
+                    left = 1e-9;
+                }
+            }
+            left -= len - pos;
+            if index % 2 == 0 {
+                current.points.push(b);
+            }
+        }
+        if index % 2 == 0 && current.points.len() > 1 {
+            out.push(current);
+        }
+    }
+    out
+}
+
+/// Coverage mask for clipping, one byte per pixel.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    data: Vec<u8>,
+}
+
+/// What a fill paints with.
+pub enum Paint<'a> {
+    Solid([u8; 3]),
+    /// Color per device pixel center, `None` where the shading is undefined.
+    Shader(&'a dyn Fn(f64, f64) -> Option<[u8; 3]>),
+}
+
+/// An RGBA image with straight (not premultiplied) alpha.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+/// The page being drawn: opaque RGBA, white to begin with.
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![255; width * height * 4],
+        }
+    }
+
+    pub fn fill(
+        &mut self,
+        path: &Path,
+        rule: FillRule,
+        paint: &Paint,
+        alpha: f32,
+        clip: Option<&Rc<Mask>>,
+    ) {
+        let polys = path.flatten();
+        self.fill_polys(&polys, rule, paint, alpha, clip);
+    }
+
+    pub fn stroke(
+        &mut self,
+        path: &Path,
+        style: &StrokeStyle,
+        paint: &Paint,
+        alpha: f32,
+        clip: Option<&Rc<Mask>>,
+    ) {
+        let polys = stroke_polygons(&path.flatten(), style);
+        self.fill_polys(&polys, FillRule::NonZero, paint, alpha, clip);
+    }
+
+    fn fill_polys(
+        &mut self,
+        polys: &[Polyline],
+        rule: FillRule,
+        paint: &Paint,
+        alpha: f32,
+        clip: Option<&Rc<Mask>>,
+    ) {
+        let width = self.width;
+        let pixels = &mut self.pixels;
+        rasterize(polys, rule, width, self.height, |y, x0, coverage| {
+            for (i, &c) in coverage.iter().enumerate() {
+                let x = x0 + i;
+                let mut a = c * alpha;
+                if let Some(mask) = clip {
+                    a *= mask.data[y * width + x] as f32 / 255.0;
+                }
+                if a <= 0.0 {
+                    continue;
+                }
+                let color = match paint {
+                    Paint::Solid(c) => *c,
+                    Paint::Shader(f) => match f(x as f64 + 0.5, y as f64 + 0.5) {
+                        Some(c) => c,
+                        None => continue,
+                    },
+                };
+                blend(&mut pixels[(y * width + x) * 4..][..4], color, a);
+            }
+        });
+    }
+
+    /// Intersects `clip` (everything when `None`) with the area of `path`.
+    pub fn clip(&self, path: &Path, rule: FillRule, clip: Option<&Rc<Mask>>) -> Rc<Mask> {
+        let mut data = vec![0u8; self.width * self.height];
+        let width = self.width;
+        rasterize(
+            &path.flatten(),
+            rule,
+            width,
+            self.height,
+            |y, x0, coverage| {
+                let row = &mut data[y * width + x0..][..coverage.len()];
+                for (d, &c) in row.iter_mut().zip(coverage) {
+                    *d = (c * 255.0).round() as u8;
+                }
+            },
+        );
+        if let Some(old) = clip {
+            for (d, &o) in data.iter_mut().zip(&old.data) {
+                *d = (*d as u16 * o as u16 / 255) as u8;
+            }
+        }
+        Rc::new(Mask { data })
+    }
+
+    /// Draws `image` mapped onto the unit square transformed by `m` (PDF image
+    /// space: the first row of samples is at the top, y = 1).
+    pub fn draw_image(
+        &mut self,
+        image: &Image,
+        m: &Matrix,
+        alpha: f32,
+        smooth: bool,
+        clip: Option<&Rc<Mask>>,
+    ) {
+        if image.width == 0 || image.height == 0 {
+            return;
+        }
+        let Some(inv) = m.invert() else {
+            return;
+        };
+        let mut unit = Path::default();
+        unit.rect(0.0, 0.0, 1.0, 1.0);
+        let Some((bx0, by0, bx1, by1)) = unit.transform(m).bounds() else {
+            return;
+        };
+        let x0 = bx0.floor().max(0.0) as usize;
+        let y0 = by0.floor().max(0.0) as usize;
+        let x1 = (bx1.ceil().max(0.0) as usize).min(self.width);
+        let y1 = (by1.ceil().max(0.0) as usize).min(self.height);
+        // Bilinear only when the image is enlarged; sampling the nearest texel
+        // is as good when it is shrunk and much faster.
+        let device_w = (m.0[0].hypot(m.0[1])).max(1e-9);
+        let smooth = smooth && device_w > image.width as f64;
+        let (w, h) = (image.width as f64, image.height as f64);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (u, v) = inv.apply(x as f64 + 0.5, y as f64 + 0.5);
+                if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+                    continue;
+                }
+                let (sx, sy) = (u * w, (1.0 - v) * h);
+                let texel = if smooth {
+                    bilinear(image, sx - 0.5, sy - 0.5)
+                } else {
+                    let ix = (sx as usize).min(image.width - 1);
+                    let iy = (sy as usize).min(image.height - 1);
+                    let p = &image.rgba[(iy * image.width + ix) * 4..][..4];
+                    [p[0], p[1], p[2], p[3]]
+                };
+                let mut a = texel[3] as f32 / 255.0 * alpha;
+                if let Some(mask) = clip {
+                    a *= mask.data[y * self.width + x] as f32 / 255.0;
+                }
+                if a > 0.0 {
+                    let color = [texel[0], texel[1], texel[2]];
+                    blend(&mut self.pixels[(y * self.width + x) * 4..][..4], color, a);
+                }
+            }
+        }
+    }
+}
+
+fn bilinear(image: &Image, x: f64, y: f64) -> [u8; 4] {
+    let x = x.clamp(0.0, (image.width - 1) as f64);
+    let y = y.clamp(0.0, (image.height - 1) as f64);
+    let (ix, iy) = (x.floor() as usize, y.floor() as usize);
+    let (fx, fy) = (x - ix as f64, y - iy as f64);
+    let ix1 = (ix + 1).min(image.width - 1);
+    let iy1 = (iy + 1).min(image.height - 1);
+    let at = |px: usize, py: usize, c: usize| image.rgba[(py * image.width + px) * 4 + c] as f64;
+    let mut out = [0u8; 4];
+    for (c, o) in out.iter_mut().enumerate() {
+        let top = at(ix, iy, c) * (1.0 - fx) + at(ix1, iy, c) * fx;
+        let bottom = at(ix, iy1, c) * (1.0 - fx) + at(ix1, iy1, c) * fx;
+        *o = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    out
+}
+
+fn blend(dst: &mut [u8], color: [u8; 3], alpha: f32) {
+    let a = alpha.min(1.0);
+    for c in 0..3 {
+        dst[c] = (dst[c] as f32 * (1.0 - a) + color[c] as f32 * a).round() as u8;
+    }
+}
@@ -0,0 +1,331 @@
+//! SVG output: a page as vector paths, the way `dvisvgm --no-fonts` draws it.
+//!
+//! Glyphs become filled outlines, so the SVG needs no fonts. Shadings and
+//! images are embedded as PNG data; shadings are sampled at [`SHADING_RES`]
+//! pixels per point inside the filled path.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+
+use super::content::{Device, Fill};
+use super::raster::{FillRule, Image, LineCap, LineJoin, Matrix, Path, Segment, StrokeStyle};
+
+/// Pixels per point when a shading is sampled into an image.
+const SHADING_RES: f64 = 2.0;
+/// Largest side of a sampled shading, in pixels.
+const MAX_SHADING_SIDE: f64 = 1024.0;
+
+/// A [`Device`] that writes SVG elements. Ids start with `prefix`, so several
+/// pages can be inlined into one HTML document.
+pub(crate) struct SvgWriter {
+    prefix: String,
+    body: String,
+    clip: Option<usize>,
+    saved: Vec<Option<usize>>,
+    next_id: usize,
+}
+
+impl SvgWriter {
+    pub fn new(prefix: &str) -> Self {
+        SvgWriter {
+            prefix: prefix.to_string(),
+            body: String::new(),
+            clip: None,
+            saved: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// The finished document, `width × height` points.
+    pub fn finish(self, width: f64, height: f64) -> String {
+        let (w, h) = (num(width), num(height));
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" \
+             width=\"{w}pt\" height=\"{h}pt\" viewBox=\"0 0 {w} {h}\">\n{}</svg>\n",
+            self.body
+        )
+    }
+
+    fn id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn clip_attr(&self) -> String {
+        match self.clip {
+            Some(id) => format!(" clip-path=\"url(#{}-c{id})\"", self.prefix),
+            None => String::new(),
+        }
+    }
+
+    /// Fills `path` with a shading, sampled into an image clipped to the path.
+    fn shade(&mut self, path: &Path, rule: FillRule, fill: &Fill, alpha: f32) {
+        let Fill::Shading(shading, inverse) = fill else {
+            return;
+        };
+        let Some((x0, y0, x1, y1)) = path.bounds() else {
+            return;
+        };
+        let (w, h) = (x1 - x0, y1 - y0);
+        if w <= 0.0 || h <= 0.0 {
+            return;
+        }
+        let res = SHADING_RES.min(MAX_SHADING_SIDE / w.max(h));
+        let (pw, ph) = (
+            ((w * res).ceil() as usize).max(1),
+            ((h * res).ceil() as usize).max(1),
+        );
+        let mut rgba = vec![0u8; pw * ph * 4];
+        for py in 0..ph {
+            for px in 0..pw {
+                let x = x0 + (px as f64 + 0.5) * w / pw as f64;
+                let y = y0 + (py as f64 + 0.5) * h / ph as f64;
+                let (u, v) = inverse.apply(x, y);
+                if let Some([r, g, b]) = shading.color_at(u, v) {
+                    rgba[(py * pw + px) * 4..][..4].copy_from_slice(&[r, g, b, 255]);
+                }
+            }
+        }
+        let image = Image {
+            width: pw,
+            height: ph,
+            rgba,
+        };
+        let id = self.id();
+        let _ = writeln!(
+            self.body,
+            "<clipPath id=\"{}-c{id}\"{}><path d=\"{}\"{}/></clipPath>",
+            self.prefix,
+            self.clip_attr(),
+            path_data(path),
+            clip_rule(rule),
+        );
+        let saved = self.clip.replace(id);
+        // Unit square to the bounds, first sample row at the top.
+        let m = Matrix([w, 0.0, 0.0, -h, x0, y1]);
+        self.image(&image, &m, alpha, true);
+        self.clip = saved;
+    }
+}
+
+impl Device for SvgWriter {
+    fn save(&mut self) {
+        self.saved.push(self.clip);
+    }
+
+    fn restore(&mut self) {
+        if let Some(clip) = self.saved.pop() {
+            self.clip = clip;
+        }
+    }
+
+    fn clip(&mut self, path: &Path, rule: FillRule) {
+        let id = self.id();
+        // A clip path's own `clip-path` intersects it with the enclosing clip.
+        let _ = writeln!(
+            self.body,
+            "<clipPath id=\"{}-c{id}\"{}><path d=\"{}\"{}/></clipPath>",
+            self.prefix,
+            self.clip_attr(),
+            path_data(path),
+            clip_rule(rule),
+        );
+        self.clip = Some(id);
+    }
+
+    fn fill(&mut self, path: &Path, rule: FillRule, fill: &Fill, alpha: f32) {
+        let Fill::Solid(rgb) = fill else {
+            return self.shade(path, rule, fill, alpha);
+        };
+        let rule = match rule {
+            FillRule::NonZero => "",
+            FillRule::EvenOdd => " fill-rule=\"evenodd\"",
+        };
+        let _ = writeln!(
+            self.body,
+            "<path d=\"{}\" fill=\"{}\"{rule}{}{}/>",
+            path_data(path),
+            hex(*rgb),
+            opacity("fill-opacity", alpha),
+            self.clip_attr(),
+        );
+    }
+
+    fn stroke(&mut self, path: &Path, style: &StrokeStyle, fill: &Fill, alpha: f32) {
+        // Shaded strokes are rare enough to draw in the shading's background.
+        let rgb = match fill {
+            Fill::Solid(rgb) => *rgb,
+            Fill::Shading(shading, _) => shading.background.unwrap_or([0, 0, 0]),
+        };
+        let mut attrs = format!(" stroke-width=\"{}\"", num(style.width));
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Round => attrs.push_str(" stroke-linecap=\"round\""),
+            LineCap::Square => attrs.push_str(" stroke-linecap=\"square\""),
+        }
+        match style.join {
+            LineJoin::Miter if style.miter_limit != 4.0 => {
+                let _ = write!(attrs, " stroke-miterlimit=\"{}\"", num(style.miter_limit));
+            }
+            LineJoin::Miter => {}
+            LineJoin::Round => attrs.push_str(" stroke-linejoin=\"round\""),
+            LineJoin::Bevel => attrs.push_str(" stroke-linejoin=\"bevel\""),
+        }
+        if !style.dash.is_empty() && style.dash.iter().any(|&d| d > 0.0) {
+            let dash: Vec<String> = style.dash.iter().map(|&d| num(d)).collect();
+            let _ = write!(attrs, " stroke-dasharray=\"{}\"", dash.join(" "));
+            if style.dash_phase != 0.0 {
+                let _ = write!(attrs, " stroke-dashoffset=\"{}\"", num(style.dash_phase));
+            }
+        }
+        let _ = writeln!(
+            self.body,
+            "<path d=\"{}\" fill=\"none\" stroke=\"{}\"{attrs}{}{}/>",
+            path_data(path),
+            hex(rgb),
+            opacity("stroke-opacity", alpha),
+            self.clip_attr(),
+        );
+    }
+
+    fn image(&mut self, image: &Image, m: &Matrix, alpha: f32, smooth: bool) {
+        if image.width == 0 || image.height == 0 {
+            return;
+        }
+        // Image space has the first row at y = 1; flip it into SVG's y-down unit square.
+        let m = Matrix([1.0, 0.0, 0.0, -1.0, 0.0, 1.0]).then(m);
+        let [a, b, c, d, e, f] = m.0.map(num);
+        let rendering = if smooth {
+            ""
+        } else {
+            " style=\"image-rendering:pixelated\""
+        };
+        let _ = writeln!(
+            self.body,
+            "<image width=\"1\" height=\"1\" preserveAspectRatio=\"none\" \
+             transform=\"matrix({a} {b} {c} {d} {e} {f})\"{rendering}{}{} \
+             href=\"data:image/png;base64,{}\"/>",
+            opacity("opacity", alpha),
+            self.clip_attr(),
+            base64(&png(image)),
+        );
+    }
+}
+
+/// Shortest decimal for a coordinate, to 1/1000 of a point.
+fn num(v: f64) -> String {
+    let v = (v * 1000.0).round() / 1000.0;
+    if v == 0.0 {
+        return "0".into();
+    }
+    let s = format!("{v:.3}");
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn path_data(path: &Path) -> String {
+    let mut d = String::new();
+    for segment in &path.segments {
+        if !d.is_empty() {
+            d.push(' ');
+        }
+        match *segment {
+            Segment::MoveTo(x, y) => {
+                let _ = write!(d, "M{} {}", num(x), num(y));
+            }
+            Segment::LineTo(x, y) => {
+                let _ = write!(d, "L{} {}", num(x), num(y));
+            }
+            Segment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                let _ = write!(
+                    d,
+                    "C{} {} {} {} {} {}",
+                    num(x1),
+                    num(y1),
+                    num(x2),
+                    num(y2),
+                    num(x3),
+                    num(y3)
+                );
+            }
+            Segment::Close => d.push('Z'),
+        }
+    }
+    d
+}
+
+fn clip_rule(rule: FillRule) -> &'static str {
+    match rule {
+        FillRule::NonZero => "",
+        FillRule::EvenOdd => " clip-rule=\"evenodd\"",
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn opacity(attr: &str, alpha: f32) -> String {
+    if alpha >= 1.0 {
+        String::new()
+    } else {
+        format!(" {attr}=\"{}\"", num(alpha.max(0.0) as f64))
+    }
+}
+
+/// `image` as an RGBA PNG.
+fn png(image: &Image) -> Vec<u8> {
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let mut crc = Crc::new();
+        crc.update(kind);
+        crc.update(data);
+        out.extend_from_slice(&crc.sum().to_be_bytes());
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // 8-bit RGBA, deflate, no filter, no interlace.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in image.rgba.chunks_exact(image.width * 4) {
+        // Each scanline starts with its filter type (0: none). Writing to a
+        // Vec cannot fail.
+        let _ = encoder.write_all(&[0]);
+        let _ = encoder.write_all(row);
+    }
+    let data = encoder.finish().unwrap_or_default();
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &data);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
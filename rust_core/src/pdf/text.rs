@@ -0,0 +1,283 @@
+//! Text layer: shown glyphs grouped into words with their boxes.
+
+use serde::Serialize;
+
+use super::content::{Device, Glyph};
+
+/// Gap along the baseline, in font sizes, that starts a new word even without
+/// a space glyph (TeX positions words with `TJ` kerns rather than spaces).
+const WORD_GAP: f64 = 0.15;
+/// Baseline shift, in font sizes, that starts a new word (and line).
+const LINE_SHIFT: f64 = 0.3;
+
+/// A word on a page, its box in points from the page's top-left corner (the
+/// same space as [`crate::synctex`] positions).
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextWord {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+struct Pending {
+    text: String,
+    bounds: (f64, f64, f64, f64),
+    end: (f64, f64),
+    direction: (f64, f64),
+    size: f64,
+}
+
+/// A [`Device`] that only listens to glyphs.
+#[derive(Default)]
+pub(crate) struct TextCollector {
+    pub words: Vec<TextWord>,
+    pending: Option<Pending>,
+}
+
+impl TextCollector {
+    pub fn finish(mut self) -> Vec<TextWord> {
+        self.flush();
+        self.words
+    }
+
+    fn flush(&mut self) {
+        if let Some(p) = self.pending.take() {
+            let (x0, y0, x1, y1) = p.bounds;
+            self.words.push(TextWord {
+                text: p.text,
+                x: x0,
+                y: y0,
+                width: x1 - x0,
+                height: y1 - y0,
+            });
+        }
+    }
+}
+
+impl Device for TextCollector {
+    fn wants_paint(&self) -> bool {
+        false
+    }
+
+    fn glyph(&mut self, glyph: &Glyph) {
+        let text = glyph.text.clone().unwrap_or_else(|| '\u{FFFD}'.to_string());
+        if text.chars().all(char::is_whitespace) {
+            self.flush();
+            return;
+        }
+        let (dx, dy) = (glyph.end.0 - glyph.origin.0, glyph.end.1 - glyph.origin.1);
+        let len = dx.hypot(dy);
+        if let Some(p) = &self.pending {
+            let direction = if len > 0.0 {
+                (dx / len, dy / len)
+            } else {
+                p.direction
+            };
+            let (gx, gy) = (glyph.origin.0 - p.end.0, glyph.origin.1 - p.end.1);
+            let along = gx * direction.0 + gy * direction.1;
+            let across = (gy * direction.0 - gx * direction.1).abs();
+            let size = p.size.max(glyph.size);
+            if across > LINE_SHIFT * size || along > WORD_GAP * size || along < -0.5 * size {
+                self.flush();
+            }
+        }
+        let (mut x0, mut y0, mut x1, mut y1) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        for &(x, y) in &glyph.quad {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+        let direction = if len > 0.0 {
+            (dx / len, dy / len)
+        } else {
+            (1.0, 0.0)
+        };
+        match &mut self.pending {
+            Some(p) => {
+                p.text.push_str(&text);
+                p.bounds = (
+                    p.bounds.0.min(x0),
+                    p.bounds.1.min(y0),
+                    p.bounds.2.max(x1),
+                    p.bounds.3.max(y1),
+                );
+                p.end = glyph.end;
+                p.size = glyph.size;
+                if len > 0.0 {
+                    p.direction = direction;
+                }
+            }
+            None => {
+                self.pending = Some(Pending {
+                    text,
+                    bounds: (x0, y0, x1, y1),
+                    end: glyph.end,
+                    direction,
+                    size: glyph.size,
+                })
+            }
+        }
+    }
+}
+
+/// Words whose boxes overlap vertically by at least this share of the shorter
+/// one sit on the same line (so sub- and superscripts stay on theirs).
+const LINE_OVERLAP: f64 = 0.5;
+
+/// A box in points from the page's top-left corner.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl TextBox {
+    fn of(word: &TextWord) -> Self {
+        TextBox {
+            x: word.x,
+            y: word.y,
+            width: word.width,
+            height: word.height,
+        }
+    }
+
+    fn union(&self, other: &TextBox) -> TextBox {
+        let (x0, y0) = (self.x.min(other.x), self.y.min(other.y));
+        let x1 = (self.x + self.width).max(other.x + other.width);
+        let y1 = (self.y + self.height).max(other.y + other.height);
+        TextBox {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        }
+    }
+}
+
+/// A line of text: its words joined by single spaces, and their bounding box.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextLine {
+    pub text: String,
+    #[serde(flatten)]
+    pub bounds: TextBox,
+    #[serde(skip)]
+    pub(crate) words: Vec<TextWord>,
+}
+
+/// The text of one page as lines, in drawing order.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PageText {
+    /// 1-based.
+    pub page: u32,
+    pub lines: Vec<TextLine>,
+}
+
+impl PageText {
+    /// The lines joined by newlines.
+    pub fn text(&self) -> String {
+        let lines: Vec<&str> = self.lines.iter().map(|l| l.text.as_str()).collect();
+        lines.join("\n")
+    }
+}
+
+/// A match of [`super::Document::search`].
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    /// 1-based.
+    pub page: u32,
+    /// One box per line the match covers, around the whole words it touches.
+    pub boxes: Vec<TextBox>,
+}
+
+/// Groups words, in drawing order, into lines. A word starts a new line when it
+/// does not overlap the current one vertically or sits left of its start.
+pub(crate) fn lines(words: Vec<TextWord>) -> Vec<TextLine> {
+    let mut lines: Vec<TextLine> = Vec::new();
+    for word in words {
+        let b = TextBox::of(&word);
+        if let Some(line) = lines.last_mut() {
+            let l = line.bounds;
+            let overlap = (l.y + l.height).min(b.y + b.height) - l.y.max(b.y);
+            let shorter = l.height.min(b.height).max(f64::EPSILON);
+            if overlap >= LINE_OVERLAP * shorter && b.x + b.height > l.x {
+                line.text.push(' ');
+                line.text.push_str(&word.text);
+                line.bounds = l.union(&b);
+                line.words.push(word);
+                continue;
+            }
+        }
+        lines.push(TextLine {
+            text: word.text.clone(),
+            bounds: b,
+            words: vec![word],
+        });
+    }
+    lines
+}
+
+/// Where `query` occurs in `page`, ignoring case and treating any run of
+/// whitespace (including line breaks) as one space.
+pub(crate) fn search(page: &PageText, query: &str) -> Vec<SearchHit> {
+    let needle: Vec<char> = normalize(query).collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    // The page's words joined by spaces, each char tagged with its (line, word).
+    let mut hay: Vec<(char, usize, usize)> = Vec::new();
+    for (li, line) in page.lines.iter().enumerate() {
+        for (wi, word) in line.words.iter().enumerate() {
+            if !hay.is_empty() {
+                hay.push((' ', li, wi));
+            }
+            hay.extend(normalize(&word.text).map(|c| (c, li, wi)));
+        }
+    }
+    let mut hits = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= hay.len() {
+        if !hay[i..i + needle.len()]
+            .iter()
+            .zip(&needle)
+            .all(|(h, n)| h.0 == *n)
+        {
+            i += 1;
+            continue;
+        }
+        let mut boxes: Vec<(usize, TextBox)> = Vec::new();
+        for &(c, li, wi) in &hay[i..i + needle.len()] {
+            if c == ' ' {
+                continue;
+            }
+            let b = TextBox::of(&page.lines[li].words[wi]);
+            match boxes.last_mut() {
+                Some((line, last)) if *line == li => *last = last.union(&b),
+                _ => boxes.push((li, b)),
+            }
+        }
+        hits.push(SearchHit {
+            page: page.page,
+            boxes: boxes.into_iter().map(|(_, b)| b).collect(),
+        });
+        i += needle.len();
+    }
+    hits
+}
+
+/// Lowercase, with whitespace runs collapsed to one space and trimmed.
+fn normalize(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.split_whitespace()
+        .enumerate()
+        .flat_map(|(i, w)| (i > 0).then_some(' ').into_iter().chain(w.chars()))
+        .flat_map(char::to_lowercase)
+}
@@ -0,0 +1,113 @@
+//! Byte-identical PDFs for identical sources.
+//!
+//! A compile with a source date ([`CompileOptions::source_date_epoch`], else
+//! `SOURCE_DATE_EPOCH` in the environment) is reproducible:
+//! - TeX's `\today` and `\time`, and the PDF's `/CreationDate` and `/ModDate`,
+//!   are that instant instead of the clock (TeX Live's `SOURCE_DATE_EPOCH` plus
+//!   `FORCE_SOURCE_DATE=1`)
+//! - xdvipdfmx derives font subset tags (`ABCDEF+NotoSerif`) from the subset
+//!   instead of picking them at random
+//! - `/ID` is a hash of the finished file ([`stabilize_id`])
+//!
+//! Fonts, images and objects are already written in the order the pages use
+//! them, so nothing else in the file depends on the run.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::compile::CompileOptions;
+
+/// Seconds since 1970 the compile is dated at, if it should be reproducible.
+pub fn source_date_epoch(options: &CompileOptions) -> Option<u64> {
+    options.source_date_epoch.or_else(|| {
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+    })
+}
+
+/// The date TeX and xdvipdfmx are given: the source date, or now.
+pub fn build_date(epoch: Option<u64>) -> SystemTime {
+    match epoch {
+        Some(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+        None => SystemTime::now(),
+    }
+}
+
+/// Replaces both halves of the trailer's `/ID [<…> <…>]` with a SHA-256 of
+/// the file (taken with the old IDs zeroed), in place, so offsets stay valid.
+/// False if the file has no `/ID`.
+pub fn stabilize_id(pdf: &mut [u8]) -> bool {
+    let Some(ids) = find_id(pdf) else {
+        return false;
+    };
+    for &(start, end) in &ids {
+        pdf[start..end].fill(b'0');
+    }
+    let digest = format!("{:x}", Sha256::digest(&*pdf));
+    for (start, end) in ids {
+        // xdvipdfmx writes 16-byte IDs, half the digest; cycling covers longer ones.
+        let id = digest.bytes().cycle();
+        for (b, d) in pdf[start..end].iter_mut().zip(id) {
+            *b = d;
+        }
+    }
+    true
+}
+
+/// Byte ranges of the two hex strings of the last `/ID` entry.
+fn find_id(pdf: &[u8]) -> Option<[(usize, usize); 2]> {
+    let at = pdf.windows(3).rposition(|w| w == b"/ID")?;
+    let mut i = skip_space(pdf, at + 3);
+    if pdf.get(i) != Some(&b'[') {
+        return None;
+    }
+    let mut ids = [(0, 0); 2];
+    for id in &mut ids {
+        i = skip_space(pdf, i + 1);
+        if pdf.get(i) != Some(&b'<') {
+            return None;
+        }
+        let start = i + 1;
+        let len = pdf[start..].iter().position(|&b| b == b'>')?;
+        *id = (start, start + len);
+        i = start + len;
+    }
+    Some(ids)
+}
+
+fn skip_space(pdf: &[u8], from: usize) -> usize {
+    from + pdf[from.min(pdf.len())..]
+        .iter()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PDF: &[u8] = b"%PDF-1.5\n1 0 obj\n<< >>\nendobj\ntrailer\n\
+        << /Root 1 0 R /ID [ <0123456789ABCDEF0123456789ABCDEF> <FEDCBA9876543210FEDCBA9876543210> ] >>\n%%EOF\n";
+
+    #[test]
+    fn stabilize_id_ignores_the_old_ids() {
+        let mut a = PDF.to_vec();
+        let mut b = String::from_utf8(PDF.to_vec())
+            .unwrap()
+            .replace("0123456789ABCDEF", "AAAAAAAAAAAAAAAA")
+            .into_bytes();
+        assert!(stabilize_id(&mut a));
+        assert!(stabilize_id(&mut b));
+        assert_eq!(a, b);
+        assert_eq!(a.len(), PDF.len());
+        assert_ne!(a, PDF);
+    }
+
+    #[test]
+    fn stabilize_id_needs_an_id() {
+        let mut pdf = b"%PDF-1.5\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n".to_vec();
+        assert!(!stabilize_id(&mut pdf));
+    }
+}
@@ -0,0 +1,163 @@
+//! What a compile may touch.
+//!
+//! Documents arrive from email and cloud drives, so compiles are restricted
+//! unless the project is marked trusted ([`crate::CompileOptions::trusted`]):
+//! - `\write18` is disabled; XeTeX logs `runsystem(…)...disabled.` instead
+//! - reads resolve in memory, the project dir, then the TeX bundle, and only
+//!   for relative names without `..` whose file, symlinks resolved, is inside
+//!   the project; absolute paths are refused, except font files in the system
+//!   and bundle font dirs that a font lookup hands to xdvipdfmx
+//! - writes (`\openout`, aux files, the PDF) go to memory and are only stored
+//!   under the job's work dir, again only for relative names without `..`
+//!
+//! Trusted projects may read absolute paths, and shell escape runs in the work
+//! dir's `shell/` folder. Writes stay confined either way.
+//!
+//! Every refused access becomes a [`Denial`], reported as a warning: the error
+//! it causes (a missing file, an unwritable stream) comes from TeX itself.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::diagnostics::{Diagnostic, Severity};
+
+/// Font files that may be read by absolute path from the font dirs: where
+/// fontconfig points for system and bundle fonts.
+const FONT_EXTENSIONS: &[&str] = &["otf", "ttf", "ttc", "pfb", "afm", "tfm"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+    Shell,
+}
+
+/// One access the sandbox refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Denial {
+    pub access: Access,
+    /// File name as TeX asked for it, or the shell command.
+    pub target: String,
+}
+
+impl Denial {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let message = match self.access {
+            Access::Read => format!(
+                "Sandbox: reading `{}' was denied; only the project and the TeX bundle are readable",
+                self.target
+            ),
+            Access::Write => format!(
+                "Sandbox: writing `{}' was denied; output stays in the compile's work dir",
+                self.target
+            ),
+            Access::Shell => format!(
+                "Sandbox: shell escape `{}' was denied; mark the project as trusted to run it",
+                self.target
+            ),
+        };
+        Diagnostic {
+            file: None,
+            line: None,
+            message,
+            severity: Severity::Warning,
+            context: None,
+        }
+    }
+}
+
+/// `name` as an absolute font file inside one of `font_dirs`, readable even in
+/// an untrusted compile.
+pub fn font_path(name: &str, font_dirs: &[PathBuf]) -> Option<PathBuf> {
+    if !Path::new(name).is_absolute() || !has_font_extension(name) {
+        return None;
+    }
+    resolve_within(Path::new(name), font_dirs)
+}
+
+/// `path` with its symlinks resolved, if it exists and the result is inside one
+/// of `roots`. Where a symlink points decides, not where it sits.
+pub fn resolve_within(path: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let real = path.canonicalize().ok()?;
+    roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| real.starts_with(root))
+        .then_some(real)
+}
+
+/// `name` ends in `.otf`, `.ttf` or another font file extension.
+pub fn has_font_extension(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| FONT_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// `runsystem(cmd)...disabled.` lines of a TeX log. The command may be wrapped
+/// over several lines at TeX's line length.
+pub fn shell_denials(log: &str) -> Vec<Denial> {
+    let mut out: Vec<Denial> = Vec::new();
+    for (i, _) in log.match_indices("runsystem(") {
+        let rest = &log[i + "runsystem(".len()..];
+        let Some(end) = rest.find(")...") else {
+            continue;
+        };
+        let outcome = &rest[end + ")...".len()..];
+        if !outcome.replace('\n', "").starts_with("disabled") {
+            continue;
+        }
+        let denial = Denial {
+            access: Access::Shell,
+            target: rest[..end].replace('\n', ""),
+        };
+        if !out.contains(&denial) {
+            out.push(denial);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn shell_denials_reads_wrapped_commands_once() {
+        let log = "runsystem(echo hi)...disabled.\n\
+                   runsystem(pdfcrop --margins 1 figure-with-a-lo\nng-name.pdf)...dis\nabled.\n\
+                   runsystem(echo hi)...disabled.\n\
+                   runsystem(date)...executed.\n";
+        let targets: Vec<String> = shell_denials(log).into_iter().map(|d| d.target).collect();
+        assert_eq!(
+            targets,
+            ["echo hi", "pdfcrop --margins 1 figure-with-a-long-name.pdf"]
+        );
+    }
+
+    #[test]
+    fn font_path_needs_an_absolute_font_inside_a_font_dir() {
+        let root = std::env::temp_dir().join(format!("livelatex-sandbox-{}", std::process::id()));
+        let fonts = root.join("fonts");
+        fs::create_dir_all(&fonts).unwrap();
+        fs::write(fonts.join("Serif.otf"), b"").unwrap();
+        fs::write(fonts.join("notes.txt"), b"").unwrap();
+        fs::write(root.join("Outside.ttf"), b"").unwrap();
+        let dirs = [fonts.clone()];
+        let path = |name: &str| fonts.join(name).to_string_lossy().into_owned();
+
+        assert!(font_path(&path("Serif.otf"), &dirs).is_some());
+        assert!(font_path(&path("notes.txt"), &dirs).is_none());
+        assert!(font_path(&path("../Outside.ttf"), &dirs).is_none());
+        assert!(font_path("Serif.otf", &dirs).is_none());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("Outside.ttf"), fonts.join("Link.ttf")).unwrap();
+            assert!(font_path(&path("Link.ttf"), &dirs).is_none());
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! SyncTeX lookups between source lines and PDF positions.
+//!
+//! XeTeX writes `texput.synctex.gz` on every pass; the last one is kept in the
+//! document's work dir (see [`crate::engine::incremental`]). Positions are PDF
+//! points with the origin at the top-left corner of the page, which is what a
+//! page bitmap uses too.
+//!
+//! Only the records the queries need are read: boxes (`(`, `[`, `h`, `v`) with
+//! their extent, and point records (`x`, `k`, `g`, `$`) that carry the precise
+//! input line of each glyph run, kern or glue.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use serde::Serialize;
+
+use crate::engine::incremental::WorkDir;
+use crate::Result;
+
+/// Name TeX gives the in-memory primary input; reported as `file: None`.
+const PRIMARY_INPUT: &str = "texput.tex";
+/// TeX scaled points per PDF big point.
+const SP_PER_BP: f64 = 65781.76;
+
+/// A spot on a page, in PDF points from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfSpot {
+    /// 1-based.
+    pub page: u32,
+    pub x: f64,
+    pub y: f64,
+    /// Extent of everything typeset from the line; 0 for a single point.
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceSpot {
+    /// `None` for the main document.
+    pub file: Option<String>,
+    /// 1-based.
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// `(`/`[` box with content, `h`/`v` void box.
+    Box,
+    /// `x`, `k`, `g`, `$`: a position only.
+    Point,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    kind: Kind,
+    tag: u32,
+    line: u32,
+    x: f64,
+    /// Baseline.
+    y: f64,
+    width: f64,
+    height: f64,
+    depth: f64,
+}
+
+impl Record {
+    fn top(&self) -> f64 {
+        self.y - self.height
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.depth
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.top() && y <= self.bottom()
+    }
+}
+
+/// Parsed SyncTeX data for one compiled PDF.
+#[derive(Debug, Default)]
+pub struct SyncTex {
+    /// Input tag → file name as TeX saw it.
+    inputs: Vec<(u32, String)>,
+    /// Records per page, in output order; index 0 is page 1.
+    pages: Vec<Vec<Record>>,
+}
+
+impl SyncTex {
+    /// Reads a `.synctex.gz` file.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut text = String::new();
+        GzDecoder::new(BufReader::new(File::open(path)?)).read_to_string(&mut text)?;
+        Ok(Self::parse(&text))
+    }
+
+    /// SyncTeX of the last successful compile to `output_path`, if there was one.
+    pub fn for_document(cache_path: &Path, output_path: &Path) -> Result<Option<Self>> {
+        let Some(work) = WorkDir::existing(cache_path, output_path) else {
+            return Ok(None);
+        };
+        let path = work.synctex_path();
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::open(&path).map(Some)
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut out = SyncTex::default();
+        let mut unit = 1.0;
+        let mut magnification = 1.0;
+        let mut in_content = false;
+        let mut page: Option<usize> = None;
+        for line in text.lines() {
+            // Inputs opened mid-run are declared inside the content section too.
+            if let Some(rest) = line.strip_prefix("Input:") {
+                if let Some((tag, name)) = rest.split_once(':') {
+                    if let Ok(tag) = tag.parse() {
+                        out.inputs.push((tag, name.to_string()));
+                    }
+                }
+                continue;
+            }
+            if !in_content {
+                if let Some(v) = line.strip_prefix("Unit:") {
+                    unit = v.trim().parse().unwrap_or(1.0);
+                } else if let Some(v) = line.strip_prefix("Magnification:") {
+                    magnification = v.trim().parse::<f64>().unwrap_or(1000.0) / 1000.0;
+                } else if line == "Content:" {
+                    in_content = true;
+                }
+                continue;
+            }
+            if line.starts_with("Postamble:") {
+                break;
+            }
+            let Some(first) = line.chars().next() else {
+                continue;
+            };
+            match first {
+                '{' => {
+                    let n: usize = line[1..].parse().unwrap_or(out.pages.len() + 1);
+                    if out.pages.len() < n {
+                        out.pages.resize_with(n, Vec::new);
+                    }
+                    page = n.checked_sub(1);
+                }
+                '}' => page = None,
+                '(' | '[' | 'h' | 'v' | 'x' | 'k' | 'g' | '$' => {
+                    let (Some(p), Some(mut rec)) = (page, parse_record(first, &line[1..])) else {
+                        continue;
+                    };
+                    let scale = unit * magnification / SP_PER_BP;
+                    rec.x *= scale;
+                    rec.y *= scale;
+                    rec.width *= scale;
+                    rec.height *= scale;
+                    rec.depth *= scale;
+                    out.pages[p].push(rec);
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.pages.len() as u32
+    }
+
+    /// Where `line` of `file` (`None`: main document) was typeset. Falls back to
+    /// the nearest following line with output, then the nearest preceding one,
+    /// so blank lines and comments still land somewhere sensible.
+    ///
+    /// XeTeX records no columns, so `column` cannot narrow the spot; the result
+    /// covers the whole line and anchors at its first glyph run.
+    pub fn forward(&self, file: Option<&str>, line: u32, _column: Option<u32>) -> Option<PdfSpot> {
+        let tags = self.tags_for(file);
+        if tags.is_empty() {
+            return None;
+        }
+        let lines_with_output = || {
+            self.pages
+                .iter()
+                .flatten()
+                .filter(|r| tags.contains(&r.tag) && r.line > 0)
+                .map(|r| r.line)
+        };
+        let target = lines_with_output()
+            .filter(|&l| l >= line)
+            .min()
+            .or_else(|| lines_with_output().filter(|&l| l < line).max())?;
+
+        let (page_index, records) = self.pages.iter().enumerate().find_map(|(i, recs)| {
+            let hits: Vec<&Record> = recs
+                .iter()
+                .filter(|r| tags.contains(&r.tag) && r.line == target)
+                .collect();
+            (!hits.is_empty()).then_some((i, hits))
+        })?;
+
+        let anchor = records
+            .iter()
+            .find(|r| r.kind == Kind::Point)
+            .unwrap_or(&records[0]);
+
+        let left = records.iter().map(|r| r.x).fold(f64::INFINITY, f64::min);
+        let right = records
+            .iter()
+            .map(|r| r.x + r.width)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let top = records
+            .iter()
+            .map(|r| r.top())
+            .fold(f64::INFINITY, f64::min);
+        let bottom = records
+            .iter()
+            .map(|r| r.bottom())
+            .fold(f64::NEG_INFINITY, f64::max);
+        Some(PdfSpot {
+            page: page_index as u32 + 1,
+            x: anchor.x,
+            y: anchor.y,
+            width: (right - left).max(0.0),
+            height: (bottom - top).max(0.0),
+        })
+    }
+
+    /// Source line under `(x, y)` on `page` (1-based). Uses the innermost box
+    /// around the point, then the closest glyph run inside it.
+    pub fn inverse(&self, page: u32, x: f64, y: f64) -> Option<SourceSpot> {
+        let records = self.pages.get((page as usize).checked_sub(1)?)?;
+        let innermost = records
+            .iter()
+            .filter(|r| r.kind == Kind::Box && r.line > 0 && r.contains(x, y))
+            .min_by(|a, b| area(a).total_cmp(&area(b)));
+
+        let distance = |r: &Record| {
+            // Being on the right text line matters more than horizontal distance.
+            let dy = if y < r.top() {
+                r.top() - y
+            } else if y > r.bottom() {
+                y - r.bottom()
+            } else {
+                0.0
+            };
+            let dx = if x < r.x {
+                r.x - x
+            } else if x > r.x + r.width {
+                x - r.x - r.width
+            } else {
+                0.0
+            };
+            dx + 4.0 * dy
+        };
+        let candidates = records.iter().filter(|r| {
+            r.kind == Kind::Point
+                && r.line > 0
+                && innermost.is_none_or(|b| r.y >= b.top() && r.y <= b.bottom())
+        });
+        let best = candidates
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .or(innermost)
+            .or_else(|| {
+                records
+                    .iter()
+                    .filter(|r| r.line > 0)
+                    .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            })?;
+        Some(SourceSpot {
+            file: self.file_for(best.tag),
+            line: best.line,
+        })
+    }
+
+    /// Input tags naming `file`; the main document when `None`.
+    fn tags_for(&self, file: Option<&str>) -> Vec<u32> {
+        let wanted = file.map_or(PRIMARY_INPUT, normalize);
+        self.inputs
+            .iter()
+            .filter(|(_, name)| normalize(name) == wanted || base_name(name) == wanted)
+            .map(|(tag, _)| *tag)
+            .collect()
+    }
+
+    fn file_for(&self, tag: u32) -> Option<String> {
+        let (_, name) = self.inputs.iter().find(|(t, _)| *t == tag)?;
+        let name = normalize(name);
+        (base_name(name) != PRIMARY_INPUT).then(|| name.to_string())
+    }
+}
+
+/// `(`/`[`/`h`/`v`: `tag,line:x,y:W,H,D`; others: `tag,line:x,y[:W]`.
+fn parse_record(kind: char, rest: &str) -> Option<Record> {
+    let mut parts = rest.split(':');
+    let (tag, line) = parts.next()?.split_once(',')?;
+    // Newer SyncTeX may append `,column` to the line.
+    let line = line.split(',').next()?;
+    let (x, y) = parts.next()?.split_once(',')?;
+    let mut rec = Record {
+        kind: Kind::Point,
+        tag: tag.parse().ok()?,
+        line: line.parse().ok()?,
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        width: 0.0,
+        height: 0.0,
+        depth: 0.0,
+    };
+    if matches!(kind, '(' | '[' | 'h' | 'v') {
+        let mut dims = parts.next()?.split(',');
+        rec.kind = Kind::Box;
+        rec.width = dims.next()?.parse().ok()?;
+        rec.height = dims.next()?.parse().ok()?;
+        rec.depth = dims.next()?.parse().ok()?;
+    }
+    Some(rec)
+}
+
+fn area(r: &Record) -> f64 {
+    r.width * (r.height + r.depth)
+}
+
+fn normalize(name: &str) -> &str {
+    name.strip_prefix("./").unwrap_or(name)
+}
+
+fn base_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit 1 and magnification 1000 make one sp a 1/65781.76 point; the
+    /// records below use multiples of 65781.76 sp so positions are whole points.
+    const SYNCTEX: &str = "\
+SyncTeX Version:1
+Input:1:./texput.tex
+Input:2:./chapter.tex
+Output:pdf
+Magnification:1000
+Unit:1
+X Offset:0
+Y Offset:0
+Content:
+{1
+[1,3:6578176,6578176:32890880,657818,0
+(1,3:6578176,6578176:32890880,657818,0
+x1,3:6578176,6578176
+x1,4:6578176,7894291
+)
+]
+}
+{2
+[2,10:6578176,6578176:32890880,657818,0
+x2,10:13156352,6578176
+]
+}
+Postamble:
+";
+
+    #[test]
+    fn parse_reads_pages_in_points() {
+        let sync = SyncTex::parse(SYNCTEX);
+        assert_eq!(sync.page_count(), 2);
+        let spot = sync.forward(None, 3, None).unwrap();
+        assert_eq!(spot.page, 1);
+        assert!((spot.x - 100.0).abs() < 1e-6);
+        assert!((spot.y - 100.0).abs() < 1e-6);
+        assert!((spot.width - 500.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn forward_falls_forward_to_the_next_line_with_output() {
+        let sync = SyncTex::parse(SYNCTEX);
+        assert_eq!(sync.forward(None, 2, None).unwrap().y.round(), 100.0);
+        assert_eq!(sync.forward(None, 4, None).unwrap().y.round(), 120.0);
+        assert_eq!(sync.forward(None, 50, None).unwrap().y.round(), 120.0);
+        let included = sync.forward(Some("chapter.tex"), 10, None).unwrap();
+        assert_eq!((included.page, included.x.round()), (2, 200.0));
+        assert_eq!(sync.forward(Some("missing.tex"), 1, None), None);
+    }
+
+    #[test]
+    fn inverse_finds_the_closest_line() {
+        let sync = SyncTex::parse(SYNCTEX);
+        assert_eq!(
+            sync.inverse(1, 150.0, 119.0),
+            Some(SourceSpot {
+                file: None,
+                line: 4
+            })
+        );
+        assert_eq!(
+            sync.inverse(2, 210.0, 99.0),
+            Some(SourceSpot {
+                file: Some("chapter.tex".to_string()),
+                line: 10,
+            })
+        );
+        assert_eq!(sync.inverse(3, 0.0, 0.0), None);
+    }
+}
@@ -0,0 +1,317 @@
+//! Typed events from a TeX log, for the app's errors/warnings panel.
+//!
+//! [`analyze`] runs [`crate::diagnostics::parse_log`] and sorts what it finds:
+//! - `Reference `x' … undefined` → [`EventKind::UndefinedReference`]
+//! - `Citation `x' … undefined` (LaTeX, natbib, biblatex) → [`EventKind::UndefinedCitation`]
+//! - `Overfull`/`Underfull` boxes → with the paragraph's first and last line
+//! - `File `x' not found`, `I can't find file`, `Unable to load picture` → [`EventKind::MissingFile`]
+//! - `LaTeX Font Warning: Font shape … using … instead` → [`EventKind::FontSubstitution`]
+//! - `Label(s) may have changed` and other `Rerun` requests → [`EventKind::Rerun`]
+//! - `! Package/Class x Error:` → [`EventKind::PackageError`]
+//!
+//! Anything else stays a plain [`EventKind::Error`] or [`EventKind::Warning`].
+//! The summaries LaTeX prints at the end (`There were undefined references.`)
+//! are dropped when the events they sum up are listed. `No file x.tex.` lines
+//! (a missing `\include`) follow as missing files; TeX gives no position for them.
+
+use serde::Serialize;
+
+use crate::diagnostics::{self, Diagnostic, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventKind {
+    UndefinedReference,
+    UndefinedCitation,
+    OverfullBox,
+    UnderfullBox,
+    MissingFile,
+    FontSubstitution,
+    Rerun,
+    PackageError,
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEvent {
+    pub kind: EventKind,
+    pub severity: Severity,
+    /// Source file; `None` for the primary input unless a main file was given.
+    pub file: Option<String>,
+    /// 1-based line, or the first line of a box's paragraph.
+    pub line: Option<u32>,
+    /// Last line of a box's paragraph (`at lines 12--14`).
+    pub end_line: Option<u32>,
+    /// The label, citation key, file, requested font shape or package the event is about.
+    pub subject: Option<String>,
+    pub message: String,
+    pub context: Option<String>,
+}
+
+/// What `analyzeTexLog` hands back to Kotlin (as JSON).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogAnalysis {
+    pub events: Vec<LogEvent>,
+    /// TeX asked for another run: cross-references or other aux data are stale.
+    pub rerun: bool,
+}
+
+impl LogAnalysis {
+    pub fn errors(&self) -> impl Iterator<Item = &LogEvent> {
+        self.events.iter().filter(|e| e.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &LogEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.severity == Severity::Warning)
+    }
+}
+
+/// Parses a TeX log into events in log order, missing `\include`s last. Positions
+/// in the primary input are given to `main_file`, as in
+/// [`crate::CompileOptions::main_file`].
+pub fn analyze(log: &str, main_file: Option<&str>) -> LogAnalysis {
+    let mut events: Vec<LogEvent> = diagnostics::parse_log(log)
+        .into_iter()
+        .map(|d| classify(d, main_file))
+        .collect();
+    let refs = events
+        .iter()
+        .any(|e| e.kind == EventKind::UndefinedReference);
+    let cites = events
+        .iter()
+        .any(|e| e.kind == EventKind::UndefinedCitation);
+    events.retain(|e| match summary(&e.message) {
+        Some(EventKind::UndefinedReference) => !refs,
+        Some(EventKind::UndefinedCitation) => !cites,
+        _ => true,
+    });
+    events.extend(log.lines().filter_map(missing_input));
+    let rerun = events.iter().any(|e| e.kind == EventKind::Rerun);
+    LogAnalysis { events, rerun }
+}
+
+fn classify(d: Diagnostic, main_file: Option<&str>) -> LogEvent {
+    let message = d.message.as_str();
+    let (kind, subject) = if d.severity == Severity::Error {
+        error_kind(message)
+    } else {
+        warning_kind(message)
+    };
+    let (line, end_line) = match kind {
+        EventKind::OverfullBox | EventKind::UnderfullBox => {
+            let (start, end) = line_range(message);
+            (start.or(d.line), end)
+        }
+        _ => (d.line, None),
+    };
+    let file = match (&d.file, main_file) {
+        (None, Some(main)) if line.is_some() => Some(main.to_string()),
+        _ => d.file.clone(),
+    };
+    LogEvent {
+        kind,
+        severity: d.severity,
+        file,
+        line,
+        end_line,
+        subject,
+        message: d.message,
+        context: d.context,
+    }
+}
+
+fn error_kind(message: &str) -> (EventKind, Option<String>) {
+    let missing = message.contains("not found")
+        || message.starts_with("I can't find file")
+        || message.starts_with("Unable to load picture");
+    if missing {
+        let name = quoted(message).or_else(|| single_quoted(message));
+        return (EventKind::MissingFile, name);
+    }
+    let package = message
+        .strip_prefix("Package ")
+        .or_else(|| message.strip_prefix("Class "))
+        .and_then(|rest| rest.split_once(" Error:"))
+        .map(|(name, _)| name.to_string());
+    match package {
+        Some(name) => (EventKind::PackageError, Some(name)),
+        None => (EventKind::Error, None),
+    }
+}
+
+fn warning_kind(message: &str) -> (EventKind, Option<String>) {
+    if message.starts_with("Overfull \\") {
+        return (EventKind::OverfullBox, None);
+    }
+    if message.starts_with("Underfull \\") {
+        return (EventKind::UnderfullBox, None);
+    }
+    if message.contains("Rerun") || message.contains("rerun LaTeX") {
+        return (EventKind::Rerun, None);
+    }
+    let warning = message
+        .split_once(" Warning: ")
+        .map_or(message, |(_, text)| text);
+    if warning.contains(" undefined") {
+        if warning.starts_with("Reference `") {
+            return (EventKind::UndefinedReference, quoted(warning));
+        }
+        if warning.starts_with("Citation `") || warning.starts_with("Citation '") {
+            return (
+                EventKind::UndefinedCitation,
+                quoted(warning).or_else(|| single_quoted(warning)),
+            );
+        }
+    }
+    if message.starts_with("LaTeX Font Warning: ")
+        && (warning.contains(" instead") || warning.contains("defaults substituted"))
+    {
+        return (EventKind::FontSubstitution, quoted(warning));
+    }
+    if let Some(name) = warning.strip_prefix("File `") {
+        if name.contains("not found") {
+            return (EventKind::MissingFile, quoted(warning));
+        }
+    }
+    (EventKind::Warning, None)
+}
+
+/// `No file chapter.tex.`, which is all `\include` says about a missing file.
+/// Auxiliary files a first run has not written yet are expected, not reported.
+fn missing_input(line: &str) -> Option<LogEvent> {
+    const AUXILIARY: &[&str] = &[
+        "aux", "toc", "lof", "lot", "loa", "out", "bbl", "ind", "gls", "acr", "nls", "nav", "snm",
+    ];
+    let name = line.strip_prefix("No file ")?.strip_suffix('.')?;
+    let (_, ext) = name.rsplit_once('.')?;
+    if AUXILIARY.contains(&ext) {
+        return None;
+    }
+    Some(LogEvent {
+        kind: EventKind::MissingFile,
+        severity: Severity::Warning,
+        file: None,
+        line: None,
+        end_line: None,
+        subject: Some(name.to_string()),
+        message: line.trim().to_string(),
+        context: None,
+    })
+}
+
+/// `There were undefined references.` and friends.
+fn summary(message: &str) -> Option<EventKind> {
+    if message.contains("There were undefined references") {
+        Some(EventKind::UndefinedReference)
+    } else if message.contains("There were undefined citations") {
+        Some(EventKind::UndefinedCitation)
+    } else {
+        None
+    }
+}
+
+/// `in paragraph at lines 12--14` → (12, 14); `detected at line 12` → (12, None).
+fn line_range(message: &str) -> (Option<u32>, Option<u32>) {
+    fn number(s: &str) -> Option<u32> {
+        s.chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+    if let Some(i) = message.rfind("at lines ") {
+        let rest = &message[i + "at lines ".len()..];
+        let end = rest.split_once("--").and_then(|(_, e)| number(e));
+        return (number(rest), end);
+    }
+    let start = message
+        .rfind("at line ")
+        .and_then(|i| number(&message[i + "at line ".len()..]));
+    (start, None)
+}
+
+/// First `` `name' `` in the message.
+fn quoted(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once('`')?;
+    let (name, _) = rest.split_once('\'')?;
+    Some(name.to_string())
+}
+
+/// First `'name'` in the message (XeTeX and biblatex quote this way).
+fn single_quoted(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once('\'')?;
+    let (name, _) = rest.split_once('\'')?;
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+This is XeTeX, Version 3.141592653-2.6-0.999996 (TeX Live 2024) (preloaded format=latex)
+(./texput.tex
+LaTeX2e <2023-11-01>
+(./intro.tex
+Overfull \\hbox (12.3pt too wide) in paragraph at lines 12--14
+[]\\TU/lmr/m/n/10 A very long word|
+)
+LaTeX Warning: Reference `fig:plot' on page 1 undefined on input line 7.
+
+LaTeX Warning: Citation `knuth84' on page 1 undefined on input line 9.
+
+No file chapter.tex.
+No file texput.toc.
+! Package amsmath Error: \\begin{align} allowed only in paragraph mode.
+
+See the amsmath package documentation for explanation.
+Type  H <return>  for immediate help.
+ ...
+
+l.21 \\begin{align}
+
+LaTeX Warning: There were undefined references.
+
+LaTeX Warning: Label(s) may have changed. Rerun to get cross-references right.
+
+)
+";
+
+    #[test]
+    fn analyze_sorts_events_by_kind() {
+        let analysis = analyze(LOG, Some("main.tex"));
+        let kinds: Vec<(EventKind, Option<&str>, Option<u32>)> = analysis
+            .events
+            .iter()
+            .map(|e| (e.kind, e.subject.as_deref(), e.line))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (EventKind::OverfullBox, None, Some(12)),
+                (EventKind::UndefinedReference, Some("fig:plot"), Some(7)),
+                (EventKind::UndefinedCitation, Some("knuth84"), Some(9)),
+                (EventKind::PackageError, Some("amsmath"), Some(21)),
+                (EventKind::Rerun, None, None),
+                (EventKind::MissingFile, Some("chapter.tex"), None),
+            ]
+        );
+        assert!(analysis.rerun);
+        assert_eq!(analysis.errors().count(), 1);
+    }
+
+    #[test]
+    fn analyze_gives_positions_to_the_right_file() {
+        let analysis = analyze(LOG, Some("main.tex"));
+        let overfull = &analysis.events[0];
+        assert_eq!(overfull.file.as_deref(), Some("intro.tex"));
+        assert_eq!(overfull.end_line, Some(14));
+        assert_eq!(analysis.events[1].file.as_deref(), Some("main.tex"));
+        assert_eq!(analyze(LOG, None).events[1].file, None);
+    }
+}
@@ -0,0 +1,132 @@
+//! TikZ figures for the preview, rendered to SVG.
+//!
+//! The preview (`TikzRenderer.convertTikzPictures`) wraps each `tikzpicture` in
+//! a standalone document and looks for `<sha1 of that document>.svg` under
+//! [`CACHE_DIR`] in the project folder. [`render_svg`] compiles such a document
+//! with the embedded engine and writes that file, with page 1 converted by
+//! [`crate::pdf::Document::page_svg`].
+//!
+//! Every figure of a project compiles to the same output path, so they share one
+//! work dir and, when their preambles match, one dumped preamble format.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::compile::{compile_with, CompileOptions};
+use crate::diagnostics::Diagnostic;
+use crate::pdf::Document;
+use crate::Result;
+
+/// SVG cache, relative to the project folder.
+pub const CACHE_DIR: &str = ".livelatex-cache/tikz";
+/// Intermediate PDF inside [`CACHE_DIR`]; removed once converted.
+const FIGURE_PDF: &str = "figure.pdf";
+
+/// Figures of one project share a work dir, so renders run one at a time.
+static RENDER: Mutex<()> = Mutex::new(());
+
+/// What `renderTikzSvg` hands back to Kotlin (as JSON).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TikzReport {
+    /// Set when the SVG exists, freshly written or cached.
+    pub svg_path: Option<String>,
+    pub errors: Vec<Diagnostic>,
+    /// Engine log; empty for a cached SVG.
+    pub log: String,
+}
+
+/// Lowercase hex SHA-1 of the document, as the preview computes it.
+pub fn key(tex_doc: &str) -> String {
+    sha1_smol::Sha1::from(tex_doc.as_bytes())
+        .digest()
+        .to_string()
+}
+
+/// Where the SVG for `tex_doc` lives under `project_dir`.
+pub fn svg_path(project_dir: &Path, tex_doc: &str) -> PathBuf {
+    project_dir
+        .join(CACHE_DIR)
+        .join(format!("{}.svg", key(tex_doc)))
+}
+
+/// Compiles the standalone `tex_doc` and writes its SVG under `project_dir`
+/// (see [`svg_path`]), unless it is already there. TeX errors come back in the
+/// report; `Err` is for problems outside the figure, as in
+/// [`crate::compile_detailed`].
+pub fn render_svg(tex_doc: &str, project_dir: &Path, cache_path: &Path) -> Result<TikzReport> {
+    let svg = svg_path(project_dir, tex_doc);
+    let done = |log: String| TikzReport {
+        svg_path: Some(svg.to_string_lossy().into_owned()),
+        errors: Vec::new(),
+        log,
+    };
+    if svg.is_file() {
+        return Ok(done(String::new()));
+    }
+
+    let _guard = RENDER.lock().unwrap_or_else(|e| e.into_inner());
+    // Another render of the same figure may have finished while we waited.
+    if svg.is_file() {
+        return Ok(done(String::new()));
+    }
+    let dir = project_dir.join(CACHE_DIR);
+    fs::create_dir_all(&dir)?;
+    let pdf = dir.join(FIGURE_PDF);
+    let options = CompileOptions {
+        project_dir: Some(project_dir.to_path_buf()),
+        ..CompileOptions::default()
+    };
+    let report = compile_with(tex_doc, &pdf, cache_path, &options)?;
+    if !report.success() {
+        return Ok(TikzReport {
+            svg_path: None,
+            errors: report.errors,
+            log: report.log,
+        });
+    }
+
+    let document = Document::open(&pdf);
+    let _ = fs::remove_file(&pdf);
+    // Ids are scoped by key: the preview inlines several figures in one page.
+    let key = key(tex_doc);
+    let markup = document?.page_svg(1, &format!("tikz-{}", &key[..12]))?;
+    let tmp = svg.with_extension("svg.part");
+    fs::write(&tmp, markup)?;
+    fs::rename(&tmp, &svg)?;
+    Ok(done(report.log))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_is_the_documents_sha1() {
+        assert_eq!(key("abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            svg_path(Path::new("/p"), "abc"),
+            Path::new("/p/.livelatex-cache/tikz/a9993e364706816aba3e25717850c26c9cd0d89d.svg")
+        );
+    }
+
+    #[test]
+    fn a_cached_svg_is_returned_without_compiling() {
+        let root = std::env::temp_dir().join(format!("livelatex-tikz-{}", std::process::id()));
+        let doc = "\\begin{tikzpicture}\\draw (0,0) -- (1,1);\\end{tikzpicture}";
+        let svg = svg_path(&root, doc);
+        fs::create_dir_all(svg.parent().unwrap()).unwrap();
+        fs::write(&svg, "<svg/>").unwrap();
+
+        // A compile would have created the cache dir.
+        let report = render_svg(doc, &root, &root.join("no-cache")).unwrap();
+        assert_eq!(report.svg_path.as_deref(), svg.to_str());
+        assert!(report.errors.is_empty());
+        assert!(report.log.is_empty());
+        assert!(!root.join("no-cache").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
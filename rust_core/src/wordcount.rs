@@ -0,0 +1,458 @@
+//! Word counts of the typeset document, read from the compiled PDF.
+//!
+//! Counting the PDF rather than the source leaves out commands, comments and
+//! everything TeX does not print, and includes what it generates (section
+//! numbers, the bibliography). A word is a run of non-blank characters with at
+//! least one letter or digit; a word hyphenated across lines counts once, and a
+//! line holding nothing but a page number is skipped.
+//!
+//! Sections come from the PDF's bookmarks ([`crate::pdf::Document::outline`]),
+//! which hyperref writes for every heading; without hyperref there is only a
+//! total. With [`WordCountOptions::exclude_captions`], a line starting
+//! `Figure 3:` or `Table 2.` (and the same in a few other languages) and the
+//! lines that follow it at the same size and spacing are skipped. With
+//! [`WordCountOptions::exclude_references`], so is the section titled
+//! References or Bibliography; without bookmarks, everything from a line reading
+//! just that to the end.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pdf::{Document, OutlineItem, PageText, TextLine};
+use crate::Result;
+
+/// First words of a caption; followed by a number such as `3`, `A.1` or `2.4`.
+const CAPTION_LABELS: &[&str] = &[
+    "Figure",
+    "Fig.",
+    "Table",
+    "Tab.",
+    "Algorithm",
+    "Listing",
+    "Abbildung",
+    "Abb.",
+    "Tabelle",
+    "Figura",
+    "Tabla",
+    "Tableau",
+];
+
+/// Headings of the bibliography, lowercase.
+const REFERENCE_HEADINGS: &[&str] = &[
+    "references",
+    "bibliography",
+    "works cited",
+    "literature cited",
+    "literatur",
+    "literaturverzeichnis",
+    "bibliographie",
+    "références",
+    "referencias",
+    "bibliografía",
+    "riferimenti bibliografici",
+];
+
+/// Baseline to baseline, in line heights, that still continues a caption.
+const CAPTION_SPACING: f64 = 1.5;
+/// Bookmarks point a little above their heading; lines this far above count.
+const DESTINATION_SLACK: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WordCountOptions {
+    pub exclude_captions: bool,
+    pub exclude_references: bool,
+}
+
+impl WordCountOptions {
+    /// Empty or blank JSON means defaults.
+    pub fn from_json(json: &str) -> Result<Self> {
+        if json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WordCount {
+    pub total: u32,
+    /// Words per page; index 0 is page 1.
+    pub pages: Vec<u32>,
+    /// Words before the first bookmarked heading (title, abstract).
+    pub unsectioned: u32,
+    /// One entry per bookmark, in outline order.
+    pub sections: Vec<SectionCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionCount {
+    pub title: String,
+    /// 1 for top-level headings.
+    pub level: u32,
+    /// 1-based page the heading is on.
+    pub page: u32,
+    /// Words from the heading to the next one at the same or a higher level,
+    /// subsections included; 0 when excluded.
+    pub words: u32,
+}
+
+/// Counts the words of the PDF at `path`.
+pub fn count_pdf(path: &Path, options: &WordCountOptions) -> Result<WordCount> {
+    Ok(count(&Document::open(path)?, options))
+}
+
+pub fn count(doc: &Document, options: &WordCountOptions) -> WordCount {
+    count_text(&doc.text(), &doc.outline(), options)
+}
+
+/// Where a line is: 1-based page, then top in points.
+type Position = (u32, f64);
+
+fn count_text(
+    pages: &[PageText],
+    outline: &[OutlineItem],
+    options: &WordCountOptions,
+) -> WordCount {
+    // Bookmarks in reading order; the outline need not be.
+    let mut order: Vec<usize> = (0..outline.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&outline[a], &outline[b]);
+        a.page.cmp(&b.page).then(a.y.total_cmp(&b.y))
+    });
+    let starts: Vec<Position> = order
+        .iter()
+        .map(|&i| (outline[i].page, outline[i].y - DESTINATION_SLACK))
+        .collect();
+
+    let references: Vec<bool> = outline
+        .iter()
+        .map(|item| options.exclude_references && is_reference_heading(&item.title))
+        .collect();
+    let skipped_sections: Vec<bool> = (0..outline.len())
+        .map(|s| within_marked(outline, &references, s))
+        .collect();
+    // Without bookmarks, the bibliography runs from its heading to the end.
+    let mut in_references = false;
+
+    let mut own = vec![0u32; outline.len()];
+    let mut count = WordCount {
+        total: 0,
+        pages: vec![0; pages.len()],
+        unsectioned: 0,
+        sections: Vec::new(),
+    };
+    let mut section = None;
+    let mut next = 0;
+    let mut hyphenated = false;
+    for (pi, page) in pages.iter().enumerate() {
+        let captions = if options.exclude_captions {
+            captions(&page.lines)
+        } else {
+            vec![false; page.lines.len()]
+        };
+        for (li, line) in page.lines.iter().enumerate() {
+            while next < starts.len() && starts[next] <= (page.page, line.bounds.y) {
+                section = Some(order[next]);
+                next += 1;
+            }
+            if options.exclude_references && outline.is_empty() && is_reference_heading(&line.text)
+            {
+                in_references = true;
+            }
+            let skipped = in_references
+                || captions[li]
+                || is_page_number(page, li)
+                || section.is_some_and(|s| skipped_sections[s]);
+            if skipped {
+                hyphenated = false;
+                continue;
+            }
+            let mut words = line.text.split_whitespace().filter(|w| is_word(w)).count() as u32;
+            let first = line.text.split_whitespace().next().unwrap_or("");
+            if hyphenated && words > 0 && first.starts_with(char::is_lowercase) {
+                words -= 1;
+            }
+            hyphenated = line
+                .text
+                .split_whitespace()
+                .last()
+                .is_some_and(ends_hyphenated);
+            count.total += words;
+            count.pages[pi] += words;
+            match section {
+                Some(s) => own[s] += words,
+                None => count.unsectioned += words,
+            }
+        }
+    }
+
+    count.sections = outline
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let end = outline[i + 1..]
+                .iter()
+                .position(|o| o.level <= item.level)
+                .map_or(outline.len(), |n| i + 1 + n);
+            SectionCount {
+                title: item.title.clone(),
+                level: item.level,
+                page: item.page,
+                words: own[i..end].iter().sum(),
+            }
+        })
+        .collect();
+    count
+}
+
+/// Whether bookmark `s` or one of its ancestors is marked.
+fn within_marked(outline: &[OutlineItem], marked: &[bool], s: usize) -> bool {
+    let mut level = outline[s].level + 1;
+    for i in (0..=s).rev() {
+        if outline[i].level < level {
+            if marked[i] {
+                return true;
+            }
+            level = outline[i].level;
+        }
+    }
+    false
+}
+
+/// Lines of `lines` that belong to a caption.
+fn captions(lines: &[TextLine]) -> Vec<bool> {
+    let mut out = vec![false; lines.len()];
+    let mut i = 0;
+    while i < lines.len() {
+        if !is_caption_start(&lines[i].text) {
+            i += 1;
+            continue;
+        }
+        out[i] = true;
+        while i + 1 < lines.len() {
+            let (cur, next) = (&lines[i].bounds, &lines[i + 1].bounds);
+            let same_size = (next.height - cur.height).abs() <= 0.2 * cur.height;
+            let spacing = next.y - cur.y;
+            if !same_size || spacing <= 0.0 || spacing > CAPTION_SPACING * cur.height {
+                break;
+            }
+            i += 1;
+            out[i] = true;
+        }
+        i += 1;
+    }
+    out
+}
+
+/// `Figure 3:`, `Table A.1.`, `Fig. 2 —`: a label, a number and punctuation.
+fn is_caption_start(text: &str) -> bool {
+    let mut tokens = text.split_whitespace();
+    let (Some(label), Some(number)) = (tokens.next(), tokens.next()) else {
+        return false;
+    };
+    if !CAPTION_LABELS.contains(&label) {
+        return false;
+    }
+    let digits = number.trim_end_matches([':', '.']);
+    let numbered = digits.chars().any(|c| c.is_ascii_digit())
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    let punctuated = number.ends_with([':', '.'])
+        || tokens
+            .next()
+            .is_some_and(|t| matches!(t, ":" | "." | "—" | "–"));
+    numbered && punctuated
+}
+
+/// `References`, `7 Bibliography`, `A. Works Cited`.
+fn is_reference_heading(text: &str) -> bool {
+    let text = text.trim();
+    let title = match text.split_once(char::is_whitespace) {
+        Some((number, rest)) if is_heading_number(number) => rest,
+        _ => text,
+    };
+    [text, title]
+        .iter()
+        .any(|t| REFERENCE_HEADINGS.contains(&t.trim().to_lowercase().as_str()))
+}
+
+/// `7`, `7.`, `A`, `A.`: a heading's number.
+fn is_heading_number(token: &str) -> bool {
+    let token = token.trim_end_matches('.');
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_digit())
+        || token.len() == 1 && token.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// A page's first or last line holding nothing but an Arabic or Roman number.
+fn is_page_number(page: &PageText, line: usize) -> bool {
+    if line != 0 && line + 1 != page.lines.len() {
+        return false;
+    }
+    let text = page.lines[line].text.trim();
+    !text.is_empty()
+        && (text.chars().all(|c| c.is_ascii_digit())
+            || text.len() <= 8 && text.chars().all(|c| "ivxlcdm".contains(c)))
+}
+
+fn is_word(token: &str) -> bool {
+    token.chars().any(char::is_alphanumeric)
+}
+
+/// `inter-` at the end of a line: a word TeX broke in two.
+fn ends_hyphenated(token: &str) -> bool {
+    let mut chars = token.chars().rev();
+    matches!(chars.next(), Some('-' | '\u{2010}' | '\u{00AD}'))
+        && chars.next().is_some_and(char::is_alphabetic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::TextBox;
+
+    /// Page `page` with `(text, top)` lines, 10 pt high.
+    fn page(page: u32, lines: &[(&str, f64)]) -> PageText {
+        PageText {
+            page,
+            lines: lines
+                .iter()
+                .map(|&(text, y)| TextLine {
+                    text: text.into(),
+                    bounds: TextBox {
+                        x: 0.0,
+                        y,
+                        width: 100.0,
+                        height: 10.0,
+                    },
+                    words: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn heading(title: &str, level: u32, page: u32, y: f64) -> OutlineItem {
+        OutlineItem {
+            title: title.into(),
+            level,
+            page,
+            y,
+        }
+    }
+
+    fn paper() -> (Vec<PageText>, Vec<OutlineItem>) {
+        let pages = vec![
+            page(
+                1,
+                &[
+                    ("Paper title", 5.0),
+                    ("1 Introduction", 20.0),
+                    ("one two three", 32.0),
+                    ("2 Method", 50.0),
+                    ("four five", 60.0),
+                    ("2.1 Detail", 70.0),
+                    ("six", 80.0),
+                    ("1", 95.0),
+                ],
+            ),
+            page(2, &[("References", 10.0), ("Knuth. The TeXbook.", 22.0)]),
+        ];
+        let outline = vec![
+            heading("Introduction", 1, 1, 20.0),
+            heading("Method", 1, 1, 50.0),
+            heading("Detail", 2, 1, 70.0),
+            heading("References", 1, 2, 10.0),
+        ];
+        (pages, outline)
+    }
+
+    #[test]
+    fn sections_include_their_subsections() {
+        let (pages, outline) = paper();
+        let count = count_text(&pages, &outline, &WordCountOptions::default());
+        assert_eq!(count.total, 18);
+        assert_eq!(count.pages, [14, 4]);
+        assert_eq!(count.unsectioned, 2);
+        let words: Vec<(&str, u32)> = count
+            .sections
+            .iter()
+            .map(|s| (s.title.as_str(), s.words))
+            .collect();
+        assert_eq!(
+            words,
+            [
+                ("Introduction", 5),
+                ("Method", 7),
+                ("Detail", 3),
+                ("References", 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn references_can_be_left_out() {
+        let options = WordCountOptions {
+            exclude_references: true,
+            ..WordCountOptions::default()
+        };
+        let (pages, outline) = paper();
+        let count = count_text(&pages, &outline, &options);
+        assert_eq!(count.total, 14);
+        assert_eq!(count.sections[3].words, 0);
+
+        // Without bookmarks, from the heading line to the end.
+        let count = count_text(&pages, &[], &options);
+        assert_eq!(count.total, 14);
+        assert_eq!(count.unsectioned, 14);
+    }
+
+    #[test]
+    fn hyphenated_words_count_once() {
+        let pages = [page(
+            1,
+            &[
+                ("an inter-", 0.0),
+                ("national effort", 12.0),
+                ("self-made", 24.0),
+            ],
+        )];
+        let count = count_text(&pages, &[], &WordCountOptions::default());
+        assert_eq!(count.total, 4);
+    }
+
+    #[test]
+    fn captions_can_be_left_out() {
+        let pages = [page(
+            1,
+            &[
+                ("Figure 1: A cat", 10.0),
+                ("sitting on a mat.", 22.0),
+                ("Body text", 60.0),
+            ],
+        )];
+        let all = count_text(&pages, &[], &WordCountOptions::default());
+        assert_eq!(all.total, 10);
+        let options = WordCountOptions {
+            exclude_captions: true,
+            ..WordCountOptions::default()
+        };
+        assert_eq!(count_text(&pages, &[], &options).total, 2);
+        assert!(!is_caption_start("Figure out what to do."));
+        assert!(is_caption_start("Table A.1. Results"));
+    }
+
+    #[test]
+    fn options_come_from_json() {
+        assert_eq!(
+            WordCountOptions::from_json(" ").unwrap(),
+            WordCountOptions::default()
+        );
+        let options = WordCountOptions::from_json(r#"{"excludeCaptions":true}"#).unwrap();
+        assert!(options.exclude_captions && !options.exclude_references);
+        assert!(WordCountOptions::from_json("{").is_err());
+    }
+}